@@ -0,0 +1,182 @@
+//! Conformance fixtures pinning what the built-in parsers make of real tool output.
+//!
+//! Each format has a directory `<dir>/<format>/` named like `--format`, with an
+//! `input` of lint output lines as the parser sees them, after any `--input-format`,
+//! and an `expected.json` array of the findings parsed from them in order. A finding
+//! is written with the fields the parser set: those left as
+//! [`Diagnostic::new`] has them, the line itself and the format's own name aren't.
+//! Lines of the input without findings, like totals and source snippets, pin that the
+//! parser skips them.
+//!
+//! A parser for a new tool is covered by dropping its pair of files in; with `bless`,
+//! the runner writes the findings parsed as `expected.json` instead of checking them,
+//! to review like any other change.
+
+use crate::diagnostic::Diagnostic;
+use crate::parsers::{FormatSpec, Parsers};
+use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// A format whose parser doesn't parse its fixture as expected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    pub format: String,
+    pub message: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.format, self.message)
+    }
+}
+
+/// The fields of `diagnostic` set by the format `name`
+fn projection(diagnostic: &Diagnostic, name: &str) -> Value {
+    let defaults = serde_json::to_value(Diagnostic::new("", None)).unwrap();
+    let mut fields = match serde_json::to_value(diagnostic).unwrap() {
+        Value::Object(fields) => fields,
+        _ => unreachable!("diagnostics serialize as objects"),
+    };
+    fields.retain(|key, value| {
+        key != "raw" && Some(&*value) != defaults.get(key) && !(key == "tool" && value == name)
+    });
+    Value::Object(fields)
+}
+
+/// What the built-in format `name` parses from the lines of `input`
+pub fn findings(name: &str, input: &str) -> Result<Vec<Value>, String> {
+    let format = match name.parse() {
+        Ok(FormatSpec::Builtin(format)) => format,
+        _ => return Err(format!("'{}' isn't a built-in format", name)),
+    };
+    let parsers = Parsers::new(&[format], HashMap::new());
+    Ok(input
+        .lines()
+        .flat_map(|line| parsers.parse(line))
+        .map(|diagnostic| projection(&diagnostic, name))
+        .collect())
+}
+
+/// How the `actual` findings differ from the `expected` ones, if they do
+fn compare(expected: &[Value], actual: &[Value]) -> Option<String> {
+    let differing = expected.iter().zip(actual).position(|(a, b)| a != b);
+    if let Some(index) = differing {
+        return Some(format!(
+            "finding {} is {}, expected {}",
+            index + 1,
+            actual[index],
+            expected[index]
+        ));
+    }
+    match actual.len().cmp(&expected.len()) {
+        std::cmp::Ordering::Equal => None,
+        std::cmp::Ordering::Greater => {
+            Some(format!("unexpected finding {}", actual[expected.len()]))
+        }
+        std::cmp::Ordering::Less => Some(format!("missing finding {}", expected[actual.len()])),
+    }
+}
+
+/// Checks the parser of each format with a directory in `dir` against its fixture,
+/// or writes what it parses as the fixture when `bless`ing
+pub fn run(dir: &Path, bless: bool) -> Result<Vec<Failure>> {
+    let mut formats = fs::read_dir(dir)
+        .with_context(|| format!("Unable to read fixtures {}", dir.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    formats.retain(|path| path.is_dir());
+    formats.sort();
+    let mut failures = Vec::new();
+    for path in formats {
+        let format = path.file_name().unwrap().to_string_lossy().into_owned();
+        let input = path.join("input");
+        let input = fs::read_to_string(&input)
+            .with_context(|| format!("Unable to read {}", input.display()))?;
+        let fail = |message| Failure {
+            format: format.clone(),
+            message,
+        };
+        let actual = match findings(&format, &input) {
+            Ok(actual) => actual,
+            Err(message) => {
+                failures.push(fail(message));
+                continue;
+            }
+        };
+        let expected_path = path.join("expected.json");
+        if bless {
+            let text = serde_json::to_string_pretty(&actual)? + "\n";
+            fs::write(&expected_path, text)
+                .with_context(|| format!("Unable to write {}", expected_path.display()))?;
+            continue;
+        }
+        let expected = fs::read_to_string(&expected_path)
+            .map_err(anyhow::Error::from)
+            .and_then(|text| Ok(serde_json::from_str::<Vec<Map<String, Value>>>(&text)?))
+            .with_context(|| format!("Unable to read {}", expected_path.display()))?;
+        let expected: Vec<_> = expected.into_iter().map(Value::Object).collect();
+        failures.extend(compare(&expected, &actual).map(fail));
+    }
+    Ok(failures)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::conformance::{findings, run};
+    use serde_json::json;
+    use std::fs;
+    use std::path::Path;
+
+    /// `DIFF_FORMAT_BLESS=1 cargo test -p diff-format-core` rewrites the fixtures
+    #[test]
+    fn test_fixtures() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let bless = std::env::var_os("DIFF_FORMAT_BLESS").is_some();
+        let failures: Vec<_> = run(&dir, bless)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn test_mismatches() {
+        assert_eq!(
+            findings("codespell", "./a.md:2: teh ==> the\nnothing here\n").unwrap(),
+            [json!({"path": "a.md", "line": 2, "message": "teh ==> the"})]
+        );
+        assert!(findings("mylint", "").is_err());
+
+        let dir = std::env::temp_dir().join(format!("diff-format-fixtures-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("codespell")).unwrap();
+        fs::create_dir_all(dir.join("mylint")).unwrap();
+        fs::write(dir.join("codespell/input"), "./a.md:2: teh ==> the\n").unwrap();
+        fs::write(dir.join("mylint/input"), "").unwrap();
+        fs::write(
+            dir.join("codespell/expected.json"),
+            r#"[{"path": "a.md", "line": 3, "message": "teh ==> the"}]"#,
+        )
+        .unwrap();
+        let failures: Vec<_> = run(&dir, false)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            failures,
+            [
+                r#"codespell: finding 1 is {"line":2,"message":"teh ==> the","path":"a.md"}, expected {"line":3,"message":"teh ==> the","path":"a.md"}"#,
+                "mylint: 'mylint' isn't a built-in format",
+            ]
+        );
+        assert!(run(&dir, true).unwrap().len() == 1);
+        assert!(run(&dir, false).unwrap().len() == 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,260 @@
+use crate::paths;
+#[cfg(feature = "clap")]
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "clap", derive(ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+
+    /// Reads the severity labels tools print, e.g. shellcheck's `style` or clang's `note`
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label.to_ascii_lowercase().as_str() {
+            "error" | "err" | "e" | "fatal" | "critical" => Some(Severity::Error),
+            "warning" | "warn" | "w" => Some(Severity::Warning),
+            "info" | "information" | "i" | "note" | "hint" | "style" | "convention" => {
+                Some(Severity::Info)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Rule code prefixes shared by flake8 plugins and pylint message categories
+const BUILTIN_SEVERITIES: &[(&str, Severity)] = &[
+    ("E", Severity::Error),
+    ("F", Severity::Error),
+    ("W", Severity::Warning),
+    ("C", Severity::Info),
+    ("R", Severity::Info),
+];
+
+/// Guesses the severity of a rule code from its longest matching prefix, checking
+/// `overrides` before the built-in flake8/pylint conventions
+pub fn infer_severity(rule: &str, overrides: &HashMap<String, Severity>) -> Option<Severity> {
+    let longest = overrides
+        .iter()
+        .filter(|(prefix, _)| rule.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, &severity)| severity);
+    longest.or_else(|| {
+        // Only codes shaped like flake8/pylint ones, e.g. `E501` or `C0301`
+        let letters = rule.trim_end_matches(|c: char| c.is_ascii_digit());
+        if letters.len() != 1 || letters.len() == rule.len() {
+            return None;
+        }
+        BUILTIN_SEVERITIES
+            .iter()
+            .find(|(prefix, _)| *prefix == letters)
+            .map(|&(_, severity)| severity)
+    })
+}
+
+/// The unit a tool counts columns in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ColumnUnit {
+    /// Unicode scalar values, the canonical unit
+    #[default]
+    Char,
+    Utf8Byte,
+    /// UTF-16 code units, used by LSP and some SARIF producers
+    Utf16,
+}
+
+/// A single-line edit suggested by the tool, starting at the diagnostic's column
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fix {
+    /// Number of characters replaced
+    pub length: u32,
+    pub replacement: String,
+}
+
+impl Fix {
+    /// Applies the fix to `line`, given the 1-based character column it starts at
+    pub fn apply(&self, line: &str, column: u32) -> String {
+        let start = column.saturating_sub(1) as usize;
+        let mut fixed: String = line.chars().take(start).collect();
+        fixed.push_str(&self.replacement);
+        fixed.extend(line.chars().skip(start + self.length as usize));
+        fixed
+    }
+}
+
+/// Another location a finding refers to, such as a `note:` a compiler prints after an
+/// error to point at the earlier declaration it conflicts with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelatedLocation {
+    pub path: String,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub message: Option<String>,
+}
+
+/// A single finding extracted from lint output
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Diagnostic {
+    pub path: String,
+    /// `None` for file-level findings (e.g. a typo in the file name)
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    /// Last line of a finding spanning several, inclusive
+    pub end_line: Option<u32>,
+    /// Column the finding ends at, on `end_line` if given: exclusive, in `column_unit`
+    pub end_column: Option<u32>,
+    /// Unit `column` and `end_column` are counted in, until normalized by the location resolver
+    pub column_unit: ColumnUnit,
+    /// Byte offset into the file, for tools that report no line
+    pub offset: Option<usize>,
+    pub severity: Option<Severity>,
+    /// Rule or check code, e.g. `E501`
+    pub rule: Option<String>,
+    pub message: Option<String>,
+    pub fix: Option<Fix>,
+    /// Source text quoted by the tool, used to re-locate stale positions
+    pub excerpt: Option<String>,
+    /// Function or test the finding is about, for tools that report no line
+    pub symbol: Option<String>,
+    /// Name of the format that produced this finding
+    pub tool: Option<String>,
+    /// Language of the changed file the finding is in, as detected with the hunk map
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Other locations the finding refers to; they travel with it but aren't matched
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub related: Vec<RelatedLocation>,
+    /// The lint output line this was parsed from, stripped of colors
+    pub raw: String,
+}
+
+impl Diagnostic {
+    /// `path` is normalized, as tools spell the same file in different ways
+    pub fn new(path: impl AsRef<str>, line: Option<u32>) -> Self {
+        Diagnostic {
+            path: paths::normalize_path(path.as_ref()),
+            line,
+            column: None,
+            end_line: None,
+            end_column: None,
+            column_unit: ColumnUnit::Char,
+            offset: None,
+            severity: None,
+            rule: None,
+            message: None,
+            fix: None,
+            excerpt: None,
+            symbol: None,
+            tool: None,
+            language: None,
+            related: Vec::new(),
+            raw: String::new(),
+        }
+    }
+
+    /// Findings without an explicit severity still fail the gate, so treat them as errors
+    pub fn effective_severity(&self) -> Severity {
+        self.severity.unwrap_or(Severity::Error)
+    }
+
+    /// First and last line of the finding, if it has a line
+    pub fn span(&self) -> Option<(u32, u32)> {
+        let line = self.line?;
+        Some((line, self.end_line.unwrap_or(line).max(line)))
+    }
+
+    /// The human readable part of the finding, falling back to the whole line
+    pub fn text(&self) -> &str {
+        self.message.as_deref().unwrap_or(&self.raw)
+    }
+
+    /// Whether this is a compiler's `note:` about the finding before it, as in
+    /// `a.c:3:5: note: previous definition is here`
+    pub fn is_note(&self) -> bool {
+        self.message
+            .as_deref()
+            .is_some_and(|message| message.starts_with("note:"))
+            || self.raw.contains(": note: ")
+    }
+
+    /// Keeps the location and message of `note` as related to this finding
+    pub fn relate(&mut self, note: &Diagnostic) {
+        let message = note.message.as_deref().map(|message| {
+            message
+                .strip_prefix("note:")
+                .map_or(message, str::trim_start)
+                .to_string()
+        });
+        self.related.push(RelatedLocation {
+            path: note.path.clone(),
+            line: note.line,
+            column: note.column,
+            message,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::{infer_severity, Diagnostic, RelatedLocation, Severity};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_infer_severity() {
+        let none = HashMap::new();
+        assert_eq!(infer_severity("F401", &none), Some(Severity::Error));
+        assert_eq!(infer_severity("W605", &none), Some(Severity::Warning));
+        assert_eq!(infer_severity("C0301", &none), Some(Severity::Info));
+        assert_eq!(infer_severity("SIM102", &none), None);
+        assert_eq!(infer_severity("E", &none), None);
+
+        let overrides = vec![
+            ("E".to_string(), Severity::Warning),
+            ("E9".to_string(), Severity::Error),
+            ("SIM".to_string(), Severity::Info),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(infer_severity("E501", &overrides), Some(Severity::Warning));
+        assert_eq!(infer_severity("E999", &overrides), Some(Severity::Error));
+        assert_eq!(infer_severity("SIM102", &overrides), Some(Severity::Info));
+    }
+
+    #[test]
+    fn test_relate() {
+        let mut error = Diagnostic::new("a.c", Some(10));
+        error.message = Some("error: redefinition of 'x'".to_string());
+        let mut note = Diagnostic::new("./a.c", Some(3));
+        note.column = Some(5);
+        note.message = Some("note: previous definition is here".to_string());
+        assert!(note.is_note() && !error.is_note());
+        let mut labelled = Diagnostic::new("b.h", Some(1));
+        labelled.raw = "b.h:1:1: note: declared here".to_string();
+        assert!(labelled.is_note());
+
+        error.relate(&note);
+        assert_eq!(
+            error.related,
+            [RelatedLocation {
+                path: "a.c".to_string(),
+                line: Some(3),
+                column: Some(5),
+                message: Some("previous definition is here".to_string()),
+            }]
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! Mapping of lines reported relative to an embedded code block (a Vue SFC `<script>`, a
+//! Markdown code fence) to absolute file lines.
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmbeddedMode {
+    /// Lines are relative to the first block
+    #[default]
+    First,
+    /// Lines count through the contents of all blocks, back to back
+    Concatenated,
+}
+
+/// `[embedded.<extension>]` config section
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmbeddedConfig {
+    /// Regex matching the line that opens a block
+    pub start: String,
+    /// Regex matching the line that closes a block
+    pub end: String,
+    #[serde(default)]
+    pub mode: EmbeddedMode,
+    /// Only remap diagnostics from these formats, all of them if unset
+    pub tools: Option<Vec<String>>,
+}
+
+pub struct EmbeddedBlocks {
+    start: Regex,
+    end: Regex,
+    mode: EmbeddedMode,
+    tools: Option<Vec<String>>,
+}
+
+impl EmbeddedBlocks {
+    pub fn new(config: &EmbeddedConfig) -> Result<Self> {
+        Ok(EmbeddedBlocks {
+            start: Regex::new(&config.start)
+                .with_context(|| format!("Invalid block start regex '{}'", config.start))?,
+            end: Regex::new(&config.end)
+                .with_context(|| format!("Invalid block end regex '{}'", config.end))?,
+            mode: config.mode,
+            tools: config.tools.clone(),
+        })
+    }
+
+    pub fn applies_to(&self, tool: Option<&str>) -> bool {
+        match (&self.tools, tool) {
+            (None, _) => true,
+            (Some(tools), Some(tool)) => tools.iter().any(|t| t == tool),
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Returns the 1-based (first, last) content lines of each block
+    fn blocks<'a>(&self, lines: impl Iterator<Item = &'a str>) -> Vec<(u32, u32)> {
+        let mut blocks = Vec::new();
+        let mut open = None;
+        for (index, line) in lines.enumerate() {
+            let line_num = index as u32 + 1;
+            match open {
+                None if self.start.is_match(line) => open = Some(line_num + 1),
+                Some(first) if self.end.is_match(line) => {
+                    if line_num > first {
+                        blocks.push((first, line_num - 1));
+                    }
+                    open = None;
+                }
+                _ => {}
+            }
+        }
+        blocks
+    }
+
+    /// Maps a block-relative line to an absolute one, if it falls in a block
+    pub fn absolute_line<'a>(
+        &self,
+        lines: impl Iterator<Item = &'a str>,
+        relative: u32,
+    ) -> Option<u32> {
+        let blocks = self.blocks(lines);
+        match self.mode {
+            EmbeddedMode::First => blocks.first().map(|&(first, _)| first + relative - 1),
+            EmbeddedMode::Concatenated => {
+                let mut remaining = relative;
+                for (first, last) in blocks {
+                    let length = last - first + 1;
+                    if remaining <= length {
+                        return Some(first + remaining - 1);
+                    }
+                    remaining -= length;
+                }
+                None
+            }
+        }
+    }
+}
+
+pub fn extension(path: &str) -> Option<&str> {
+    Path::new(path).extension()?.to_str()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::embedded::{EmbeddedBlocks, EmbeddedConfig, EmbeddedMode};
+
+    fn blocks(start: &str, end: &str, mode: EmbeddedMode) -> EmbeddedBlocks {
+        EmbeddedBlocks::new(&EmbeddedConfig {
+            start: start.to_string(),
+            end: end.to_string(),
+            mode,
+            tools: None,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_vue_script_block() {
+        let source =
+            "<template>\n  <p/>\n</template>\n<script>\nconst a = 1\nconst b = 2\n</script>\n";
+        let vue = blocks("^<script", "^</script>", EmbeddedMode::First);
+        assert_eq!(vue.absolute_line(source.lines(), 1), Some(5));
+        assert_eq!(vue.absolute_line(source.lines(), 2), Some(6));
+    }
+
+    #[test]
+    fn test_concatenated_fences() {
+        let source = "# Doc\n```py\na = 1\nb = 2\n```\ntext\n```py\nc = 3\n```\n";
+        let markdown = blocks(r"^```py", r"^```\s*$", EmbeddedMode::Concatenated);
+        assert_eq!(markdown.absolute_line(source.lines(), 2), Some(4));
+        assert_eq!(markdown.absolute_line(source.lines(), 3), Some(8));
+        assert_eq!(markdown.absolute_line(source.lines(), 4), None);
+    }
+}
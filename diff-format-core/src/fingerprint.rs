@@ -0,0 +1,144 @@
+//! Stable identifiers for findings, for correlating them across tools and runs.
+//!
+//! The algorithm is part of the interface and never changes: 64-bit FNV-1a over the UTF-8
+//! bytes of each part followed by a NUL byte, printed as 16 lowercase hex digits. A
+//! finding's parts are its path, its rule (empty if none) and its message, but not its
+//! line, so fingerprints survive unrelated edits above it.
+//!
+//! Reports and publishers use [`finding_fingerprint`]; GitLab code quality reports hash
+//! the trimmed source line of the finding in place of its message, which
+//! `fingerprint --line` does too. Baselines keep a format of their own.
+
+#[cfg(feature = "clap")]
+use anyhow::{Context, Result};
+#[cfg(feature = "clap")]
+use clap::Args;
+#[cfg(feature = "clap")]
+use std::{fs, path::Path};
+
+#[cfg(feature = "clap")]
+#[derive(Args, Debug)]
+pub struct FingerprintArgs {
+    /// Path of the finding, relative to the repository root
+    #[arg(long)]
+    file: String,
+
+    /// Line of the finding, to hash its source line rather than the message as GitLab
+    /// code quality reports do; the message is hashed if the file has no such line
+    #[arg(long)]
+    line: Option<u32>,
+
+    /// Rule or check code, if the tool reports one
+    #[arg(long)]
+    rule: Option<String>,
+
+    /// Message as the tool reports it
+    #[arg(long)]
+    message: String,
+}
+
+#[cfg(feature = "clap")]
+impl FingerprintArgs {
+    /// The fingerprint of the finding, reading its line from the file under `root`
+    pub fn fingerprint(&self, root: &Path) -> Result<String> {
+        let source_line = match self.line {
+            Some(line) => {
+                let path = root.join(&self.file);
+                let text = fs::read_to_string(&path)
+                    .with_context(|| format!("Unable to read {}", path.display()))?;
+                (line as usize)
+                    .checked_sub(1)
+                    .and_then(|index| text.lines().nth(index))
+                    .map(|source| source.trim().to_string())
+            }
+            None => None,
+        };
+        let content = source_line.as_deref().unwrap_or(&self.message);
+        Ok(finding_fingerprint(
+            &self.file,
+            self.rule.as_deref(),
+            content,
+        ))
+    }
+}
+
+/// Stable identifier for a finding that survives line shifts.
+///
+/// Uses 64-bit FNV-1a rather than `DefaultHasher`, whose output may change between Rust releases.
+pub fn fingerprint(parts: &[&str]) -> String {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let mut hash = OFFSET_BASIS;
+    for part in parts {
+        // Separate parts so ("ab", "c") and ("a", "bc") differ
+        for byte in part.bytes().chain(std::iter::once(0)) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(PRIME);
+        }
+    }
+    format!("{:016x}", hash)
+}
+
+/// The fingerprint reports and publishers attach to a finding
+pub fn finding_fingerprint(path: &str, rule: Option<&str>, message: &str) -> String {
+    fingerprint(&[path, rule.unwrap_or_default(), message])
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fingerprint::{finding_fingerprint, fingerprint};
+
+    #[test]
+    fn test_fingerprint_is_stable() {
+        assert_eq!(fingerprint(&[]), "cbf29ce484222325");
+        assert_eq!(
+            fingerprint(&["src/a.py", "E501"]),
+            fingerprint(&["src/a.py", "E501"])
+        );
+        assert_ne!(fingerprint(&["ab", "c"]), fingerprint(&["a", "bc"]));
+    }
+
+    #[test]
+    fn test_finding_fingerprint_is_frozen() {
+        // Published values, which must never change
+        assert_eq!(
+            finding_fingerprint("src/a.py", Some("E501"), "E501 Line too long"),
+            "5877876416e9b35a"
+        );
+        assert_eq!(
+            finding_fingerprint("src/a.py", None, "x"),
+            fingerprint(&["src/a.py", "", "x"])
+        );
+    }
+
+    #[cfg(feature = "clap")]
+    #[test]
+    fn test_fingerprint_args() {
+        use crate::fingerprint::FingerprintArgs;
+
+        let root =
+            std::env::temp_dir().join(format!("diff-format-fingerprint-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("a.py"), "x = 1\n  long_line = 2\n").unwrap();
+        let args = |line| FingerprintArgs {
+            file: "a.py".to_string(),
+            line,
+            rule: Some("E501".to_string()),
+            message: "E501 Line too long".to_string(),
+        };
+        assert_eq!(
+            args(Some(2)).fingerprint(&root).unwrap(),
+            finding_fingerprint("a.py", Some("E501"), "long_line = 2")
+        );
+        // Past the end of the file, and without --line, the message is hashed
+        for line in [Some(9), None] {
+            assert_eq!(
+                args(line).fingerprint(&root).unwrap(),
+                finding_fingerprint("a.py", Some("E501"), "E501 Line too long")
+            );
+        }
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,41 @@
+//! The parts of diff-format other tools build on: hunk maps of changed lines, matching
+//! findings against them, the [`Diagnostic`](diagnostic::Diagnostic) model and the
+//! parsers of lint output.
+//!
+//! ```
+//! use diff_format_core::matcher::MatchPolicy;
+//! use diff_format_core::parsers::{Format, Parsers};
+//! use diff_format_core::{is_changed, HunkMap};
+//! use std::collections::HashMap;
+//!
+//! let file_hunks: HunkMap = vec![("a.py".to_string(), vec![(10, 12)])]
+//!     .into_iter()
+//!     .collect();
+//! let parsers = Parsers::new(&[Format::Python], HashMap::new());
+//! let diagnostic = &parsers.parse("a.py:11:1: E501 line too long")[0];
+//! assert!(is_changed(&file_hunks, diagnostic, MatchPolicy::Line));
+//! ```
+//!
+//! This crate is versioned apart from the `diff-format` command line and follows
+//! semver: the types and functions exported here only break in a major release. New
+//! formats and finding fields are not breaking, as [`Format`](parsers::Format) and
+//! [`Diagnostic`](diagnostic::Diagnostic) are non-exhaustive; build diagnostics with
+//! [`Diagnostic::new`](diagnostic::Diagnostic::new). The `clap` feature derives the
+//! argument parsing the command line uses, and is off by default.
+
+#[cfg(test)]
+mod conformance;
+pub mod diagnostic;
+pub mod embedded;
+pub mod fingerprint;
+pub mod manifest;
+pub mod matcher;
+pub mod parsers;
+pub mod paths;
+pub mod ranges;
+
+pub use matcher::{is_changed, is_number_in_sorted_ranges, overlaps_sorted_ranges};
+pub use ranges::HunkMap;
+
+/// Changed lines on the new side, checked inclusively at both ends
+pub type HunkRange = (u32, u32);
@@ -0,0 +1,242 @@
+//! Points Kubernetes manifest findings, which name a resource and a field instead of a
+//! line, at the line of that field.
+//!
+//! Parsers put the resource and field in the diagnostic's symbol as
+//! `<Kind>/<name><JSON pointer>`, e.g. `Deployment/web/spec/template/spec/containers/0`.
+//! The YAML is walked by indentation only, which covers block style manifests; fields
+//! in flow style (`{...}`) are located at their parent.
+
+use crate::diagnostic::Diagnostic;
+use log::debug;
+use std::fs;
+use std::path::Path;
+
+pub fn is_manifest(path: &str) -> bool {
+    path.ends_with(".yaml") || path.ends_with(".yml")
+}
+
+/// The symbol for a field of a resource, `pointer` being empty or starting with `/`
+pub fn symbol(kind: &str, name: &str, pointer: &str) -> String {
+    format!("{}/{}{}", kind, name, pointer)
+}
+
+/// Turns kubeval's `spec.containers.0.image` into a JSON pointer
+pub fn dotted_pointer(field: &str) -> String {
+    if field == "(root)" {
+        return String::new();
+    }
+    field
+        .split('.')
+        .map(|segment| format!("/{}", segment.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+#[derive(Clone)]
+struct Entry<'a> {
+    line: u32,
+    column: usize,
+    text: &'a str,
+}
+
+/// The line of the field, or of the resource's `kind` when the field can't be found
+fn find_line(source: &str, kind: &str, name: &str, pointer: &[String]) -> Option<u32> {
+    let mut documents = vec![Vec::new()];
+    for (index, line) in source.lines().enumerate() {
+        let text = line.trim_start();
+        if line.starts_with("---") {
+            documents.push(Vec::new());
+        } else if !text.is_empty() && !text.starts_with('#') {
+            documents.last_mut().unwrap().push(Entry {
+                line: index as u32 + 1,
+                column: line.len() - text.len(),
+                text,
+            });
+        }
+    }
+    let value = |document: &[Entry<'_>], path: &[&str]| -> Option<String> {
+        let mut scope = document.to_vec();
+        let mut found = None;
+        for segment in path {
+            let (entry, children) = step(&scope, segment)?;
+            found = Some(entry);
+            scope = children;
+        }
+        key_value(found?.text).map(|(_, value)| unquote(value).to_string())
+    };
+    // kubeval prefixes names with the namespace
+    let short_name = name.split_once('.').map(|(_, name)| name);
+    let document = documents.iter().find(|document| {
+        value(document, &["kind"]).as_deref() == Some(kind) && {
+            let found = value(document, &["metadata", "name"]);
+            found.as_deref() == Some(name) || found.as_deref() == short_name
+        }
+    })?;
+    let (mut line, _) = step(document, "kind")?;
+    let mut scope = document.clone();
+    for segment in pointer {
+        match step(&scope, segment) {
+            Some((entry, children)) => {
+                line = entry;
+                scope = children;
+            }
+            None => break,
+        }
+    }
+    Some(line.line)
+}
+
+/// The entry for a mapping key or list index among `scope`, with the entries nested in it
+fn step<'a>(scope: &[Entry<'a>], segment: &str) -> Option<(Entry<'a>, Vec<Entry<'a>>)> {
+    let base = scope.first()?.column;
+    let at_base = |entry: &Entry<'_>| entry.column == base;
+    let is_item = |entry: &Entry<'_>| entry.text == "-" || entry.text.starts_with("- ");
+    let (index, entry) = match segment.parse::<usize>() {
+        Ok(item) => scope
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| at_base(entry) && is_item(entry))
+            .nth(item)?,
+        Err(_) => scope.iter().enumerate().find(|(_, entry)| {
+            at_base(entry) && key_value(entry.text).is_some_and(|(key, _)| key == segment)
+        })?,
+    };
+    let mut children = Vec::new();
+    let rest = &scope[index + 1..];
+    let nested = if is_item(entry) {
+        // An item's first key shares its line, e.g. `- name: web`
+        let text = entry.text[1..].trim_start();
+        if !text.is_empty() {
+            children.push(Entry {
+                line: entry.line,
+                column: base + entry.text.len() - text.len(),
+                text,
+            });
+        }
+        rest.iter().take_while(|entry| entry.column > base).count()
+    } else {
+        // A list may sit at the same indentation as its key
+        let empty = key_value(entry.text).is_some_and(|(_, value)| value.is_empty());
+        rest.iter()
+            .take_while(|entry| entry.column > base || (empty && at_base(entry) && is_item(entry)))
+            .count()
+    };
+    children.extend_from_slice(&rest[..nested]);
+    Some((entry.clone(), children))
+}
+
+fn key_value(text: &str) -> Option<(&str, &str)> {
+    let end = text
+        .find(": ")
+        .or_else(|| text.strip_suffix(':').map(str::len))?;
+    let value = text[end + 1..].trim();
+    // Drop trailing comments from plain values
+    let value = match value.find(" #") {
+        Some(comment) if !value.starts_with(['"', '\'']) => value[..comment].trim_end(),
+        _ => value,
+    };
+    Some((unquote(&text[..end]), value))
+}
+
+fn unquote(text: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = text
+            .strip_prefix(quote)
+            .and_then(|text| text.strip_suffix(quote))
+        {
+            return inner;
+        }
+    }
+    text
+}
+
+/// Sets the line of a manifest finding that has a symbol but no line
+pub fn locate(workdir: &Path, diagnostic: &mut Diagnostic) {
+    let symbol = match (&diagnostic.symbol, diagnostic.line) {
+        (Some(symbol), None) => symbol,
+        _ => return,
+    };
+    let mut segments = symbol.splitn(3, '/');
+    let (kind, name) = match (segments.next(), segments.next()) {
+        (Some(kind), Some(name)) => (kind, name),
+        _ => return,
+    };
+    let pointer: Vec<String> = segments
+        .next()
+        .into_iter()
+        .flat_map(|pointer| pointer.split('/'))
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+        .collect();
+    let source = match fs::read_to_string(workdir.join(&diagnostic.path)) {
+        Ok(source) => source,
+        Err(err) => {
+            debug!(
+                "Can't read '{}' to find {}: {}",
+                diagnostic.path, symbol, err
+            );
+            return;
+        }
+    };
+    diagnostic.line = find_line(&source, kind, name, &pointer);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::manifest::{dotted_pointer, find_line};
+
+    const MANIFEST: &str = "\
+apiVersion: v1
+kind: Service
+metadata:
+  name: web
+---
+# The app itself
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: \"web\"
+spec:
+  replicas: \"3\"  # quoted by mistake
+  template:
+    spec:
+      containers:
+      - name: web
+        image: nginx
+      - name: sidecar
+        ports:
+          - containerPort: http
+";
+
+    #[test]
+    fn test_find_line() {
+        let find = |kind, pointer: &str| {
+            let pointer: Vec<String> = dotted_pointer(pointer)
+                .split('/')
+                .skip(1)
+                .map(str::to_string)
+                .collect();
+            find_line(MANIFEST, kind, "web", &pointer)
+        };
+        assert_eq!(find("Deployment", "spec.replicas"), Some(12));
+        assert_eq!(
+            find("Deployment", "spec.template.spec.containers.0.image"),
+            Some(17)
+        );
+        assert_eq!(
+            find("Deployment", "spec.template.spec.containers.1"),
+            Some(18)
+        );
+        assert_eq!(
+            find(
+                "Deployment",
+                "spec.template.spec.containers.1.ports.0.containerPort"
+            ),
+            Some(20)
+        );
+        // Missing fields fall back to the deepest one found
+        assert_eq!(find("Deployment", "spec.strategy"), Some(11));
+        assert_eq!(find("Deployment", "spec.template.spec.volumes"), Some(14));
+        assert_eq!(find("Service", "(root)"), Some(2));
+        assert_eq!(find("ConfigMap", "data"), None);
+    }
+}
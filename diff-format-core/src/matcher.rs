@@ -0,0 +1,143 @@
+//! Whether a finding is on a change: the hunk ranges a diff touched, looked up by
+//! line, span, hunk, file or directory.
+
+use crate::diagnostic::Diagnostic;
+use crate::ranges::HunkMap;
+use crate::HunkRange;
+use serde::Deserialize;
+
+/// How close to a change a diagnostic has to be to count as new
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchPolicy {
+    /// On a changed line
+    #[default]
+    Line,
+    /// Within a hunk's surrounding context lines
+    Hunk,
+    /// Anywhere in a changed file
+    File,
+    /// On a directory with a changed file under it, for tools that report against
+    /// directories; findings on files match like `file`
+    Directory,
+}
+
+/// Context lines around a hunk for the `hunk` match policy, as in `git diff`'s default
+const HUNK_CONTEXT: u32 = 3;
+
+/// Whether `number` is in one of `ranges`, which must be sorted and non-overlapping
+pub fn is_number_in_sorted_ranges(ranges: &[HunkRange], number: u32) -> bool {
+    let mut low = 0;
+    let mut high = ranges.len();
+
+    while low < high {
+        let mid = low + (high - low) / 2;
+        match (number >= ranges[mid].0, number <= ranges[mid].1) {
+            (true, true) => return true,    // Number is within the current range
+            (true, false) => low = mid + 1, // Number is greater than the current range, search in the right half
+            (false, _) => high = mid, // Number is less than the current range, search in the left half
+        }
+    }
+
+    false
+}
+
+/// Whether any line from `start` to `end` is in one of `ranges`, which must be sorted
+/// and non-overlapping
+pub fn overlaps_sorted_ranges(ranges: &[HunkRange], start: u32, end: u32) -> bool {
+    // The first range not ending before the span
+    let index = ranges.partition_point(|&(_, range_end)| range_end < start);
+    ranges
+        .get(index)
+        .is_some_and(|&(range_start, _)| range_start <= end)
+}
+
+/// Whether `directory`, with or without a trailing `/`, is a changed file of
+/// `file_hunks` or has one under it
+fn has_changed_file(file_hunks: &HunkMap, directory: &str) -> bool {
+    let directory = directory.trim_end_matches('/');
+    if directory.is_empty() || directory == "." {
+        return !file_hunks.is_empty();
+    }
+    file_hunks.contains_key(directory)
+        || file_hunks.keys().any(|path| {
+            path.strip_prefix(directory)
+                .is_some_and(|rest| rest.starts_with('/'))
+        })
+}
+
+/// Whether `diagnostic` is on a change in `file_hunks`, as close as `policy` requires.
+/// Findings spanning several lines are on a change if any of their lines is
+pub fn is_changed(file_hunks: &HunkMap, diagnostic: &Diagnostic, policy: MatchPolicy) -> bool {
+    match (file_hunks.get(&diagnostic.path), diagnostic.span(), policy) {
+        (_, _, MatchPolicy::Directory) => has_changed_file(file_hunks, &diagnostic.path),
+        (Some(_), _, MatchPolicy::File) => true,
+        (Some(hunk_ranges), Some((start, end)), MatchPolicy::Line) => {
+            overlaps_sorted_ranges(hunk_ranges, start, end)
+        }
+        (Some(hunk_ranges), Some((start, end)), MatchPolicy::Hunk) => {
+            hunk_ranges.iter().any(|&(hunk_start, hunk_end)| {
+                end + HUNK_CONTEXT >= hunk_start && start <= hunk_end.saturating_add(HUNK_CONTEXT)
+            })
+        }
+        // File-level findings match any changed file
+        (Some(_), None, _) => true,
+        (None, _, _) => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::Diagnostic;
+    use crate::matcher::{is_changed, MatchPolicy};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_match_policies() {
+        let file_hunks: HashMap<_, _> = vec![("a.py".to_string(), vec![(10, 12)])]
+            .into_iter()
+            .collect();
+        let at = |path: &str, line| Diagnostic::new(path, Some(line));
+
+        assert!(is_changed(&file_hunks, &at("a.py", 11), MatchPolicy::Line));
+        assert!(!is_changed(&file_hunks, &at("a.py", 14), MatchPolicy::Line));
+        assert!(is_changed(&file_hunks, &at("a.py", 14), MatchPolicy::Hunk));
+        assert!(is_changed(&file_hunks, &at("a.py", 7), MatchPolicy::Hunk));
+        assert!(!is_changed(&file_hunks, &at("a.py", 16), MatchPolicy::Hunk));
+        assert!(is_changed(&file_hunks, &at("a.py", 100), MatchPolicy::File));
+        assert!(!is_changed(&file_hunks, &at("b.py", 11), MatchPolicy::File));
+
+        // Spans count if any of their lines is changed
+        let mut span = at("a.py", 5);
+        span.end_line = Some(10);
+        assert!(is_changed(&file_hunks, &span, MatchPolicy::Line));
+        span.end_line = Some(9);
+        assert!(!is_changed(&file_hunks, &span, MatchPolicy::Line));
+        assert!(is_changed(&file_hunks, &span, MatchPolicy::Hunk));
+        span.line = Some(13);
+        span.end_line = Some(20);
+        assert!(!is_changed(&file_hunks, &span, MatchPolicy::Line));
+    }
+
+    #[test]
+    fn test_directory_policy() {
+        let file_hunks: HashMap<_, _> = vec![("src/utils/a.py".to_string(), vec![(1, 1)])]
+            .into_iter()
+            .collect();
+        let changed = |path: &str| {
+            is_changed(
+                &file_hunks,
+                &Diagnostic::new(path, None),
+                MatchPolicy::Directory,
+            )
+        };
+        assert!(changed("src/utils/"));
+        assert!(changed("src/utils"));
+        assert!(changed("src"));
+        assert!(changed("./"));
+        assert!(changed("src/utils/a.py"));
+        assert!(!changed("src/util"));
+        assert!(!changed("src/utils/b.py"));
+        assert!(!changed("tests/"));
+    }
+}
@@ -0,0 +1,1110 @@
+use crate::diagnostic::{ColumnUnit, Diagnostic, Fix, Severity};
+use crate::embedded;
+use crate::fingerprint;
+use crate::manifest;
+use anyhow::{bail, Context, Result};
+#[cfg(feature = "clap")]
+use clap::ValueEnum;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::str::FromStr;
+use std::sync::LazyLock as Lazy;
+
+/// A named lint output format, selected with `--format <name>`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ParserProfile {
+    /// Regex with named groups, at least `file`; see the `RegexParser` for the others
+    pub regex: String,
+}
+
+/// Built-in lint output formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(ValueEnum))]
+#[non_exhaustive]
+pub enum Format {
+    /// `file:line` prefixed output (flake8, pylint, mypy...)
+    Python,
+    /// `typos --format brief`
+    Typos,
+    /// `typos --format json`
+    TyposJson,
+    /// codespell's default output
+    Codespell,
+    /// pytest's short test summary (`-r fE`)
+    Pytest,
+    /// actionlint's default output; the source snippets under each finding are skipped
+    Actionlint,
+    /// actionlint JSON lines, from
+    /// `-format '{{range $err := .}}{{json $err}}{{"\n"}}{{end}}'`
+    ActionlintJson,
+    /// kubeconform's default text output, located by resource and field
+    Kubeconform,
+    /// kubeval's default output, located by resource and field
+    Kubeval,
+    /// `black --check`; findings are about whole files
+    Black,
+    /// The files `rustfmt --check -l` lists; not tried by `auto`, as any line naming a
+    /// `.rs` file would match
+    Rustfmt,
+    /// The files `gofmt -l` lists; not tried by `auto`, like `rustfmt`
+    Gofmt,
+    /// The findings of a gitleaks JSON report, read with `--input-format json-array`
+    Gitleaks,
+    /// trufflehog's `--json` lines
+    Trufflehog,
+    /// Broken links, from lychee's `--format json` report read with `--input-format
+    /// lychee`, or markdown-link-check results with the `file` they were found in as
+    /// JSON lines; links reported without a line are looked up in the file
+    LinkCheck,
+    /// Duplicated code, from a jscpd or PMD CPD report read with `--input-format
+    /// duplicates`; each copy on changed lines names the others
+    Duplicates,
+    /// clang-tidy's diagnostics, from its `--export-fixes` YAML read with
+    /// `--input-format clang-tidy-fixes`; located by byte offset into the file
+    ClangTidyFixes,
+    /// Every format above, preferring the one configured for the file's extension
+    /// when several accept a line
+    Auto,
+}
+
+/// Shared between the threads of `--jobs`
+pub trait LintParser: Send + Sync {
+    fn parse(&self, line: &str) -> Option<Diagnostic>;
+}
+
+impl Format {
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Python => "python",
+            Format::Typos => "typos",
+            Format::TyposJson => "typos-json",
+            Format::Codespell => "codespell",
+            Format::Pytest => "pytest",
+            Format::Actionlint => "actionlint",
+            Format::ActionlintJson => "actionlint-json",
+            Format::Kubeconform => "kubeconform",
+            Format::Kubeval => "kubeval",
+            Format::Black => "black",
+            Format::Rustfmt => "rustfmt",
+            Format::Gofmt => "gofmt",
+            Format::Gitleaks => "gitleaks",
+            Format::Trufflehog => "trufflehog",
+            Format::LinkCheck => "link-check",
+            Format::Duplicates => "duplicates",
+            Format::ClangTidyFixes => "clang-tidy-fixes",
+            Format::Auto => "auto",
+        }
+    }
+
+    /// Secret scanners, whose findings `--always-report` keeps off the diff by default
+    pub fn is_secret_scanner(self) -> bool {
+        matches!(self, Format::Gitleaks | Format::Trufflehog)
+    }
+
+    pub fn parser(self) -> Box<dyn LintParser> {
+        match self {
+            Format::Python => Box::new(RegexParser::new(
+                r"(?P<file>.+?):(?P<line>\d+)(?::(?P<col>\d+))?(?:-(?P<end_line>\d+)(?::(?P<end_col>\d+))?)?(?::\s*(?P<message>(?:(?P<rule>[A-Z]+\d+)\b)?.*))?",
+            )),
+            // Lines are optional: typos also reports misspelled file names
+            Format::Typos => Box::new(RegexParser::new(
+                r"^(?P<file>.+?):(?:(?P<line>\d+):(?P<col>\d+):)? (?P<message>`(?P<typo>.*)` -> `(?P<correction>[^`]*)`|`.*` -> .*)$",
+            )),
+            Format::TyposJson => Box::new(TyposJsonParser),
+            Format::Codespell => Box::new(RegexParser::new(
+                r"^(?P<file>.+?):(?:(?P<line>\d+):)? (?P<message>.+ ==> .+)$",
+            )),
+            Format::Pytest => Box::new(RegexParser::new(
+                r"^(?:FAILED|ERROR) (?P<file>[^\s:]+\.py)(?:::(?P<symbol>\S+))?(?: - (?P<message>.*))?$",
+            )),
+            Format::Actionlint => Box::new(RegexParser::new(
+                r"^(?P<file>[^:]+\.ya?ml):(?P<line>\d+):(?P<col>\d+): (?P<message>.*) \[(?P<rule>[\w-]+)\]$",
+            )),
+            Format::ActionlintJson => Box::new(ActionlintJsonParser),
+            Format::Kubeconform => Box::new(KubeconformParser),
+            Format::Kubeval => Box::new(KubevalParser),
+            Format::Black => Box::new(RegexParser::new(
+                r"^(?P<message>would reformat) (?P<file>.+)$",
+            )),
+            Format::Rustfmt => Box::new(FileListParser::new("rs", "needs formatting with rustfmt")),
+            Format::Gofmt => Box::new(FileListParser::new("go", "needs formatting with gofmt")),
+            Format::Gitleaks => Box::new(GitleaksParser),
+            Format::Trufflehog => Box::new(TrufflehogParser),
+            Format::LinkCheck => Box::new(LinkCheckParser),
+            Format::Duplicates => Box::new(DuplicatesParser),
+            Format::ClangTidyFixes => Box::new(ClangTidyFixesParser),
+            Format::Auto => unreachable!("auto is expanded by Parsers"),
+        }
+    }
+}
+
+/// A `--format` value: a built-in format or the name of a `[parsers.<name>]` profile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormatSpec {
+    Builtin(Format),
+    Profile(String),
+}
+
+impl FromStr for FormatSpec {
+    type Err = Infallible;
+
+    /// Unknown names are taken as profiles, checked once the config is loaded
+    fn from_str(name: &str) -> Result<Self, Infallible> {
+        let format = AUTO_FORMATS
+            .iter()
+            .chain(&UNTRIED_FORMATS)
+            .find(|format| format.name() == name);
+        Ok(match format {
+            Some(&format) => FormatSpec::Builtin(format),
+            None => FormatSpec::Profile(name.to_string()),
+        })
+    }
+}
+
+impl FormatSpec {
+    pub fn name(&self) -> &str {
+        match self {
+            FormatSpec::Builtin(format) => format.name(),
+            FormatSpec::Profile(name) => name,
+        }
+    }
+}
+
+/// Formats tried by `auto`, most specific first since `python` accepts nearly any
+/// `file:line` prefix
+const AUTO_FORMATS: [Format; 15] = [
+    Format::Gitleaks,
+    Format::Trufflehog,
+    Format::LinkCheck,
+    Format::Duplicates,
+    Format::ClangTidyFixes,
+    Format::TyposJson,
+    Format::ActionlintJson,
+    Format::Typos,
+    Format::Codespell,
+    Format::Pytest,
+    Format::Actionlint,
+    Format::Kubeconform,
+    Format::Kubeval,
+    Format::Black,
+    Format::Python,
+];
+
+/// The other formats, only used when named
+const UNTRIED_FORMATS: [Format; 3] = [Format::Rustfmt, Format::Gofmt, Format::Auto];
+
+/// Which diagnostics to keep when several formats accept the same line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "clap", derive(ValueEnum))]
+pub enum ParserStrategy {
+    /// The first format in order, unless `[extensions]` prefers another
+    #[default]
+    First,
+    /// The format that extracted the most fields, e.g. a rule and a column
+    Best,
+    /// One diagnostic per accepting format
+    All,
+}
+
+/// The configured formats' parsers, tried in order
+pub struct Parsers {
+    /// Parsers by the name findings are attributed to
+    parsers: Vec<(String, Box<dyn LintParser>)>,
+    /// Format or profile name to prefer by file extension when several parse a line
+    extensions: HashMap<String, String>,
+    strategy: ParserStrategy,
+}
+
+impl Parsers {
+    /// Without an `extensions` preference, the first format accepting a line wins, which
+    /// keeps spellchecker findings in e.g. `.py` files with the spellchecker
+    pub fn new(formats: &[Format], extensions: HashMap<String, String>) -> Self {
+        let specs: Vec<_> = formats.iter().copied().map(FormatSpec::Builtin).collect();
+        Parsers::with_profiles(&specs, &HashMap::new(), extensions)
+            .expect("Built-in formats need no profiles")
+    }
+
+    /// Like [`Parsers::new`], also accepting the names of `profiles`
+    pub fn with_profiles(
+        specs: &[FormatSpec],
+        profiles: &HashMap<String, ParserProfile>,
+        extensions: HashMap<String, String>,
+    ) -> Result<Self> {
+        let mut expanded = Vec::new();
+        for spec in specs {
+            let added = match spec {
+                FormatSpec::Builtin(Format::Auto) => AUTO_FORMATS
+                    .iter()
+                    .map(|&format| FormatSpec::Builtin(format))
+                    .collect(),
+                _ => vec![spec.clone()],
+            };
+            for spec in added {
+                if !expanded.contains(&spec) {
+                    expanded.push(spec);
+                }
+            }
+        }
+        let mut parsers = Vec::new();
+        for spec in expanded {
+            let parser: Box<dyn LintParser> = match &spec {
+                FormatSpec::Builtin(format) => format.parser(),
+                FormatSpec::Profile(name) => match profiles.get(name) {
+                    Some(profile) => Box::new(
+                        RegexParser::profile(&profile.regex)
+                            .with_context(|| format!("Invalid [parsers.{}] regex", name))?,
+                    ),
+                    None => bail!(
+                        "Unknown format '{}', neither built in nor a [parsers.{}] profile",
+                        name,
+                        name
+                    ),
+                },
+            };
+            parsers.push((spec.name().to_string(), parser));
+        }
+        Ok(Parsers {
+            parsers,
+            extensions,
+            strategy: ParserStrategy::First,
+        })
+    }
+
+    pub fn with_strategy(self, strategy: ParserStrategy) -> Self {
+        Parsers { strategy, ..self }
+    }
+
+    /// Diagnostics on `line`, of which there is at most one unless the strategy is `all`.
+    /// Formats are tried lazily, so `first` stops at the first match unless a later
+    /// format is the one for the file's extension
+    pub fn parse(&self, line: &str) -> Vec<Diagnostic> {
+        let mut candidates = self
+            .parsers
+            .iter()
+            .filter_map(|(name, parser)| Some((name, parser.parse(line)?)));
+        let preferred = |(name, diagnostic): &(&String, Diagnostic)| {
+            embedded::extension(&diagnostic.path)
+                .and_then(|extension| self.extensions.get(extension))
+                == Some(*name)
+        };
+        // Only the diagnostics returned get the line and the format's name
+        let finish = |(name, mut diagnostic): (&String, Diagnostic)| {
+            diagnostic.raw = line.to_string();
+            diagnostic.tool = Some(name.clone());
+            diagnostic
+        };
+        let chosen = match self.strategy {
+            ParserStrategy::All => return candidates.map(finish).collect(),
+            ParserStrategy::First => {
+                let first = candidates.next();
+                let later = match &first {
+                    Some(first) if !self.extensions.is_empty() && !preferred(first) => {
+                        candidates.find(preferred)
+                    }
+                    _ => None,
+                };
+                later.or(first)
+            }
+            // Earlier candidates win ties, so iterate in reverse for `max_by_key`
+            ParserStrategy::Best => candidates
+                .collect::<Vec<_>>()
+                .into_iter()
+                .rev()
+                .max_by_key(|candidate| (field_count(&candidate.1), preferred(candidate))),
+        };
+        chosen.map(finish).into_iter().collect()
+    }
+}
+
+/// How much structure a parser extracted from a line
+fn field_count(diagnostic: &Diagnostic) -> usize {
+    [
+        diagnostic.line.is_some() || diagnostic.offset.is_some(),
+        diagnostic.column.is_some(),
+        diagnostic.severity.is_some(),
+        diagnostic.rule.is_some(),
+        diagnostic.message.is_some(),
+        diagnostic.fix.is_some(),
+        diagnostic.excerpt.is_some(),
+        diagnostic.symbol.is_some(),
+    ]
+    .iter()
+    .filter(|&&extracted| extracted)
+    .count()
+}
+
+/// Parses lines using a regex with named `file`, `line`, `col`, `severity`, `rule` and
+/// `message` groups, and `end_line` and `end_col` for findings spanning several lines.
+///
+/// A `typo` group together with a `correction` group yields a fix replacing the former.
+/// Quoted source is taken from an `excerpt` group, falling back to `typo`, and the
+/// function concerned from a `symbol` group.
+pub struct RegexParser {
+    regex: Regex,
+}
+
+impl RegexParser {
+    fn new(pattern: &str) -> Self {
+        RegexParser {
+            regex: Regex::new(pattern).expect("Failed to create builtin regex"),
+        }
+    }
+
+    /// A user-supplied pattern, which needs at least a `file` group
+    pub fn profile(pattern: &str) -> Result<Self> {
+        let regex = Regex::new(pattern)?;
+        if !regex.capture_names().any(|name| name == Some("file")) {
+            bail!("missing a (?P<file>...) group");
+        }
+        Ok(RegexParser { regex })
+    }
+}
+
+impl LintParser for RegexParser {
+    fn parse(&self, line: &str) -> Option<Diagnostic> {
+        let captures = self.regex.captures(line)?;
+        let path = captures.name("file")?.as_str();
+        let line_num = match captures.name("line") {
+            Some(m) => Some(m.as_str().parse().ok()?),
+            None => None,
+        };
+        let mut diagnostic = Diagnostic::new(path, line_num);
+        diagnostic.column = captures.name("col").and_then(|m| m.as_str().parse().ok());
+        diagnostic.end_line = captures
+            .name("end_line")
+            .and_then(|m| m.as_str().parse().ok());
+        diagnostic.end_column = captures
+            .name("end_col")
+            .and_then(|m| m.as_str().parse().ok());
+        diagnostic.severity = captures
+            .name("severity")
+            .and_then(|m| Severity::from_label(m.as_str()));
+        diagnostic.rule = captures.name("rule").map(|m| m.as_str().to_string());
+        diagnostic.message = captures.name("message").map(|m| m.as_str().to_string());
+        diagnostic.excerpt = captures
+            .name("excerpt")
+            .or_else(|| captures.name("typo"))
+            .map(|m| m.as_str().to_string());
+        diagnostic.symbol = captures.name("symbol").map(|m| m.as_str().to_string());
+        if let (Some(typo), Some(correction), Some(_)) = (
+            captures.name("typo"),
+            captures.name("correction"),
+            diagnostic.column,
+        ) {
+            diagnostic.fix = Some(Fix {
+                length: typo.as_str().chars().count() as u32,
+                replacement: correction.as_str().to_string(),
+            });
+        }
+        Some(diagnostic)
+    }
+}
+
+/// Parses formatters' lists of files that need formatting, one path per line, into
+/// file-level findings
+pub struct FileListParser {
+    extension: &'static str,
+    message: &'static str,
+}
+
+impl FileListParser {
+    fn new(extension: &'static str, message: &'static str) -> Self {
+        FileListParser { extension, message }
+    }
+}
+
+impl LintParser for FileListParser {
+    fn parse(&self, line: &str) -> Option<Diagnostic> {
+        let path = line.trim_end();
+        let (stem, extension) = path.rsplit_once('.')?;
+        if extension != self.extension || stem.is_empty() || path.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let mut diagnostic = Diagnostic::new(path, None);
+        diagnostic.message = Some(self.message.to_string());
+        Some(diagnostic)
+    }
+}
+
+#[derive(Deserialize)]
+struct TyposJsonEntry {
+    #[serde(rename = "type")]
+    kind: String,
+    path: String,
+    line_num: Option<u32>,
+    byte_offset: Option<u32>,
+    typo: String,
+    #[serde(default)]
+    corrections: Vec<String>,
+}
+
+/// Parses the JSON lines emitted by `typos --format json`
+pub struct TyposJsonParser;
+
+impl LintParser for TyposJsonParser {
+    fn parse(&self, line: &str) -> Option<Diagnostic> {
+        let entry: TyposJsonEntry = serde_json::from_str(line).ok()?;
+        if entry.kind != "typo" {
+            return None;
+        }
+        // Typos in file names are reported without a line (or as line 0)
+        let line_num = entry.line_num.filter(|&n| n > 0);
+        let mut diagnostic = Diagnostic::new(entry.path, line_num);
+        diagnostic.column = line_num.and(entry.byte_offset.map(|offset| offset + 1));
+        diagnostic.column_unit = ColumnUnit::Utf8Byte;
+        // Only unambiguous corrections can be suggested
+        if let (Some(_), [correction]) = (diagnostic.column, &entry.corrections[..]) {
+            diagnostic.fix = Some(Fix {
+                length: entry.typo.chars().count() as u32,
+                replacement: correction.clone(),
+            });
+        }
+        diagnostic.excerpt = Some(entry.typo.clone());
+        diagnostic.message = Some(format!(
+            "`{}` -> {}",
+            entry.typo,
+            entry
+                .corrections
+                .iter()
+                .map(|c| format!("`{}`", c))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+        Some(diagnostic)
+    }
+}
+
+#[derive(Deserialize)]
+struct ActionlintEntry {
+    message: String,
+    filepath: String,
+    line: u32,
+    column: u32,
+    kind: String,
+}
+
+/// Parses actionlint errors printed one JSON object per line
+pub struct ActionlintJsonParser;
+
+impl LintParser for ActionlintJsonParser {
+    fn parse(&self, line: &str) -> Option<Diagnostic> {
+        let entry: ActionlintEntry = serde_json::from_str(line).ok()?;
+        let mut diagnostic = Diagnostic::new(entry.filepath, Some(entry.line));
+        diagnostic.column = Some(entry.column);
+        diagnostic.severity = Some(Severity::Error);
+        diagnostic.rule = Some(entry.kind);
+        diagnostic.message = Some(entry.message);
+        Some(diagnostic)
+    }
+}
+
+/// Names a secret in messages without quoting it, so equal findings of different
+/// secrets on a line get distinct fingerprints while reports stay safe to share
+fn secret_message(description: &str, secret: &str) -> String {
+    format!(
+        "{} (secret {})",
+        description,
+        &fingerprint::fingerprint(&[secret])[..8]
+    )
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct GitleaksEntry {
+    #[serde(rename = "RuleID")]
+    rule_id: String,
+    description: String,
+    file: String,
+    start_line: u32,
+    end_line: Option<u32>,
+    start_column: Option<u32>,
+    end_column: Option<u32>,
+    #[serde(default)]
+    secret: String,
+}
+
+/// Parses a finding of a gitleaks JSON report, once `--input-format json-array` put it
+/// on its own line
+pub struct GitleaksParser;
+
+impl LintParser for GitleaksParser {
+    fn parse(&self, line: &str) -> Option<Diagnostic> {
+        let entry: GitleaksEntry = serde_json::from_str(line).ok()?;
+        let mut diagnostic = Diagnostic::new(entry.file, Some(entry.start_line));
+        diagnostic.end_line = entry.end_line;
+        diagnostic.column = entry.start_column.filter(|&column| column > 0);
+        diagnostic.end_column = entry.end_column.filter(|&column| column > 0);
+        diagnostic.severity = Some(Severity::Error);
+        diagnostic.message = Some(secret_message(&entry.description, &entry.secret));
+        diagnostic.rule = Some(entry.rule_id);
+        Some(diagnostic)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TrufflehogEntry {
+    source_metadata: TrufflehogSource,
+    detector_name: String,
+    #[serde(default)]
+    verified: bool,
+    #[serde(default)]
+    raw: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TrufflehogSource {
+    /// Keyed by the source type, e.g. `Git` or `Filesystem`
+    data: HashMap<String, TrufflehogLocation>,
+}
+
+#[derive(Deserialize)]
+struct TrufflehogLocation {
+    file: String,
+    line: Option<u32>,
+}
+
+/// Parses the JSON lines of `trufflehog --json`, for sources that locate findings in files
+pub struct TrufflehogParser;
+
+impl LintParser for TrufflehogParser {
+    fn parse(&self, line: &str) -> Option<Diagnostic> {
+        let entry: TrufflehogEntry = serde_json::from_str(line).ok()?;
+        let location = entry.source_metadata.data.into_values().next()?;
+        let mut diagnostic = Diagnostic::new(location.file, location.line.filter(|&n| n > 0));
+        diagnostic.severity = Some(Severity::Error);
+        let status = if entry.verified {
+            "verified"
+        } else {
+            "unverified"
+        };
+        diagnostic.message = Some(secret_message(
+            &format!("{} {} secret", status, entry.detector_name),
+            &entry.raw,
+        ));
+        diagnostic.rule = Some(entry.detector_name);
+        Some(diagnostic)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LinkEntry {
+    /// lychee's input source, or markdown-link-check's file
+    #[serde(alias = "file")]
+    source: String,
+    #[serde(alias = "link")]
+    url: String,
+    /// lychee's `{"text": ..., "code": ...}`, or markdown-link-check's `alive`, `dead`,
+    /// `ignored` or `error`
+    #[serde(default)]
+    status: Value,
+    status_code: Option<u32>,
+    line: Option<u32>,
+    column: Option<u32>,
+}
+
+/// Parses a broken link of lychee or markdown-link-check, quoting its URL so the link
+/// can be found in the file when no line is given
+pub struct LinkCheckParser;
+
+impl LintParser for LinkCheckParser {
+    fn parse(&self, line: &str) -> Option<Diagnostic> {
+        let entry: LinkEntry = serde_json::from_str(line).ok()?;
+        let status = match &entry.status {
+            Value::String(status) if status == "alive" || status == "ignored" => return None,
+            Value::String(status) => Some(status.as_str()),
+            status => status["text"].as_str(),
+        };
+        let code = entry
+            .status_code
+            .or_else(|| entry.status["code"].as_u64().map(|code| code as u32));
+        let mut diagnostic = Diagnostic::new(entry.source, entry.line.filter(|&n| n > 0));
+        diagnostic.column = entry.column.filter(|&n| n > 0);
+        diagnostic.severity = Some(Severity::Error);
+        diagnostic.rule = code.map(|code| code.to_string());
+        diagnostic.message = Some(match status {
+            Some(status) => format!("Broken link {}: {}", entry.url, status),
+            None => format!("Broken link {}", entry.url),
+        });
+        diagnostic.excerpt = Some(entry.url);
+        Some(diagnostic)
+    }
+}
+
+#[derive(Deserialize)]
+struct DuplicateCopy {
+    file: String,
+    start: u32,
+    end: u32,
+}
+
+#[derive(Deserialize)]
+struct DuplicateEntry {
+    #[serde(flatten)]
+    copy: DuplicateCopy,
+    lines: u32,
+    counterparts: Vec<DuplicateCopy>,
+}
+
+/// Parses a copy of a duplicated block, spanning its lines so that it is reported when
+/// any of them changed, with the other copies in its message
+pub struct DuplicatesParser;
+
+impl LintParser for DuplicatesParser {
+    fn parse(&self, line: &str) -> Option<Diagnostic> {
+        let entry: DuplicateEntry = serde_json::from_str(line).ok()?;
+        let DuplicateCopy { file, start, end } = entry.copy;
+        let mut diagnostic = Diagnostic::new(file, Some(start).filter(|&n| n > 0));
+        diagnostic.end_line = Some(end).filter(|&end| end > start);
+        diagnostic.severity = Some(Severity::Warning);
+        diagnostic.rule = Some("duplicate".to_string());
+        let counterparts: Vec<_> = entry
+            .counterparts
+            .iter()
+            .map(|other| format!("{}:{}-{}", other.file, other.start, other.end))
+            .collect();
+        diagnostic.message = Some(format!(
+            "{} line(s) duplicated in {}",
+            entry.lines,
+            counterparts.join(", ")
+        ));
+        Some(diagnostic)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ClangTidyEntry {
+    diagnostic_name: String,
+    message: String,
+    file_path: String,
+    file_offset: usize,
+    level: Option<String>,
+}
+
+/// Parses a clang-tidy diagnostic, leaving its offset to be resolved to a line and
+/// column in the file
+pub struct ClangTidyFixesParser;
+
+impl LintParser for ClangTidyFixesParser {
+    fn parse(&self, line: &str) -> Option<Diagnostic> {
+        let entry: ClangTidyEntry = serde_json::from_str(line).ok()?;
+        let mut diagnostic = Diagnostic::new(entry.file_path, None);
+        diagnostic.offset = Some(entry.file_offset);
+        diagnostic.severity = entry.level.as_deref().and_then(Severity::from_label);
+        diagnostic.rule = Some(entry.diagnostic_name);
+        diagnostic.message = Some(entry.message);
+        Some(diagnostic)
+    }
+}
+
+static KUBECONFORM: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?P<file>\S+\.ya?ml) - (?:(?P<kind>\w+) (?P<name>\S+) (?:is invalid|failed validation)|(?P<error>failed validation)): (?P<message>.*)$").unwrap()
+});
+/// The field kubeconform quotes in its message, e.g. `at '/spec/replicas'`
+static KUBECONFORM_FIELD: Lazy<Regex> = Lazy::new(|| Regex::new(r"'(/[^']*)'").unwrap());
+
+/// Parses kubeconform's text output, leaving the line to the manifest locator
+pub struct KubeconformParser;
+
+impl LintParser for KubeconformParser {
+    fn parse(&self, line: &str) -> Option<Diagnostic> {
+        let captures = KUBECONFORM.captures(line)?;
+        let mut diagnostic = Diagnostic::new(&captures["file"], None);
+        let message = &captures["message"];
+        if let (Some(kind), Some(name)) = (captures.name("kind"), captures.name("name")) {
+            let pointer = KUBECONFORM_FIELD
+                .captures(message)
+                .map_or("", |field| field.get(1).unwrap().as_str());
+            diagnostic.symbol = Some(manifest::symbol(kind.as_str(), name.as_str(), pointer));
+        }
+        diagnostic.severity = Some(Severity::Error);
+        diagnostic.message = Some(match captures.name("kind") {
+            Some(kind) => format!("{} {}: {}", kind.as_str(), &captures["name"], message),
+            None => message.to_string(),
+        });
+        Some(diagnostic)
+    }
+}
+
+static KUBEVAL: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?P<severity>WARN|ERR)\s+- (?P<file>\S+) contains an invalid (?P<kind>\w+) \((?P<name>[^)]+)\) - (?P<field>\S+): (?P<message>.*)$").unwrap()
+});
+
+/// Parses kubeval's findings, leaving the line to the manifest locator
+pub struct KubevalParser;
+
+impl LintParser for KubevalParser {
+    fn parse(&self, line: &str) -> Option<Diagnostic> {
+        let captures = KUBEVAL.captures(line)?;
+        let mut diagnostic = Diagnostic::new(&captures["file"], None);
+        let pointer = manifest::dotted_pointer(&captures["field"]);
+        diagnostic.symbol = Some(manifest::symbol(
+            &captures["kind"],
+            &captures["name"],
+            &pointer,
+        ));
+        diagnostic.severity = Severity::from_label(&captures["severity"]);
+        diagnostic.message = Some(format!("{}: {}", &captures["field"], &captures["message"]));
+        Some(diagnostic)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::Severity;
+    use crate::parsers::{Format, FormatSpec, ParserProfile, ParserStrategy, Parsers};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_python_regex() {
+        let diagnostic = Format::Python
+            .parser()
+            .parse("pysrc/main.py:753:89: E501 Line too long")
+            .unwrap();
+        assert_eq!(diagnostic.path, "pysrc/main.py");
+        assert_eq!(diagnostic.line, Some(753));
+        assert_eq!(diagnostic.column, Some(89));
+        assert_eq!(diagnostic.rule.as_deref(), Some("E501"));
+        assert_eq!(diagnostic.message.as_deref(), Some("E501 Line too long"));
+
+        let diagnostic = Format::Python
+            .parser()
+            .parse("src/a.py:3: error: Name 'x' is not defined")
+            .unwrap();
+        assert_eq!(diagnostic.line, Some(3));
+        assert_eq!(diagnostic.rule, None);
+
+        let parser = Format::Python.parser();
+        let diagnostic = parser
+            .parse("src/a.py:10-15: R0915 Too many statements")
+            .unwrap();
+        assert_eq!(diagnostic.span(), Some((10, 15)));
+        assert_eq!(diagnostic.rule.as_deref(), Some("R0915"));
+        let diagnostic = parser.parse("src/a.py:10:5-12:3: E1 spans").unwrap();
+        assert_eq!(
+            (
+                diagnostic.column,
+                diagnostic.end_line,
+                diagnostic.end_column
+            ),
+            (Some(5), Some(12), Some(3))
+        );
+    }
+
+    #[test]
+    fn test_typos_brief() {
+        let parser = Format::Typos.parser();
+        let diagnostic = parser.parse("./README.md:3:9: `teh` -> `the`").unwrap();
+        assert_eq!(diagnostic.path, "README.md");
+        assert_eq!(diagnostic.line, Some(3));
+        assert_eq!(diagnostic.column, Some(9));
+        assert_eq!(diagnostic.excerpt.as_deref(), Some("teh"));
+        assert_eq!(
+            diagnostic.fix.unwrap().apply("Fix teh typo", 5),
+            "Fix the typo"
+        );
+
+        let diagnostic = parser.parse("./src/teh.rs: `teh` -> `the`").unwrap();
+        assert_eq!(diagnostic.path, "src/teh.rs");
+        assert_eq!(diagnostic.line, None);
+    }
+
+    #[test]
+    fn test_typos_json() {
+        let parser = Format::TyposJson.parser();
+        let diagnostic = parser
+            .parse(r#"{"type":"typo","path":"./docs/a.md","line_num":4,"byte_offset":2,"typo":"teh","corrections":["the"]}"#)
+            .unwrap();
+        assert_eq!(diagnostic.path, "docs/a.md");
+        assert_eq!(diagnostic.line, Some(4));
+        assert_eq!(diagnostic.column, Some(3));
+        assert_eq!(diagnostic.message.as_deref(), Some("`teh` -> `the`"));
+        assert_eq!(diagnostic.fix.unwrap().replacement, "the");
+
+        assert!(parser
+            .parse(r#"{"type":"binary_file","path":"./logo.png"}"#)
+            .is_none());
+    }
+
+    #[test]
+    fn test_codespell() {
+        let parser = Format::Codespell.parser();
+        let diagnostic = parser.parse("./README.md:12: recieve ==> receive").unwrap();
+        assert_eq!(diagnostic.path, "README.md");
+        assert_eq!(diagnostic.line, Some(12));
+        assert_eq!(diagnostic.message.as_deref(), Some("recieve ==> receive"));
+    }
+
+    #[test]
+    fn test_pytest() {
+        let parser = Format::Pytest.parser();
+        let diagnostic = parser
+            .parse("FAILED tests/test_x.py::TestX::test_y[1] - AssertionError: 1 != 2")
+            .unwrap();
+        assert_eq!(diagnostic.path, "tests/test_x.py");
+        assert_eq!(diagnostic.line, None);
+        assert_eq!(diagnostic.symbol.as_deref(), Some("TestX::test_y[1]"));
+        assert_eq!(
+            diagnostic.message.as_deref(),
+            Some("AssertionError: 1 != 2")
+        );
+
+        let diagnostic = parser.parse("ERROR tests/test_z.py").unwrap();
+        assert_eq!(diagnostic.symbol, None);
+        assert!(parser.parse("PASSED tests/test_x.py::test_y").is_none());
+    }
+
+    #[test]
+    fn test_actionlint() {
+        let line = r#".github/workflows/ci.yml:10:9: property "foo" is not defined in object type {} [expression]"#;
+        let diagnostic = Format::Actionlint.parser().parse(line).unwrap();
+        assert_eq!(diagnostic.path, ".github/workflows/ci.yml");
+        assert_eq!((diagnostic.line, diagnostic.column), (Some(10), Some(9)));
+        assert_eq!(diagnostic.rule.as_deref(), Some("expression"));
+        assert!(Format::Actionlint
+            .parser()
+            .parse("10 |         run: echo ${{ matrix.foo }}")
+            .is_none());
+
+        let line = r#"{"message":"label \"ubuntu-99\" is unknown","filepath":"./.github/workflows/ci.yml","line":4,"column":14,"kind":"runner-label","snippet":"    runs-on: ubuntu-99\n             ^~~~~~~~~","end_column":22}"#;
+        let diagnostic = Format::ActionlintJson.parser().parse(line).unwrap();
+        assert_eq!(diagnostic.path, ".github/workflows/ci.yml");
+        assert_eq!((diagnostic.line, diagnostic.column), (Some(4), Some(14)));
+        assert_eq!(diagnostic.rule.as_deref(), Some("runner-label"));
+        assert_eq!(diagnostic.severity, Some(Severity::Error));
+    }
+
+    #[test]
+    fn test_kubernetes() {
+        let line = "k8s/web.yaml - Deployment web is invalid: problem validating schema. Check JSON formatting: jsonschema validation failed with 'https://example.com/deployment.json#' - at '/spec/replicas': got string, want integer";
+        let diagnostic = Format::Kubeconform.parser().parse(line).unwrap();
+        assert_eq!(diagnostic.path, "k8s/web.yaml");
+        assert_eq!(diagnostic.line, None);
+        assert_eq!(
+            diagnostic.symbol.as_deref(),
+            Some("Deployment/web/spec/replicas")
+        );
+        let line = "k8s/bad.yaml - failed validation: error unmarshalling resource: yaml: line 3";
+        let diagnostic = Format::Kubeconform.parser().parse(line).unwrap();
+        assert_eq!(diagnostic.symbol, None);
+
+        let line = "WARN - k8s/web.yaml contains an invalid Deployment (default.web) - spec.template.spec.containers.0.image: Invalid type. Expected: string, given: integer";
+        let diagnostic = Format::Kubeval.parser().parse(line).unwrap();
+        assert_eq!(
+            diagnostic.symbol.as_deref(),
+            Some("Deployment/default.web/spec/template/spec/containers/0/image")
+        );
+        assert_eq!(diagnostic.severity, Some(Severity::Warning));
+        assert!(Format::Kubeval
+            .parser()
+            .parse("PASS - k8s/web.yaml contains a valid Deployment (web)")
+            .is_none());
+    }
+
+    #[test]
+    fn test_formatter_file_lists() {
+        let diagnostic = Format::Black
+            .parser()
+            .parse("would reformat /repo/src/a.py")
+            .unwrap();
+        assert_eq!(diagnostic.path, "/repo/src/a.py");
+        assert_eq!(diagnostic.line, None);
+        assert!(Format::Black
+            .parser()
+            .parse("1 file would be reformatted.")
+            .is_none());
+
+        let rustfmt = Format::Rustfmt.parser();
+        let diagnostic = rustfmt.parse("src/main.rs").unwrap();
+        assert_eq!(
+            (diagnostic.path.as_str(), diagnostic.line),
+            ("src/main.rs", None)
+        );
+        assert_eq!(diagnostic.text(), "needs formatting with rustfmt");
+        assert!(rustfmt.parse("main.go").is_none());
+        assert!(rustfmt.parse("    src/main.rs").is_none());
+        assert!(Format::Gofmt.parser().parse("cmd/serve.go").is_some());
+    }
+
+    #[test]
+    fn test_secret_scanners() {
+        let line = r#"{"Description":"Generic API Key","StartLine":3,"EndLine":3,"StartColumn":9,"EndColumn":40,"Match":"key = 'abc'","Secret":"abc","File":"app/settings.py","RuleID":"generic-api-key"}"#;
+        let diagnostic = Format::Gitleaks.parser().parse(line).unwrap();
+        assert_eq!(diagnostic.path, "app/settings.py");
+        assert_eq!((diagnostic.line, diagnostic.column), (Some(3), Some(9)));
+        assert_eq!(diagnostic.rule.as_deref(), Some("generic-api-key"));
+        let message = diagnostic.message.unwrap();
+        assert!(message.starts_with("Generic API Key (secret "));
+        assert!(!message.contains("abc"));
+        let other = Format::Gitleaks
+            .parser()
+            .parse(&line.replace(r#""Secret":"abc""#, r#""Secret":"xyz""#))
+            .unwrap();
+        assert_ne!(other.message.unwrap(), message);
+
+        let line = r#"{"SourceMetadata":{"Data":{"Filesystem":{"file":"deploy/env.sh","line":7}}},"SourceType":15,"DetectorName":"AWS","Verified":true,"Raw":"AKIA","Redacted":"AKIA****"}"#;
+        let diagnostic = Format::Trufflehog.parser().parse(line).unwrap();
+        assert_eq!(
+            (diagnostic.path.as_str(), diagnostic.line),
+            ("deploy/env.sh", Some(7))
+        );
+        assert_eq!(diagnostic.rule.as_deref(), Some("AWS"));
+        assert!(diagnostic
+            .message
+            .unwrap()
+            .starts_with("verified AWS secret (secret "));
+        assert!(Format::Trufflehog
+            .parser()
+            .parse(r#"{"SourceMetadata":{"Data":{"Slack":{"channel":"x"}}},"DetectorName":"AWS"}"#)
+            .is_none());
+        assert!(Format::Gitleaks.is_secret_scanner() && !Format::Python.is_secret_scanner());
+    }
+
+    #[test]
+    fn test_link_check() {
+        let parser = Format::LinkCheck.parser();
+        let diagnostic = parser
+            .parse(r#"{"source":"docs/a.md","url":"https://x.test/gone","status":{"text":"Failed: Network error","code":404}}"#)
+            .unwrap();
+        assert_eq!(
+            (diagnostic.path.as_str(), diagnostic.line),
+            ("docs/a.md", None)
+        );
+        assert_eq!(diagnostic.rule.as_deref(), Some("404"));
+        assert_eq!(
+            diagnostic.message.as_deref(),
+            Some("Broken link https://x.test/gone: Failed: Network error")
+        );
+        assert_eq!(diagnostic.excerpt.as_deref(), Some("https://x.test/gone"));
+
+        let diagnostic = parser
+            .parse(r#"{"file":"README.md","link":"./missing.md","status":"dead","statusCode":400,"line":3}"#)
+            .unwrap();
+        assert_eq!(
+            (diagnostic.path.as_str(), diagnostic.line),
+            ("README.md", Some(3))
+        );
+        assert_eq!(
+            diagnostic.message.as_deref(),
+            Some("Broken link ./missing.md: dead")
+        );
+        assert!(parser
+            .parse(r#"{"file":"README.md","link":"https://x.test","status":"alive"}"#)
+            .is_none());
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let parser = Format::Duplicates.parser();
+        let diagnostic = parser
+            .parse(r#"{"file":"src/b.js","start":20,"end":30,"lines":11,"counterparts":[{"file":"src/a.js","start":1,"end":11}]}"#)
+            .unwrap();
+        assert_eq!(diagnostic.path, "src/b.js");
+        assert_eq!(diagnostic.span(), Some((20, 30)));
+        assert_eq!(diagnostic.rule.as_deref(), Some("duplicate"));
+        assert_eq!(
+            diagnostic.message.as_deref(),
+            Some("11 line(s) duplicated in src/a.js:1-11")
+        );
+        assert!(parser
+            .parse(r#"{"file":"README.md","link":"./missing.md","status":"dead"}"#)
+            .is_none());
+    }
+
+    #[test]
+    fn test_clang_tidy_fixes() {
+        let parser = Format::ClangTidyFixes.parser();
+        let diagnostic = parser
+            .parse(r#"{"DiagnosticName":"misc-unused-parameters","Message":"parameter 'x' is unused","FilePath":"src/a.cpp","FileOffset":12,"Level":"Warning"}"#)
+            .unwrap();
+        assert_eq!(
+            (diagnostic.path.as_str(), diagnostic.line, diagnostic.offset),
+            ("src/a.cpp", None, Some(12))
+        );
+        assert_eq!(diagnostic.severity, Some(Severity::Warning));
+        assert_eq!(diagnostic.rule.as_deref(), Some("misc-unused-parameters"));
+        assert_eq!(
+            diagnostic.message.as_deref(),
+            Some("parameter 'x' is unused")
+        );
+        assert!(parser
+            .parse(r#"{"file":"src/b.js","start":20,"end":30,"lines":11,"counterparts":[]}"#)
+            .is_none());
+    }
+
+    #[test]
+    fn test_auto() {
+        let tools = |parsers: &Parsers, line| -> Vec<_> {
+            parsers
+                .parse(line)
+                .into_iter()
+                .map(|diagnostic| diagnostic.tool.unwrap())
+                .collect()
+        };
+        let parsers = Parsers::new(&[Format::Auto], HashMap::new());
+        // Both typos and python accept this line
+        let line = "a.py:3:9: `teh` -> `the`";
+        assert_eq!(tools(&parsers, line), ["typos"]);
+
+        let extensions: HashMap<_, _> = vec![("py".to_string(), "python".to_string())]
+            .into_iter()
+            .collect();
+        let parsers = Parsers::new(&[Format::Auto], extensions.clone());
+        assert_eq!(tools(&parsers, line), ["python"]);
+        let line = "README.md:3:9: `teh` -> `the`";
+        assert_eq!(tools(&parsers, line), ["typos"]);
+
+        // The typos parser also extracts a fix and an excerpt
+        let line = "a.py:3:9: `teh` -> `the`";
+        let parsers = Parsers::new(&[Format::Python, Format::Typos], extensions.clone())
+            .with_strategy(ParserStrategy::Best);
+        assert_eq!(tools(&parsers, line), ["typos"]);
+        let parsers = Parsers::new(&[Format::Python, Format::Typos], extensions)
+            .with_strategy(ParserStrategy::All);
+        assert_eq!(tools(&parsers, line), ["python", "typos"]);
+        assert!(parsers.parse("1 error found").is_empty());
+    }
+
+    #[test]
+    fn test_profiles() {
+        let profile = ParserProfile {
+            regex: r"^(?P<file>[^:]+):(?P<line>\d+):(?P<col>\d+): (?P<severity>\w+): (?P<message>.*) \[(?P<rule>[\w.-]+)\]$".to_string(),
+        };
+        let profiles: HashMap<_, _> = vec![("clang-tidy".to_string(), profile)]
+            .into_iter()
+            .collect();
+        let specs: Vec<FormatSpec> = vec!["clang-tidy".parse().unwrap()];
+        let parsers = Parsers::with_profiles(&specs, &profiles, HashMap::new()).unwrap();
+        let diagnostics =
+            parsers.parse("src/a.cc:12:3: warning: use nullptr [modernize-use-nullptr]");
+        assert_eq!(diagnostics.len(), 1);
+        let diagnostic = &diagnostics[0];
+        assert_eq!(diagnostic.tool.as_deref(), Some("clang-tidy"));
+        assert_eq!((diagnostic.line, diagnostic.column), (Some(12), Some(3)));
+        assert_eq!(diagnostic.severity, Some(Severity::Warning));
+        assert_eq!(diagnostic.rule.as_deref(), Some("modernize-use-nullptr"));
+
+        let specs: Vec<FormatSpec> = vec!["golangci-lint".parse().unwrap()];
+        assert!(Parsers::with_profiles(&specs, &profiles, HashMap::new()).is_err());
+        assert_eq!("python".parse(), Ok(FormatSpec::Builtin(Format::Python)));
+    }
+
+    #[cfg(feature = "clap")]
+    #[test]
+    fn test_format_names() {
+        use clap::ValueEnum;
+
+        for &format in Format::value_variants() {
+            let name = format.to_possible_value().unwrap().get_name().to_string();
+            assert_eq!(format.name(), name);
+            assert_eq!(name.parse(), Ok(FormatSpec::Builtin(format)));
+        }
+    }
+}
@@ -0,0 +1,426 @@
+//! Turning the paths tools report into the repository-relative ones the hunk map keys,
+//! in one place so every input spells a file the same way.
+//!
+//! Resolving a path may stat or canonicalize it, which is slow on network filesystems,
+//! so [`PathMapper`] keeps the recent answers, and those of the filesystem calls behind
+//! them, in bounded caches.
+
+#[cfg(feature = "clap")]
+use clap::ValueEnum;
+use log::debug;
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::hash::Hash;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+/// Entries kept by each cache of a [`PathMapper`]
+pub const CACHE_SIZE: usize = 1 << 14;
+
+/// What reported paths through symlinks become
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "clap", derive(ValueEnum))]
+pub enum SymlinkPolicy {
+    /// The path of the file the symlinked directories lead to, as git tracks it
+    #[default]
+    Resolve,
+    /// The path as reported, for repositories that track files under symlinked
+    /// directories by those paths; only the root itself is resolved
+    Keep,
+}
+
+/// Spells a reported path the way the hunk map keys it, so `./src/a.py`, `src//a.py`
+/// and `src/../src/a.py` all become `src/a.py`. Leading `..` are kept for
+/// [`escapes_root`] to reject
+pub fn normalize_path(path: &str) -> String {
+    let absolute = path.starts_with('/');
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => match parts.last() {
+                Some(&last) if last != ".." => {
+                    parts.pop();
+                }
+                // `/..` is `/`
+                _ if absolute => {}
+                _ => parts.push(part),
+            },
+            _ => parts.push(part),
+        }
+    }
+    let joined = parts.join("/");
+    if absolute {
+        format!("/{}", joined)
+    } else {
+        joined
+    }
+}
+
+/// `path` relative to `root` when it is under it, and normalized, for reports that
+/// name files by absolute path
+pub fn root_relative(path: &Path, root: &Path) -> String {
+    let path = path.strip_prefix(root).unwrap_or(path);
+    normalize_path(&path.to_string_lossy())
+}
+
+/// Whether a reported path points outside the repository rooted at `root`, judged
+/// lexically since the file may not exist
+pub fn escapes_root(path: &str, root: &Path) -> bool {
+    let path = Path::new(path);
+    if path.is_absolute() {
+        return !path.starts_with(root);
+    }
+    let mut depth = 0usize;
+    for component in path.components() {
+        match component {
+            Component::ParentDir if depth == 0 => return true,
+            Component::ParentDir => depth -= 1,
+            Component::Normal(_) => depth += 1,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// A map keeping its `capacity` most recently used entries
+#[derive(Debug, Clone)]
+struct Lru<K, V> {
+    capacity: usize,
+    entries: HashMap<K, (V, u64)>,
+    /// Keys by when they were last used
+    order: BTreeMap<u64, K>,
+    tick: u64,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> Lru<K, V> {
+    fn new(capacity: usize) -> Self {
+        Lru {
+            capacity: capacity.max(1),
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    fn get<Q: Hash + Eq + ?Sized>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+    {
+        let (value, used) = self.entries.get_mut(key)?;
+        let key = self.order.remove(used).unwrap();
+        self.tick += 1;
+        *used = self.tick;
+        self.order.insert(self.tick, key);
+        Some(value.clone())
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        self.tick += 1;
+        if let Some((_, used)) = self.entries.get(&key) {
+            self.order.remove(used);
+        } else if self.entries.len() >= self.capacity {
+            if let Some((_, oldest)) = self.order.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.order.insert(self.tick, key.clone());
+        self.entries.insert(key, (value, self.tick));
+    }
+
+    /// The cached value for `key`, computed by `compute` when missing
+    fn get_or_insert_with(&mut self, key: &K, compute: impl FnOnce() -> V) -> V {
+        if let Some(value) = self.get(key) {
+            return value;
+        }
+        let value = compute();
+        self.insert(key.clone(), value.clone());
+        value
+    }
+}
+
+/// Whether the filesystem under `root` ignores case, judged by an entry of the root
+/// found under its name with the case swapped
+fn ignores_case(root: &Path) -> bool {
+    let entries = match fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let swapped: String = name
+            .chars()
+            .map(|c| match c.is_uppercase() {
+                true => c.to_lowercase().next().unwrap_or(c),
+                false => c.to_uppercase().next().unwrap_or(c),
+            })
+            .collect();
+        if swapped != name {
+            return root.join(swapped).exists();
+        }
+    }
+    false
+}
+
+/// Rewrites the paths linters report to the repository-relative ones the hunk map keys:
+/// absolute paths under the root, paths relative to a subdirectory the tool ran from,
+/// paths through symlinked directories, Windows separators, prefixes of paths that
+/// only exist where the linter ran, e.g. in a container, and on filesystems ignoring
+/// case, paths spelled with another case than the files have
+#[derive(Debug, Clone)]
+pub struct PathMapper {
+    root: PathBuf,
+    canonical_root: Option<PathBuf>,
+    /// The invocation directory relative to the root, when it is a subdirectory of it
+    cwd: Option<String>,
+    strip_prefixes: Vec<String>,
+    symlinks: SymlinkPolicy,
+    ignores_case: bool,
+    mapped: Lru<String, String>,
+    canonical: Lru<PathBuf, Option<PathBuf>>,
+    /// Entry names of directories, for spelling paths with the case of the files
+    listings: Lru<PathBuf, Arc<Vec<String>>>,
+}
+
+impl PathMapper {
+    pub fn new(root: &Path, cwd: &Path) -> Self {
+        let root = std::path::absolute(root).unwrap_or_else(|_| root.to_path_buf());
+        let canonical_root = fs::canonicalize(&root).ok();
+        let cwd = match (&canonical_root, fs::canonicalize(cwd)) {
+            (Some(canonical_root), Ok(cwd)) => cwd
+                .strip_prefix(canonical_root)
+                .ok()
+                .map(|cwd| cwd.to_string_lossy().into_owned())
+                .filter(|cwd| !cwd.is_empty()),
+            _ => None,
+        };
+        PathMapper {
+            ignores_case: ignores_case(&root),
+            root,
+            canonical_root,
+            cwd,
+            strip_prefixes: Vec::new(),
+            symlinks: SymlinkPolicy::default(),
+            mapped: Lru::new(CACHE_SIZE),
+            canonical: Lru::new(CACHE_SIZE),
+            listings: Lru::new(CACHE_SIZE),
+        }
+    }
+
+    /// Prefixes to remove from reported paths, leaving them relative to the root
+    pub fn with_strip_prefixes(mut self, prefixes: &[String]) -> Self {
+        self.strip_prefixes = prefixes
+            .iter()
+            .map(|prefix| prefix.replace('\\', "/"))
+            .collect();
+        self
+    }
+
+    pub fn with_symlink_policy(mut self, symlinks: SymlinkPolicy) -> Self {
+        self.symlinks = symlinks;
+        self
+    }
+
+    pub fn map(&mut self, path: &str) -> String {
+        if let Some(mapped) = self.mapped.get(path) {
+            return mapped;
+        }
+        let mapped = self.map_uncached(path);
+        let mapped = match self.ignores_case {
+            true => self.spell_as_on_disk(mapped),
+            false => mapped,
+        };
+        if mapped != path {
+            debug!("Reading '{}' as '{}'", path, mapped);
+        }
+        self.mapped.insert(path.to_string(), mapped.clone());
+        mapped
+    }
+
+    fn canonicalize(&mut self, path: &Path) -> Option<PathBuf> {
+        self.canonical
+            .get_or_insert_with(&path.to_path_buf(), || fs::canonicalize(path).ok())
+    }
+
+    fn map_uncached(&mut self, path: &str) -> String {
+        let path = path.replace('\\', "/");
+        if let Some(rest) = self
+            .strip_prefixes
+            .iter()
+            .find_map(|prefix| path.strip_prefix(prefix.as_str()))
+        {
+            return normalize_path(rest.trim_start_matches('/'));
+        }
+        if Path::new(&path).is_absolute() {
+            return self
+                .relative(Path::new(&normalize_path(&path)))
+                .unwrap_or_else(|| normalize_path(&path));
+        }
+        let path = normalize_path(&path);
+        let exists = |path: &str| self.root.join(path).exists();
+        let path = match &self.cwd {
+            // Tools run from a subdirectory print paths relative to it
+            Some(cwd) if !exists(&path) || exists(&format!("{}/{}", cwd, path)) => {
+                normalize_path(&format!("{}/{}", cwd, path))
+            }
+            _ => path,
+        };
+        if self.symlinks == SymlinkPolicy::Keep {
+            return path;
+        }
+        // Through a symlinked directory, resolving only the directories so a tracked
+        // symlink keeps its own path
+        let parent = Path::new(&path)
+            .parent()
+            .map(|parent| self.root.join(parent));
+        let resolved = parent.and_then(|parent| {
+            let directory = self.canonicalize(&parent)?;
+            let relative = directory.strip_prefix(self.canonical_root.as_ref()?).ok()?;
+            let name = Path::new(&path).file_name()?;
+            Some(relative.join(name).to_string_lossy().into_owned())
+        });
+        resolved.unwrap_or(path)
+    }
+
+    /// `absolute` relative to the root, as given or with symlinks resolved
+    fn relative(&mut self, absolute: &Path) -> Option<String> {
+        let relative = |root: &Path, path: &Path| {
+            path.strip_prefix(root)
+                .ok()
+                .map(|relative| relative.to_string_lossy().into_owned())
+        };
+        let canonical_root = self.canonical_root.clone()?;
+        if let Some(path) =
+            relative(&self.root, absolute).or_else(|| relative(&canonical_root, absolute))
+        {
+            return Some(path);
+        }
+        if self.symlinks == SymlinkPolicy::Keep {
+            return None;
+        }
+        relative(&canonical_root, &self.canonicalize(absolute)?)
+    }
+
+    /// `path` with each component spelled as the directory entry it names, when it names
+    /// one by ignoring case
+    fn spell_as_on_disk(&mut self, path: String) -> String {
+        if Path::new(&path).is_absolute() || path.starts_with("..") {
+            return path;
+        }
+        let mut directory = self.root.clone();
+        let mut spelled = Vec::new();
+        for part in path.split('/') {
+            let root = &self.root;
+            let names = self.listings.get_or_insert_with(&directory, || {
+                let dir = directory.strip_prefix(root).unwrap_or(&directory);
+                debug!("Listing '{}' to match the case of paths", dir.display());
+                let names = fs::read_dir(&directory).map(|entries| {
+                    entries
+                        .flatten()
+                        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                        .collect()
+                });
+                Arc::new(names.unwrap_or_default())
+            });
+            let name = match names.iter().find(|name| name.as_str() == part) {
+                Some(name) => name,
+                None => match names
+                    .iter()
+                    .find(|name| name.to_lowercase() == part.to_lowercase())
+                {
+                    Some(name) => name,
+                    None => return path,
+                },
+            };
+            directory.push(name);
+            spelled.push(name.clone());
+        }
+        spelled.join("/")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::paths::{escapes_root, normalize_path, Lru, PathMapper, SymlinkPolicy, CACHE_SIZE};
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(normalize_path("src/a.py"), "src/a.py");
+        assert_eq!(normalize_path("./src/a.py"), "src/a.py");
+        assert_eq!(normalize_path("src//a.py"), "src/a.py");
+        assert_eq!(normalize_path("src/./a.py"), "src/a.py");
+        assert_eq!(normalize_path("src/../src/a.py"), "src/a.py");
+        assert_eq!(normalize_path("src/../../other/a.py"), "../other/a.py");
+        assert_eq!(normalize_path("/repo//src/../a.py"), "/repo/a.py");
+        assert_eq!(normalize_path("/../a.py"), "/a.py");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_path_mapper() {
+        let root = std::env::temp_dir().join(format!("path-mapper-{}", std::process::id()));
+        fs::create_dir_all(root.join("src/pkg")).unwrap();
+        fs::write(root.join("src/pkg/a.py"), "").unwrap();
+        fs::write(root.join("top.py"), "").unwrap();
+        std::os::unix::fs::symlink("src", root.join("link")).unwrap();
+
+        let mut paths = PathMapper::new(&root, Path::new("/"))
+            .with_strip_prefixes(&["/workspace/".to_string(), "C:\\build\\".to_string()]);
+        let absolute = root.join("src/pkg/a.py");
+        assert_eq!(paths.map(absolute.to_str().unwrap()), "src/pkg/a.py");
+        assert_eq!(paths.map("./src/pkg/a.py"), "src/pkg/a.py");
+        assert_eq!(paths.map("src\\pkg\\a.py"), "src/pkg/a.py");
+        assert_eq!(paths.map("link/pkg/a.py"), "src/pkg/a.py");
+        assert_eq!(paths.map("/workspace/src/pkg/a.py"), "src/pkg/a.py");
+        assert_eq!(paths.map("C:\\build\\top.py"), "top.py");
+        assert_eq!(paths.map("/elsewhere/a.py"), "/elsewhere/a.py");
+
+        let mut kept =
+            PathMapper::new(&root, Path::new("/")).with_symlink_policy(SymlinkPolicy::Keep);
+        assert_eq!(kept.map("link/pkg/a.py"), "link/pkg/a.py");
+        let through_link = root.join("link/pkg/a.py");
+        assert_eq!(kept.map(through_link.to_str().unwrap()), "link/pkg/a.py");
+        assert_eq!(kept.map("./src/pkg/a.py"), "src/pkg/a.py");
+
+        // As on filesystems ignoring case, where the wrong case names the file too
+        paths.ignores_case = true;
+        paths.mapped = Lru::new(CACHE_SIZE);
+        assert_eq!(paths.map("SRC/Pkg/A.py"), "src/pkg/a.py");
+        assert_eq!(paths.map("src/pkg/missing.py"), "src/pkg/missing.py");
+
+        // Run from src/, relative paths are relative to it unless they exist from the root
+        let mut paths = PathMapper::new(&root, &root.join("src"));
+        assert_eq!(paths.map("pkg/a.py"), "src/pkg/a.py");
+        assert_eq!(paths.map("top.py"), "top.py");
+        assert_eq!(paths.map("../top.py"), "top.py");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_escapes_root() {
+        let root = Path::new("/repo");
+        assert!(!escapes_root("src/../lib/a.c", root));
+        assert!(escapes_root("src/../../other/a.c", root));
+        assert!(escapes_root("../other/a.c", root));
+        assert!(!escapes_root("/repo/src/a.c", root));
+        assert!(escapes_root("/elsewhere/a.c", root));
+    }
+    #[test]
+    fn test_lru() {
+        let mut lru = Lru::new(2);
+        lru.insert("a".to_string(), 1);
+        lru.insert("b".to_string(), 2);
+        assert_eq!(lru.get("a"), Some(1));
+        // `b` is the least recently used
+        lru.insert("c".to_string(), 3);
+        assert_eq!(lru.get("b"), None);
+        assert_eq!((lru.get("a"), lru.get("c")), (Some(1), Some(3)));
+        lru.insert("a".to_string(), 4);
+        assert_eq!(lru.get("a"), Some(4));
+        assert_eq!(lru.entries.len(), 2);
+        assert_eq!(lru.order.len(), 2);
+    }
+}
@@ -0,0 +1,78 @@
+//! Set operations on the hunk ranges of several diffs against the same new side.
+
+use crate::HunkRange;
+use std::collections::HashMap;
+
+pub type HunkMap = HashMap<String, Vec<HunkRange>>;
+
+/// Keeps ranges sorted and non-overlapping, as matching relies on a binary search
+pub fn merge_ranges(mut ranges: Vec<HunkRange>) -> Vec<HunkRange> {
+    ranges.sort_unstable();
+    let mut merged: Vec<HunkRange> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.0 <= last.1 => last.1 = last.1.max(range.1),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// Adds the lines changed in `other` to `file_hunks`
+pub fn union(file_hunks: &mut HunkMap, other: HunkMap) {
+    for (path, ranges) in other {
+        let merged = file_hunks.entry(path).or_default();
+        merged.extend(ranges);
+        *merged = merge_ranges(std::mem::take(merged));
+    }
+}
+
+/// Keeps only the lines that also changed relative to an excluded ref, dropping those
+/// that the excluded ref already has
+pub fn exclude(file_hunks: &mut HunkMap, excluded: &HunkMap) {
+    file_hunks.retain(|path, ranges| {
+        let other = match excluded.get(path) {
+            Some(other) => other,
+            None => return false,
+        };
+        *ranges = ranges
+            .iter()
+            .flat_map(|&(start, end)| {
+                other
+                    .iter()
+                    .filter(move |&&(other_start, other_end)| {
+                        other_start <= end && start <= other_end
+                    })
+                    .map(move |&(other_start, other_end)| {
+                        (start.max(other_start), end.min(other_end))
+                    })
+            })
+            .collect();
+        !ranges.is_empty()
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ranges::{exclude, union, HunkMap};
+
+    fn hunks(ranges: &[(u32, u32)]) -> HunkMap {
+        vec![("a.py".to_string(), ranges.to_vec())]
+            .into_iter()
+            .collect()
+    }
+
+    #[test]
+    fn test_union_and_exclude() {
+        let mut file_hunks = hunks(&[(1, 3), (10, 12)]);
+        union(&mut file_hunks, hunks(&[(2, 5), (20, 20)]));
+        assert_eq!(file_hunks["a.py"], [(1, 5), (10, 12), (20, 20)]);
+
+        // Lines 1-3 were cherry-picked to the excluded ref
+        exclude(&mut file_hunks, &hunks(&[(4, 11)]));
+        assert_eq!(file_hunks["a.py"], [(4, 5), (10, 11)]);
+
+        exclude(&mut file_hunks, &HunkMap::new());
+        assert!(file_hunks.is_empty());
+    }
+}
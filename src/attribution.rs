@@ -0,0 +1,81 @@
+use anyhow::{bail, Context, Result};
+use git2::{Blame, BlameOptions, Commit, Oid, Repository};
+use log::debug;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// A commit range given as `base..head`
+pub struct CommitRange<'r> {
+    pub base: Commit<'r>,
+    pub head: Commit<'r>,
+}
+
+impl<'r> CommitRange<'r> {
+    pub fn parse(repo: &'r Repository, range: &str) -> Result<Self> {
+        let (base, head) = match range.split_once("..") {
+            Some((base, head)) if !head.starts_with('.') => (base, head),
+            _ => bail!("Expected a range like 'base..head', got '{}'", range),
+        };
+        let commit = |rev: &str| -> Result<Commit<'r>> {
+            let rev = if rev.is_empty() { "HEAD" } else { rev };
+            repo.revparse_single(rev)
+                .and_then(|object| object.peel_to_commit())
+                .with_context(|| format!("Unable to resolve '{}' to a commit", rev))
+        };
+        Ok(CommitRange {
+            base: commit(base)?,
+            head: commit(head)?,
+        })
+    }
+}
+
+/// Finds which commit of a range last touched a line, using blame limited to the range
+pub struct Attributor<'r> {
+    repo: &'r Repository,
+    base: Oid,
+    head: Oid,
+    blames: HashMap<String, Option<Blame<'r>>>,
+}
+
+impl<'r> Attributor<'r> {
+    pub fn new(repo: &'r Repository, range: &CommitRange) -> Self {
+        Attributor {
+            repo,
+            base: range.base.id(),
+            head: range.head.id(),
+            blames: HashMap::new(),
+        }
+    }
+
+    pub fn commit_for(&mut self, path: &str, line: u32) -> Option<Commit<'r>> {
+        let (repo, base, head) = (self.repo, self.base, self.head);
+        let blame = self
+            .blames
+            .entry(path.to_string())
+            .or_insert_with(|| {
+                let mut options = BlameOptions::new();
+                options.oldest_commit(base).newest_commit(head);
+                repo.blame_file(Path::new(path), Some(&mut options))
+                    .map_err(|err| debug!("Can't blame '{}': {}", path, err))
+                    .ok()
+            })
+            .as_ref()?;
+        let hunk = blame.get_line(line as usize)?;
+        // Boundary lines predate the range
+        if hunk.is_boundary() || hunk.final_commit_id() == base {
+            return None;
+        }
+        repo.find_commit(hunk.final_commit_id()).ok()
+    }
+}
+
+/// A note pointing at the commit a finding should be fixed up into
+pub fn fixup_note(commit: &Commit) -> String {
+    let id = commit.id().to_string();
+    format!(
+        "    introduced by {} \"{}\" (git commit --fixup={})",
+        &id[..7],
+        commit.summary().unwrap_or_default(),
+        &id[..7]
+    )
+}
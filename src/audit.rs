@@ -0,0 +1,70 @@
+//! Auditing what the filter hides: reporting the suppressed findings instead of the new
+//! ones, or echoing both with a label, so pre-existing issues can be burnt down.
+
+use clap::{Args, ValueEnum};
+use std::borrow::Cow;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Show {
+    /// Findings on the change
+    #[default]
+    New,
+    /// Findings the filter drops as pre-existing, outside the change or in the baseline
+    Suppressed,
+}
+
+#[derive(Args, Debug, Clone, Copy, Default)]
+pub struct Audit {
+    /// Which findings to report
+    #[arg(long, value_enum, default_value_t)]
+    show: Show,
+
+    /// Echo every finding of the lint output prefixed with NEW or EXISTING; only the
+    /// new ones are reported to other outputs and fail the run
+    #[arg(long, conflicts_with = "show")]
+    pub annotate: bool,
+}
+
+impl Audit {
+    /// Whether a finding that is `new`, or pre-existing otherwise, is reported
+    pub fn reports(self, new: bool) -> bool {
+        match self.show {
+            Show::New => new,
+            Show::Suppressed => !new,
+        }
+    }
+
+    /// The lint output `line` to echo for a finding
+    pub fn label(self, line: &str, new: bool) -> Cow<'_, str> {
+        match (self.annotate, new) {
+            (false, _) => Cow::Borrowed(line),
+            (true, true) => Cow::Owned(format!("NEW {}", line)),
+            (true, false) => Cow::Owned(format!("EXISTING {}", line)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::audit::{Audit, Show};
+
+    #[test]
+    fn test_audit() {
+        let default = Audit::default();
+        assert!(default.reports(true) && !default.reports(false));
+        assert_eq!(default.label("a.py:1: E1", true), "a.py:1: E1");
+
+        let suppressed = Audit {
+            show: Show::Suppressed,
+            annotate: false,
+        };
+        assert!(!suppressed.reports(true) && suppressed.reports(false));
+
+        let annotate = Audit {
+            show: Show::New,
+            annotate: true,
+        };
+        assert_eq!(annotate.label("a.py:1: E1", true), "NEW a.py:1: E1");
+        assert_eq!(annotate.label("a.py:1: E1", false), "EXISTING a.py:1: E1");
+    }
+}
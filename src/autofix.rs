@@ -0,0 +1,270 @@
+//! `--apply-fixes`: writes the fixes tools suggested for findings on changed lines back
+//! into the working tree, in the style `.editorconfig` sets for each file.
+//!
+//! With `--fixes-report`, the changed lines of the fixed working tree are written along
+//! with which findings the fixes resolved and where the others moved, for a second run
+//! of the linter to confirm the fixes converged.
+
+use crate::diagnostic::{Diagnostic, Fix};
+use crate::editorconfig::{self, Style};
+use crate::output::write_atomic;
+use crate::{is_number_in_sorted_ranges, HunkMap};
+use anyhow::{Context, Result};
+use log::warn;
+use serde_json::{json, Value};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// Splits `text` into lines, each with its line ending
+fn split_lines(text: &str) -> Vec<(&str, &str)> {
+    let mut lines = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let end = rest.find(['\n', '\r']).unwrap_or(rest.len());
+        let ending = match &rest[end..] {
+            ending if ending.starts_with("\r\n") => 2,
+            "" => 0,
+            _ => 1,
+        };
+        lines.push((&rest[..end], &rest[end..end + ending]));
+        rest = &rest[end + ending..];
+    }
+    lines
+}
+
+/// The fixes applied to the working tree
+#[derive(Debug, Default)]
+pub struct Applied {
+    /// The indices of the findings resolved, in order
+    pub resolved: Vec<usize>,
+    /// The edits made, fewer than the findings resolved when several have the same fix
+    edits: usize,
+    /// For the files fixed with replacements adding lines, the line each line moved to
+    moved: HashMap<String, Vec<u32>>,
+}
+
+impl Applied {
+    /// Fixes with the same replacement as another, as when two formats report a typo,
+    /// count once
+    pub fn count(&self) -> usize {
+        self.edits
+    }
+
+    /// The line `line` of `path` is on once fixed
+    pub fn moved_line(&self, path: &str, line: u32) -> u32 {
+        self.moved
+            .get(path)
+            .and_then(|moved| moved.get(line.checked_sub(1)? as usize))
+            .copied()
+            .unwrap_or(line)
+    }
+
+    /// The report of `--fixes-report`: the hunks of the fixed tree, `file_hunks`, the
+    /// findings of `diagnostics` resolved and the others where they are now
+    pub fn report(&self, diagnostics: &[Diagnostic], file_hunks: &HunkMap) -> Value {
+        let hunks: BTreeMap<_, _> = file_hunks.iter().collect();
+        let (mut resolved, mut remaining) = (Vec::new(), Vec::new());
+        for (index, diagnostic) in diagnostics.iter().enumerate() {
+            if self.resolved.binary_search(&index).is_ok() {
+                resolved.push(diagnostic.clone());
+                continue;
+            }
+            let moved = |line| self.moved_line(&diagnostic.path, line);
+            let mut diagnostic = diagnostic.clone();
+            diagnostic.line = diagnostic.line.map(moved);
+            diagnostic.end_line = diagnostic.end_line.map(moved);
+            remaining.push(diagnostic);
+        }
+        json!({
+            "applied": self.count(),
+            "hunks": hunks,
+            "resolved": resolved,
+            "remaining": remaining,
+        })
+    }
+}
+
+/// Applies the fixes of `diagnostics` on changed lines of the files under `workdir`.
+/// Fixes overlapping one already applied are skipped, and lines without a fix are left
+/// as they are
+pub fn apply_fixes(
+    workdir: &Path,
+    diagnostics: &[Diagnostic],
+    file_hunks: &HunkMap,
+) -> Result<Applied> {
+    let mut by_file: BTreeMap<&str, Vec<(u32, u32, &Fix, usize)>> = BTreeMap::new();
+    for (index, diagnostic) in diagnostics.iter().enumerate() {
+        if let (Some(fix), Some(line), Some(column)) =
+            (&diagnostic.fix, diagnostic.line, diagnostic.column)
+        {
+            let changed = file_hunks
+                .get(&diagnostic.path)
+                .is_some_and(|ranges| is_number_in_sorted_ranges(ranges, line));
+            if changed {
+                by_file
+                    .entry(&diagnostic.path)
+                    .or_default()
+                    .push((line, column, fix, index));
+            }
+        }
+    }
+
+    let mut applied = Applied::default();
+    for (path, mut fixes) in by_file {
+        let style = editorconfig::style(workdir, path)?;
+        let file = workdir.join(path);
+        let text = fs::read_to_string(&file)
+            .with_context(|| format!("Unable to read {}", file.display()))?;
+        // Each line with its ending, and once fixed whether to reindent it
+        let mut lines: Vec<(String, &str, Option<bool>)> = split_lines(&text)
+            .into_iter()
+            .map(|(line, ending)| (line.to_string(), ending, None))
+            .collect();
+        // From the end of each line, so earlier columns stay put, restyling lines once
+        // all their fixes are in
+        fixes.sort_by_key(|&(line, column, _, finding)| (Reverse((line, column)), finding));
+        let mut start_of_last = None;
+        let mut last: Option<(u32, u32, &Fix)> = None;
+        for (line, column, fix, finding) in fixes {
+            if last == Some((line, column, fix)) {
+                applied.resolved.push(finding);
+                continue;
+            }
+            let overlaps = start_of_last.is_some_and(|(last_line, last_column)| {
+                last_line == line && column + fix.length > last_column
+            });
+            let index = line.checked_sub(1).map(|index| index as usize);
+            let (source, _, reindent) = match index.and_then(|index| lines.get_mut(index)) {
+                Some(source) if !overlaps => source,
+                _ => {
+                    warn!("Skipping the fix for {}:{}:{}", path, line, column);
+                    continue;
+                }
+            };
+            *reindent =
+                Some(reindent.unwrap_or(false) | Style::touches_indentation(source, fix, column));
+            *source = fix.apply(source, column);
+            start_of_last = Some((line, column));
+            last = Some((line, column, fix));
+            applied.edits += 1;
+            applied.resolved.push(finding);
+        }
+        if lines.iter().any(|(line, _, _)| line.contains('\n')) {
+            let mut next = 1;
+            let moved = lines
+                .iter()
+                .map(|(line, _, _)| {
+                    let moved = next;
+                    next += 1 + line.matches('\n').count() as u32;
+                    moved
+                })
+                .collect();
+            applied.moved.insert(path.to_string(), moved);
+        }
+        let mut fixed = String::with_capacity(text.len());
+        for (line, ending, reindent) in &lines {
+            match reindent {
+                Some(reindent) => {
+                    fixed.push_str(&style.restyle(line, *reindent));
+                    fixed.push_str(match (style.end_of_line, ending.is_empty()) {
+                        (Some(end_of_line), false) => end_of_line.as_str(),
+                        _ => ending,
+                    });
+                }
+                None => {
+                    fixed.push_str(line);
+                    fixed.push_str(ending);
+                }
+            }
+        }
+        if fixed != text {
+            write_atomic(&file, fixed.as_bytes())
+                .with_context(|| format!("Unable to write {}", file.display()))?;
+        }
+    }
+    applied.resolved.sort_unstable();
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::autofix::apply_fixes;
+    use crate::diagnostic::{Diagnostic, Fix};
+    use crate::fixtures::TempDir;
+    use std::collections::HashMap;
+    use std::fs;
+
+    fn fix_in(path: &str, line: u32, column: u32, length: u32, replacement: &str) -> Diagnostic {
+        let mut diagnostic = Diagnostic::new(path, Some(line));
+        diagnostic.column = Some(column);
+        diagnostic.fix = Some(Fix {
+            length,
+            replacement: replacement.to_string(),
+        });
+        diagnostic
+    }
+
+    #[test]
+    fn test_apply_fixes() {
+        let dir = TempDir::new("autofix").unwrap();
+        fs::write(
+            dir.join(".editorconfig"),
+            "root = true\n[*.py]\nindent_style = space\nend_of_line = lf\n",
+        )
+        .unwrap();
+        fs::write(dir.join("a.py"), "if x:\r\n\tteh = 1\r\nteh\r\n").unwrap();
+
+        let fix = |line, column, length, replacement: &str| {
+            fix_in("a.py", line, column, length, replacement)
+        };
+        let diagnostics = vec![
+            fix(2, 2, 3, "the"),
+            fix(2, 1, 1, "\t\t"),
+            // Overlaps the fix at column 2
+            fix(2, 1, 2, ""),
+            // Unchanged
+            fix(3, 1, 3, "the"),
+        ];
+        let file_hunks: HashMap<_, _> = vec![("a.py".to_string(), vec![(1, 2)])]
+            .into_iter()
+            .collect();
+        let applied = apply_fixes(&dir, &diagnostics, &file_hunks).unwrap();
+        assert_eq!(applied.resolved, [0, 1]);
+        assert_eq!(applied.count(), 2);
+        assert_eq!(
+            fs::read_to_string(dir.join("a.py")).unwrap(),
+            "if x:\r\n                the = 1\nteh\r\n"
+        );
+
+        // A fix adding lines moves the findings below it
+        fs::write(dir.join("b.py"), "x = 1\ny = 2\n").unwrap();
+        let mut unfixed = Diagnostic::new("b.py", Some(2));
+        unfixed.rule = Some("E1".to_string());
+        let diagnostics = vec![
+            fix_in("b.py", 1, 1, 0, "import os\n"),
+            fix_in("b.py", 1, 1, 0, "import os\n"),
+            unfixed,
+        ];
+        let file_hunks: HashMap<_, _> = vec![("b.py".to_string(), vec![(1, 2)])]
+            .into_iter()
+            .collect();
+        let applied = apply_fixes(&dir, &diagnostics, &file_hunks).unwrap();
+        assert_eq!(applied.count(), 1);
+        assert_eq!(applied.resolved, [0, 1]);
+        assert_eq!(
+            fs::read_to_string(dir.join("b.py")).unwrap(),
+            "import os\nx = 1\ny = 2\n"
+        );
+        let fixed_hunks: HashMap<_, _> = vec![("b.py".to_string(), vec![(1, 3)])]
+            .into_iter()
+            .collect();
+        let report = applied.report(&diagnostics, &fixed_hunks);
+        assert_eq!(report["applied"], 1);
+        assert_eq!(report["hunks"]["b.py"], serde_json::json!([[1, 3]]));
+        assert_eq!(report["resolved"].as_array().unwrap().len(), 2);
+        assert_eq!(report["remaining"][0]["line"], 3);
+        assert_eq!(report["remaining"][0]["rule"], "E1");
+    }
+}
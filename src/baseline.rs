@@ -0,0 +1,201 @@
+//! Known findings to suppress, stored per file so they can follow renames.
+//!
+//! A baseline file starts with a version header followed by one `path<TAB>fingerprint`
+//! line per finding. Fingerprints never include the path, so a rename only rewrites the
+//! first column. They hash the rule and the finding's source line with its whitespace
+//! normalized, but not its line number, so findings that unrelated edits shift stay
+//! suppressed.
+
+use crate::diagnostic::Diagnostic;
+use crate::fingerprint;
+use crate::location::LocationResolver;
+use crate::output;
+use anyhow::{bail, Context, Result};
+use clap::{Args, Subcommand};
+use git2::{Delta, Diff};
+use log::info;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const HEADER: &str = "# diff-format baseline v1";
+
+/// Without an action, prints a baseline of the findings in the lint output on stdin
+#[derive(Args, Debug)]
+pub struct BaselineArgs {
+    #[command(subcommand)]
+    action: Option<BaselineAction>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BaselineAction {
+    /// Rewrite the paths of a baseline for files renamed since --gitref
+    Migrate {
+        /// Baseline file, rewritten in place
+        file: PathBuf,
+    },
+}
+
+impl BaselineArgs {
+    pub fn action(&self) -> Option<&BaselineAction> {
+        self.action.as_ref()
+    }
+}
+
+/// Fingerprint of a finding in a baseline, from its rule and the source line it is on.
+/// File-level findings, and those whose line can't be read, use their message instead.
+///
+/// Unlike [`fingerprint::finding_fingerprint`] it leaves the path out, which baselines
+/// store beside it, and collapses whitespace; baselines already written depend on it
+pub fn baseline_fingerprint(diagnostic: &Diagnostic, resolver: &mut LocationResolver) -> String {
+    let source_line = diagnostic
+        .line
+        .and_then(|line| resolver.source_line(&diagnostic.path, line));
+    let text = source_line.unwrap_or_else(|| diagnostic.text());
+    let normalized: Vec<&str> = text.split_whitespace().collect();
+    fingerprint::fingerprint(&[
+        diagnostic.rule.as_deref().unwrap_or_default(),
+        &normalized.join(" "),
+    ])
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Baseline {
+    entries: BTreeSet<(String, String)>,
+}
+
+impl Baseline {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut lines = text.lines();
+        if lines.next() != Some(HEADER) {
+            bail!("Missing '{}' header", HEADER);
+        }
+        let mut entries = BTreeSet::new();
+        for (index, line) in lines.enumerate() {
+            match line.split_once('\t') {
+                Some((path, fingerprint)) => {
+                    entries.insert((path.to_string(), fingerprint.to_string()));
+                }
+                None if line.is_empty() => {}
+                None => bail!("Malformed entry on line {}: '{}'", index + 2, line),
+            }
+        }
+        Ok(Baseline { entries })
+    }
+
+    pub fn insert(&mut self, diagnostic: &Diagnostic, resolver: &mut LocationResolver) {
+        let fingerprint = baseline_fingerprint(diagnostic, resolver);
+        self.entries.insert((diagnostic.path.clone(), fingerprint));
+    }
+
+    /// Whether the baseline holds `diagnostic`, wherever in its file it is now
+    pub fn contains(&self, diagnostic: &Diagnostic, resolver: &mut LocationResolver) -> bool {
+        let fingerprint = baseline_fingerprint(diagnostic, resolver);
+        self.entries
+            .contains(&(diagnostic.path.clone(), fingerprint))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text =
+            fs::read_to_string(path).with_context(|| format!("Can't read {}", path.display()))?;
+        Baseline::parse(&text).with_context(|| format!("Invalid baseline {}", path.display()))
+    }
+
+    pub fn render(&self) -> String {
+        let mut text = format!("{}\n", HEADER);
+        for (path, fingerprint) in &self.entries {
+            text.push_str(&format!("{}\t{}\n", path, fingerprint));
+        }
+        text
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        output::write_atomic(path, self.render().as_bytes())
+            .with_context(|| format!("Can't write {}", path.display()))
+    }
+
+    /// Moves entries of renamed files to their new path, returning how many moved
+    pub fn migrate(&mut self, renames: &HashMap<String, String>) -> usize {
+        let mut moved = 0;
+        self.entries = std::mem::take(&mut self.entries)
+            .into_iter()
+            .map(|(path, fingerprint)| match renames.get(&path) {
+                Some(new_path) => {
+                    moved += 1;
+                    (new_path.clone(), fingerprint)
+                }
+                None => (path, fingerprint),
+            })
+            .collect();
+        moved
+    }
+}
+
+/// Old to new paths of the files a diff renamed, which needs rename detection enabled
+pub fn renames(diff: &Diff) -> HashMap<String, String> {
+    diff.deltas()
+        .filter(|delta| delta.status() == Delta::Renamed)
+        .filter_map(|delta| {
+            let old = delta.old_file().path()?.to_str()?;
+            let new = delta.new_file().path()?.to_str()?;
+            Some((old.to_string(), new.to_string()))
+        })
+        .collect()
+}
+
+/// Rewrites the baseline at `path` for the renames in `diff`
+pub fn migrate_file(path: &Path, diff: &Diff) -> Result<()> {
+    let mut baseline = Baseline::load(path)?;
+    let moved = baseline.migrate(&renames(diff));
+    baseline.save(path)?;
+    info!("Moved {} baseline entries to renamed files", moved);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::baseline::Baseline;
+    use crate::diagnostic::Diagnostic;
+    use crate::fixtures::TempDir;
+    use crate::location::LocationResolver;
+    use std::collections::HashMap;
+    use std::fs;
+
+    #[test]
+    fn test_line_shifts() {
+        let root = TempDir::new("baseline").unwrap();
+        let finding = |line, rule: &str| {
+            let mut diagnostic = Diagnostic::new("a.py", Some(line));
+            diagnostic.rule = Some(rule.to_string());
+            diagnostic
+        };
+
+        fs::write(root.join("a.py"), "import os\nx = 1\n").unwrap();
+        let mut baseline = Baseline::default();
+        baseline.insert(&finding(1, "F401"), &mut LocationResolver::new(root.path()));
+
+        // Lines added above shift the import, and its indentation changed
+        fs::write(root.join("a.py"), "y = 2\nz = 3\n  import  os\nx = 1\n").unwrap();
+        let mut resolver = LocationResolver::new(root.path());
+        assert!(baseline.contains(&finding(3, "F401"), &mut resolver));
+        assert!(!baseline.contains(&finding(3, "E501"), &mut resolver));
+        assert!(!baseline.contains(&finding(1, "F401"), &mut resolver));
+        assert_eq!(Baseline::parse(&baseline.render()).unwrap(), baseline);
+    }
+
+    #[test]
+    fn test_migrate() {
+        let mut baseline =
+            Baseline::parse("# diff-format baseline v1\nold.py\t0123\nkept.py\t4567\n").unwrap();
+        let renames: HashMap<_, _> = vec![("old.py".to_string(), "new.py".to_string())]
+            .into_iter()
+            .collect();
+        assert_eq!(baseline.migrate(&renames), 1);
+        assert_eq!(
+            baseline.render(),
+            "# diff-format baseline v1\nkept.py\t4567\nnew.py\t0123\n"
+        );
+
+        assert!(Baseline::parse("old.py\t0123\n").is_err());
+    }
+}
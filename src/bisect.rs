@@ -0,0 +1,319 @@
+//! `bisect`: the commit a finding first appeared in, found by bisecting the first-parent
+//! history up to `--rev`.
+//!
+//! A finding is identified as in a baseline, by its rule and the source line it is on
+//! with whitespace collapsed, so it is followed as lines above it come and go. With a
+//! `--linter`, each commit tried is written to a temporary directory and linted there;
+//! without one, a commit has the finding when the file has its source line, which is
+//! quicker but blames the line rather than the setting that made it a finding. Either
+//! way the finding is taken to have stayed once it appeared, as in `git bisect`.
+
+use crate::parallel::parse_line;
+use crate::parsers::Parsers;
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use git2::{Commit, ObjectType, Oid, Repository, Tree, TreeWalkMode, TreeWalkResult};
+use log::info;
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+#[derive(Args, Debug)]
+pub struct BisectArgs {
+    /// Rule code of the finding
+    #[arg(long)]
+    rule: String,
+
+    /// File the finding is in, relative to the repository root
+    #[arg(long)]
+    file: String,
+
+    /// Line the finding is on in --rev
+    #[arg(long)]
+    line: u32,
+
+    /// Lint command reporting the finding, run with `sh -c` in a checkout of each
+    /// commit tried and parsed with --format; without one, commits are told apart by
+    /// the source line of the finding
+    #[arg(long, value_name = "COMMAND")]
+    linter: Option<String>,
+
+    /// Newest commit, which must have the finding
+    #[arg(long, default_value = "HEAD")]
+    rev: String,
+}
+
+/// `line` with runs of whitespace collapsed and its ends trimmed
+fn normalized(line: &str) -> String {
+    line.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The lines of `path` in `tree`, if it has such a file
+fn file_lines(repo: &Repository, tree: &Tree, path: &str) -> Result<Option<Vec<String>>> {
+    let entry = match tree.get_path(Path::new(path)) {
+        Ok(entry) => entry,
+        Err(_) => return Ok(None),
+    };
+    let blob = match entry.to_object(repo)?.into_blob() {
+        Ok(blob) => blob,
+        Err(_) => return Ok(None),
+    };
+    let text = String::from_utf8_lossy(blob.content());
+    Ok(Some(text.lines().map(normalized).collect()))
+}
+
+/// Writes the files of `tree` under `dir`, leaving out submodules and symlinks
+fn write_tree(repo: &Repository, tree: &Tree, dir: &Path) -> Result<()> {
+    let mut error = None;
+    tree.walk(TreeWalkMode::PreOrder, |parent, entry| {
+        let is_file = entry.kind() == Some(ObjectType::Blob) && entry.filemode() != 0o120000;
+        if !is_file {
+            return TreeWalkResult::Ok;
+        }
+        let path = dir.join(parent).join(entry.name().unwrap_or_default());
+        let written = entry
+            .to_object(repo)
+            .map_err(anyhow::Error::from)
+            .and_then(|object| {
+                fs::create_dir_all(path.parent().unwrap())?;
+                fs::write(&path, object.as_blob().unwrap().content())?;
+                Ok(())
+            });
+        match written {
+            Ok(()) => TreeWalkResult::Ok,
+            Err(err) => {
+                error = Some(err.context(format!("Unable to write {}", path.display())));
+                TreeWalkResult::Abort
+            }
+        }
+    })?;
+    error.map_or(Ok(()), Err)
+}
+
+/// The index of the oldest of `commits`, newest first, for which `present` holds,
+/// given that it holds for the first and, from some commit on, for none of the older
+fn oldest_present(commits: &[Oid], mut present: impl FnMut(Oid) -> Result<bool>) -> Result<usize> {
+    // Past the end stands for the empty history before the root commit
+    let (mut newest, mut oldest) = (0, commits.len());
+    while oldest - newest > 1 {
+        let middle = newest + (oldest - newest) / 2;
+        if present(commits[middle])? {
+            newest = middle;
+        } else {
+            oldest = middle;
+        }
+    }
+    Ok(newest)
+}
+
+impl BisectArgs {
+    pub fn runs_linter(&self) -> bool {
+        self.linter.is_some()
+    }
+
+    /// Whether `commit` has the finding, whose source line is `source`
+    fn present(
+        &self,
+        repo: &Repository,
+        parsers: &Parsers,
+        commit: &Commit,
+        source: &str,
+    ) -> Result<bool> {
+        let tree = commit.tree()?;
+        let lines = match file_lines(repo, &tree, &self.file)? {
+            Some(lines) => lines,
+            None => return Ok(false),
+        };
+        let linter = match &self.linter {
+            Some(linter) => linter,
+            None => return Ok(lines.iter().any(|line| line == source)),
+        };
+        let dir = env::temp_dir().join(format!(
+            "diff-format-bisect-{}-{}",
+            std::process::id(),
+            commit.id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        write_tree(repo, &tree, &dir)?;
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(linter)
+            .current_dir(&dir)
+            .output()
+            .with_context(|| format!("Unable to run '{}'", linter));
+        fs::remove_dir_all(&dir).with_context(|| format!("Unable to remove {}", dir.display()))?;
+        let output = output?;
+        let text =
+            String::from_utf8_lossy(&output.stdout) + String::from_utf8_lossy(&output.stderr);
+        let found = text.lines().any(|line| {
+            parse_line(parsers, line.to_string(), true)
+                .1
+                .iter()
+                .any(|diagnostic| {
+                    let path = Path::new(&diagnostic.path);
+                    let path = path.strip_prefix(&dir).unwrap_or(path);
+                    let path = path.strip_prefix(".").unwrap_or(path);
+                    let line = diagnostic
+                        .line
+                        .and_then(|line| lines.get((line as usize).wrapping_sub(1)));
+                    diagnostic.rule.as_deref() == Some(self.rule.as_str())
+                        && path == Path::new(&self.file)
+                        && line.map(String::as_str) == Some(source)
+                })
+        });
+        Ok(found)
+    }
+
+    /// Finds the commit the finding first appeared in, describing it with the number of
+    /// commits tried
+    pub fn run(&self, repo: &Repository, parsers: &Parsers) -> Result<String> {
+        let newest = repo
+            .revparse_single(&self.rev)
+            .and_then(|object| object.peel_to_commit())
+            .with_context(|| format!("Unable to find commit '{}'", self.rev))?;
+        let lines = file_lines(repo, &newest.tree()?, &self.file)?
+            .with_context(|| format!("{} has no file {}", self.rev, self.file))?;
+        let source = match lines.get((self.line as usize).wrapping_sub(1)) {
+            Some(source) if !source.is_empty() => source.clone(),
+            _ => bail!(
+                "{}:{} isn't a line with code in {}",
+                self.file,
+                self.line,
+                self.rev
+            ),
+        };
+        if !self.present(repo, parsers, &newest, &source)? {
+            bail!(
+                "{} has no {} finding at {}:{}",
+                self.rev,
+                self.rule,
+                self.file,
+                self.line
+            );
+        }
+
+        let mut revwalk = repo.revwalk()?;
+        revwalk.push(newest.id())?;
+        revwalk.simplify_first_parent()?;
+        let commits = revwalk.collect::<Result<Vec<_>, _>>()?;
+        let mut tried = 1;
+        let index = oldest_present(&commits, |oid| {
+            tried += 1;
+            let present = self.present(repo, parsers, &repo.find_commit(oid)?, &source)?;
+            info!(
+                "{}: {}",
+                oid,
+                if present { "has it" } else { "doesn't have it" }
+            );
+            Ok(present)
+        })?;
+        let first = repo.find_commit(commits[index])?;
+        let mut text = format!("{} {}\n", first.id(), first.summary().unwrap_or_default());
+        let author = first.author();
+        writeln!(
+            text,
+            "{} finding at {}:{} first appeared in this commit by {}, {} of {} commit(s) tried",
+            self.rule,
+            self.file,
+            self.line,
+            author.name().unwrap_or("unknown"),
+            tried,
+            commits.len()
+        )?;
+        if index + 1 == commits.len() {
+            text.push_str("It is in the first commit of the history\n");
+        }
+        Ok(text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bisect::{oldest_present, write_tree, BisectArgs};
+    use crate::fixtures::TempDir;
+    use crate::parsers::{Format, Parsers};
+    use git2::{Oid, Repository, Signature};
+    use std::collections::HashMap;
+    use std::fs;
+
+    #[test]
+    fn test_oldest_present() {
+        let commits: Vec<_> = (1..=9u8)
+            .map(|n| Oid::from_bytes(&[n; 20]).unwrap())
+            .collect();
+        let present = |count: usize| {
+            let commits = commits.clone();
+            move |oid| Ok(commits[..count].contains(&oid))
+        };
+        assert_eq!(oldest_present(&commits, present(1)).unwrap(), 0);
+        assert_eq!(oldest_present(&commits, present(4)).unwrap(), 3);
+        assert_eq!(oldest_present(&commits, present(9)).unwrap(), 8);
+    }
+
+    #[test]
+    fn test_bisect() {
+        let dir = TempDir::new("bisect-repo").unwrap();
+        let repo = Repository::init(&dir).unwrap();
+        let signature = Signature::now("Ann", "ann@example.com").unwrap();
+        let mut parents = Vec::new();
+        let mut ids = Vec::new();
+        for (message, text) in [
+            ("base", "a = 1\n"),
+            ("long", "a = 1\nx  =  'long'\n"),
+            ("above", "b = 2\na = 1\nx = 'long'\n"),
+            ("unrelated", "b = 2\na = 1\nx = 'long'\n# end\n"),
+        ] {
+            let blob = repo.blob(text.as_bytes()).unwrap();
+            let mut builder = repo.treebuilder(None).unwrap();
+            builder.insert("a.py", blob, 0o100644).unwrap();
+            let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+            let parent_refs: Vec<_> = parents.iter().collect();
+            let id = repo
+                .commit(
+                    Some("HEAD"),
+                    &signature,
+                    &signature,
+                    message,
+                    &tree,
+                    &parent_refs,
+                )
+                .unwrap();
+            parents = vec![repo.find_commit(id).unwrap()];
+            ids.push(id);
+        }
+        let parsers = Parsers::new(&[Format::Python], HashMap::new());
+        let bisect = |line, linter: Option<&str>| {
+            BisectArgs {
+                rule: "E501".to_string(),
+                file: "a.py".to_string(),
+                line,
+                linter: linter.map(str::to_string),
+                rev: "HEAD".to_string(),
+            }
+            .run(&repo, &parsers)
+        };
+
+        let text = bisect(3, None).unwrap();
+        assert!(text.starts_with(&format!("{} long\n", ids[1])), "{}", text);
+        assert!(text.contains("first appeared in this commit by Ann"));
+        let text = bisect(2, None).unwrap();
+        assert!(text.ends_with("It is in the first commit of the history\n"));
+        assert!(bisect(9, None).is_err());
+
+        // The linter reports the line wherever it is
+        let linter = "grep -n long a.py | sed 's/^\\([0-9]*\\):.*/a.py:\\1:1: E501 too long/'";
+        let text = bisect(3, Some(linter)).unwrap();
+        assert!(text.starts_with(&format!("{} long\n", ids[1])), "{}", text);
+        assert!(bisect(1, Some(linter)).is_err());
+
+        let out = TempDir::new("bisect-tree").unwrap();
+        let tree = repo.head().unwrap().peel_to_tree().unwrap();
+        write_tree(&repo, &tree, &out).unwrap();
+        assert_eq!(
+            fs::read_to_string(out.join("a.py")).unwrap(),
+            "b = 2\na = 1\nx = 'long'\n# end\n"
+        );
+    }
+}
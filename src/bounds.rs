@@ -0,0 +1,305 @@
+use crate::fingerprint::fingerprint;
+use crate::HunkRange;
+use anyhow::{bail, Result};
+use clap::{Args, ValueEnum};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// How the end of a `(new_start, new_start + new_lines)` hunk range is interpreted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum HunkBounds {
+    /// The line right after the hunk counts as changed, matching historical behavior
+    #[default]
+    Inclusive,
+    /// Only the hunk's own lines count as changed
+    Exclusive,
+}
+
+/// Which lines count as changed for a pure deletion, whose `(new_start, new_start)`
+/// range covers no line and `new_start` is the line before the removed ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Deletions {
+    /// Whatever --hunk-bounds makes of the range: the line before the deletion when
+    /// inclusive, none when exclusive, matching historical behavior
+    #[default]
+    Bounds,
+    /// No line, so findings next to removed code aren't blamed on the change
+    None,
+    /// The line before the deletion
+    Before,
+    /// The lines before and after the deletion, which it joined together
+    Around,
+}
+
+/// Whether `range` is that of a pure deletion, before bounds are applied
+pub fn is_deletion(range: HunkRange) -> bool {
+    range.0 == range.1
+}
+
+impl HunkBounds {
+    /// The lines `range` covers, inclusively, or `None` if it covers none
+    pub fn resolve(self, deletions: Deletions, (start, end): HunkRange) -> Option<HunkRange> {
+        let (start, end) = match (is_deletion((start, end)), deletions, self) {
+            (true, Deletions::None, _) => return None,
+            (true, Deletions::Before, _) => (start, start),
+            (true, Deletions::Around, _) => (start.max(1), start + 1),
+            (_, _, HunkBounds::Inclusive) => (start, end),
+            (_, _, HunkBounds::Exclusive) => (start, end.saturating_sub(1)),
+        };
+        // Line 0 is before a deletion at the top of the file
+        (start.max(1) <= end).then_some((start.max(1), end))
+    }
+
+    /// Rewrites ranges so they can always be checked inclusively, dropping those that
+    /// cover no line. Files stay, as changed files, even without a range left
+    pub fn apply(self, deletions: Deletions, file_hunks: &mut HashMap<String, Vec<HunkRange>>) {
+        for ranges in file_hunks.values_mut() {
+            *ranges = ranges
+                .iter()
+                .filter_map(|&range| self.resolve(deletions, range))
+                .collect();
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct HunksArgs {
+    /// Print JSON lines instead of text
+    #[arg(long)]
+    json: bool,
+
+    /// Print a digest of the hunks instead, for later jobs to pass to --expect-checksum
+    #[arg(long, conflicts_with = "json")]
+    checksum: bool,
+}
+
+#[derive(Serialize)]
+struct ListedHunk<'a> {
+    path: &'a str,
+    /// `lines`, or `deletion` for a hunk that only removed lines
+    kind: &'static str,
+    /// The line before a deletion
+    #[serde(skip_serializing_if = "Option::is_none")]
+    after: Option<u32>,
+    /// The lines that count as changed, inclusive; absent when none do
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lines: Option<HunkRange>,
+}
+
+/// A digest of `file_hunks`, before bounds are applied, that doesn't depend on the
+/// order of files or hunks: the fingerprint of each path followed by its ranges
+pub fn checksum(file_hunks: &HashMap<String, Vec<HunkRange>>) -> String {
+    let mut files: Vec<_> = file_hunks
+        .iter()
+        .map(|(path, ranges)| {
+            let mut ranges = ranges.clone();
+            ranges.sort_unstable();
+            let ranges: Vec<_> = ranges
+                .iter()
+                .map(|(start, end)| format!("{}-{}", start, end))
+                .collect();
+            (path.as_str(), ranges.join(","))
+        })
+        .collect();
+    files.sort_unstable();
+    let parts: Vec<_> = files
+        .iter()
+        .flat_map(|(path, ranges)| [*path, ranges.as_str()])
+        .collect();
+    fingerprint(&parts)
+}
+
+/// Fails unless `file_hunks` has the `expected` checksum, as when the workdir changed
+/// between the job that computed it and this one
+pub fn expect_checksum(expected: &str, file_hunks: &HashMap<String, Vec<HunkRange>>) -> Result<()> {
+    let actual = checksum(file_hunks);
+    if !actual.eq_ignore_ascii_case(expected.trim()) {
+        bail!(
+            "The changed lines have checksum {}, not the expected {}; did the workdir \
+             change since they were computed?",
+            actual,
+            expected
+        );
+    }
+    Ok(())
+}
+
+/// Lists the hunks of `file_hunks`, before bounds are applied, with the lines each
+/// counts as changed once they are, or their checksum
+pub fn list(
+    args: &HunksArgs,
+    bounds: HunkBounds,
+    deletions: Deletions,
+    file_hunks: &HashMap<String, Vec<HunkRange>>,
+) -> String {
+    if args.checksum {
+        return checksum(file_hunks) + "\n";
+    }
+    let mut paths: Vec<_> = file_hunks.keys().collect();
+    paths.sort();
+    let mut output = String::new();
+    for path in paths {
+        for &range in &file_hunks[path] {
+            let deletion = is_deletion(range);
+            let hunk = ListedHunk {
+                path,
+                kind: if deletion { "deletion" } else { "lines" },
+                after: deletion.then_some(range.0),
+                lines: bounds.resolve(deletions, range),
+            };
+            if args.json {
+                output.push_str(&serde_json::to_string(&hunk).unwrap());
+                output.push('\n');
+                continue;
+            }
+            let lines = match hunk.lines {
+                Some((start, end)) if start == end => format!("line {}", start),
+                Some((start, end)) => format!("lines {}-{}", start, end),
+                None => "no line".to_string(),
+            };
+            match hunk.after {
+                Some(after) => {
+                    writeln!(output, "{}\tdeletion after line {}\t{}", path, after, lines)
+                }
+                None => writeln!(output, "{}\t{}", path, lines),
+            }
+            .unwrap();
+        }
+    }
+    output
+}
+
+/// A diagnostic on or right next to a hunk edge, where the two interpretations may disagree
+#[derive(Debug, PartialEq, Eq)]
+pub struct NearMiss {
+    pub hunk: HunkRange,
+    pub inclusive: bool,
+    pub exclusive: bool,
+}
+
+/// Checks `line` against raw (historical, inclusive) ranges for a near miss
+pub fn near_miss(ranges: &[HunkRange], line: u32) -> Option<NearMiss> {
+    ranges
+        .iter()
+        .find(|&&(start, end)| line + 1 == start || line == end || line == end + 1)
+        .map(|&(start, end)| NearMiss {
+            hunk: (start, end),
+            inclusive: start <= line && line <= end,
+            exclusive: start <= line && line < end,
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bounds::{
+        checksum, expect_checksum, list, near_miss, Deletions, HunkBounds, HunksArgs, NearMiss,
+    };
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_exclusive_bounds() {
+        let mut file_hunks: HashMap<_, _> = vec![
+            ("a.py".to_string(), vec![(3, 5), (9, 9)]),
+            ("b.py".to_string(), vec![(4, 4)]),
+        ]
+        .into_iter()
+        .collect();
+        HunkBounds::Exclusive.apply(Deletions::Bounds, &mut file_hunks);
+        assert_eq!(file_hunks["a.py"], [(3, 4)]);
+        assert!(file_hunks["b.py"].is_empty());
+    }
+
+    #[test]
+    fn test_deletions() {
+        let resolve = |bounds: HunkBounds, deletions, range| bounds.resolve(deletions, range);
+        let inclusive = HunkBounds::Inclusive;
+        assert_eq!(resolve(inclusive, Deletions::Bounds, (9, 9)), Some((9, 9)));
+        assert_eq!(resolve(inclusive, Deletions::None, (9, 9)), None);
+        assert_eq!(resolve(inclusive, Deletions::Around, (9, 9)), Some((9, 10)));
+        assert_eq!(
+            resolve(HunkBounds::Exclusive, Deletions::Before, (9, 9)),
+            Some((9, 9))
+        );
+        // A deletion at the top of the file has line 0 before it
+        assert_eq!(resolve(inclusive, Deletions::Before, (0, 0)), None);
+        assert_eq!(resolve(inclusive, Deletions::Around, (0, 0)), Some((1, 1)));
+        // Only ranges without new lines are deletions
+        assert_eq!(resolve(inclusive, Deletions::None, (9, 10)), Some((9, 10)));
+
+        let file_hunks: HashMap<_, _> = vec![("a.py".to_string(), vec![(3, 5), (9, 9)])]
+            .into_iter()
+            .collect();
+        let args = |json| HunksArgs {
+            json,
+            checksum: false,
+        };
+        assert_eq!(
+            list(&args(false), inclusive, Deletions::None, &file_hunks),
+            "a.py\tlines 3-5\na.py\tdeletion after line 9\tno line\n"
+        );
+        assert_eq!(
+            list(&args(true), inclusive, Deletions::Bounds, &file_hunks),
+            "{\"path\":\"a.py\",\"kind\":\"lines\",\"lines\":[3,5]}\n\
+             {\"path\":\"a.py\",\"kind\":\"deletion\",\"after\":9,\"lines\":[9,9]}\n"
+        );
+    }
+
+    #[test]
+    fn test_checksum() {
+        let file_hunks: HashMap<_, _> = vec![
+            ("a.py".to_string(), vec![(9, 9), (3, 5)]),
+            ("b.py".to_string(), vec![(1, 2)]),
+        ]
+        .into_iter()
+        .collect();
+        let reordered: HashMap<_, _> = vec![
+            ("b.py".to_string(), vec![(1, 2)]),
+            ("a.py".to_string(), vec![(3, 5), (9, 9)]),
+        ]
+        .into_iter()
+        .collect();
+        let digest = checksum(&file_hunks);
+        assert_eq!(digest.len(), 16);
+        assert_eq!(checksum(&reordered), digest);
+        assert!(expect_checksum(&digest.to_uppercase(), &file_hunks).is_ok());
+
+        let mut drifted = file_hunks.clone();
+        drifted.get_mut("b.py").unwrap()[0] = (1, 3);
+        assert!(expect_checksum(&digest, &drifted).is_err());
+        drifted.remove("b.py");
+        drifted.insert("c.py".to_string(), vec![(1, 2)]);
+        assert_ne!(checksum(&drifted), digest);
+        let args = HunksArgs {
+            json: false,
+            checksum: true,
+        };
+        assert_eq!(
+            list(&args, HunkBounds::Inclusive, Deletions::Bounds, &file_hunks),
+            digest + "\n"
+        );
+    }
+
+    #[test]
+    fn test_near_miss() {
+        let ranges = [(3, 5)];
+        assert_eq!(near_miss(&ranges, 4), None);
+        assert_eq!(near_miss(&ranges, 10), None);
+        assert_eq!(
+            near_miss(&ranges, 5),
+            Some(NearMiss {
+                hunk: (3, 5),
+                inclusive: true,
+                exclusive: false
+            })
+        );
+        assert_eq!(
+            near_miss(&ranges, 2),
+            Some(NearMiss {
+                hunk: (3, 5),
+                inclusive: false,
+                exclusive: false
+            })
+        );
+    }
+}
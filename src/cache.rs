@@ -0,0 +1,245 @@
+//! `--cache`: the lint output of changed files whose content was already linted,
+//! replayed instead of linting them again, for repeated local runs and CI retries.
+//!
+//! The linter after `--` gets the files to lint in place of a `{files}` word, so it
+//! only lints those missing from the cache; its output is stored per file once it
+//! exits without failing. An entry is keyed by the blob OID of the file and a hash of
+//! the rule set: the lint command, the formats parsing its output and the
+//! `--cache-key-file`s, like the linter's config, so changing any of them starts over.
+//! Lines of the output that name none of the files, like totals, aren't replayed.
+
+use crate::fingerprint::fingerprint;
+use crate::input::Lines;
+use crate::output::write_atomic;
+use crate::parallel::parse_line;
+use crate::parsers::{FormatSpec, Parsers};
+use crate::paths::PathMapper;
+use anyhow::{bail, Context, Result};
+use git2::{ObjectType, Oid};
+use log::debug;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// The word of the lint command standing for the files to lint
+pub const FILES: &str = "{files}";
+
+pub struct ResultCache {
+    dir: PathBuf,
+    /// Hash of what besides the content of a file decides its findings
+    rules: String,
+}
+
+/// A run of the linter over the changed files missing from the cache
+pub struct CachedRun {
+    cache: ResultCache,
+    /// The root-relative path and blob OID of each file linted
+    linted: Vec<(String, Oid)>,
+    /// The lint command, with the files linted in place of `{files}`
+    command: Option<Vec<String>>,
+    /// The lines the linter printed, stored once it exits
+    output: Rc<RefCell<Vec<String>>>,
+}
+
+impl ResultCache {
+    pub fn new(
+        dir: &Path,
+        command: &[String],
+        formats: &[FormatSpec],
+        key_files: &[PathBuf],
+    ) -> Result<Self> {
+        if !command.iter().any(|word| word == FILES) {
+            bail!(
+                "--cache lints only the files it misses, give them to the linter as {}",
+                FILES
+            );
+        }
+        let mut parts = vec![env!("CARGO_PKG_VERSION").to_string(), command.join("\0")];
+        parts.extend(formats.iter().map(|format| format.name().to_string()));
+        for path in key_files {
+            let text = fs::read_to_string(path)
+                .with_context(|| format!("Unable to read cache key file {}", path.display()))?;
+            parts.push(text);
+        }
+        let parts: Vec<_> = parts.iter().map(String::as_str).collect();
+        Ok(ResultCache {
+            dir: dir.to_path_buf(),
+            rules: fingerprint(&parts),
+        })
+    }
+
+    fn entry(&self, oid: Oid) -> PathBuf {
+        let key = fingerprint(&[&oid.to_string(), &self.rules]);
+        self.dir.join(format!("{}.txt", key))
+    }
+
+    fn load(&self, oid: Oid) -> Option<Vec<String>> {
+        let text = fs::read_to_string(self.entry(oid)).ok()?;
+        Some(text.lines().map(str::to_string).collect())
+    }
+
+    fn store(&self, oid: Oid, lines: &[String]) -> Result<()> {
+        let path = self.entry(oid);
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Unable to create cache {}", self.dir.display()))?;
+        let text: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+        write_atomic(&path, text.as_bytes())
+            .with_context(|| format!("Unable to write {}", path.display()))
+    }
+
+    /// Splits the `files` of `workdir`, relative to it, into the lines cached for them
+    /// and a run linting the others with `command` from `cwd`. Files that are gone
+    /// are neither
+    pub fn plan<'a>(
+        self,
+        workdir: &Path,
+        cwd: &Path,
+        files: impl IntoIterator<Item = &'a String>,
+        command: &[String],
+    ) -> Result<(Vec<String>, CachedRun)> {
+        let mut files: Vec<_> = files.into_iter().collect();
+        files.sort();
+        let mut cached = Vec::new();
+        let mut linted = Vec::new();
+        let mut arguments = Vec::new();
+        for path in files {
+            let file = workdir.join(path);
+            if !file.is_file() {
+                continue;
+            }
+            let oid = Oid::hash_file(ObjectType::Blob, &file)
+                .with_context(|| format!("Unable to hash {}", file.display()))?;
+            match self.load(oid) {
+                Some(lines) => {
+                    debug!("{}: {} cached line(s)", path, lines.len());
+                    cached.extend(lines);
+                }
+                None => {
+                    let argument = file.strip_prefix(cwd).unwrap_or(&file);
+                    arguments.push(argument.to_string_lossy().into_owned());
+                    linted.push((path.clone(), oid));
+                }
+            }
+        }
+        debug!(
+            "Linting {} file(s), replaying {} cached line(s)",
+            linted.len(),
+            cached.len()
+        );
+        let command = (!linted.is_empty()).then(|| {
+            command
+                .iter()
+                .flat_map(|word| match word.as_str() {
+                    FILES => arguments.clone(),
+                    _ => vec![word.clone()],
+                })
+                .collect()
+        });
+        let run = CachedRun {
+            cache: self,
+            linted,
+            command,
+            output: Rc::default(),
+        };
+        Ok((cached, run))
+    }
+}
+
+impl CachedRun {
+    /// The command to lint the files missing from the cache, if any are
+    pub fn command(&self) -> Option<&[String]> {
+        self.command.as_deref()
+    }
+
+    /// `lines` of the linter's output, kept to store once it exits
+    pub fn tee<'a>(&self, lines: Lines<'a>) -> Lines<'a> {
+        let output = self.output.clone();
+        Box::new(lines.inspect(move |line| {
+            if let Ok(line) = line {
+                output.borrow_mut().push(line.clone());
+            }
+        }))
+    }
+
+    /// Stores the output of the linter by the file each line names, parsed with
+    /// `parsers` and mapped to the root with `paths`; files without findings are
+    /// cached as clean
+    pub fn store(self, parsers: &Parsers, paths: &mut PathMapper) -> Result<()> {
+        let mut by_file: HashMap<&str, Vec<String>> = self
+            .linted
+            .iter()
+            .map(|(path, _)| (path.as_str(), Vec::new()))
+            .collect();
+        for line in self.output.borrow().iter() {
+            let (_, diagnostics) = parse_line(parsers, line.clone(), true);
+            let path = diagnostics
+                .first()
+                .map(|diagnostic| paths.map(&diagnostic.path));
+            if let Some(lines) = path.and_then(|path| by_file.get_mut(path.as_str())) {
+                lines.push(line.clone());
+            }
+        }
+        for (path, oid) in &self.linted {
+            self.cache.store(*oid, &by_file[path.as_str()])?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cache::ResultCache;
+    use crate::fixtures::TempDir;
+    use crate::parsers::{Format, Parsers};
+    use crate::paths::PathMapper;
+    use std::collections::HashMap;
+    use std::fs;
+
+    #[test]
+    fn test_cache() {
+        let dir = TempDir::new("cache").unwrap();
+        let (workdir, store) = (dir.join("repo"), dir.join("cache"));
+        fs::create_dir_all(&workdir).unwrap();
+        fs::write(workdir.join("a.py"), "import os\n").unwrap();
+        fs::write(workdir.join("b.py"), "x = 1\n").unwrap();
+        let command: Vec<_> = ["ruff", "check", "{files}"].map(String::from).into();
+        let formats = ["python".parse().unwrap()];
+        let parsers = Parsers::new(&[Format::Python], HashMap::new());
+        let files = [
+            "a.py".to_string(),
+            "b.py".to_string(),
+            "gone.py".to_string(),
+        ];
+        let cache = || ResultCache::new(&store, &command, &formats, &[]).unwrap();
+
+        let (cached, run) = cache().plan(&workdir, &workdir, &files, &command).unwrap();
+        assert!(cached.is_empty());
+        assert_eq!(run.command().unwrap(), ["ruff", "check", "a.py", "b.py"]);
+        let output = vec![
+            Ok("a.py:1:8: F401 `os` imported but unused".to_string()),
+            Ok("Found 1 error.".to_string()),
+        ];
+        assert_eq!(run.tee(Box::new(output.into_iter())).count(), 2);
+        run.store(&parsers, &mut PathMapper::new(&workdir, &workdir))
+            .unwrap();
+
+        // Both are cached, b.py as clean, until one of them changes
+        let (cached, run) = cache().plan(&workdir, &workdir, &files, &command).unwrap();
+        assert_eq!(cached, ["a.py:1:8: F401 `os` imported but unused"]);
+        assert!(run.command().is_none());
+        fs::write(workdir.join("b.py"), "x = 2\n").unwrap();
+        let (cached, run) = cache().plan(&workdir, &workdir, &files, &command).unwrap();
+        assert_eq!(cached.len(), 1);
+        assert_eq!(run.command().unwrap(), ["ruff", "check", "b.py"]);
+
+        // A different rule set misses
+        fs::write(dir.join("ruff.toml"), "select = ['E']\n").unwrap();
+        let keyed = ResultCache::new(&store, &command, &formats, &[dir.join("ruff.toml")]).unwrap();
+        let (cached, _) = keyed.plan(&workdir, &workdir, &files, &command).unwrap();
+        assert!(cached.is_empty());
+        let plain: Vec<_> = ["ruff", "check", "."].map(String::from).into();
+        assert!(ResultCache::new(&store, &plain, &formats, &[]).is_err());
+    }
+}
@@ -0,0 +1,314 @@
+//! Checkstyle XML reports, as written by Checkstyle itself, ktlint, detekt and ESLint's
+//! `checkstyle` formatter, cut down to the errors on changed lines.
+//!
+//! The format is flat, `<file>` elements holding `<error>` elements whose attributes
+//! carry everything, so a small tag scanner reads it; attributes are written back in
+//! their original order.
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::paths;
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+
+pub(crate) type Attributes = Vec<(String, String)>;
+
+struct File {
+    name: String,
+    errors: Vec<Attributes>,
+}
+
+pub struct CheckstyleReport {
+    version: Option<String>,
+    files: Vec<File>,
+}
+
+/// A tag: its name, whether it closes an element, and its attributes
+pub(crate) struct Tag<'a> {
+    pub name: &'a str,
+    pub closing: bool,
+    pub attributes: Attributes,
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest.find(';').map(|end| (&rest[1..end], end));
+        let decoded = entity.and_then(|(name, _)| match name {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                None => name
+                    .strip_prefix('#')?
+                    .parse()
+                    .ok()
+                    .and_then(char::from_u32),
+            },
+        });
+        match (decoded, entity) {
+            (Some(c), Some((_, end))) => {
+                unescaped.push(c);
+                rest = &rest[end + 1..];
+            }
+            _ => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\n' => escaped.push_str("&#10;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn parse_attributes(mut text: &str) -> Result<Attributes> {
+    let mut attributes = Vec::new();
+    loop {
+        text = text.trim_start();
+        if text.is_empty() {
+            return Ok(attributes);
+        }
+        let (name, rest) = text
+            .split_once('=')
+            .with_context(|| format!("Malformed attributes '{}'", text))?;
+        let rest = rest.trim_start();
+        let quote = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => quote,
+            _ => bail!("Unquoted value for attribute '{}'", name.trim()),
+        };
+        let end = rest[1..]
+            .find(quote)
+            .with_context(|| format!("Unterminated value for attribute '{}'", name.trim()))?;
+        attributes.push((name.trim().to_string(), unescape(&rest[1..end + 1])));
+        text = &rest[end + 2..];
+    }
+}
+
+/// The element tags of `text` in order, skipping declarations, comments, text and
+/// CDATA sections
+pub(crate) fn tags(text: &str) -> Result<Vec<Tag<'_>>> {
+    let mut tags = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        if let Some(comment) = rest.strip_prefix("!--") {
+            let end = comment.find("-->").context("Unterminated comment")?;
+            rest = &comment[end + 3..];
+            continue;
+        }
+        if let Some(data) = rest.strip_prefix("![CDATA[") {
+            let end = data.find("]]>").context("Unterminated CDATA section")?;
+            rest = &data[end + 3..];
+            continue;
+        }
+        let end = rest.find('>').context("Unterminated tag")?;
+        let tag = &rest[..end];
+        rest = &rest[end + 1..];
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        let (closing, tag) = match tag.strip_prefix('/') {
+            Some(tag) => (true, tag),
+            None => (false, tag),
+        };
+        let tag = tag.strip_suffix('/').unwrap_or(tag);
+        let (name, attributes) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+        tags.push(Tag {
+            name,
+            closing,
+            attributes: parse_attributes(attributes)?,
+        });
+    }
+    Ok(tags)
+}
+
+pub(crate) fn attribute<'a>(attributes: &'a Attributes, name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+}
+
+impl CheckstyleReport {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut version = None;
+        let mut files: Vec<File> = Vec::new();
+        let mut in_file = false;
+        let mut root = false;
+        for tag in tags(text).context("Invalid Checkstyle XML")? {
+            match (tag.name, tag.closing) {
+                ("checkstyle", false) => {
+                    root = true;
+                    version = attribute(&tag.attributes, "version").map(str::to_string);
+                }
+                ("file", false) => {
+                    let name = attribute(&tag.attributes, "name")
+                        .context("<file> without a name")?
+                        .to_string();
+                    files.push(File {
+                        name,
+                        errors: Vec::new(),
+                    });
+                    in_file = true;
+                }
+                ("file", true) => in_file = false,
+                ("error", false) if in_file => {
+                    files.last_mut().unwrap().errors.push(tag.attributes);
+                }
+                _ => {}
+            }
+        }
+        if !root {
+            bail!("Not a Checkstyle report, it has no <checkstyle> element");
+        }
+        Ok(CheckstyleReport { version, files })
+    }
+
+    /// Every error as a diagnostic, with paths under `root` made relative to it
+    pub fn diagnostics(&self, root: &Path) -> Vec<Diagnostic> {
+        self.files
+            .iter()
+            .flat_map(|file| {
+                file.errors
+                    .iter()
+                    .map(move |error| diagnostic(&file.name, error, root))
+            })
+            .collect()
+    }
+
+    /// Keeps the errors `keep` accepts. Files stay listed, as the report also records
+    /// which files were checked
+    pub fn retain(&mut self, root: &Path, mut keep: impl FnMut(&Diagnostic) -> bool) {
+        for file in &mut self.files {
+            let name = &file.name;
+            file.errors
+                .retain(|error| keep(&diagnostic(name, error, root)));
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<checkstyle");
+        if let Some(version) = &self.version {
+            xml.push_str(&format!(" version=\"{}\"", escape(version)));
+        }
+        xml.push_str(">\n");
+        for file in &self.files {
+            xml.push_str(&format!("  <file name=\"{}\">\n", escape(&file.name)));
+            for error in &file.errors {
+                xml.push_str("    <error");
+                for (name, value) in error {
+                    xml.push_str(&format!(" {}=\"{}\"", name, escape(value)));
+                }
+                xml.push_str("/>\n");
+            }
+            xml.push_str("  </file>\n");
+        }
+        xml.push_str("</checkstyle>\n");
+        xml
+    }
+}
+
+fn diagnostic(name: &str, error: &Attributes, root: &Path) -> Diagnostic {
+    let number = |name| attribute(error, name).and_then(|value| value.parse().ok());
+    let mut diagnostic = Diagnostic::new(paths::root_relative(Path::new(name), root), None);
+    // Checkstyle uses line 0 for findings about the file itself
+    diagnostic.line = number("line").filter(|&line| line > 0);
+    diagnostic.column = number("column").filter(|&column| column > 0);
+    diagnostic.severity = attribute(error, "severity").and_then(Severity::from_label);
+    diagnostic.message = attribute(error, "message").map(str::to_string);
+    let source = attribute(error, "source");
+    // Checks are named by class or dotted path, e.g. `eslint.rules.no-unused-vars`
+    diagnostic.rule = source.and_then(|source| source.rsplit('.').next().map(str::to_string));
+    diagnostic.tool = Some("checkstyle".to_string());
+
+    // A plain line, for text outputs written to files
+    let mut raw = diagnostic.path.clone();
+    for number in [diagnostic.line, diagnostic.column].iter().flatten() {
+        raw.push_str(&format!(":{}", number));
+    }
+    raw.push_str(&format!(
+        ": {}: {}",
+        diagnostic.effective_severity().as_str(),
+        diagnostic.message.as_deref().unwrap_or_default()
+    ));
+    if let Some(source) = source {
+        raw.push_str(&format!(" [{}]", source));
+    }
+    diagnostic.raw = raw;
+    diagnostic
+}
+
+#[cfg(test)]
+mod test {
+    use crate::checkstyle::CheckstyleReport;
+    use crate::diagnostic::Severity;
+    use std::path::Path;
+
+    const REPORT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- <error> in a comment -->
+<checkstyle version="8.0">
+<file name="/repo/src/Main.java">
+<error line="3" column="8" severity="warning" message="Unused import - java.util.List &amp; &lt;T&gt;." source="com.puppycrawl.tools.checkstyle.checks.imports.UnusedImportsCheck"/>
+<error line="10" severity="error" message='Line is longer than 100 characters.' source="LineLengthCheck"></error>
+</file>
+<file name="src/Empty.kt">
+</file>
+</checkstyle>
+"#;
+
+    #[test]
+    fn test_checkstyle() {
+        let root = Path::new("/repo");
+        let mut report = CheckstyleReport::parse(REPORT).unwrap();
+        let diagnostics = report.diagnostics(root);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].path, "src/Main.java");
+        assert_eq!(
+            (diagnostics[0].line, diagnostics[0].column),
+            (Some(3), Some(8))
+        );
+        assert_eq!(diagnostics[0].severity, Some(Severity::Warning));
+        assert_eq!(diagnostics[0].rule.as_deref(), Some("UnusedImportsCheck"));
+        assert_eq!(
+            diagnostics[0].text(),
+            "Unused import - java.util.List & <T>."
+        );
+        assert_eq!(
+            diagnostics[1].raw,
+            "src/Main.java:10: error: Line is longer than 100 characters. [LineLengthCheck]"
+        );
+
+        report.retain(root, |diagnostic| diagnostic.line == Some(3));
+        assert_eq!(
+            report.render(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<checkstyle version=\"8.0\">\n  \
+             <file name=\"/repo/src/Main.java\">\n    <error line=\"3\" column=\"8\" \
+             severity=\"warning\" message=\"Unused import - java.util.List &amp; &lt;T&gt;.\" \
+             source=\"com.puppycrawl.tools.checkstyle.checks.imports.UnusedImportsCheck\"/>\n  \
+             </file>\n  <file name=\"src/Empty.kt\">\n  </file>\n</checkstyle>\n"
+        );
+
+        assert!(CheckstyleReport::parse("<checkstyle><file></file></checkstyle>").is_err());
+        assert!(CheckstyleReport::parse("{}").is_err());
+    }
+}
@@ -0,0 +1,123 @@
+//! Equivalence of hunks by content, in the spirit of `git cherry` and `git patch-id`.
+
+use crate::fingerprint::fingerprint;
+use crate::HunkRange;
+use anyhow::{Context, Result};
+use git2::{Diff, DiffOptions, Oid, Repository};
+use log::{debug, info};
+use std::collections::{HashMap, HashSet};
+
+/// The hunks of `diff` as `(path, range, id)`, where the id covers the path and the
+/// removed and added lines with whitespace stripped, but not line numbers
+fn hunk_ids(diff: &Diff) -> Result<Vec<(String, HunkRange, String)>> {
+    let mut hunks: Vec<(String, HunkRange, Vec<String>)> = Vec::new();
+    diff.foreach(
+        &mut |_, _| true,
+        None,
+        None,
+        Some(&mut |delta, hunk, line| {
+            let (path, hunk) = match (delta.new_file().path().and_then(|p| p.to_str()), hunk) {
+                (Some(path), Some(hunk)) => (path, hunk),
+                _ => return true,
+            };
+            let range = (hunk.new_start(), hunk.new_start() + hunk.new_lines());
+            let content: String = String::from_utf8_lossy(line.content())
+                .split_whitespace()
+                .collect();
+            let key = format!("{}{}", line.origin(), content);
+            match hunks.last_mut() {
+                Some((last_path, last_range, lines))
+                    if last_path == path && *last_range == range =>
+                {
+                    lines.push(key)
+                }
+                _ => hunks.push((path.to_string(), range, vec![key])),
+            }
+            true
+        }),
+    )
+    .context("Issue when iterating over diff")?;
+    Ok(hunks
+        .into_iter()
+        .map(|(path, range, lines)| {
+            let mut parts = vec![path.as_str()];
+            parts.extend(lines.iter().map(String::as_str));
+            let id = fingerprint(&parts);
+            (path, range, id)
+        })
+        .collect())
+}
+
+/// Ids of the hunks of every non-merge commit reachable from `upstream` but not `head`
+pub fn upstream_hunk_ids(repo: &Repository, upstream: Oid, head: Oid) -> Result<HashSet<String>> {
+    let mut walk = repo.revwalk()?;
+    walk.push(upstream)?;
+    walk.hide(head)?;
+    let mut ids = HashSet::new();
+    let mut commits = 0;
+    for oid in walk {
+        let commit = repo.find_commit(oid?)?;
+        if commit.parent_count() != 1 {
+            continue;
+        }
+        let mut options = DiffOptions::new();
+        options.context_lines(0);
+        let diff = repo.diff_tree_to_tree(
+            Some(&commit.parent(0)?.tree()?),
+            Some(&commit.tree()?),
+            Some(&mut options),
+        )?;
+        ids.extend(hunk_ids(&diff)?.into_iter().map(|(_, _, id)| id));
+        commits += 1;
+    }
+    debug!(
+        "Collected {} hunk ids from {} upstream commit(s)",
+        ids.len(),
+        commits
+    );
+    Ok(ids)
+}
+
+/// Drops the hunks of `diff` that some upstream commit already made
+pub fn drop_equivalent(
+    diff: &Diff,
+    upstream_ids: &HashSet<String>,
+    file_hunks: &mut HashMap<String, Vec<HunkRange>>,
+) -> Result<()> {
+    let mut dropped = 0;
+    for (path, range, id) in hunk_ids(diff)? {
+        if !upstream_ids.contains(&id) {
+            continue;
+        }
+        if let Some(ranges) = file_hunks.get_mut(&path) {
+            let before = ranges.len();
+            ranges.retain(|&other| other != range);
+            dropped += before - ranges.len();
+        }
+    }
+    file_hunks.retain(|_, ranges| !ranges.is_empty());
+    info!("Dropped {} hunk(s) already made upstream", dropped);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cherry::hunk_ids;
+    use git2::Diff;
+
+    fn ids(patch: &str) -> Vec<(String, (u32, u32), String)> {
+        hunk_ids(&Diff::from_buffer(patch.as_bytes()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_hunk_ids_ignore_position_and_whitespace() {
+        let upstream =
+            ids("diff --git a/a.py b/a.py\n--- a/a.py\n+++ b/a.py\n@@ -2 +2 @@\n-b\n+B = 1\n");
+        let ours =
+            ids("diff --git a/a.py b/a.py\n--- a/a.py\n+++ b/a.py\n@@ -4 +4 @@\n-b\n+B =  1\n");
+        let other = ids("diff --git a/a.py b/a.py\n--- a/a.py\n+++ b/a.py\n@@ -4 +4 @@\n-b\n+c\n");
+        assert_eq!(ours[0].1, (4, 5));
+        assert_eq!(upstream[0].2, ours[0].2);
+        assert_ne!(upstream[0].2, other[0].2);
+    }
+}
@@ -0,0 +1,148 @@
+use crate::output::OutputFormat;
+use crate::publish::PublishTarget;
+use clap::ValueEnum;
+use log::info;
+use serde::Deserialize;
+use std::env;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Ci {
+    /// Detect the provider from the environment
+    Auto,
+    Github,
+    Gitlab,
+    Circleci,
+    Drone,
+    Buildkite,
+}
+
+/// Provider specific defaults, used for anything not set explicitly on the command line
+#[derive(Debug, Default, PartialEq)]
+pub struct CiPlan {
+    pub output_format: Option<OutputFormat>,
+    pub publish: Vec<PublishTarget>,
+    /// Extra reports written to disk as artifacts
+    pub reports: Vec<(OutputFormat, PathBuf)>,
+}
+
+fn env_flag(name: &str) -> bool {
+    env::var(name).is_ok_and(|value| value == "true")
+}
+
+fn detect() -> Option<Ci> {
+    if env_flag("GITHUB_ACTIONS") {
+        Some(Ci::Github)
+    } else if env_flag("GITLAB_CI") {
+        Some(Ci::Gitlab)
+    } else if env_flag("CIRCLECI") {
+        Some(Ci::Circleci)
+    } else if env_flag("DRONE") {
+        Some(Ci::Drone)
+    } else if env_flag("BUILDKITE") {
+        Some(Ci::Buildkite)
+    } else {
+        None
+    }
+}
+
+/// Whether this runs in a CI job, of a known provider or any that sets `CI`
+pub fn in_ci() -> bool {
+    detect().is_some() || env::var_os("CI").is_some_and(|value| !value.is_empty())
+}
+
+/// Joins `file` onto the directory named by `var`, or the current directory if it is unset
+fn path_from_env(var: &str, file: &str) -> PathBuf {
+    let dir = env::var_os(var).map(PathBuf::from).unwrap_or_default();
+    expand_home(dir, env::var_os("HOME")).join(file)
+}
+
+/// `dir` with a leading `~` replaced by `home`, as CircleCI sets its working directory
+/// to `~/project` unexpanded, or the current directory if there is no `home`
+fn expand_home(dir: PathBuf, home: Option<OsString>) -> PathBuf {
+    match (dir.strip_prefix("~"), home) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        (Ok(_), None) => PathBuf::new(),
+        (Err(_), _) => dir,
+    }
+}
+
+impl Ci {
+    pub fn plan(self) -> CiPlan {
+        let provider = match self {
+            Ci::Auto => match detect() {
+                Some(provider) => provider,
+                None => {
+                    info!("No CI provider detected");
+                    return CiPlan::default();
+                }
+            },
+            provider => provider,
+        };
+        info!("Using defaults for {:?}", provider);
+
+        match provider {
+            Ci::Gitlab => CiPlan {
+                reports: vec![
+                    (
+                        OutputFormat::Junit,
+                        path_from_env("CI_PROJECT_DIR", "diff-format.junit.xml"),
+                    ),
+                    (
+                        OutputFormat::Codequality,
+                        path_from_env("CI_PROJECT_DIR", "gl-code-quality-report.json"),
+                    ),
+                ],
+                ..CiPlan::default()
+            },
+            // Picked up by `store_test_results: {path: test-results}`
+            Ci::Circleci => CiPlan {
+                reports: vec![(
+                    OutputFormat::Junit,
+                    path_from_env(
+                        "CIRCLE_WORKING_DIRECTORY",
+                        "test-results/diff-format/results.xml",
+                    ),
+                )],
+                ..CiPlan::default()
+            },
+            Ci::Drone => CiPlan {
+                reports: vec![(
+                    OutputFormat::Junit,
+                    path_from_env("DRONE_WORKSPACE", "reports/diff-format.xml"),
+                )],
+                ..CiPlan::default()
+            },
+            Ci::Buildkite => CiPlan {
+                publish: vec![PublishTarget::Buildkite],
+                ..CiPlan::default()
+            },
+            Ci::Github => CiPlan {
+                output_format: Some(OutputFormat::Github),
+                ..CiPlan::default()
+            },
+            Ci::Auto => CiPlan::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ci::expand_home;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_expand_home() {
+        let home = || Some("/home/circleci".into());
+        assert_eq!(
+            expand_home("~/project".into(), home()),
+            Path::new("/home/circleci/project")
+        );
+        assert_eq!(expand_home("~/project".into(), None), PathBuf::new());
+        for dir in ["/builds/app", "~user/project", ""] {
+            assert_eq!(expand_home(dir.into(), home()), Path::new(dir));
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! Owners of paths, read from a CODEOWNERS file the way GitHub and GitLab read it.
+
+use crate::config::compile_glob;
+use anyhow::{Context, Result};
+use globset::GlobMatcher;
+use std::fs;
+use std::path::Path;
+
+/// Where code hosts look for the file, in the order GitHub does
+const LOCATIONS: &[&str] = &[
+    ".github/CODEOWNERS",
+    "CODEOWNERS",
+    "docs/CODEOWNERS",
+    ".gitlab/CODEOWNERS",
+];
+
+struct Rule {
+    globs: Vec<GlobMatcher>,
+    owners: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct CodeOwners {
+    rules: Vec<Rule>,
+}
+
+/// Gitignore style patterns: unanchored unless they contain a slash before their end,
+/// and matching everything under a directory they name
+fn pattern_globs(pattern: &str) -> Result<Vec<GlobMatcher>> {
+    let anchored = pattern.trim_end_matches('/').contains('/');
+    let directory_only = pattern.ends_with('/');
+    let pattern = pattern.trim_matches('/');
+    let base = if anchored {
+        pattern.to_string()
+    } else {
+        format!("**/{}", pattern)
+    };
+    let mut globs = vec![compile_glob(&format!("{}/**", base))?];
+    if !directory_only {
+        globs.push(compile_glob(&base)?);
+    }
+    Ok(globs)
+}
+
+impl CodeOwners {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut rules = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split(" #").next().unwrap_or_default().trim();
+            // Comments and GitLab `[Section]` headers
+            if line.is_empty() || line.starts_with(['#', '[', '^']) {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let pattern = fields.next().unwrap_or_default();
+            let globs = pattern_globs(pattern)
+                .with_context(|| format!("Invalid CODEOWNERS pattern on line {}", index + 1))?;
+            rules.push(Rule {
+                globs,
+                owners: fields.map(str::to_string).collect(),
+            });
+        }
+        Ok(CodeOwners { rules })
+    }
+
+    /// Reads the first CODEOWNERS file found in `workdir`, or none if there is none
+    pub fn discover(workdir: &Path) -> Result<Self> {
+        for location in LOCATIONS {
+            let path = workdir.join(location);
+            if path.is_file() {
+                let text = fs::read_to_string(&path)
+                    .with_context(|| format!("Unable to read {}", path.display()))?;
+                return CodeOwners::parse(&text);
+            }
+        }
+        Ok(CodeOwners::default())
+    }
+
+    /// Owners of `path` by the last rule that matches it, none if no rule does or the
+    /// rule names no owner
+    pub fn owners(&self, path: &str) -> &[String] {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.globs.iter().any(|glob| glob.is_match(path)))
+            .map_or(&[], |rule| &rule.owners)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::codeowners::CodeOwners;
+
+    #[test]
+    fn test_owners() {
+        let owners = CodeOwners::parse(
+            "\
+# Fallback
+*       @org/platform
+*.py    @py-team # inline comment
+/docs/  docs@example.com
+build/  @build
+[Frontend]
+web/**/*.ts @org/web @alice
+/web/generated.ts
+",
+        )
+        .unwrap();
+        let of = |path| owners.owners(path).join(" ");
+        assert_eq!(of("Makefile"), "@org/platform");
+        assert_eq!(of("src/a.py"), "@py-team");
+        assert_eq!(of("docs/guide/index.md"), "docs@example.com");
+        assert_eq!(of("src/docs/a.md"), "@org/platform");
+        assert_eq!(of("tools/build/run.sh"), "@build");
+        assert_eq!(of("web/app/main.ts"), "@org/web @alice");
+        assert_eq!(of("web/generated.ts"), "");
+    }
+}
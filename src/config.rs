@@ -0,0 +1,820 @@
+use crate::ci::{self, Ci};
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::embedded::{EmbeddedBlocks, EmbeddedConfig};
+use crate::expression::Expression;
+use crate::gates::GateConfig;
+use crate::normalize;
+use crate::output::{self, OutputFormat};
+use crate::parsers::{Format, RegexParser};
+use crate::publish::PublishTarget;
+use crate::rollout::Rollouts;
+use crate::rule_docs::RuleDocs;
+use crate::score::Weights;
+use crate::waiver::Waivers;
+use anyhow::{bail, Context, Result};
+use clap::{Args, Subcommand, ValueEnum};
+use globset::{GlobBuilder, GlobMatcher};
+use log::{info, warn};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub use crate::matcher::MatchPolicy;
+pub use crate::parsers::ParserProfile;
+
+pub const DEFAULT_CONFIG: &str = ".diff-format.toml";
+
+/// Version of the config layout this build reads and writes, as `schema = 2`
+pub const SCHEMA: u32 = 2;
+
+/// Rewrites a config table of one schema into the next, returning what it changed
+type Migration = fn(&mut toml::Table) -> Vec<String>;
+
+/// The migration from each schema to the next, starting from 1
+const MIGRATIONS: [Migration; 1] = [dated_rollouts];
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ToolConfig {
+    #[serde(rename = "match")]
+    pub match_policy: Option<MatchPolicy>,
+    /// Whether to normalize the format's findings, overriding the top-level `normalize`
+    pub normalize: Option<bool>,
+    /// The tool's own severity labels, e.g. `blocker = "error"`, when normalizing
+    #[serde(default)]
+    pub severities: HashMap<String, Severity>,
+    /// Prefixes stripped from the tool's messages when normalizing, e.g. `"mypy: "`
+    #[serde(default)]
+    pub strip_prefixes: Vec<String>,
+}
+
+/// Least severe matched finding that fails the run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FailOn {
+    Info,
+    Warning,
+    Error,
+    /// Report findings but never fail because of them
+    Never,
+}
+
+impl From<Severity> for FailOn {
+    fn from(severity: Severity) -> Self {
+        match severity {
+            Severity::Info => FailOn::Info,
+            Severity::Warning => FailOn::Warning,
+            Severity::Error => FailOn::Error,
+        }
+    }
+}
+
+impl FailOn {
+    pub fn fails(self, severity: Severity) -> bool {
+        match self {
+            FailOn::Never => false,
+            FailOn::Info => true,
+            FailOn::Warning => severity >= Severity::Warning,
+            FailOn::Error => severity >= Severity::Error,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyConfig {
+    pub fail_on: Option<FailOn>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EscalateConfig {
+    pub min_severity: Severity,
+}
+
+/// Compiled `[policy."<glob>"]` and `[escalate."<glob>"]` tables
+pub struct Policies {
+    globs: Vec<(String, GlobMatcher, FailOn)>,
+    floors: Vec<(GlobMatcher, Severity)>,
+}
+
+impl Policies {
+    /// The policy of the longest, and so most specific, pattern matching `path`
+    pub fn fail_on(&self, path: &str) -> Option<FailOn> {
+        self.globs
+            .iter()
+            .filter(|(_, matcher, _)| matcher.is_match(path))
+            .max_by_key(|(pattern, _, _)| pattern.len())
+            .map(|&(_, _, fail_on)| fail_on)
+    }
+
+    /// Raises the severity of `diagnostic` to the highest floor of the patterns
+    /// matching its path, for the fail policy to see
+    pub fn escalate(&self, diagnostic: &mut Diagnostic) {
+        let floor = self
+            .floors
+            .iter()
+            .filter(|(matcher, _)| matcher.is_match(&diagnostic.path))
+            .map(|&(_, floor)| floor)
+            .max();
+        if let Some(floor) = floor.filter(|&floor| floor > diagnostic.effective_severity()) {
+            diagnostic.severity = Some(floor);
+        }
+    }
+}
+
+pub fn compile_glob(pattern: &str) -> Result<GlobMatcher> {
+    Ok(GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()?
+        .compile_matcher())
+}
+
+/// Settings for one environment, e.g. `[profile.ci]`, used for whatever the command
+/// line leaves unset so laptops and pipelines can share one invocation
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfig {
+    pub output_format: Option<OutputFormat>,
+    pub quiet: Option<bool>,
+    #[serde(default)]
+    pub publish: Vec<PublishTarget>,
+    pub ci: Option<Ci>,
+    pub fail_on: Option<Severity>,
+    /// A `--policy` expression
+    pub policy: Option<String>,
+}
+
+/// An approved exception for a rule on matching paths, until it expires
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WaiverConfig {
+    pub rule: String,
+    /// Glob matched against finding paths
+    pub path: String,
+    pub approved_by: String,
+    /// Last day the waiver applies, as a TOML date
+    pub expires: toml::value::Datetime,
+}
+
+/// A rule reported as a non-failing warning until a date
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RolloutConfig {
+    pub rule: String,
+    /// First day the rule fails the run, as a TOML date
+    pub warn_until: toml::value::Datetime,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Version of the config layout, 1 when missing; older ones are migrated on load
+    pub schema: Option<u32>,
+    /// Per-format settings, keyed by format name
+    #[serde(default)]
+    pub tool: HashMap<String, ToolConfig>,
+    /// Take severities from the labels findings' messages start with and strip them,
+    /// see [`normalize`](crate::normalize)
+    #[serde(default)]
+    pub normalize: bool,
+    /// Block-relative line mapping, keyed by file extension
+    #[serde(default)]
+    pub embedded: HashMap<String, EmbeddedConfig>,
+    /// Format `auto` prefers for findings in files with an extension, by format name
+    #[serde(default)]
+    pub extensions: HashMap<String, String>,
+    /// Rule documentation URL templates by file extension, naming the rule as `{rule}`,
+    /// see [`rule_docs`](crate::rule_docs)
+    #[serde(default)]
+    pub rule_docs: HashMap<String, String>,
+    /// Severity of findings without one, keyed by rule code prefix
+    #[serde(default)]
+    pub severity: HashMap<String, Severity>,
+    /// Rule codes `--fold-equivalent` treats as another tool's, e.g. `C0301 = "E501"`
+    #[serde(default)]
+    pub fold: HashMap<String, String>,
+    /// Fail policies, keyed by a glob matched against finding paths
+    #[serde(default)]
+    pub policy: HashMap<String, PolicyConfig>,
+    /// Least severity of findings under a path, keyed by a glob like `policy`
+    #[serde(default)]
+    pub escalate: HashMap<String, EscalateConfig>,
+    /// Lint output formats defined by regex, keyed by the name `--format` takes
+    #[serde(default)]
+    pub parsers: HashMap<String, ParserProfile>,
+    /// Suppressed rules, failing the run again once expired
+    #[serde(default)]
+    pub waivers: Vec<WaiverConfig>,
+    /// Rules being enabled, as warnings for now
+    #[serde(default)]
+    pub rollout: Vec<RolloutConfig>,
+    /// Defaults for an environment, keyed by the name `--profile` takes
+    #[serde(default)]
+    pub profile: HashMap<String, ProfileConfig>,
+    /// Checks the `gates` subcommand runs against the same diff, in order
+    #[serde(default)]
+    pub gate: Vec<GateConfig>,
+    /// Weights of each severity for `--max-score`
+    #[serde(default)]
+    pub score: Weights,
+}
+
+/// Replaces each `${VAR}` in `text` with the variable's value; `$${` is a literal `${`
+fn interpolate(text: &str, var: &impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(index) = rest.find("${") {
+        if rest[..index].ends_with('$') {
+            result.push_str(&rest[..index]);
+            result.push('{');
+            rest = &rest[index + 2..];
+            continue;
+        }
+        result.push_str(&rest[..index]);
+        let end = match rest[index..].find('}') {
+            Some(end) => index + end,
+            None => bail!("unterminated '${{' in '{}'", text),
+        };
+        let name = &rest[index + 2..end];
+        match var(name) {
+            Some(value) => result.push_str(&value),
+            None => bail!("environment variable '{}' is not set", name),
+        }
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Interpolates every string in `value`, naming the offending key on errors
+fn interpolate_value(
+    value: &mut toml::Value,
+    key: &str,
+    var: &impl Fn(&str) -> Option<String>,
+) -> Result<()> {
+    match value {
+        toml::Value::String(text) => {
+            *text = interpolate(text, var).with_context(|| format!("[{}]", key))?
+        }
+        toml::Value::Array(values) => {
+            for value in values {
+                interpolate_value(value, key, var)?;
+            }
+        }
+        toml::Value::Table(table) => {
+            for (name, value) in table {
+                let key = if key.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{}.{}", key, name)
+                };
+                interpolate_value(value, &key, var)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Schema 2 takes `[[rollout]] warn_until` as a TOML date only, as `expires` of
+/// `[[waivers]]` always was, instead of also a `"YYYY-MM-DD"` string
+fn dated_rollouts(table: &mut toml::Table) -> Vec<String> {
+    let mut changes = Vec::new();
+    let rollouts = table.get_mut("rollout").and_then(toml::Value::as_array_mut);
+    for rollout in rollouts.into_iter().flatten() {
+        let warn_until = rollout.get_mut("warn_until");
+        if let Some(toml::Value::String(text)) = warn_until {
+            if let Ok(date) = text.parse::<toml::value::Datetime>() {
+                changes.push(format!(
+                    "[[rollout]] warn_until \"{}\" is now the date {}",
+                    text, date
+                ));
+                *warn_until.unwrap() = toml::Value::Datetime(date);
+            }
+        }
+    }
+    changes
+}
+
+/// The schema `table` declares
+fn schema(table: &toml::Table) -> Result<u32> {
+    let value = match table.get("schema") {
+        Some(value) => value,
+        None => return Ok(1),
+    };
+    value
+        .as_integer()
+        .and_then(|schema| u32::try_from(schema).ok())
+        .filter(|&schema| schema >= 1)
+        .with_context(|| format!("schema {} is not a version number", value))
+}
+
+/// Migrates `table` to the current [`SCHEMA`], returning what changed besides the
+/// version. Configs of a newer schema than this build reads are refused
+pub fn migrate(table: &mut toml::Table) -> Result<Vec<String>> {
+    let schema = schema(table)?;
+    if schema > SCHEMA {
+        bail!(
+            "schema {} is newer than the {} this diff-format reads, upgrade diff-format",
+            schema,
+            SCHEMA
+        );
+    }
+    let changes = MIGRATIONS[schema as usize - 1..]
+        .iter()
+        .flat_map(|migration| migration(table))
+        .collect();
+    table.insert("schema".to_string(), toml::Value::Integer(SCHEMA.into()));
+    Ok(changes)
+}
+
+/// Without `--config`, the config file of the repository is migrated
+#[derive(Args, Debug)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    action: ConfigAction,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    /// Rewrite the config file in place in the current schema. Comments aren't kept,
+    /// and keys come out sorted
+    Migrate,
+}
+
+impl ConfigArgs {
+    /// Runs the action on the config at `path`, or else the one of `workdir`
+    pub fn run(&self, path: Option<&Path>, workdir: &Path) -> Result<()> {
+        let ConfigAction::Migrate = self.action;
+        let path = path.map_or_else(|| workdir.join(DEFAULT_CONFIG), PathBuf::from);
+        migrate_file(&path)
+    }
+}
+
+/// Rewrites the config at `path` in the current schema, unless it already is
+pub fn migrate_file(path: &Path) -> Result<()> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Can't read config {}", path.display()))?;
+    let mut table: toml::Table =
+        toml::from_str(&text).with_context(|| format!("Invalid config {}", path.display()))?;
+    let from = schema(&table).with_context(|| format!("Invalid config {}", path.display()))?;
+    if from == SCHEMA {
+        info!("{} is already at schema {}", path.display(), SCHEMA);
+        return Ok(());
+    }
+    let changes = migrate(&mut table)?;
+    // Refuse to write what wouldn't load
+    toml::Value::Table(table.clone())
+        .try_into::<Config>()
+        .with_context(|| format!("Invalid config {}", path.display()))?;
+    let text = toml::to_string_pretty(&table)?;
+    output::write_atomic(path, text.as_bytes())
+        .with_context(|| format!("Can't write config {}", path.display()))?;
+    for change in &changes {
+        info!("{}", change);
+    }
+    info!(
+        "Migrated {} from schema {} to {}",
+        path.display(),
+        from,
+        SCHEMA
+    );
+    Ok(())
+}
+
+impl Config {
+    /// Parses a config, expanding `${VAR}` in string values from the environment unless
+    /// `env_interp` is off, and migrating it from an older schema
+    pub fn parse(text: &str, env_interp: bool) -> Result<Self> {
+        Ok(Config::parse_migrated(text, env_interp)?.0)
+    }
+
+    /// The config in `text`, and what migrating it from an older schema changed
+    fn parse_migrated(text: &str, env_interp: bool) -> Result<(Self, Vec<String>)> {
+        let mut table: toml::Table = toml::from_str(text)?;
+        if env_interp {
+            for (name, value) in table.iter_mut() {
+                interpolate_value(value, name, &|name| env::var(name).ok())?;
+            }
+        }
+        let changes = migrate(&mut table)?;
+        Ok((toml::Value::Table(table).try_into()?, changes))
+    }
+
+    pub fn load(path: &Path, env_interp: bool) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Can't read config {}", path.display()))?;
+        let (config, changes) = Config::parse_migrated(&text, env_interp)
+            .with_context(|| format!("Invalid config {}", path.display()))?;
+        if !changes.is_empty() {
+            warn!(
+                "{} is in an older config schema, rewrite it with `diff-format config migrate`: {}",
+                path.display(),
+                changes.join("; ")
+            );
+        }
+        Ok(config)
+    }
+
+    /// Loads `path` if given, otherwise the repository's config file if there is one
+    pub fn discover(path: Option<&Path>, workdir: &Path, env_interp: bool) -> Result<Self> {
+        match path {
+            Some(path) => Config::load(path, env_interp),
+            None => {
+                let default = workdir.join(DEFAULT_CONFIG);
+                if default.exists() {
+                    Config::load(&default, env_interp)
+                } else {
+                    Ok(Config::default())
+                }
+            }
+        }
+    }
+
+    /// Returns every problem found, resolving names and compiling regexes without using them
+    pub fn validate(&self) -> Vec<String> {
+        let known_format = |name: &str| self.is_known_format(name);
+        let mut problems = Vec::new();
+        for (name, profile) in &self.parsers {
+            if Format::from_str(name, false).is_ok() {
+                problems.push(format!("[parsers.{}]: shadows the built-in format", name));
+            }
+            if let Err(err) = RegexParser::profile(&profile.regex) {
+                problems.push(format!("[parsers.{}]: invalid regex: {}", name, err));
+            }
+        }
+        for name in self.tool.keys() {
+            if !known_format(name) {
+                problems.push(format!("[tool.{}]: unknown format '{}'", name, name));
+            }
+        }
+        for (extension, embedded) in &self.embedded {
+            if let Err(err) = EmbeddedBlocks::new(embedded) {
+                problems.push(format!("[embedded.{}]: {:#}", extension, err));
+            }
+            for tool in embedded.tools.iter().flatten() {
+                if !known_format(tool) {
+                    problems.push(format!(
+                        "[embedded.{}]: unknown format '{}'",
+                        extension, tool
+                    ));
+                }
+            }
+        }
+        for (extension, format) in &self.extensions {
+            if !known_format(format) {
+                problems.push(format!(
+                    "[extensions]: unknown format '{}' for '{}'",
+                    format, extension
+                ));
+            }
+        }
+        for (extension, template) in &self.rule_docs {
+            if !template.contains("{rule}") {
+                problems.push(format!(
+                    "[rule_docs]: the template for '{}' doesn't name the {{rule}}",
+                    extension
+                ));
+            }
+        }
+        for pattern in self.policy.keys() {
+            if let Err(err) = compile_glob(pattern) {
+                problems.push(format!("[policy.\"{}\"]: {}", pattern, err));
+            }
+        }
+        for pattern in self.escalate.keys() {
+            if let Err(err) = compile_glob(pattern) {
+                problems.push(format!("[escalate.\"{}\"]: {}", pattern, err));
+            }
+        }
+        for waiver in &self.waivers {
+            if let Err(err) = Waivers::new(std::slice::from_ref(waiver)) {
+                problems.push(format!("{:#}", err));
+            }
+        }
+        if let Err(err) = Rollouts::new(&self.rollout) {
+            problems.push(format!("{:#}", err));
+        }
+        for (name, profile) in &self.profile {
+            if let Some(Err(err)) = profile.policy.as_deref().map(Expression::parse) {
+                problems.push(format!("[profile.{}]: invalid policy: {:#}", name, err));
+            }
+            if profile.policy.is_some() && profile.fail_on.is_some() {
+                problems.push(format!(
+                    "[profile.{}]: policy and fail_on can't both be set",
+                    name
+                ));
+            }
+        }
+        problems.sort();
+        problems
+    }
+
+    /// The profile named `name`, which must exist, or else `ci` when running in CI and
+    /// `local` otherwise, if the config has it
+    pub fn profile(&self, name: Option<&str>) -> Result<Option<(&str, &ProfileConfig)>> {
+        let (name, required) = match name {
+            Some(name) => (name, true),
+            None if ci::in_ci() => ("ci", false),
+            None => ("local", false),
+        };
+        match self.profile.get_key_value(name) {
+            Some((name, profile)) => Ok(Some((name.as_str(), profile))),
+            None if required => bail!("No [profile.{}] in the config", name),
+            None => Ok(None),
+        }
+    }
+
+    pub fn policies(&self) -> Result<Policies> {
+        let mut globs = Vec::new();
+        for (pattern, policy) in &self.policy {
+            let matcher = compile_glob(pattern)
+                .with_context(|| format!("Invalid [policy.\"{}\"] pattern", pattern))?;
+            if let Some(fail_on) = policy.fail_on {
+                globs.push((pattern.clone(), matcher, fail_on));
+            }
+        }
+        let mut floors = Vec::new();
+        for (pattern, escalate) in &self.escalate {
+            let matcher = compile_glob(pattern)
+                .with_context(|| format!("Invalid [escalate.\"{}\"] pattern", pattern))?;
+            floors.push((matcher, escalate.min_severity));
+        }
+        Ok(Policies { globs, floors })
+    }
+
+    pub fn waivers(&self) -> Result<Waivers> {
+        Waivers::new(&self.waivers)
+    }
+
+    pub fn rollouts(&self) -> Result<Rollouts> {
+        Rollouts::new(&self.rollout)
+    }
+
+    /// Whether `name` is a built-in format or a `[parsers]` profile
+    fn is_known_format(&self, name: &str) -> bool {
+        Format::from_str(name, false).is_ok() || self.parsers.contains_key(name)
+    }
+
+    /// The `[extensions]` table, skipping unknown format names
+    pub fn extension_formats(&self) -> HashMap<String, String> {
+        self.extensions
+            .iter()
+            .filter(|(_, format)| self.is_known_format(format))
+            .map(|(extension, format)| (extension.clone(), format.clone()))
+            .collect()
+    }
+
+    pub fn rule_docs(&self) -> RuleDocs<'_> {
+        RuleDocs::new(&self.rule_docs)
+    }
+
+    /// Normalizes `diagnostic` as its format's `[tool.<name>]` table or `normalize` says
+    pub fn normalize(&self, diagnostic: &mut Diagnostic) {
+        let tool = diagnostic
+            .tool
+            .as_deref()
+            .and_then(|tool| self.tool.get(tool));
+        match tool {
+            Some(tool) if tool.normalize.unwrap_or(self.normalize) => {
+                normalize::normalize(diagnostic, tool)
+            }
+            None if self.normalize => normalize::normalize(diagnostic, &ToolConfig::default()),
+            _ => {}
+        }
+    }
+
+    pub fn match_policy(&self, tool: Option<&str>) -> MatchPolicy {
+        tool.and_then(|tool| self.tool.get(tool))
+            .and_then(|tool| tool.match_policy)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::{interpolate, migrate, migrate_file, Config, FailOn, MatchPolicy, SCHEMA};
+    use crate::diagnostic::{Diagnostic, Severity};
+    use crate::fixtures::TempDir;
+    use crate::output::OutputFormat;
+    use crate::publish::PublishTarget;
+    use std::fs;
+
+    #[test]
+    fn test_match_policies() {
+        let config: Config = toml::from_str(
+            r#"
+            [tool.python]
+            match = "hunk"
+
+            [tool.codespell]
+            match = "file"
+
+            [tool.modules]
+            match = "directory"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.match_policy(Some("python")), MatchPolicy::Hunk);
+        assert_eq!(config.match_policy(Some("codespell")), MatchPolicy::File);
+        assert_eq!(config.match_policy(Some("modules")), MatchPolicy::Directory);
+        assert_eq!(config.match_policy(Some("typos")), MatchPolicy::Line);
+        assert_eq!(config.match_policy(None), MatchPolicy::Line);
+
+        assert!(toml::from_str::<Config>("[tool.python]\nmatch = \"nearby\"").is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let config: Config = toml::from_str(
+            r#"
+            [tool.flake9]
+            match = "hunk"
+
+            [embedded.vue]
+            start = "^<script"
+            end = "^</script("
+            tools = ["python", "eslint"]
+
+            [parsers.shellcheck]
+            regex = '^(?P<path>[^:]+):(?P<line>\d+)'
+
+            [tool.shellcheck]
+            match = "hunk"
+
+            [rule_docs]
+            rs = "https://docs.test/clippy"
+            "#,
+        )
+        .unwrap();
+        let problems = config.validate();
+        assert_eq!(problems.len(), 5);
+        assert!(problems[0].starts_with("[embedded.vue]: Invalid block end regex"));
+        assert_eq!(problems[1], "[embedded.vue]: unknown format 'eslint'");
+        assert_eq!(
+            problems[2],
+            "[parsers.shellcheck]: invalid regex: missing a (?P<file>...) group"
+        );
+        assert_eq!(
+            problems[3],
+            "[rule_docs]: the template for 'rs' doesn't name the {rule}"
+        );
+        assert_eq!(problems[4], "[tool.flake9]: unknown format 'flake9'");
+    }
+
+    #[test]
+    fn test_profiles() {
+        let config: Config = toml::from_str(
+            r#"
+            [profile.local]
+            output_format = "vscode-problems"
+            fail_on = "error"
+
+            [profile.ci]
+            publish = ["github"]
+            ci = "auto"
+            policy = "severity >="
+            "#,
+        )
+        .unwrap();
+        let (name, local) = config.profile(Some("local")).unwrap().unwrap();
+        assert_eq!(name, "local");
+        assert_eq!(local.output_format, Some(OutputFormat::VscodeProblems));
+        assert_eq!(local.fail_on, Some(Severity::Error));
+        let (_, ci) = config.profile(Some("ci")).unwrap().unwrap();
+        assert_eq!(ci.publish, [PublishTarget::Github]);
+        assert!(config.profile(Some("staging")).is_err());
+        assert!(Config::default().profile(None).unwrap().is_none());
+
+        let problems = config.validate();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("[profile.ci]: invalid policy"));
+        assert!(toml::from_str::<Config>(
+            "[profile.ci]
+cache = true"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let var = |name: &str| (name == "BRANCH").then(|| "main".to_string());
+        assert_eq!(
+            interpolate("origin/${BRANCH}$", &var).unwrap(),
+            "origin/main$"
+        );
+        assert_eq!(interpolate("$${BRANCH}", &var).unwrap(), "${BRANCH}");
+        let err = interpolate("${TOKEN}", &var).unwrap_err();
+        assert_eq!(err.to_string(), "environment variable 'TOKEN' is not set");
+        assert!(interpolate("${BRANCH", &var).is_err());
+
+        assert!(Config::parse(
+            "[embedded.vue]\nstart = \"${DIFF_FORMAT_UNSET}\"\nend = \"x\"",
+            true
+        )
+        .is_err());
+        assert!(Config::parse(
+            "[embedded.vue]\nstart = \"${DIFF_FORMAT_UNSET}\"\nend = \"x\"",
+            false
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_migrate() {
+        let mut table: toml::Table =
+            toml::from_str("[[rollout]]\nrule = \"E1\"\nwarn_until = \"2025-03-01\"\n").unwrap();
+        let changes = migrate(&mut table).unwrap();
+        assert_eq!(
+            changes,
+            ["[[rollout]] warn_until \"2025-03-01\" is now the date 2025-03-01"]
+        );
+        assert_eq!(table["schema"].as_integer(), Some(2));
+        assert!(table["rollout"][0]["warn_until"].is_datetime());
+        assert!(migrate(&mut table).unwrap().is_empty());
+        assert!(Config::parse("schema = 3", false).is_err());
+        assert!(Config::parse("schema = \"2\"", false).is_err());
+        assert!(Config::parse(
+            "schema = 2\n[[rollout]]\nrule = \"E1\"\nwarn_until = \"2025-03-01\"",
+            false
+        )
+        .is_err());
+
+        let dir = TempDir::new("config").unwrap();
+        let path = dir.join("diff-format.toml");
+        fs::write(
+            &path,
+            "# rules\n[[rollout]]\nrule = \"E1\"\nwarn_until = \"2025-03-01\"\n",
+        )
+        .unwrap();
+        migrate_file(&path).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.contains("warn_until = 2025-03-01\n"));
+        assert_eq!(Config::load(&path, false).unwrap().schema, Some(SCHEMA));
+        migrate_file(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), text);
+    }
+
+    #[test]
+    fn test_policies() {
+        let config: Config = toml::from_str(
+            r#"
+            [policy."src/**"]
+            fail_on = "warning"
+
+            [policy."src/experiments/**"]
+            fail_on = "never"
+            "#,
+        )
+        .unwrap();
+        let policies = config.policies().unwrap();
+        assert_eq!(policies.fail_on("src/app/main.py"), Some(FailOn::Warning));
+        assert_eq!(
+            policies.fail_on("src/experiments/a.py"),
+            Some(FailOn::Never)
+        );
+        assert_eq!(policies.fail_on("docs/a.md"), None);
+        assert!(!FailOn::Warning.fails(Severity::Info));
+        assert!(FailOn::Warning.fails(Severity::Error));
+    }
+
+    #[test]
+    fn test_escalate() {
+        let config: Config = toml::from_str(
+            r#"
+            [escalate."auth/**"]
+            min_severity = "warning"
+
+            [escalate."auth/tokens/**"]
+            min_severity = "error"
+            "#,
+        )
+        .unwrap();
+        let policies = config.policies().unwrap();
+        let escalated = |path: &str, severity| {
+            let mut diagnostic = Diagnostic::new(path, Some(1));
+            diagnostic.severity = Some(severity);
+            policies.escalate(&mut diagnostic);
+            diagnostic.severity.unwrap()
+        };
+        assert_eq!(
+            escalated("auth/login.py", Severity::Info),
+            Severity::Warning
+        );
+        assert_eq!(escalated("auth/login.py", Severity::Error), Severity::Error);
+        assert_eq!(
+            escalated("auth/tokens/jwt.py", Severity::Info),
+            Severity::Error
+        );
+        assert_eq!(escalated("docs/auth.md", Severity::Info), Severity::Info);
+
+        let config: Config =
+            toml::from_str("[escalate.\"a/[\"]\nmin_severity = \"error\"\n").unwrap();
+        assert!(config.policies().is_err());
+        assert_eq!(config.validate().len(), 1);
+    }
+}
@@ -0,0 +1,110 @@
+//! The `contains` query: whether lines are considered changed, for shell scripts and
+//! Makefiles that would otherwise pipe made-up lint output through the filter.
+
+use crate::config::MatchPolicy;
+use crate::diagnostic::Diagnostic;
+use crate::{is_changed, HunkMap};
+use anyhow::{bail, Error, Result};
+use clap::Args;
+use std::fmt;
+use std::str::FromStr;
+
+/// A line to look up, or a whole file without a line number
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub path: String,
+    pub line: Option<u32>,
+}
+
+impl FromStr for Location {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let (path, line) = match text.rsplit_once(':') {
+            Some((path, line)) => match line.parse() {
+                Ok(0) | Err(_) => bail!("Expected PATH:LINE with a line from 1, got '{}'", text),
+                Ok(line) => (path, Some(line)),
+            },
+            None => (text, None),
+        };
+        if path.is_empty() {
+            bail!("Expected PATH:LINE, got '{}'", text);
+        }
+        Ok(Location {
+            path: path.to_string(),
+            line,
+        })
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}", self.path, line),
+            None => write!(f, "{}", self.path),
+        }
+    }
+}
+
+impl Location {
+    /// Whether the line, or any line of the file, is changed in `file_hunks`
+    pub fn is_changed(&self, file_hunks: &HunkMap) -> bool {
+        let diagnostic = Diagnostic::new(&self.path, self.line);
+        is_changed(file_hunks, &diagnostic, MatchPolicy::Line)
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ContainsArgs {
+    /// Lines to look up, relative to the repository root; a bare PATH asks whether the
+    /// file changed at all
+    #[arg(required = true, value_name = "PATH:LINE")]
+    locations: Vec<Location>,
+
+    /// Print `changed` or `unchanged` after each location instead of only exiting with
+    /// the verdict
+    #[arg(long)]
+    print: bool,
+}
+
+/// Looks up the locations, returning whether they are all changed
+pub fn check(args: &ContainsArgs, file_hunks: &HunkMap) -> bool {
+    let mut all = true;
+    for location in &args.locations {
+        let changed = location.is_changed(file_hunks);
+        if args.print {
+            let verdict = if changed { "changed" } else { "unchanged" };
+            println!("{}\t{}", location, verdict);
+        }
+        all &= changed;
+    }
+    all
+}
+
+#[cfg(test)]
+mod test {
+    use crate::contains::Location;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_location() {
+        let file_hunks: HashMap<_, _> = vec![("src/a.py".to_string(), vec![(10, 12)])]
+            .into_iter()
+            .collect();
+        let changed = |text: &str| text.parse::<Location>().unwrap().is_changed(&file_hunks);
+        assert!(changed("src/a.py:10"));
+        assert!(changed("src/a.py:12"));
+        assert!(!changed("src/a.py:13"));
+        assert!(changed("src/a.py"));
+        assert!(!changed("src/b.py:10"));
+        assert!(!changed("src/b.py"));
+
+        assert_eq!(
+            "C:/a.py:3".parse::<Location>().unwrap().to_string(),
+            "C:/a.py:3"
+        );
+        assert!("a.py:0".parse::<Location>().is_err());
+        assert!("a.py:x".parse::<Location>().is_err());
+        assert!(":3".parse::<Location>().is_err());
+    }
+}
@@ -0,0 +1,167 @@
+//! Lint output of several linters in one stream, as a wrapper running them all prints
+//! it, split by tool so each part is parsed with its own format.
+//!
+//! A `##tool:NAME` line starts a section parsed with the format NAME, until the next
+//! one or a bare `##tool:`; a `--demux PREFIX=>FORMAT` route parses the lines starting
+//! with PREFIX with FORMAT wherever they are. Neither framing is echoed: sentinel lines
+//! are dropped, and prefixes taken off their lines. Other lines go to the `--format`
+//! parsers.
+
+use crate::parsers::{FormatSpec, ParserProfile, ParserStrategy, Parsers};
+use anyhow::{bail, Result};
+use log::warn;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Start of the lines naming the format of the next section
+pub const SENTINEL: &str = "##tool:";
+
+/// A `PREFIX=>FORMAT` route
+#[derive(Debug, Clone)]
+pub struct Route {
+    prefix: String,
+    format: FormatSpec,
+}
+
+impl FromStr for Route {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.split_once("=>") {
+            Some((prefix, format)) if !prefix.is_empty() && !format.is_empty() => Ok(Route {
+                prefix: prefix.to_string(),
+                format: format.parse().unwrap(),
+            }),
+            _ => bail!("Expected PREFIX=>FORMAT, got '{}'", value),
+        }
+    }
+}
+
+/// A line to parse with the parsers of its tool, or with the default ones without
+pub type Routed = (String, Option<Arc<Parsers>>);
+
+pub struct Demux<'a> {
+    routes: Vec<(String, Arc<Parsers>)>,
+    profiles: &'a HashMap<String, ParserProfile>,
+    extensions: HashMap<String, String>,
+    strategy: ParserStrategy,
+    /// The parsers of each section format seen, `None` for unknown ones
+    sections: HashMap<String, Option<Arc<Parsers>>>,
+    /// The section the lines are in
+    current: Option<Arc<Parsers>>,
+}
+
+impl<'a> Demux<'a> {
+    /// Routes lines by `routes` and sections, parsing them with the formats they name and
+    /// `profiles`, `extensions` and `strategy` as for `--format`
+    pub fn new(
+        routes: &[Route],
+        profiles: &'a HashMap<String, ParserProfile>,
+        extensions: HashMap<String, String>,
+        strategy: ParserStrategy,
+    ) -> Result<Self> {
+        let mut demux = Demux {
+            routes: Vec::new(),
+            profiles,
+            extensions,
+            strategy,
+            sections: HashMap::new(),
+            current: None,
+        };
+        for route in routes {
+            let parsers = demux.parsers(&route.format)?;
+            demux.routes.push((route.prefix.clone(), parsers));
+        }
+        Ok(demux)
+    }
+
+    fn parsers(&self, format: &FormatSpec) -> Result<Arc<Parsers>> {
+        let parsers = Parsers::with_profiles(
+            std::slice::from_ref(format),
+            self.profiles,
+            self.extensions.clone(),
+        )?;
+        Ok(Arc::new(parsers.with_strategy(self.strategy)))
+    }
+
+    /// `line` with its prefix taken off and the parsers for it, or `None` for a sentinel
+    /// line
+    pub fn route(&mut self, line: String) -> Option<Routed> {
+        if let Some(name) = line.strip_prefix(SENTINEL) {
+            let name = name.trim();
+            self.current = if name.is_empty() {
+                None
+            } else {
+                match self.sections.get(name) {
+                    Some(parsers) => parsers.clone(),
+                    None => {
+                        let parsers = self.parsers(&name.parse().unwrap());
+                        if let Err(err) = &parsers {
+                            warn!("{:#}, parsing the {} section with --format", err, name);
+                        }
+                        let parsers = parsers.ok();
+                        self.sections.insert(name.to_string(), parsers.clone());
+                        parsers
+                    }
+                }
+            };
+            return None;
+        }
+        for (prefix, parsers) in &self.routes {
+            if let Some(rest) = line.strip_prefix(prefix.as_str()) {
+                return Some((rest.to_string(), Some(parsers.clone())));
+            }
+        }
+        Some((line, self.current.clone()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::demux::{Demux, Route};
+    use crate::parallel::ParsedLines;
+    use crate::parsers::{Format, Parsers};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_demux() {
+        assert!("eslint".parse::<Route>().is_err());
+        let routes: Vec<Route> = vec!["[typos] =>typos".parse().unwrap()];
+        let profiles = HashMap::new();
+        let input = [
+            "a.py:1:1: E1 python",
+            "##tool:codespell",
+            "a.py:2: teh ==> the",
+            "[typos] a.py:3:5: `teh` -> `the`",
+            "##tool:",
+            "a.py:4:1: E2 python again",
+            "##tool:nonesuch",
+            "a.py:5:1: E3 unknown section",
+        ];
+        let parsers = Arc::new(Parsers::new(&[Format::Python], HashMap::new()));
+        for jobs in [1, 2] {
+            let demux = Demux::new(&routes, &profiles, HashMap::new(), Default::default()).unwrap();
+            let lines = input.iter().map(|line| Ok(line.to_string()));
+            let parsed: Vec<_> = ParsedLines::new(Box::new(lines), parsers.clone(), jobs, false)
+                .with_demux(demux)
+                .map(Result::unwrap)
+                .collect();
+            let tools: Vec<_> = parsed
+                .iter()
+                .map(|(line, diagnostics)| (line.as_str(), diagnostics[0].tool.as_deref().unwrap()))
+                .collect();
+            assert_eq!(
+                tools,
+                [
+                    ("a.py:1:1: E1 python", "python"),
+                    ("a.py:2: teh ==> the", "codespell"),
+                    ("a.py:3:5: `teh` -> `the`", "typos"),
+                    ("a.py:4:1: E2 python again", "python"),
+                    ("a.py:5:1: E3 unknown section", "python"),
+                ]
+            );
+        }
+    }
+}
@@ -0,0 +1,391 @@
+//! The `deps` gate: the violations of architectural rules, e.g. import cycles and
+//! forbidden layer imports, that a dependency checker reported, located at the import
+//! they come from and filtered like lint output. A rule can so be introduced while
+//! existing violations remain, failing only imports the diff adds.
+//!
+//! dependency-cruiser names the files of a violation but not the line, which is found
+//! by the module specifier imported. import-linter names modules and lines, and jdeps
+//! classes, which are looked up in the `--source-root`s.
+
+use crate::diagnostic::{Diagnostic, Severity};
+use anyhow::{bail, Context, Result};
+use clap::{Args, ValueEnum};
+use log::debug;
+use regex::Regex;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock as Lazy;
+
+/// A link of an import-linter chain, as in `- a.b -> a.c (l.6, l.12)`
+static IMPORT_LINK: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\s*(?:- )?([\w.]+) -> ([\w.]+) \(([^)]*)\)$").unwrap());
+
+/// A jdeps dependency, as in `com.a.Foo -> sun.misc.Unsafe  JDK internal API (jdk.unsupported)`
+static JDEPS_LINE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\s+([\w.$]+)\s+->\s+([\w.$]+)\s+(.*\S)\s*$").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DepsTool {
+    /// `depcruise --output-type json`
+    DependencyCruiser,
+    /// The output of import-linter's `lint-imports`
+    ImportLinter,
+    /// `jdeps -jdkinternals` or `jdeps -verbose:class`; dependencies on JDK internals
+    /// and on classes that aren't found are violations
+    Jdeps,
+}
+
+#[derive(Args, Debug)]
+pub struct DepsArgs {
+    /// The report of the dependency checker
+    #[arg(long, value_name = "PATH")]
+    report: PathBuf,
+
+    /// The dependency checker that wrote --report
+    #[arg(long, value_enum)]
+    tool: DepsTool,
+
+    /// Directory, relative to the root, that the modules of import-linter or the
+    /// classes of jdeps are looked up in; `.` and `src` or `src/main/java` and
+    /// `src/test/java` by default
+    #[arg(long, value_name = "DIR")]
+    source_root: Vec<PathBuf>,
+}
+
+/// An import breaking a rule
+#[derive(Debug, PartialEq, Eq)]
+struct Violation {
+    /// The importing file, relative to the root
+    path: String,
+    line: Option<u32>,
+    rule: String,
+    severity: Severity,
+    message: String,
+}
+
+impl Violation {
+    fn into_diagnostic(self, tool: DepsTool) -> Diagnostic {
+        let mut diagnostic = Diagnostic::new(&self.path, self.line);
+        diagnostic.raw = match self.line {
+            Some(line) => format!("{}:{}: {}: {}", self.path, line, self.rule, self.message),
+            None => format!("{}: {}: {}", self.path, self.rule, self.message),
+        };
+        diagnostic.rule = Some(self.rule);
+        diagnostic.severity = Some(self.severity);
+        diagnostic.message = Some(self.message);
+        diagnostic.tool = Some(tool.to_possible_value().unwrap().get_name().to_string());
+        diagnostic
+    }
+}
+
+/// The first line of `source` quoting the module specifier `module`, as imports do
+fn quoting_line(source: &str, module: &str) -> Option<u32> {
+    let quoted = ['\'', '"', '`'].map(|quote| format!("{}{}{}", quote, module, quote));
+    source
+        .lines()
+        .position(|line| quoted.iter().any(|quoted| line.contains(quoted.as_str())))
+        .map(|index| index as u32 + 1)
+}
+
+/// The names of a dependency-cruiser cycle, which are objects since version 13
+fn cycle_names(cycle: &[Value]) -> Vec<&str> {
+    cycle
+        .iter()
+        .filter_map(|step| step.as_str().or_else(|| step["name"].as_str()))
+        .collect()
+}
+
+fn dependency_cruiser(report: &str, workdir: &Path) -> Result<Vec<Violation>> {
+    let report: Value = serde_json::from_str(report)?;
+    let Some(violations) = report["summary"]["violations"].as_array() else {
+        bail!("No summary.violations, is this a dependency-cruiser JSON report?");
+    };
+    let empty = Vec::new();
+    let modules = report["modules"].as_array().unwrap_or(&empty);
+    let mut found = Vec::new();
+    for violation in violations {
+        let severity = match violation["rule"]["severity"].as_str() {
+            Some("ignore") => continue,
+            Some(label) => Severity::from_label(label).unwrap_or(Severity::Error),
+            None => Severity::Error,
+        };
+        let (Some(from), Some(rule)) = (
+            violation["from"].as_str(),
+            violation["rule"]["name"].as_str(),
+        ) else {
+            continue;
+        };
+        let to = violation["to"].as_str().filter(|&to| to != from);
+        let cycle = cycle_names(violation["cycle"].as_array().unwrap_or(&empty));
+        let message = match (to, cycle.is_empty()) {
+            (_, false) => format!("{} -> {}", from, cycle.join(" -> ")),
+            (Some(to), true) => format!("{} -> {}", from, to),
+            (None, true) => format!("{} breaks the rule", from),
+        };
+        // The specifier `from` imports `to` with, as dependency-cruiser resolved it
+        let specifier = to.and_then(|to| {
+            modules.iter().find(|module| module["source"] == from)?["dependencies"]
+                .as_array()?
+                .iter()
+                .find(|dependency| dependency["resolved"] == to)?["module"]
+                .as_str()
+        });
+        let line = specifier.and_then(|specifier| {
+            let source = fs::read_to_string(workdir.join(from)).ok()?;
+            quoting_line(&source, specifier)
+        });
+        found.push(Violation {
+            path: from.to_string(),
+            line,
+            rule: rule.to_string(),
+            severity,
+            message,
+        });
+    }
+    Ok(found)
+}
+
+/// The file of the dotted `name` under one of `roots`, e.g. `a/b.py` or
+/// `a/b/__init__.py` for the Python module `a.b`
+fn find_source(workdir: &Path, roots: &[PathBuf], name: &str, extension: &str) -> Option<String> {
+    let relative = name.replace('.', "/");
+    let candidates = [
+        format!("{}.{}", relative, extension),
+        format!("{}/__init__.{}", relative, extension),
+    ];
+    roots.iter().find_map(|root| {
+        candidates.iter().find_map(|candidate| {
+            let path = root.join(candidate);
+            workdir.join(&path).is_file().then(|| {
+                let path = path.strip_prefix(".").unwrap_or(&path);
+                path.to_string_lossy().into_owned()
+            })
+        })
+    })
+}
+
+fn import_linter(report: &str, workdir: &Path, roots: &[PathBuf]) -> Vec<Violation> {
+    let lines: Vec<_> = report.lines().collect();
+    let broken = lines
+        .iter()
+        .position(|line| line.trim() == "Broken contracts")
+        .map_or(lines.len(), |index| index + 2);
+    let (mut contract, mut rule) = (None, None);
+    let mut found = Vec::new();
+    for (index, line) in lines.iter().enumerate().skip(broken) {
+        let underlined = lines.get(index + 1).is_some_and(|next| {
+            !next.is_empty() && next.chars().all(|c| c == '-') && next.len() == line.len()
+        });
+        if underlined {
+            contract = Some(line.trim());
+            continue;
+        }
+        if let Some(statement) = line.trim().strip_suffix(':') {
+            rule = Some(statement);
+            continue;
+        }
+        let (Some(link), Some(contract)) = (IMPORT_LINK.captures(line), contract) else {
+            continue;
+        };
+        let Some(path) = find_source(workdir, roots, &link[1], "py") else {
+            debug!("Skipping {}, it isn't under a --source-root", &link[1]);
+            continue;
+        };
+        let message = format!(
+            "{} imports {}; {}",
+            &link[1],
+            &link[2],
+            rule.unwrap_or("the contract is broken")
+        );
+        for number in link[3].split(',') {
+            found.push(Violation {
+                path: path.clone(),
+                line: number
+                    .trim()
+                    .strip_prefix("l.")
+                    .and_then(|n| n.parse().ok()),
+                rule: contract.to_string(),
+                severity: Severity::Error,
+                message: message.clone(),
+            });
+        }
+    }
+    found
+}
+
+fn jdeps(report: &str, workdir: &Path, roots: &[PathBuf]) -> Vec<Violation> {
+    let mut found = Vec::new();
+    for captures in report.lines().filter_map(|line| JDEPS_LINE.captures(line)) {
+        let (from, to, detail) = (&captures[1], &captures[2], &captures[3]);
+        let rule = if detail.contains("JDK internal API") {
+            "jdk-internal-api"
+        } else if detail == "not found" {
+            "missing-dependency"
+        } else {
+            continue;
+        };
+        // Nested classes are in the file of the outermost
+        let outer = from.split('$').next().unwrap();
+        let Some(path) = find_source(workdir, roots, outer, "java") else {
+            debug!("Skipping {}, it isn't under a --source-root", outer);
+            continue;
+        };
+        let imported = to.split('$').next().unwrap();
+        let package = imported.rsplit_once('.').map_or("", |(package, _)| package);
+        let line = fs::read_to_string(workdir.join(&path))
+            .ok()
+            .and_then(|source| {
+                source
+                    .lines()
+                    .position(|line| {
+                        line.contains(imported) || line.trim() == format!("import {}.*;", package)
+                    })
+                    .map(|index| index as u32 + 1)
+            });
+        found.push(Violation {
+            path,
+            line,
+            rule: rule.to_string(),
+            severity: Severity::Error,
+            message: format!("{} depends on {}: {}", from, to, detail),
+        });
+    }
+    found
+}
+
+/// The violations of the report of `args`, for the files of `workdir`
+pub fn check(args: &DepsArgs, workdir: &Path) -> Result<Vec<Diagnostic>> {
+    let report = fs::read_to_string(&args.report)
+        .with_context(|| format!("Unable to read {}", args.report.display()))?;
+    let roots = match (args.source_root.is_empty(), args.tool) {
+        (false, _) => args.source_root.clone(),
+        (true, DepsTool::Jdeps) => vec!["src/main/java".into(), "src/test/java".into()],
+        (true, _) => vec![".".into(), "src".into()],
+    };
+    let violations = match args.tool {
+        DepsTool::DependencyCruiser => dependency_cruiser(&report, workdir)
+            .with_context(|| format!("Unable to parse {}", args.report.display()))?,
+        DepsTool::ImportLinter => import_linter(&report, workdir, &roots),
+        DepsTool::Jdeps => jdeps(&report, workdir, &roots),
+    };
+    debug!(
+        "{} violation(s) in {}",
+        violations.len(),
+        args.report.display()
+    );
+    Ok(violations
+        .into_iter()
+        .map(|violation| violation.into_diagnostic(args.tool))
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::deps::{dependency_cruiser, import_linter, jdeps, Violation};
+    use crate::diagnostic::Severity;
+    use crate::fixtures::TempDir;
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_reports() {
+        let dir = TempDir::new("deps").unwrap();
+        for (path, text) in [
+            ("src/a.js", "// a\nimport { b } from './b';\n"),
+            (
+                "shop/web/views.py",
+                "import os\n\nfrom shop.db import models\n",
+            ),
+            (
+                "src/main/java/com/shop/Cache.java",
+                "package com.shop;\n\nimport sun.misc.Unsafe;\n",
+            ),
+        ] {
+            fs::create_dir_all(dir.join(path).parent().unwrap()).unwrap();
+            fs::write(dir.join(path), text).unwrap();
+        }
+
+        let report = r#"{
+            "modules": [{"source": "src/a.js", "dependencies": [{"module": "./b", "resolved": "src/b.js"}]}],
+            "summary": {"violations": [
+                {"type": "cycle", "from": "src/a.js", "to": "src/b.js", "rule": {"severity": "warn", "name": "no-circular"},
+                 "cycle": [{"name": "src/b.js"}, {"name": "src/a.js"}]},
+                {"type": "module", "from": "src/c.js", "to": "src/c.js", "rule": {"severity": "error", "name": "no-orphans"}},
+                {"type": "dependency", "from": "src/a.js", "to": "src/d.js", "rule": {"severity": "ignore", "name": "x"}}
+            ]}
+        }"#;
+        assert_eq!(
+            dependency_cruiser(report, &dir).unwrap(),
+            [
+                Violation {
+                    path: "src/a.js".to_string(),
+                    line: Some(2),
+                    rule: "no-circular".to_string(),
+                    severity: Severity::Warning,
+                    message: "src/a.js -> src/b.js -> src/a.js".to_string(),
+                },
+                Violation {
+                    path: "src/c.js".to_string(),
+                    line: None,
+                    rule: "no-orphans".to_string(),
+                    severity: Severity::Error,
+                    message: "src/c.js breaks the rule".to_string(),
+                },
+            ]
+        );
+        assert!(dependency_cruiser("{}", &dir).is_err());
+
+        let report = "\
+Contracts: 1 kept, 1 broken.
+
+
+----------------
+Broken contracts
+----------------
+
+Layered architecture
+--------------------
+
+shop.web is not allowed to import shop.db:
+
+- shop.web.views -> shop.db.models (l.3, l.9)
+
+- shop.web.forms -> shop.db (l.1)
+";
+        let roots = [PathBuf::from("."), PathBuf::from("src")];
+        let found = import_linter(report, &dir, &roots);
+        // forms.py isn't in the tree
+        assert_eq!(found.len(), 2);
+        assert_eq!(
+            (found[0].path.as_str(), found[0].line, found[1].line),
+            ("shop/web/views.py", Some(3), Some(9))
+        );
+        assert_eq!(found[0].rule, "Layered architecture");
+        assert_eq!(
+            found[0].message,
+            "shop.web.views imports shop.db.models; shop.web is not allowed to import shop.db"
+        );
+
+        let report = "\
+classes -> java.base
+   com.shop.Cache                                     -> sun.misc.Unsafe                                    JDK internal API (jdk.unsupported)
+   com.shop.Cache$Entry                               -> java.lang.Object                                   java.base
+   com.shop.Gone                                      -> sun.misc.Unsafe                                    JDK internal API (jdk.unsupported)
+";
+        let found = jdeps(report, &dir, &[PathBuf::from("src/main/java")]);
+        assert_eq!(found.len(), 1);
+        assert_eq!(
+            (
+                found[0].path.as_str(),
+                found[0].line,
+                found[0].rule.as_str()
+            ),
+            (
+                "src/main/java/com/shop/Cache.java",
+                Some(3),
+                "jdk-internal-api"
+            )
+        );
+    }
+}
@@ -0,0 +1,142 @@
+//! Maps lines reported against an older revision to the lines they are at in the workdir.
+
+use crate::HunkRange;
+use anyhow::{Context, Result};
+use git2::{DiffOptions, Patch, Repository, Tree};
+use log::debug;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Line changes of one file, as `(old_start, old_lines, new_start, new_lines)` hunks
+#[derive(Debug, Default)]
+pub struct LineMap {
+    hunks: Vec<(u32, u32, u32, u32)>,
+}
+
+impl LineMap {
+    /// `hunks` must be sorted, as a patch lists them
+    pub fn from_hunks(hunks: Vec<(u32, u32, u32, u32)>) -> Self {
+        LineMap { hunks }
+    }
+
+    pub fn from_patch(patch: &Patch) -> Result<Self> {
+        let mut hunks = Vec::with_capacity(patch.num_hunks());
+        for index in 0..patch.num_hunks() {
+            let (hunk, _) = patch.hunk(index)?;
+            hunks.push((
+                hunk.old_start(),
+                hunk.old_lines(),
+                hunk.new_start(),
+                hunk.new_lines(),
+            ));
+        }
+        Ok(LineMap { hunks })
+    }
+
+    /// The new line for an old one; a rewritten line maps to the start of its
+    /// replacement, and `None` means the line was deleted
+    pub fn map(&self, line: u32) -> Option<u32> {
+        let mut shift = 0i64;
+        for &(old_start, old_lines, new_start, new_lines) in &self.hunks {
+            // Pure insertions go after `old_start`, other hunks replace from it
+            if line < old_start || (old_lines == 0 && line == old_start) {
+                break;
+            }
+            if line < old_start + old_lines {
+                return if new_lines > 0 { Some(new_start) } else { None };
+            }
+            shift += new_lines as i64 - old_lines as i64;
+        }
+        Some((line as i64 + shift) as u32)
+    }
+
+    /// The smallest range holding the new lines of the lines in `range` that are left,
+    /// `None` if all of them were deleted
+    pub fn map_range(&self, (start, end): HunkRange) -> Option<HunkRange> {
+        let mut lines = (start..=end).filter_map(|line| self.map(line));
+        let first = lines.next()?;
+        Some(lines.fold((first, first), |(min, max), line| {
+            (min.min(line), max.max(line))
+        }))
+    }
+}
+
+/// Corrects diagnostic lines for files that changed between linting and diffing
+pub struct DriftMapper<'r> {
+    repo: &'r Repository,
+    tree: Tree<'r>,
+    workdir: PathBuf,
+    maps: HashMap<String, Option<LineMap>>,
+}
+
+impl<'r> DriftMapper<'r> {
+    pub fn new(repo: &'r Repository, tree: Tree<'r>, workdir: &Path) -> Self {
+        DriftMapper {
+            repo,
+            tree,
+            workdir: workdir.to_path_buf(),
+            maps: HashMap::new(),
+        }
+    }
+
+    /// Maps `line` of `path` at the lint revision, `None` if it no longer exists
+    pub fn map(&mut self, path: &str, line: u32) -> Option<u32> {
+        let (repo, tree, workdir) = (self.repo, &self.tree, &self.workdir);
+        let map = self.maps.entry(path.to_string()).or_insert_with(|| {
+            line_map(repo, tree, workdir, path)
+                .map_err(|err| debug!("No line drift correction for '{}': {:#}", path, err))
+                .ok()
+        });
+        match map {
+            Some(map) => map.map(line),
+            None => Some(line),
+        }
+    }
+}
+
+fn line_map(repo: &Repository, tree: &Tree, workdir: &Path, path: &str) -> Result<LineMap> {
+    let blob = tree
+        .get_path(Path::new(path))?
+        .to_object(repo)?
+        .peel_to_blob()?;
+    let current = fs::read(workdir.join(path)).context("Unable to read workdir file")?;
+    let mut options = DiffOptions::new();
+    options.context_lines(0);
+    let patch = Patch::from_blob_and_buffer(
+        &blob,
+        Some(Path::new(path)),
+        &current,
+        Some(Path::new(path)),
+        Some(&mut options),
+    )?;
+    LineMap::from_patch(&patch)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::drift::LineMap;
+    use git2::{DiffOptions, Patch};
+
+    #[test]
+    fn test_line_map() {
+        let old = "a\nb\nc\nd\ne\nf\n";
+        // Two lines inserted at the top, `c` rewritten, `e` deleted
+        let new = "x\ny\na\nb\nC\nd\nf\n";
+        let mut options = DiffOptions::new();
+        options.context_lines(0);
+        let patch = Patch::from_buffers(
+            old.as_bytes(),
+            None,
+            new.as_bytes(),
+            None,
+            Some(&mut options),
+        )
+        .unwrap();
+        let map = LineMap::from_patch(&patch).unwrap();
+        let mapped: Vec<_> = (1..=6).map(|line| map.map(line)).collect();
+        assert_eq!(mapped, [Some(3), Some(4), Some(5), Some(6), None, Some(7)]);
+        assert_eq!(map.map_range((4, 5)), Some((6, 6)));
+        assert_eq!(map.map_range((5, 5)), None);
+    }
+}
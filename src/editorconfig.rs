@@ -0,0 +1,310 @@
+//! The `.editorconfig` conventions of a file, so text inserted by fixes is indented,
+//! trimmed and terminated the way the project wants rather than the way the tool's
+//! author does.
+//!
+//! Only the `.editorconfig` files between the file and the repository root are read,
+//! stopping at one with `root = true`.
+
+use crate::diagnostic::Fix;
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobMatcher};
+use log::warn;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+pub const FILE_NAME: &str = ".editorconfig";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndentStyle {
+    Tab,
+    Space,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndOfLine {
+    Lf,
+    Crlf,
+    Cr,
+}
+
+impl EndOfLine {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EndOfLine::Lf => "\n",
+            EndOfLine::Crlf => "\r\n",
+            EndOfLine::Cr => "\r",
+        }
+    }
+}
+
+/// The properties fixes care about; unset ones leave the text as the tool wrote it
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Style {
+    pub indent_style: Option<IndentStyle>,
+    pub indent_size: Option<usize>,
+    pub tab_width: Option<usize>,
+    pub end_of_line: Option<EndOfLine>,
+    pub trim_trailing_whitespace: bool,
+}
+
+struct Section {
+    glob: GlobMatcher,
+    properties: Vec<(String, String)>,
+}
+
+/// An `.editorconfig` file: whether it is the root one, and its sections in order
+struct ConfigFile {
+    root: bool,
+    sections: Vec<Section>,
+}
+
+/// A section glob without a `/` matches in any directory, one with a `/` from the
+/// directory of the file
+fn compile(pattern: &str) -> Result<GlobMatcher, globset::Error> {
+    let pattern = match pattern.strip_prefix('/') {
+        Some(anchored) => anchored.to_string(),
+        None if pattern.contains('/') => pattern.to_string(),
+        None => format!("**/{}", pattern),
+    };
+    Ok(GlobBuilder::new(&pattern)
+        .literal_separator(true)
+        .build()?
+        .compile_matcher())
+}
+
+impl ConfigFile {
+    fn parse(text: &str) -> Self {
+        let mut file = ConfigFile {
+            root: false,
+            sections: Vec::new(),
+        };
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+            if let Some(pattern) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                match compile(pattern) {
+                    Ok(glob) => file.sections.push(Section {
+                        glob,
+                        properties: Vec::new(),
+                    }),
+                    Err(err) => warn!("Ignoring .editorconfig section [{}]: {}", pattern, err),
+                }
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim().to_lowercase(), value.trim().to_lowercase()),
+                None => continue,
+            };
+            match file.sections.last_mut() {
+                Some(section) => section.properties.push((key, value)),
+                // Only `root` belongs before the first section
+                None => file.root |= key == "root" && value == "true",
+            }
+        }
+        file
+    }
+}
+
+impl Style {
+    fn set(&mut self, key: &str, value: &str) {
+        match key {
+            "indent_style" => {
+                self.indent_style = match value {
+                    "tab" => Some(IndentStyle::Tab),
+                    "space" => Some(IndentStyle::Space),
+                    _ => None,
+                }
+            }
+            // `tab` means the tab width
+            "indent_size" => self.indent_size = value.parse().ok(),
+            "tab_width" => self.tab_width = value.parse().ok(),
+            "end_of_line" => {
+                self.end_of_line = match value {
+                    "lf" => Some(EndOfLine::Lf),
+                    "crlf" => Some(EndOfLine::Crlf),
+                    "cr" => Some(EndOfLine::Cr),
+                    _ => None,
+                }
+            }
+            "trim_trailing_whitespace" => self.trim_trailing_whitespace = value == "true",
+            _ => {}
+        }
+    }
+
+    /// Columns a tab advances to the next multiple of
+    fn tab_width(&self) -> usize {
+        self.tab_width.or(self.indent_size).unwrap_or(8).max(1)
+    }
+
+    /// `line` with its leading whitespace redone in the configured indent style
+    fn reindent(&self, line: &str) -> String {
+        let style = match self.indent_style {
+            Some(style) => style,
+            None => return line.to_string(),
+        };
+        let body = line.trim_start_matches([' ', '\t']);
+        let tab_width = self.tab_width();
+        let width = line[..line.len() - body.len()]
+            .chars()
+            .fold(0, |width, c| match c {
+                '\t' => width + tab_width - width % tab_width,
+                _ => width + 1,
+            });
+        let indent = match style {
+            IndentStyle::Space => " ".repeat(width),
+            IndentStyle::Tab => "\t".repeat(width / tab_width) + &" ".repeat(width % tab_width),
+        };
+        indent + body
+    }
+
+    /// Whether `fix` at the 1-based `column` of `line` edits its indentation, so the
+    /// line needs reindenting once fixed
+    pub fn touches_indentation(line: &str, fix: &Fix, column: u32) -> bool {
+        let indentation = line.chars().take_while(|c| matches!(c, ' ' | '\t')).count();
+        column as usize <= indentation
+            || column as usize == indentation + 1 && fix.replacement.starts_with([' ', '\t'])
+    }
+
+    /// `line`, without its line ending, as the fixes applied to it should have left it
+    pub fn restyle(&self, line: &str, reindent: bool) -> String {
+        let mut line = match reindent {
+            true => self.reindent(line),
+            false => line.to_string(),
+        };
+        if self.trim_trailing_whitespace {
+            line.truncate(line.trim_end().len());
+        }
+        line
+    }
+
+    /// `line`, without its line ending, with `fix` applied at the 1-based `column`
+    pub fn apply(&self, fix: &Fix, line: &str, column: u32) -> String {
+        let reindent = Style::touches_indentation(line, fix, column);
+        self.restyle(&fix.apply(line, column), reindent)
+    }
+}
+
+fn read(path: &Path) -> Result<Option<ConfigFile>> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(Some(ConfigFile::parse(&text))),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).with_context(|| format!("Unable to read {}", path.display())),
+    }
+}
+
+/// The conventions for `path`, relative to the repository root `workdir`
+pub fn style(workdir: &Path, path: &str) -> Result<Style> {
+    // From the file's directory up, then applied from the root down so closer files win
+    let mut files = Vec::new();
+    let mut directory = Path::new(path).parent();
+    while let Some(relative) = directory {
+        if let Some(file) = read(&workdir.join(relative).join(FILE_NAME))? {
+            let root = file.root;
+            files.push((relative, file));
+            if root {
+                break;
+            }
+        }
+        directory = relative.parent();
+    }
+    let mut style = Style::default();
+    for (relative, file) in files.iter().rev() {
+        let path = Path::new(path)
+            .strip_prefix(relative)
+            .unwrap_or(Path::new(path));
+        for section in file
+            .sections
+            .iter()
+            .filter(|section| section.glob.is_match(path))
+        {
+            for (key, value) in &section.properties {
+                style.set(key, value);
+            }
+        }
+    }
+    Ok(style)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::Fix;
+    use crate::editorconfig::{style, EndOfLine, IndentStyle, Style};
+    use crate::fixtures::TempDir;
+    use std::fs;
+
+    #[test]
+    fn test_style() {
+        let dir = TempDir::new("editorconfig").unwrap();
+        fs::create_dir_all(dir.join("src/vendor")).unwrap();
+        fs::write(
+            dir.join(".editorconfig"),
+            "root = true\n\n[*]\nend_of_line = lf\nindent_style = space\nindent_size = 4\n\n\
+             [*.{go,mk}]\nindent_style = tab\n\n[/src/*.py]\ntrim_trailing_whitespace = true\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("src/vendor/.editorconfig"),
+            "[*]\nend_of_line = CRLF\n",
+        )
+        .unwrap();
+
+        let python = style(&dir, "src/a.py").unwrap();
+        assert_eq!(
+            python,
+            Style {
+                indent_style: Some(IndentStyle::Space),
+                indent_size: Some(4),
+                tab_width: None,
+                end_of_line: Some(EndOfLine::Lf),
+                trim_trailing_whitespace: true,
+            }
+        );
+        assert!(!style(&dir, "tests/a.py").unwrap().trim_trailing_whitespace);
+        assert_eq!(
+            style(&dir, "cmd/main.go").unwrap().indent_style,
+            Some(IndentStyle::Tab)
+        );
+        assert_eq!(
+            style(&dir, "src/vendor/b.py").unwrap().end_of_line,
+            Some(EndOfLine::Crlf)
+        );
+    }
+
+    #[test]
+    fn test_apply() {
+        let tabs = Style {
+            indent_style: Some(IndentStyle::Tab),
+            indent_size: Some(4),
+            ..Style::default()
+        };
+        let indent = Fix {
+            length: 1,
+            replacement: "        ".to_string(),
+        };
+        assert_eq!(tabs.apply(&indent, "\tx = 1", 1), "\t\tx = 1");
+        // Fixes past the indentation leave it alone
+        let typo = Fix {
+            length: 3,
+            replacement: "the".to_string(),
+        };
+        assert_eq!(tabs.apply(&typo, "    teh  ", 5), "    the  ");
+
+        let spaces = Style {
+            indent_style: Some(IndentStyle::Space),
+            tab_width: Some(2),
+            trim_trailing_whitespace: true,
+            ..Style::default()
+        };
+        let tab = Fix {
+            length: 0,
+            replacement: "\t".to_string(),
+        };
+        assert_eq!(spaces.apply(&tab, "\tx = 1 ", 1), "    x = 1");
+        assert_eq!(spaces.apply(&typo, "\tteh ", 2), "\tthe");
+    }
+}
@@ -0,0 +1,207 @@
+//! Errors of the library API, so embedders can tell failures apart and recover from
+//! them instead of the process aborting. The binary maps them to exit codes.
+//!
+//! Git operations that race with other processes, like one holding `index.lock` or a
+//! network filesystem briefly failing to serve objects, are retried a few times with
+//! jittered backoff before giving up with [`Error::GitBusy`].
+
+use crate::shuffle::{next_random, seed};
+use crate::state;
+use git2::{ErrorClass, ErrorCode};
+use log::debug;
+use std::fmt;
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+/// Attempts of a git operation that keeps failing transiently
+pub const ATTEMPTS: u32 = 5;
+
+/// The delay before the first retry, doubled for each one after it
+const BACKOFF: Duration = Duration::from_millis(50);
+
+/// What transient failures of the filesystem say, in the OS's words
+const TRANSIENT_MESSAGES: &[&str] = &[
+    "index.lock",
+    "resource temporarily unavailable",
+    "device or resource busy",
+    "stale file handle",
+    "interrupted system call",
+    "text file busy",
+];
+
+#[derive(Debug)]
+pub enum Error {
+    /// A git operation failed, described by the message
+    Git {
+        message: &'static str,
+        source: git2::Error,
+    },
+    /// A git operation still failed transiently after [`ATTEMPTS`] attempts, as when
+    /// another process kept the index locked
+    GitBusy {
+        message: &'static str,
+        source: git2::Error,
+    },
+    /// A path in a diff is not UTF-8, so findings can't name it; lossily decoded
+    NonUtf8Path(String),
+    /// Reading lint output failed
+    Io(io::Error),
+    /// A thread parsing lint output panicked
+    ParserPanicked,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Wraps a git error with what was being done, for `map_err`
+pub fn git(message: &'static str) -> impl FnOnce(git2::Error) -> Error {
+    move |source| Error::Git { message, source }
+}
+
+/// Whether `err` is one that trying again may not run into, as lock contention and
+/// the hiccups of network filesystems
+pub fn is_transient(err: &git2::Error) -> bool {
+    if err.code() == ErrorCode::Locked {
+        return true;
+    }
+    let message = err.message().to_ascii_lowercase();
+    matches!(
+        err.class(),
+        ErrorClass::Os | ErrorClass::Filesystem | ErrorClass::Index | ErrorClass::Odb
+    ) && TRANSIENT_MESSAGES
+        .iter()
+        .any(|transient| message.contains(transient))
+}
+
+/// Runs `operation`, retrying it with jittered exponential backoff while it fails
+/// transiently, and wraps its error with `message` like [`git`]
+pub fn retried<T>(
+    message: &'static str,
+    mut operation: impl FnMut() -> std::result::Result<T, git2::Error>,
+) -> Result<T> {
+    let mut random = seed() | 1;
+    let mut delay = BACKOFF;
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let source = match operation() {
+            Ok(value) => return Ok(value),
+            Err(source) => source,
+        };
+        if !is_transient(&source) {
+            return Err(Error::Git { message, source });
+        }
+        if attempt == ATTEMPTS {
+            return Err(Error::GitBusy { message, source });
+        }
+        debug!("{} (attempt {}): {}, retrying", message, attempt, source);
+        // Up to half again, so runners that collided don't collide again
+        let jitter = next_random(&mut random) % (delay.as_millis() as u64 / 2 + 1);
+        thread::sleep(delay + Duration::from_millis(jitter));
+        delay *= 2;
+    }
+}
+
+impl Error {
+    /// The exit code of the binary: that of a checkout that can't be diffed for
+    /// repository errors, then `EX_TEMPFAIL`, for CI to retry the job on, `EX_IOERR`
+    /// and `EX_SOFTWARE` from sysexits.h
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Git { .. } | Error::NonUtf8Path(_) => state::EXIT_CODE,
+            Error::GitBusy { .. } => 75,
+            Error::Io(_) => 74,
+            Error::ParserPanicked => 70,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Git { message, .. } => f.write_str(message),
+            Error::GitBusy { message, .. } => write!(
+                f,
+                "{}, still failing after {} attempts; retry once whatever holds the \
+                 repository is done",
+                message, ATTEMPTS
+            ),
+            Error::NonUtf8Path(path) => write!(f, "Path '{}' in the diff is not UTF-8", path),
+            Error::Io(_) => f.write_str("Unable to read lint output"),
+            Error::ParserPanicked => f.write_str("A thread parsing lint output panicked"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Git { source, .. } | Error::GitBusy { source, .. } => Some(source),
+            Error::Io(source) => Some(source),
+            Error::NonUtf8Path(_) | Error::ParserPanicked => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::{git, is_transient, retried, Error, ATTEMPTS};
+    use git2::{ErrorClass, ErrorCode};
+
+    #[test]
+    fn test_error() {
+        let err = git("Unable to parse gitref")(git2::Error::from_str("not found"));
+        assert_eq!(
+            format!("{:#}", anyhow::Error::from(err)),
+            "Unable to parse gitref: not found"
+        );
+        assert_eq!(
+            Error::NonUtf8Path("a\u{fffd}.py".to_string()).exit_code(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_retried() {
+        let locked = || {
+            git2::Error::new(
+                ErrorCode::Locked,
+                ErrorClass::Index,
+                "failed to lock file '.git/index.lock' for writing",
+            )
+        };
+        let stale = git2::Error::new(ErrorCode::GenericError, ErrorClass::Os, "Stale file handle");
+        assert!(is_transient(&locked()));
+        assert!(is_transient(&stale));
+        assert!(!is_transient(&git2::Error::from_str("not found")));
+
+        let mut attempts = 0;
+        let value = retried("Unable to read the index", || {
+            attempts += 1;
+            if attempts < 3 {
+                Err(locked())
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(value.unwrap(), 3);
+
+        let mut attempts = 0;
+        let err = retried("Unable to read the index", || -> Result<(), _> {
+            attempts += 1;
+            Err(locked())
+        })
+        .unwrap_err();
+        assert_eq!(attempts, ATTEMPTS);
+        assert_eq!(err.exit_code(), 75);
+        assert!(err.to_string().contains("still failing after 5 attempts"));
+
+        let mut attempts = 0;
+        let err = retried("Unable to parse gitref", || -> Result<(), _> {
+            attempts += 1;
+            Err(git2::Error::from_str("not found"))
+        })
+        .unwrap_err();
+        assert_eq!((attempts, err.exit_code()), (1, 3));
+    }
+}
@@ -0,0 +1,187 @@
+//! Runs the linter as a child process (`diff-format -- flake8 src/`), filtering both its
+//! output streams.
+//!
+//! Linters usually exit non-zero when they report findings, so their exit status only
+//! matters when nothing in their output parsed as one: then it most likely crashed or
+//! was misconfigured, and its stderr and exit code are passed on.
+//!
+//! With `--fail-fast` the linter is stopped once a finding fails the run. On Unix it then
+//! runs in a process group of its own, so whatever it started is stopped along with it;
+//! elsewhere, as on Windows where that would take a job object, only the linter itself
+//! is killed and what it started keeps running.
+
+use crate::input::{InputFormat, Lines};
+use anyhow::{Context, Result};
+use log::warn;
+use std::io::{self, BufReader, Read};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+#[cfg(unix)]
+use std::time::{Duration, Instant};
+
+/// How long a stopped linter has to exit after SIGTERM before it is killed
+#[cfg(unix)]
+const GRACE: Duration = Duration::from_secs(2);
+
+pub struct Linter {
+    program: String,
+    child: Child,
+    stderr: Arc<Mutex<Vec<String>>>,
+}
+
+fn forward(
+    input_format: InputFormat,
+    stream: impl Read + Send + 'static,
+    sender: SyncSender<io::Result<String>>,
+    copy: Option<Arc<Mutex<Vec<String>>>>,
+) {
+    thread::spawn(move || {
+        for line in input_format.lines(BufReader::new(stream)) {
+            if let (Some(copy), Ok(line)) = (&copy, &line) {
+                copy.lock().unwrap().push(line.clone());
+            }
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+impl Linter {
+    /// Starts `command`, returning the lines of its stdout and stderr as they arrive,
+    /// buffering up to `capacity` of them. An `isolated` linter gets a process group of
+    /// its own for [`Linter::terminate`], which also keeps terminal signals such as
+    /// Ctrl-C from reaching it
+    pub fn spawn(
+        command: &[String],
+        input_format: InputFormat,
+        capacity: usize,
+        isolated: bool,
+    ) -> Result<(Self, Lines<'static>)> {
+        let program = command[0].clone();
+        let mut child = Command::new(&program);
+        #[cfg(unix)]
+        if isolated {
+            use std::os::unix::process::CommandExt;
+            child.process_group(0);
+        }
+        #[cfg(not(unix))]
+        let _ = isolated;
+        let mut child = child
+            .args(&command[1..])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Unable to run '{}'", program))?;
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        let stderr = Arc::new(Mutex::new(Vec::new()));
+        forward(
+            input_format,
+            child.stdout.take().unwrap(),
+            sender.clone(),
+            None,
+        );
+        forward(
+            input_format,
+            child.stderr.take().unwrap(),
+            sender,
+            Some(stderr.clone()),
+        );
+        let linter = Linter {
+            program,
+            child,
+            stderr,
+        };
+        Ok((linter, Box::new(receiver.into_iter())))
+    }
+
+    /// Stops the linter and, when isolated, the processes it started, sending SIGTERM
+    /// first and SIGKILL to what is left after [`GRACE`], and reaps it
+    pub fn terminate(&mut self) -> Result<()> {
+        #[cfg(unix)]
+        {
+            let group = -(self.child.id() as libc::pid_t);
+            let signal = |signal| unsafe { libc::kill(group, signal) };
+            if signal(libc::SIGTERM) == 0 {
+                let deadline = Instant::now() + GRACE;
+                while self.child.try_wait()?.is_none() && Instant::now() < deadline {
+                    thread::sleep(Duration::from_millis(20));
+                }
+                // Also what the linter started and left behind, even once it exited
+                signal(libc::SIGKILL);
+            } else {
+                // Not isolated, so without a group of its own
+                let _ = self.child.kill();
+            }
+        }
+        // Without a job object to kill, the processes the linter started are left running
+        #[cfg(not(unix))]
+        let _ = self.child.kill();
+        self.child
+            .wait()
+            .with_context(|| format!("Unable to wait for '{}'", self.program))?;
+        Ok(())
+    }
+
+    /// Waits for the linter, returning the exit code to pass on when it failed without
+    /// `reported` findings, after replaying its stderr
+    pub fn finish(mut self, reported: bool) -> Result<Option<i32>> {
+        let status = self
+            .child
+            .wait()
+            .with_context(|| format!("Unable to wait for '{}'", self.program))?;
+        if status.success() || reported {
+            return Ok(None);
+        }
+        for line in self.stderr.lock().unwrap().iter() {
+            eprintln!("{}", line);
+        }
+        warn!(
+            "'{}' exited with {} without reporting any findings",
+            self.program, status
+        );
+        // Killed by a signal
+        Ok(Some(status.code().unwrap_or(2)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::exec::Linter;
+    use crate::input::InputFormat;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_linter_streams() {
+        let command = ["sh", "-c", "echo a.py:1: E1 x; echo oops >&2; exit 3"].map(String::from);
+        let (linter, lines) = Linter::spawn(&command, InputFormat::Text, 10, false).unwrap();
+        let mut lines: Vec<String> = lines.map(Result::unwrap).collect();
+        lines.sort();
+        assert_eq!(lines, ["a.py:1: E1 x", "oops"]);
+        assert_eq!(linter.stderr.lock().unwrap().clone(), ["oops"]);
+        assert_eq!(linter.finish(false).unwrap(), Some(3));
+
+        let command = ["sh", "-c", "exit 1"].map(String::from);
+        let (linter, lines) = Linter::spawn(&command, InputFormat::Text, 10, false).unwrap();
+        assert_eq!(lines.count(), 0);
+        assert_eq!(linter.finish(true).unwrap(), None);
+    }
+
+    #[test]
+    fn test_terminate() {
+        // The background sleep ignores SIGTERM, so it takes the SIGKILL after the grace
+        let script = "trap '' TERM; sleep 30 & echo a.py:1: E1 x; wait";
+        let command = ["sh", "-c", script].map(String::from);
+        let started = Instant::now();
+        let (mut linter, mut lines) = Linter::spawn(&command, InputFormat::Text, 10, true).unwrap();
+        assert_eq!(lines.next().unwrap().unwrap(), "a.py:1: E1 x");
+        linter.terminate().unwrap();
+        // Every writer of the pipes is gone
+        assert_eq!(lines.count(), 0);
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(linter.finish(true).unwrap(), None);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::ranges::merge_ranges;
+use crate::HunkRange;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use log::debug;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use tree_sitter::{Language, Node, Parser, Point};
+
+/// Syntactic construct each hunk is widened to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExpandTo {
+    Function,
+    Class,
+    Statement,
+}
+
+const FUNCTION_KINDS: &[&str] = &[
+    "function_definition",  // Python, C
+    "function_item",        // Rust
+    "function_declaration", // JavaScript, Go
+    "method_definition",    // JavaScript
+    "method_declaration",   // Go
+    "arrow_function",
+    "function_expression",
+];
+
+const CLASS_KINDS: &[&str] = &[
+    "class_definition",  // Python
+    "class_declaration", // JavaScript
+    "impl_item",         // Rust
+    "trait_item",
+    "struct_item",
+    "enum_item",
+    "struct_specifier", // C
+    "type_declaration", // Go
+];
+
+/// Nodes whose children are statements
+const BLOCK_KINDS: &[&str] = &[
+    "module",
+    "block",
+    "source_file",
+    "translation_unit",
+    "program",
+    "compound_statement",
+    "statement_block",
+    "declaration_list",
+    "class_body",
+];
+
+fn language(path: &str) -> Option<Language> {
+    let extension = Path::new(path).extension()?.to_str()?;
+    let language = match extension {
+        "py" | "pyi" => tree_sitter_python::LANGUAGE,
+        "rs" => tree_sitter_rust::LANGUAGE,
+        "js" | "jsx" | "mjs" | "cjs" => tree_sitter_javascript::LANGUAGE,
+        "go" => tree_sitter_go::LANGUAGE,
+        "c" | "h" => tree_sitter_c::LANGUAGE,
+        _ => return None,
+    };
+    Some(language.into())
+}
+
+fn enclosing<'a>(mut node: Node<'a>, expand_to: ExpandTo) -> Option<Node<'a>> {
+    loop {
+        let parent = node.parent()?;
+        let found = match expand_to {
+            ExpandTo::Function => FUNCTION_KINDS.contains(&node.kind()),
+            ExpandTo::Class => CLASS_KINDS.contains(&node.kind()),
+            ExpandTo::Statement => BLOCK_KINDS.contains(&parent.kind()),
+        };
+        if found {
+            return Some(node);
+        }
+        node = parent;
+    }
+}
+
+fn expand_range(root: Node, lines: &[&str], range: HunkRange, expand_to: ExpandTo) -> HunkRange {
+    let start_row = range.0.saturating_sub(1) as usize;
+    let end_row = (range.1.saturating_sub(1) as usize).max(start_row);
+    let end_column = lines.get(end_row).map_or(0, |line| line.len());
+    let node = match root
+        .named_descendant_for_point_range(Point::new(start_row, 0), Point::new(end_row, end_column))
+    {
+        Some(node) => node,
+        None => return range,
+    };
+    match enclosing(node, expand_to) {
+        Some(construct) => (
+            range.0.min(construct.start_position().row as u32 + 1),
+            range.1.max(construct.end_position().row as u32 + 1),
+        ),
+        None => range,
+    }
+}
+
+pub fn expand_source(
+    source: &str,
+    language: &Language,
+    ranges: &[HunkRange],
+    expand_to: ExpandTo,
+) -> Result<Vec<HunkRange>> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(language)
+        .context("Incompatible tree-sitter grammar")?;
+    let tree = parser
+        .parse(source, None)
+        .context("Unable to parse source")?;
+    let lines: Vec<&str> = source.lines().collect();
+    Ok(merge_ranges(
+        ranges
+            .iter()
+            .map(|&range| expand_range(tree.root_node(), &lines, range, expand_to))
+            .collect(),
+    ))
+}
+
+pub fn expand_hunks(
+    expand_to: ExpandTo,
+    workdir: &Path,
+    file_hunks: &mut HashMap<String, Vec<HunkRange>>,
+) -> Result<()> {
+    for (path, ranges) in file_hunks.iter_mut() {
+        let language = match language(path) {
+            Some(language) => language,
+            None => continue,
+        };
+        let source = fs::read_to_string(workdir.join(path))
+            .with_context(|| format!("Unable to read '{}'", path))?;
+        *ranges = expand_source(&source, &language, ranges, expand_to)
+            .with_context(|| format!("Unable to expand hunks of '{}'", path))?;
+        debug!("Expanded hunks of '{}' to {:?}", path, ranges);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::expand::{expand_source, language, ExpandTo};
+
+    const SOURCE: &str = "\
+import os
+
+
+class A:
+    def f(self):
+        x = [
+            1,
+        ]
+        return x
+
+    def g(self):
+        pass
+";
+
+    #[test]
+    fn test_expand_python() {
+        let python = language("a.py").unwrap();
+        let expand = |ranges: &[(u32, u32)], expand_to| {
+            expand_source(SOURCE, &python, ranges, expand_to).unwrap()
+        };
+        assert_eq!(expand(&[(7, 7)], ExpandTo::Function), [(5, 9)]);
+        assert_eq!(expand(&[(7, 7)], ExpandTo::Statement), [(6, 8)]);
+        assert_eq!(expand(&[(7, 7), (12, 12)], ExpandTo::Class), [(4, 12)]);
+        // Nothing encloses top-level code
+        assert_eq!(expand(&[(1, 1)], ExpandTo::Function), [(1, 1)]);
+    }
+}
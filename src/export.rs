@@ -0,0 +1,201 @@
+//! Changed ranges and matched findings for editor extensions to highlight.
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::output;
+use crate::staging::Staging;
+use crate::HunkRange;
+use anyhow::{Context, Result};
+use clap::{Args, ValueEnum};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EditorFormat {
+    /// JetBrains IDEs: 1-based lines and `HighlightSeverity` names
+    Idea,
+    /// VS Code: 0-based `Range`s and `DiagnosticSeverity` names
+    Vscode,
+}
+
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    #[arg(long, value_enum)]
+    format: EditorFormat,
+
+    /// File to write, relative to the repository root unless absolute
+    #[arg(long, default_value = ".diff-format/editor.json")]
+    output: PathBuf,
+}
+
+fn severity(format: EditorFormat, severity: Severity) -> &'static str {
+    match (format, severity) {
+        (EditorFormat::Idea, Severity::Error) => "ERROR",
+        (EditorFormat::Idea, Severity::Warning) => "WARNING",
+        (EditorFormat::Idea, Severity::Info) => "WEAK_WARNING",
+        (EditorFormat::Vscode, Severity::Error) => "Error",
+        (EditorFormat::Vscode, Severity::Warning) => "Warning",
+        (EditorFormat::Vscode, Severity::Info) => "Information",
+    }
+}
+
+fn diagnostic_json(format: EditorFormat, diagnostic: &Diagnostic) -> Value {
+    let line = diagnostic.line.unwrap_or(1);
+    let column = diagnostic.column.unwrap_or(1);
+    let end_line = diagnostic.end_line.unwrap_or(line).max(line);
+    let end_column = diagnostic.end_column.unwrap_or(column).max(1);
+    let mut value = match format {
+        EditorFormat::Idea => json!({ "line": line, "column": column }),
+        EditorFormat::Vscode => json!({
+            "range": {
+                "start": { "line": line - 1, "character": column - 1 },
+                "end": { "line": end_line - 1, "character": end_column - 1 },
+            },
+        }),
+    };
+    value["severity"] = json!(severity(format, diagnostic.effective_severity()));
+    value["message"] = json!(diagnostic.text());
+    value["code"] = json!(diagnostic.rule);
+    value["source"] = json!(diagnostic.tool);
+    if !diagnostic.related.is_empty() {
+        let related: Vec<_> = diagnostic
+            .related
+            .iter()
+            .map(|related| {
+                let line = related.line.unwrap_or(1);
+                let column = related.column.unwrap_or(1);
+                let mut value = match format {
+                    EditorFormat::Idea => {
+                        json!({ "path": related.path, "line": line, "column": column })
+                    }
+                    EditorFormat::Vscode => json!({
+                        "path": related.path,
+                        "position": { "line": line - 1, "character": column - 1 },
+                    }),
+                };
+                value["message"] = json!(related.message);
+                value
+            })
+            .collect();
+        value["relatedLocations"] = json!(related);
+    }
+    value
+}
+
+/// `{"version": 1, "files": {path: {"changed": [...], "diagnostics": [...]}}}`, with
+/// changed ranges as inclusive `[start, end]` line pairs in the format's line base.
+///
+/// With `staging`, files also get a `sources` array giving for each changed range
+/// whether it is `staged`, `unstaged` or `both`, or `null` when it is neither.
+pub fn render(
+    format: EditorFormat,
+    file_hunks: &HashMap<String, Vec<HunkRange>>,
+    staging: Option<&Staging>,
+    diagnostics: &[Diagnostic],
+) -> Result<String> {
+    let base = match format {
+        EditorFormat::Idea => 0,
+        EditorFormat::Vscode => 1,
+    };
+    let mut files = BTreeMap::new();
+    for (path, ranges) in file_hunks {
+        let changed: Vec<_> = ranges
+            .iter()
+            .map(|&(start, end)| [start.saturating_sub(base), end.saturating_sub(base)])
+            .collect();
+        let mut file = json!({ "changed": changed, "diagnostics": [] });
+        if let Some(staging) = staging {
+            let sources: Vec<_> = ranges
+                .iter()
+                .map(|&range| staging.source(path, range))
+                .collect();
+            file["sources"] = json!(sources);
+        }
+        files.insert(path.as_str(), file);
+    }
+    for diagnostic in diagnostics {
+        let file = files
+            .entry(diagnostic.path.as_str())
+            .or_insert_with(|| json!({ "changed": [], "diagnostics": [] }));
+        file["diagnostics"]
+            .as_array_mut()
+            .unwrap()
+            .push(diagnostic_json(format, diagnostic));
+    }
+    Ok(serde_json::to_string_pretty(&json!({ "version": 1, "files": files }))? + "\n")
+}
+
+pub fn write(
+    args: &ExportArgs,
+    workdir: &Path,
+    file_hunks: &HashMap<String, Vec<HunkRange>>,
+    staging: Option<&Staging>,
+    diagnostics: &[Diagnostic],
+) -> Result<()> {
+    let path = workdir.join(&args.output);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Unable to create {}", parent.display()))?;
+    }
+    output::write_atomic(
+        &path,
+        render(args.format, file_hunks, staging, diagnostics)?.as_bytes(),
+    )
+    .with_context(|| format!("Unable to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::Diagnostic;
+    use crate::export::{render, EditorFormat};
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_vscode_export() {
+        let file_hunks: HashMap<_, _> = vec![("a.py".to_string(), vec![(3, 4)])]
+            .into_iter()
+            .collect();
+        let mut diagnostic = Diagnostic::new("a.py", Some(3));
+        diagnostic.column = Some(5);
+        diagnostic.rule = Some("W605".to_string());
+        diagnostic.message = Some("W605 invalid escape".to_string());
+        diagnostic.severity = Some(crate::diagnostic::Severity::Warning);
+
+        let export: Value = serde_json::from_str(
+            &render(
+                EditorFormat::Vscode,
+                &file_hunks,
+                None,
+                &[diagnostic.clone()],
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let file = &export["files"]["a.py"];
+        assert_eq!(file["changed"], json!([[2, 3]]));
+        assert!(file.get("sources").is_none());
+        assert_eq!(
+            file["diagnostics"][0]["range"]["start"],
+            json!({"line": 2, "character": 4})
+        );
+        assert_eq!(file["diagnostics"][0]["severity"], "Warning");
+        assert!(file["diagnostics"][0].get("relatedLocations").is_none());
+
+        let mut note = Diagnostic::new("b.py", Some(8));
+        note.message = Some("note: defined here".to_string());
+        diagnostic.relate(&note);
+
+        let export: Value = serde_json::from_str(
+            &render(EditorFormat::Idea, &file_hunks, None, &[diagnostic]).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(export["files"]["a.py"]["changed"], json!([[3, 4]]));
+        assert_eq!(export["files"]["a.py"]["diagnostics"][0]["line"], 3);
+        assert_eq!(
+            export["files"]["a.py"]["diagnostics"][0]["relatedLocations"],
+            json!([{"path": "b.py", "line": 8, "column": 1, "message": "defined here"}])
+        );
+    }
+}
@@ -0,0 +1,447 @@
+//! Gating expressions for `--policy`, a small subset of CEL evaluated per finding.
+//!
+//! ```text
+//! severity >= "warning" || (rule.startsWith("SEC") && file.contains("src/"))
+//! ```
+//!
+//! Fields are `file`, `rule`, `message`, `tool` and `language` (strings, empty when
+//! missing), `line` and `column` (integers, 0 when missing) and `severity`, compared
+//! with the strings `"info"`, `"warning"` and `"error"`. Strings have `startsWith`,
+//! `endsWith`, `contains` and `matches` (a regex) methods. Expressions are type
+//! checked when parsed.
+
+use crate::diagnostic::{Diagnostic, Severity};
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Token {
+    Ident(String),
+    Str(String),
+    Int(i64),
+    Op(&'static str),
+}
+
+const OPERATORS: &[&str] = &[
+    "||", "&&", "==", "!=", "<=", ">=", "<", ">", "!", "(", ")", ".", ",",
+];
+
+pub(crate) fn tokenize(text: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        let c = rest.chars().next().unwrap();
+        let length = if c == '"' || c == '\'' {
+            let mut value = String::new();
+            let mut chars = rest.char_indices().skip(1);
+            let end = loop {
+                match chars.next() {
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, escaped)) => value.push(escaped),
+                        None => bail!("unterminated string"),
+                    },
+                    Some((index, quote)) if quote == c => break index,
+                    Some((_, other)) => value.push(other),
+                    None => bail!("unterminated string"),
+                }
+            };
+            tokens.push(Token::Str(value));
+            end + 1
+        } else if c.is_ascii_digit() {
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            tokens.push(Token::Int(rest[..digits].parse()?));
+            digits
+        } else if c.is_alphabetic() || c == '_' {
+            let length = rest
+                .find(|c: char| !c.is_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..length].to_string()));
+            length
+        } else {
+            match OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+                Some(op) => {
+                    tokens.push(Token::Op(op));
+                    op.len()
+                }
+                None => bail!("unexpected '{}'", c),
+            }
+        };
+        rest = rest[length..].trim_start();
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Type {
+    Bool,
+    Int,
+    Str,
+    Severity,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    File,
+    Rule,
+    Message,
+    Tool,
+    Language,
+    Line,
+    Column,
+    Severity,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "file" => Field::File,
+            "rule" => Field::Rule,
+            "message" => Field::Message,
+            "tool" => Field::Tool,
+            "language" => Field::Language,
+            "line" => Field::Line,
+            "column" => Field::Column,
+            "severity" => Field::Severity,
+            _ => return None,
+        })
+    }
+
+    fn value_type(self) -> Type {
+        match self {
+            Field::Line | Field::Column => Type::Int,
+            Field::Severity => Type::Severity,
+            _ => Type::Str,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Method {
+    StartsWith,
+    EndsWith,
+    Contains,
+    Matches,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Bool(bool),
+    Int(i64),
+    Str(String),
+    Severity(Severity),
+    Field(Field),
+    Not(Box<Node>),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Compare(&'static str, Box<Node>, Box<Node>),
+    Call(Method, Box<Node>, Box<Node>),
+    /// `matches` with its regex compiled when parsed
+    Matches(Box<Node>, Regex),
+}
+
+#[derive(Debug, PartialEq, PartialOrd)]
+enum Value<'a> {
+    Bool(bool),
+    Int(i64),
+    Str(&'a str),
+    Severity(Severity),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        if self.peek() == Some(&Token::Op(OPERATORS.iter().find(|o| **o == op).unwrap())) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<()> {
+        if !self.eat(op) {
+            bail!("expected '{}'", op);
+        }
+        Ok(())
+    }
+
+    fn or(&mut self) -> Result<(Node, Type)> {
+        let mut left = self.and()?;
+        while self.eat("||") {
+            let right = self.and()?;
+            left = (
+                Node::Or(
+                    Box::new(bool_operand(left)?),
+                    Box::new(bool_operand(right)?),
+                ),
+                Type::Bool,
+            );
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<(Node, Type)> {
+        let mut left = self.unary()?;
+        while self.eat("&&") {
+            let right = self.unary()?;
+            left = (
+                Node::And(
+                    Box::new(bool_operand(left)?),
+                    Box::new(bool_operand(right)?),
+                ),
+                Type::Bool,
+            );
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<(Node, Type)> {
+        if self.eat("!") {
+            let operand = self.unary()?;
+            return Ok((Node::Not(Box::new(bool_operand(operand)?)), Type::Bool));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<(Node, Type)> {
+        let left = self.call()?;
+        let op = match self.peek() {
+            Some(Token::Op(op)) if ["==", "!=", "<", "<=", ">", ">="].contains(op) => *op,
+            _ => return Ok(left),
+        };
+        self.position += 1;
+        let right = self.call()?;
+        let (left, right) = unify(left, right)?;
+        if left.1 == Type::Bool && !["==", "!="].contains(&op) {
+            bail!("'{}' can't compare booleans", op);
+        }
+        Ok((
+            Node::Compare(op, Box::new(left.0), Box::new(right.0)),
+            Type::Bool,
+        ))
+    }
+
+    fn call(&mut self) -> Result<(Node, Type)> {
+        let receiver = self.primary()?;
+        if !self.eat(".") {
+            return Ok(receiver);
+        }
+        let name = match self.peek() {
+            Some(Token::Ident(name)) => name.clone(),
+            _ => bail!("expected a method name after '.'"),
+        };
+        self.position += 1;
+        let method = match name.as_str() {
+            "startsWith" | "startswith" => Method::StartsWith,
+            "endsWith" | "endswith" => Method::EndsWith,
+            "contains" => Method::Contains,
+            "matches" => Method::Matches,
+            _ => bail!("unknown method '{}'", name),
+        };
+        if receiver.1 != Type::Str {
+            bail!("'{}' needs a string", name);
+        }
+        self.expect("(")?;
+        let argument = self.or()?;
+        self.expect(")")?;
+        if argument.1 != Type::Str {
+            bail!("'{}' takes a string", name);
+        }
+        let node = match (method, argument.0) {
+            (Method::Matches, Node::Str(pattern)) => Node::Matches(
+                Box::new(receiver.0),
+                Regex::new(&pattern).with_context(|| format!("invalid regex '{}'", pattern))?,
+            ),
+            (Method::Matches, _) => bail!("'matches' takes a string literal"),
+            (method, argument) => Node::Call(method, Box::new(receiver.0), Box::new(argument)),
+        };
+        Ok((node, Type::Bool))
+    }
+
+    fn primary(&mut self) -> Result<(Node, Type)> {
+        let token = match self.peek() {
+            Some(token) => token.clone(),
+            None => bail!("unexpected end of expression"),
+        };
+        self.position += 1;
+        Ok(match token {
+            Token::Str(value) => (Node::Str(value), Type::Str),
+            Token::Int(value) => (Node::Int(value), Type::Int),
+            Token::Ident(name) if name == "true" || name == "false" => {
+                (Node::Bool(name == "true"), Type::Bool)
+            }
+            Token::Ident(name) => match Field::from_name(&name) {
+                Some(field) => (Node::Field(field), field.value_type()),
+                None => bail!("unknown field '{}'", name),
+            },
+            Token::Op("(") => {
+                let inner = self.or()?;
+                self.expect(")")?;
+                inner
+            }
+            Token::Op(op) => bail!("unexpected '{}'", op),
+        })
+    }
+}
+
+fn bool_operand((node, value_type): (Node, Type)) -> Result<Node> {
+    if value_type != Type::Bool {
+        bail!("expected a condition, found a {:?} value", value_type);
+    }
+    Ok(node)
+}
+
+/// Checks comparison operands have the same type, reading string literals compared to
+/// `severity` as severities
+fn unify(left: (Node, Type), right: (Node, Type)) -> Result<((Node, Type), (Node, Type))> {
+    let as_severity = |(node, value_type): (Node, Type)| match node {
+        Node::Str(label) if value_type == Type::Str => match Severity::from_label(&label) {
+            Some(severity) => Ok((Node::Severity(severity), Type::Severity)),
+            None => bail!("'{}' is not a severity", label),
+        },
+        node => Ok((node, value_type)),
+    };
+    let (left, right) = match (left.1, right.1) {
+        (Type::Severity, Type::Str) => (left, as_severity(right)?),
+        (Type::Str, Type::Severity) => (as_severity(left)?, right),
+        _ => (left, right),
+    };
+    if left.1 != right.1 {
+        bail!("can't compare {:?} with {:?}", left.1, right.1);
+    }
+    Ok((left, right))
+}
+
+/// A parsed and type checked `--policy` expression
+#[derive(Debug, Clone)]
+pub struct Expression {
+    root: Node,
+}
+
+impl Expression {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            position: 0,
+        };
+        let root = bool_operand(parser.or()?)?;
+        if let Some(token) = parser.peek() {
+            bail!("unexpected {:?}", token);
+        }
+        Ok(Expression { root })
+    }
+
+    pub fn evaluate(&self, diagnostic: &Diagnostic) -> bool {
+        evaluate(&self.root, diagnostic) == Value::Bool(true)
+    }
+}
+
+impl FromStr for Expression {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        Expression::parse(text)
+    }
+}
+
+fn evaluate<'a>(node: &'a Node, diagnostic: &'a Diagnostic) -> Value<'a> {
+    let truthy = |node| evaluate(node, diagnostic) == Value::Bool(true);
+    let text = |node| match evaluate(node, diagnostic) {
+        Value::Str(text) => text,
+        _ => "",
+    };
+    match node {
+        Node::Bool(value) => Value::Bool(*value),
+        Node::Int(value) => Value::Int(*value),
+        Node::Str(value) => Value::Str(value),
+        Node::Severity(severity) => Value::Severity(*severity),
+        Node::Field(field) => match field {
+            Field::File => Value::Str(&diagnostic.path),
+            Field::Rule => Value::Str(diagnostic.rule.as_deref().unwrap_or_default()),
+            Field::Message => Value::Str(diagnostic.text()),
+            Field::Tool => Value::Str(diagnostic.tool.as_deref().unwrap_or_default()),
+            Field::Language => Value::Str(diagnostic.language.as_deref().unwrap_or_default()),
+            Field::Line => Value::Int(diagnostic.line.unwrap_or(0).into()),
+            Field::Column => Value::Int(diagnostic.column.unwrap_or(0).into()),
+            Field::Severity => Value::Severity(diagnostic.effective_severity()),
+        },
+        Node::Not(operand) => Value::Bool(!truthy(operand)),
+        Node::And(left, right) => Value::Bool(truthy(left) && truthy(right)),
+        Node::Or(left, right) => Value::Bool(truthy(left) || truthy(right)),
+        Node::Compare(op, left, right) => {
+            let ordering = evaluate(left, diagnostic).partial_cmp(&evaluate(right, diagnostic));
+            Value::Bool(match *op {
+                "==" => ordering == Some(Ordering::Equal),
+                "!=" => ordering != Some(Ordering::Equal),
+                "<" => ordering == Some(Ordering::Less),
+                "<=" => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                ">" => ordering == Some(Ordering::Greater),
+                _ => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+            })
+        }
+        Node::Call(method, receiver, argument) => {
+            let (receiver, argument) = (text(receiver), text(argument));
+            Value::Bool(match method {
+                Method::StartsWith => receiver.starts_with(argument),
+                Method::EndsWith => receiver.ends_with(argument),
+                _ => receiver.contains(argument),
+            })
+        }
+        Node::Matches(receiver, regex) => Value::Bool(regex.is_match(text(receiver))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::{Diagnostic, Severity};
+    use crate::expression::Expression;
+
+    #[test]
+    fn test_evaluate() {
+        let mut diagnostic = Diagnostic::new("src/auth.py", Some(12));
+        diagnostic.rule = Some("SEC101".to_string());
+        diagnostic.severity = Some(Severity::Warning);
+
+        let policy = r#"severity >= "error" || (rule.startswith("SEC") && file.contains("src/"))"#;
+        let expression = Expression::parse(policy).unwrap();
+        assert!(expression.evaluate(&diagnostic));
+        diagnostic.path = "tests/test_auth.py".to_string();
+        assert!(!expression.evaluate(&diagnostic));
+        diagnostic.severity = None;
+        assert!(expression.evaluate(&diagnostic));
+
+        let expression =
+            Expression::parse("!(line < 10) && file.matches('^tests/') && tool == ''").unwrap();
+        assert!(expression.evaluate(&diagnostic));
+        let expression = Expression::parse("language == 'Python'").unwrap();
+        assert!(!expression.evaluate(&diagnostic));
+        diagnostic.language = Some("Python".to_string());
+        assert!(expression.evaluate(&diagnostic));
+    }
+
+    #[test]
+    fn test_type_errors() {
+        for (text, error) in [
+            ("severity >= \"fatalish\"", "'fatalish' is not a severity"),
+            ("line == \"12\"", "can't compare Int with Str"),
+            ("rule", "expected a condition, found a Str value"),
+            ("line.contains(\"1\")", "'contains' needs a string"),
+            ("owner == \"me\"", "unknown field 'owner'"),
+            ("(rule == \"E1\"", "expected ')'"),
+        ] {
+            assert_eq!(Expression::parse(text).unwrap_err().to_string(), error);
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use crate::config::MatchPolicy;
+use crate::diagnostic::Diagnostic;
+use crate::error::{retried, Result};
+use crate::fixtures;
+use crate::hunks::{generate_hunkmap, get_diff, get_tree, tree_hunkmap};
+use crate::{is_changed, is_number_in_sorted_ranges, paths, HunkMap};
+use git2::{Repository, Tree};
+use std::path::Path;
+
+/// The lines a change touched, for deciding which findings are new
+pub struct DiffFilter {
+    hunks: HunkMap,
+}
+
+impl DiffFilter {
+    /// Lines of the workdir of the repository at `path` that differ from `gitref`
+    pub fn from_repo(path: impl AsRef<Path>, gitref: &str) -> Result<Self> {
+        let repo = retried("Can't open repository", || Repository::open(path.as_ref()))?;
+        let tree = get_tree(&repo, gitref)?;
+        let hunks = generate_hunkmap(&get_diff(&repo, &tree, None, None, false)?)?;
+        Ok(DiffFilter { hunks })
+    }
+
+    /// Lines of `head` that differ from `base`, both trees of `repo`, which may live in
+    /// memory (see [`crate::fixtures`])
+    pub fn from_trees(repo: &Repository, base: &Tree, head: &Tree) -> Result<Self> {
+        Ok(DiffFilter {
+            hunks: tree_hunkmap(repo, base, head)?,
+        })
+    }
+
+    /// Lines of the `head` files that differ from the `base` ones, compared in memory
+    pub fn from_files<'a>(
+        base: impl IntoIterator<Item = (&'a str, &'a str)>,
+        head: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Result<Self> {
+        let repo = fixtures::memory_repo()?;
+        let base = fixtures::write_tree(&repo, base)?;
+        let head = fixtures::write_tree(&repo, head)?;
+        DiffFilter::from_trees(&repo, &base, &head)
+    }
+
+    /// Wraps changed ranges computed elsewhere, sorted and non-overlapping per file
+    pub fn from_hunks(hunks: HunkMap) -> Self {
+        DiffFilter { hunks }
+    }
+
+    pub fn hunks(&self) -> &HunkMap {
+        &self.hunks
+    }
+
+    /// Whether line `line` of `file`, relative to the repository root, changed
+    pub fn contains(&self, file: &str, line: u32) -> bool {
+        self.hunks
+            .get(&paths::normalize_path(file))
+            .is_some_and(|ranges| is_number_in_sorted_ranges(ranges, line))
+    }
+
+    /// Whether a finding counts as new under `policy`
+    pub fn matches(&self, diagnostic: &Diagnostic, policy: MatchPolicy) -> bool {
+        is_changed(&self.hunks, diagnostic, policy)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::MatchPolicy;
+    use crate::diagnostic::Diagnostic;
+    use crate::DiffFilter;
+
+    #[test]
+    fn test_contains() {
+        let filter = DiffFilter::from_hunks(
+            vec![("src/a.py".to_string(), vec![(3, 5)])]
+                .into_iter()
+                .collect(),
+        );
+        assert!(filter.contains("src/a.py", 4));
+        assert!(filter.contains("./src/a.py", 5));
+        assert!(!filter.contains("src/a.py", 6));
+        assert!(!filter.contains("src/b.py", 4));
+        assert!(filter.matches(&Diagnostic::new("src/a.py", None), MatchPolicy::Line));
+    }
+
+    #[test]
+    fn test_from_files() {
+        let filter = DiffFilter::from_files(
+            [
+                ("src/a.py", "a\nb\nc\n"),
+                ("src/b.py", "x\n"),
+                ("src/old_c.py", "1\n2\n3\n4\nfive\n"),
+            ],
+            [
+                ("src/a.py", "a\nB\nc\n"),
+                ("src/b.py", "x\n"),
+                ("docs/new.md", "hi\n"),
+                ("src/c.py", "1\n2\n3\n4\n5\n"),
+            ],
+        )
+        .unwrap();
+        assert!(filter.contains("src/a.py", 2));
+        assert!(!filter.contains("src/a.py", 1));
+        assert!(filter.contains("docs/new.md", 1));
+        assert!(!filter.hunks().contains_key("src/b.py"));
+        assert!(filter.contains("src/c.py", 5));
+        assert!(!filter.contains("src/c.py", 4));
+    }
+}
@@ -0,0 +1,120 @@
+//! Findings from a base report that sat on lines the diff touched and are now gone.
+
+use crate::diagnostic::Diagnostic;
+use crate::i18n::{Lang, Message};
+use crate::HunkRange;
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Identifies a finding independently of its line, which the change may have moved
+pub type FindingKey = (String, Option<String>, String);
+
+pub fn finding_key(diagnostic: &Diagnostic) -> FindingKey {
+    (
+        diagnostic.path.clone(),
+        diagnostic.rule.clone(),
+        diagnostic.text().to_string(),
+    )
+}
+
+/// Base findings on touched old-side lines without a counterpart in the current findings
+pub fn find_fixed<'a>(
+    base: &'a [Diagnostic],
+    old_hunks: &HashMap<String, Vec<HunkRange>>,
+    current: &HashMap<FindingKey, usize>,
+) -> Vec<&'a Diagnostic> {
+    let mut remaining = current.clone();
+    let mut fixed = Vec::new();
+    for diagnostic in base {
+        let touched = match (old_hunks.get(&diagnostic.path), diagnostic.line) {
+            (Some(ranges), Some(line)) => ranges
+                .iter()
+                .any(|&(start, end)| start <= line && line <= end),
+            _ => false,
+        };
+        if !touched {
+            continue;
+        }
+        match remaining.get_mut(&finding_key(diagnostic)) {
+            // Still reported, possibly on a shifted line
+            Some(count) if *count > 0 => *count -= 1,
+            _ => fixed.push(diagnostic),
+        }
+    }
+    fixed
+}
+
+pub fn render_markdown(fixed: &[&Diagnostic], lang: Lang) -> String {
+    if fixed.is_empty() {
+        return String::new();
+    }
+    let mut markdown = format!(
+        "### :tada: {}\n\n",
+        lang.format(Message::Fixed, &[&fixed.len()])
+    );
+    for diagnostic in fixed {
+        markdown.push_str(&format!(
+            "- `{}:{}` {}\n",
+            diagnostic.path,
+            diagnostic.line.unwrap_or_default(),
+            diagnostic.text()
+        ));
+    }
+    markdown
+}
+
+#[derive(Serialize)]
+struct FixedEntry<'a> {
+    path: &'a str,
+    line: Option<u32>,
+    rule: Option<&'a str>,
+    message: &'a str,
+}
+
+pub fn render_json(fixed: &[&Diagnostic]) -> Result<String> {
+    let entries: Vec<_> = fixed
+        .iter()
+        .map(|diagnostic| FixedEntry {
+            path: &diagnostic.path,
+            line: diagnostic.line,
+            rule: diagnostic.rule.as_deref(),
+            message: diagnostic.text(),
+        })
+        .collect();
+    Ok(serde_json::to_string_pretty(&serde_json::json!({ "fixed": entries }))? + "\n")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::Diagnostic;
+    use crate::fixed::{find_fixed, finding_key, render_markdown};
+    use crate::i18n::Lang;
+    use std::collections::HashMap;
+
+    fn finding(line: u32, rule: &str) -> Diagnostic {
+        let mut diagnostic = Diagnostic::new("a.py", Some(line));
+        diagnostic.rule = Some(rule.to_string());
+        diagnostic.message = Some(format!("{} message", rule));
+        diagnostic
+    }
+
+    #[test]
+    fn test_find_fixed() {
+        let base = [finding(3, "E501"), finding(4, "F401"), finding(20, "E302")];
+        let old_hunks: HashMap<_, _> = vec![("a.py".to_string(), vec![(2, 5)])]
+            .into_iter()
+            .collect();
+        // F401 moved to another line, E302 is untouched
+        let current: HashMap<_, _> = vec![(finding_key(&finding(9, "F401")), 1)]
+            .into_iter()
+            .collect();
+
+        let fixed = find_fixed(&base, &old_hunks, &current);
+        assert_eq!(fixed, [&base[0]]);
+        assert_eq!(
+            render_markdown(&fixed, Lang::En),
+            "### :tada: 1 existing issue(s) fixed on changed lines\n\n- `a.py:3` E501 message\n"
+        );
+    }
+}
@@ -0,0 +1,128 @@
+//! Repositories held entirely in memory, for tests and for embedders that compare
+//! file contents they already have.
+//!
+//! ```
+//! use diff_format::fixtures;
+//!
+//! let repo = fixtures::memory_repo()?;
+//! let base = fixtures::write_tree(&repo, [("src/a.py", "a\nb\n")])?;
+//! let head = fixtures::write_tree(&repo, [("src/a.py", "a\nB\n")])?;
+//! let filter = diff_format::DiffFilter::from_trees(&repo, &base, &head)?;
+//! assert!(filter.contains("src/a.py", 2));
+//! # Ok::<(), diff_format::Error>(())
+//! ```
+//!
+//! Tests that need files on disk write them to a [`TempDir`].
+
+use crate::error::{git, Result};
+use git2::{FileMode, Odb, Oid, Repository, Tree};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static TEMP_DIRS: AtomicUsize = AtomicUsize::new(0);
+
+/// A repository without a directory, whose objects live in a mempack backend
+pub fn memory_repo() -> Result<Repository> {
+    let in_memory = || {
+        let odb = Odb::new()?;
+        odb.add_new_mempack_backend(1000)?;
+        Repository::from_odb(odb)
+    };
+    in_memory().map_err(git("Can't create in-memory repository"))
+}
+
+#[derive(Default)]
+struct Directory<'a> {
+    files: BTreeMap<&'a str, &'a [u8]>,
+    directories: BTreeMap<&'a str, Directory<'a>>,
+}
+
+impl<'a> Directory<'a> {
+    fn insert(&mut self, path: &'a str, contents: &'a [u8]) {
+        match path.split_once('/') {
+            Some((directory, rest)) => self
+                .directories
+                .entry(directory)
+                .or_default()
+                .insert(rest, contents),
+            None => {
+                self.files.insert(path, contents);
+            }
+        }
+    }
+
+    fn write(&self, repo: &Repository) -> std::result::Result<Oid, git2::Error> {
+        let mut builder = repo.treebuilder(None)?;
+        for (name, contents) in &self.files {
+            builder.insert(name, repo.blob(contents)?, FileMode::Blob.into())?;
+        }
+        for (name, directory) in &self.directories {
+            builder.insert(name, directory.write(repo)?, FileMode::Tree.into())?;
+        }
+        builder.write()
+    }
+}
+
+/// Writes a tree holding `files`, given as `/`-separated paths and their contents
+pub fn write_tree<'r, 'a>(
+    repo: &'r Repository,
+    files: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Result<Tree<'r>> {
+    let mut root = Directory::default();
+    for (path, contents) in files {
+        root.insert(path, contents.as_bytes());
+    }
+    let oid = root.write(repo).map_err(git("Can't write tree"))?;
+    repo.find_tree(oid).map_err(git("Can't write tree"))
+}
+
+/// An empty directory under the system's temporary one, removed with what it holds on
+/// drop, so a test that fails halfway doesn't leave it behind
+#[derive(Debug)]
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// A directory named `diff-format-<name>-…`, unique to the process and the call
+    pub fn new(name: &str) -> io::Result<Self> {
+        let path = std::env::temp_dir().join(format!(
+            "diff-format-{}-{}-{}",
+            name,
+            std::process::id(),
+            TEMP_DIRS.fetch_add(1, Ordering::Relaxed)
+        ));
+        // Left by a process with the same id that was killed
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path)?;
+        Ok(TempDir { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
@@ -0,0 +1,149 @@
+use crate::diagnostic::Diagnostic;
+use std::collections::HashMap;
+
+/// Codes other tools report for ruff and flake8 (pycodestyle, pyflakes) rules the
+/// findings fold into
+const BUILTIN_ALIASES: &[(&str, &str)] = &[
+    ("C0301", "E501"), // pylint line-too-long
+    ("C0303", "W291"), // pylint trailing-whitespace
+    ("C0304", "W292"), // pylint missing-final-newline
+    ("C0321", "E701"), // pylint multiple-statements
+    ("W0611", "F401"), // pylint unused-import
+    ("W0612", "F841"), // pylint unused-variable
+    ("E0602", "F821"), // pylint undefined-variable
+];
+
+/// A finding kept so far, which equivalent ones from other tools fold into
+struct Folded {
+    index: usize,
+    text: String,
+    tools: Vec<String>,
+}
+
+/// Folds findings that several tools report for the same rule on the same line into
+/// the first one, for --fold-equivalent
+pub struct Folder<'a> {
+    /// Configured aliases, checked before the built-in ones
+    aliases: &'a HashMap<String, String>,
+    seen: HashMap<(String, Option<u32>, String), Folded>,
+}
+
+impl<'a> Folder<'a> {
+    pub fn new(aliases: &'a HashMap<String, String>) -> Self {
+        Folder {
+            aliases,
+            seen: HashMap::new(),
+        }
+    }
+
+    fn canonical<'r>(&'r self, rule: &'r str) -> &'r str {
+        self.aliases
+            .get(rule)
+            .map(String::as_str)
+            .or_else(|| {
+                BUILTIN_ALIASES
+                    .iter()
+                    .find(|(alias, _)| *alias == rule)
+                    .map(|(_, canonical)| *canonical)
+            })
+            .unwrap_or(rule)
+    }
+
+    /// Folds `diagnostic` into an equivalent finding of another tool in `matched`,
+    /// noting the agreeing tool in its message. Returns false when there is none, in
+    /// which case the caller is expected to push `diagnostic` onto `matched`
+    pub fn fold(&mut self, matched: &mut [Diagnostic], diagnostic: &Diagnostic) -> bool {
+        let (rule, tool) = match (&diagnostic.rule, &diagnostic.tool) {
+            (Some(rule), Some(tool)) => (rule, tool),
+            _ => return false,
+        };
+        let key = (
+            diagnostic.path.clone(),
+            diagnostic.line,
+            self.canonical(rule).to_string(),
+        );
+        match self.seen.get_mut(&key) {
+            Some(folded) if !folded.tools.contains(tool) => {
+                folded.tools.push(tool.clone());
+                let first = &mut matched[folded.index];
+                first.message = Some(format!(
+                    "{} (also reported by {})",
+                    folded.text,
+                    folded.tools[1..].join(", ")
+                ));
+                // Unset severities count as errors
+                first.severity = match (first.severity, diagnostic.severity) {
+                    (Some(first), Some(other)) => Some(first.max(other)),
+                    _ => None,
+                };
+                true
+            }
+            // The same tool reporting a rule twice is two findings
+            Some(_) => false,
+            None => {
+                self.seen.insert(
+                    key,
+                    Folded {
+                        index: matched.len(),
+                        text: diagnostic.text().to_string(),
+                        tools: vec![tool.clone()],
+                    },
+                );
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::{Diagnostic, Severity};
+    use crate::fold::Folder;
+    use std::collections::HashMap;
+
+    fn finding(tool: &str, line: u32, rule: &str) -> Diagnostic {
+        let mut diagnostic = Diagnostic::new("a.py", Some(line));
+        diagnostic.tool = Some(tool.to_string());
+        diagnostic.rule = Some(rule.to_string());
+        diagnostic.message = Some(format!("{} says {}", tool, rule));
+        diagnostic.severity = Some(Severity::Info);
+        diagnostic
+    }
+
+    #[test]
+    fn test_fold() {
+        let aliases = vec![("unused".to_string(), "F401".to_string())]
+            .into_iter()
+            .collect();
+        let mut folder = Folder::new(&aliases);
+        let mut matched = Vec::new();
+        let mut push = |diagnostic: Diagnostic| {
+            if !folder.fold(&mut matched, &diagnostic) {
+                matched.push(diagnostic);
+            }
+        };
+        push(finding("ruff", 1, "F401"));
+        push(finding("flake8", 1, "F401"));
+        let mut pylint = finding("pylint", 1, "W0611");
+        pylint.severity = Some(Severity::Warning);
+        push(pylint);
+        push(finding("custom", 1, "unused"));
+        // Another line, another rule, or the same tool again stay apart
+        push(finding("flake8", 2, "F401"));
+        push(finding("flake8", 1, "E501"));
+        push(finding("ruff", 1, "F401"));
+
+        assert_eq!(matched.len(), 4);
+        assert_eq!(
+            matched[0].message.as_deref(),
+            Some("ruff says F401 (also reported by flake8, pylint, custom)")
+        );
+        assert_eq!(matched[0].severity, Some(Severity::Warning));
+        assert_eq!(matched[3].message.as_deref(), Some("ruff says F401"));
+
+        let none = HashMap::new();
+        let mut folder = Folder::new(&none);
+        // Findings without a rule are never folded
+        assert!(!folder.fold(&mut [], &Diagnostic::new("a.py", Some(1))));
+    }
+}
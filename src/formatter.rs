@@ -0,0 +1,256 @@
+//! Unified diffs from formatters like `black --diff`, cut down to the fixes that touch
+//! changed lines.
+//!
+//! The formatter runs on the workdir, so the old side of its diff is the new side of
+//! ours. Each formatter hunk is kept whole when any line it removes, or any line next
+//! to where it inserts, is changed.
+
+use crate::diagnostic::{Diagnostic, Fix};
+use crate::paths;
+use crate::unified_diff::{diff_lines, header_path, DiffLine};
+use crate::{is_number_in_sorted_ranges, HunkRange};
+use std::collections::HashMap;
+
+struct Hunk {
+    old_start: u32,
+    old_len: u32,
+    new_len: u32,
+    /// Text after the second `@@`, usually the enclosing function
+    section: String,
+    body: Vec<String>,
+}
+
+impl Hunk {
+    /// Old lines the hunk removes or inserts next to
+    fn touched(&self) -> Vec<u32> {
+        let mut touched = Vec::new();
+        let mut old = self.old_start.max(1);
+        for line in &self.body {
+            match line.chars().next() {
+                Some('-') => {
+                    touched.push(old);
+                    old += 1;
+                }
+                Some('+') => touched.extend([old.saturating_sub(1).max(1), old]),
+                Some('\\') => {}
+                _ => old += 1,
+            }
+        }
+        touched
+    }
+
+    fn diagnostic(&self, path: &str) -> Diagnostic {
+        let removed: Vec<_> = self.changes('-').collect();
+        let added: Vec<_> = self.changes('+').collect();
+        let line = self.touched().into_iter().next();
+        let mut diagnostic = Diagnostic::new(path, line);
+        diagnostic.tool = Some("formatter".to_string());
+        diagnostic.message = Some(format!(
+            "would be reformatted ({} line(s) removed, {} added)",
+            removed.len(),
+            added.len()
+        ));
+        if let ([old], [new]) = (&removed[..], &added[..]) {
+            let (column, fix) = line_fix(old, new);
+            diagnostic.column = Some(column);
+            diagnostic.fix = Some(fix);
+        }
+        diagnostic.raw = format!("{}:{}: {}", path, line.unwrap_or(0), diagnostic.text());
+        diagnostic
+    }
+
+    fn changes(&self, sign: char) -> impl Iterator<Item = &str> {
+        self.body
+            .iter()
+            .filter_map(move |line| line.strip_prefix(sign))
+    }
+}
+
+/// The smallest edit turning `old` into `new`, as its 1-based column and a [`Fix`]
+fn line_fix(old: &str, new: &str) -> (u32, Fix) {
+    let old: Vec<char> = old.chars().collect();
+    let new: Vec<char> = new.chars().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    (
+        prefix as u32 + 1,
+        Fix {
+            length: (old.len() - prefix - suffix) as u32,
+            replacement: new[prefix..new.len() - suffix].iter().collect(),
+        },
+    )
+}
+
+pub struct FormatterDiff {
+    files: Vec<(String, Vec<Hunk>)>,
+}
+
+impl FormatterDiff {
+    pub fn parse<'a>(lines: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut files: Vec<(String, Vec<Hunk>)> = Vec::new();
+        let mut old_path = None;
+        for line in diff_lines(lines) {
+            let line = match line {
+                DiffLine::Header(line) => line,
+                DiffLine::Hunk(header) => {
+                    if let Some((_, hunks)) = files.last_mut() {
+                        hunks.push(Hunk {
+                            old_start: header.old_start,
+                            old_len: header.old_lines,
+                            new_len: header.new_lines,
+                            section: header.section.to_string(),
+                            body: Vec::new(),
+                        });
+                    }
+                    continue;
+                }
+                DiffLine::Body(line) => {
+                    if let Some(hunk) = files.last_mut().and_then(|(_, hunks)| hunks.last_mut()) {
+                        hunk.body.push(line.to_string());
+                    }
+                    continue;
+                }
+            };
+            if let Some(path) = line.strip_prefix("--- ") {
+                old_path = Some(header_path(path).to_string());
+            } else if let (Some(path), Some(old)) = (line.strip_prefix("+++ "), &old_path) {
+                // Git style `a/` and `b/` prefixes, only when both sides have them
+                let new = header_path(path);
+                let path = match (old.strip_prefix("a/"), new.strip_prefix("b/")) {
+                    (Some(old), Some(new)) if old == new => new,
+                    _ => old,
+                };
+                files.push((paths::normalize_path(path), Vec::new()));
+                old_path = None;
+            }
+        }
+        FormatterDiff { files }
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.files.iter().map(|(path, _)| path.as_str())
+    }
+
+    /// Drops the hunks that touch no changed line, and the files left without hunks
+    pub fn filter(&mut self, file_hunks: &HashMap<String, Vec<HunkRange>>) {
+        self.files.retain_mut(|(path, hunks)| {
+            let ranges = match file_hunks.get(path.as_str()) {
+                Some(ranges) => ranges,
+                None => return false,
+            };
+            hunks.retain(|hunk| {
+                hunk.touched()
+                    .into_iter()
+                    .any(|line| is_number_in_sorted_ranges(ranges, line))
+            });
+            !hunks.is_empty()
+        });
+    }
+
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.files
+            .iter()
+            .flat_map(|(path, hunks)| hunks.iter().map(move |hunk| hunk.diagnostic(path)))
+            .collect()
+    }
+
+    /// A patch `git apply` accepts, with new side positions shifted for dropped hunks
+    pub fn render(&self) -> String {
+        let mut patch = String::new();
+        for (path, hunks) in &self.files {
+            patch.push_str(&format!("--- a/{}\n+++ b/{}\n", path, path));
+            let mut offset = 0i64;
+            for hunk in hunks {
+                let new_start = if hunk.new_len == 0 {
+                    hunk.old_start as i64 + offset - 1
+                } else if hunk.old_len == 0 {
+                    hunk.old_start as i64 + offset + 1
+                } else {
+                    hunk.old_start as i64 + offset
+                };
+                patch.push_str(&format!(
+                    "@@ -{},{} +{},{} @@{}\n",
+                    hunk.old_start,
+                    hunk.old_len,
+                    new_start.max(0),
+                    hunk.new_len,
+                    hunk.section
+                ));
+                for line in &hunk.body {
+                    patch.push_str(line);
+                    patch.push('\n');
+                }
+                offset += hunk.new_len as i64 - hunk.old_len as i64;
+            }
+        }
+        patch
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::formatter::FormatterDiff;
+    use std::collections::HashMap;
+
+    const BLACK_DIFF: &str = "\
+--- src/a.py\t2024-01-01 00:00:00.000000+00:00
++++ src/a.py\t2024-01-01 00:00:01.000000+00:00
+@@ -1,3 +1,3 @@
+-import os,sys
++import os, sys
+ 
+ x = 1
+@@ -10,2 +10,3 @@ def f():
+     return 1
++
+ y = 2
+";
+
+    #[test]
+    fn test_filter_formatter_diff() {
+        let file_hunks: HashMap<_, _> = vec![("src/a.py".to_string(), vec![(1, 1)])]
+            .into_iter()
+            .collect();
+        let mut diff = FormatterDiff::parse(BLACK_DIFF.lines());
+        assert_eq!(diff.paths().collect::<Vec<_>>(), ["src/a.py"]);
+        diff.filter(&file_hunks);
+        assert_eq!(
+            diff.render(),
+            "--- a/src/a.py\n+++ b/src/a.py\n@@ -1,3 +1,3 @@\n-import os,sys\n+import os, sys\n \n x = 1\n"
+        );
+        let diagnostics = diff.diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, Some(1));
+        assert_eq!(diagnostics[0].column, Some(11));
+        let fix = diagnostics[0].fix.as_ref().unwrap();
+        assert_eq!((fix.length, fix.replacement.as_str()), (0, " "));
+        assert_eq!(fix.apply("import os,sys", 11), "import os, sys");
+
+        // Only the second hunk, whose new side moves up once the first is dropped
+        let file_hunks: HashMap<_, _> = vec![("src/a.py".to_string(), vec![(10, 10)])]
+            .into_iter()
+            .collect();
+        let mut diff = FormatterDiff::parse(BLACK_DIFF.lines());
+        diff.filter(&file_hunks);
+        assert!(diff.render().contains("@@ -10,2 +10,3 @@ def f():\n"));
+        assert_eq!(diff.diagnostics()[0].fix, None);
+
+        // A removed line starting with `-- ` is part of the hunk, not a file header
+        let lua = "--- a.lua\n+++ a.lua\n@@ -1,2 +1,2 @@\n a = 1\n--- comment\n+b = 22\n";
+        let file_hunks: HashMap<_, _> = vec![("a.lua".to_string(), vec![(2, 2)])]
+            .into_iter()
+            .collect();
+        let mut diff = FormatterDiff::parse(lua.lines());
+        diff.filter(&file_hunks);
+        assert_eq!(
+            diff.render(),
+            format!("--- a/a.lua\n+++ b/a.lua\n{}", &lua[20..])
+        );
+        assert_eq!(diff.diagnostics()[0].line, Some(2));
+    }
+}
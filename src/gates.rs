@@ -0,0 +1,405 @@
+//! The `gates` subcommand: several checks run in one process against the same hunk map,
+//! each with its own input and policy, and one summary and exit code for all of them.
+//!
+//! ```toml
+//! [[gate]]
+//! name = "lint"
+//! kind = "lint"
+//! report = "ruff.txt"
+//! fail_on = "warning"
+//!
+//! [[gate]]
+//! name = "todos"
+//! kind = "todos"
+//! ticket = '[A-Z]+-\d+'
+//! ```
+//!
+//! `lint` gates filter a saved lint or coverage report like the default command does;
+//! `todos`, `metrics` and `license` gates take the options of their subcommands.
+
+use crate::config::{Config, FailOn};
+use crate::diagnostic::{self, Diagnostic};
+use crate::expression::{tokenize, Token};
+use crate::i18n::{self, Lang, Message};
+use crate::input::InputFormat;
+use crate::license::{self, LicenseArgs};
+use crate::location::LocationResolver;
+use crate::metrics::{self, MetricsArgs};
+use crate::todos::{self, TodoArgs};
+use crate::waiver;
+use crate::{is_changed, HunkMap};
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Args, Debug)]
+pub struct GatesArgs {
+    /// Which gates must pass for the run to, as `&&`, `||` and `!` over gate names with
+    /// parentheses, e.g. `lint && (todos || license)`; by default all of them
+    #[arg(long, value_name = "EXPR")]
+    pass: Option<String>,
+}
+
+/// A `[[gate]]` table
+#[derive(Debug, Deserialize)]
+pub struct GateConfig {
+    /// How the gate is called in the summary and in `--pass`
+    pub name: String,
+    #[serde(flatten)]
+    pub check: Check,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Check {
+    Lint(LintGate),
+    Todos(TodoArgs),
+    Metrics(MetricsArgs),
+    License(LicenseArgs),
+}
+
+/// A saved report filtered like lint output on stdin
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LintGate {
+    pub report: PathBuf,
+    /// Formats to parse the report with, as `--format` takes them; `auto` by default
+    #[serde(default)]
+    pub format: Vec<String>,
+    /// The container of the report, as `--input-format` takes it
+    #[serde(default)]
+    pub input_format: InputFormat,
+    /// Least severe finding that fails the gate, unless a `[policy]` says otherwise
+    pub fail_on: Option<FailOn>,
+}
+
+/// What a gate found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub name: String,
+    pub findings: usize,
+    pub failed: bool,
+}
+
+/// What the gates share
+pub struct Shared<'a> {
+    pub config: &'a Config,
+    pub workdir: &'a Path,
+    pub file_hunks: &'a HunkMap,
+    /// The hunks before --hunk-bounds, whose added lines `todos` gates scan
+    pub raw_hunks: &'a HunkMap,
+    /// The files the diff adds, for `license` gates
+    pub added: &'a [String],
+}
+
+/// Filters the findings of a lint gate, printing those on changed lines
+fn lint(context: &Shared, gate: &LintGate, findings: Vec<Diagnostic>) -> Result<Outcome> {
+    let config = context.config;
+    let policies = config.policies()?;
+    let waivers = config.waivers()?;
+    let rollouts = config.rollouts()?;
+    let today = waiver::today();
+    let mut outcome = Outcome {
+        name: String::new(),
+        findings: 0,
+        failed: false,
+    };
+    for mut diagnostic in findings {
+        config.normalize(&mut diagnostic);
+        if let (None, Some(rule)) = (diagnostic.severity, &diagnostic.rule) {
+            diagnostic.severity = diagnostic::infer_severity(rule, &config.severity);
+        }
+        policies.escalate(&mut diagnostic);
+        let policy = config.match_policy(diagnostic.tool.as_deref());
+        if !is_changed(context.file_hunks, &diagnostic, policy) {
+            continue;
+        }
+        if waivers
+            .find(&diagnostic, today)
+            .is_some_and(|waiver| !waiver.expired(today))
+        {
+            continue;
+        }
+        println!("{}", diagnostic.raw);
+        outcome.findings += 1;
+        if rollouts.active(&diagnostic, today).is_none() {
+            outcome.failed |= policies
+                .fail_on(&diagnostic.path)
+                .or(gate.fail_on)
+                .unwrap_or(FailOn::Info)
+                .fails(diagnostic.effective_severity());
+        }
+    }
+    Ok(outcome)
+}
+
+/// Runs every gate in order, returning what each found and whether the run passes;
+/// `read_report` reads the findings of lint gates, which are resolved with `resolver`
+pub fn run(
+    args: &GatesArgs,
+    context: &Shared,
+    resolver: &mut LocationResolver,
+    mut read_report: impl FnMut(&LintGate) -> Result<Vec<Diagnostic>>,
+) -> Result<(Vec<Outcome>, bool)> {
+    let gates = &context.config.gate;
+    if gates.is_empty() {
+        bail!("No [[gate]] tables in the config");
+    }
+    for (index, gate) in gates.iter().enumerate() {
+        if gates[..index].iter().any(|other| other.name == gate.name) {
+            bail!("Two gates are named '{}'", gate.name);
+        }
+    }
+    // Checked before the gates run, which may take a while
+    let names: Vec<_> = gates.iter().map(|gate| gate.name.as_str()).collect();
+    let requirement = args
+        .pass
+        .as_deref()
+        .map(|pass| Requirement::parse(pass, &names))
+        .transpose()?;
+    let mut outcomes = Vec::new();
+    for gate in gates {
+        let with_count = |findings: usize| Outcome {
+            name: String::new(),
+            findings,
+            failed: findings > 0,
+        };
+        let outcome = match &gate.check {
+            Check::Lint(lint_gate) => {
+                let mut findings = read_report(lint_gate)?;
+                for diagnostic in &mut findings {
+                    resolver.resolve(diagnostic);
+                }
+                lint(context, lint_gate, findings)
+            }
+            Check::Todos(args) => {
+                todos::check(args, context.workdir, context.raw_hunks).map(with_count)
+            }
+            Check::Metrics(args) => {
+                metrics::check(args, context.workdir, context.file_hunks).map(with_count)
+            }
+            Check::License(args) => {
+                license::check(args, context.workdir, context.added).map(|findings| {
+                    for diagnostic in &findings {
+                        println!("{}", diagnostic.raw);
+                    }
+                    with_count(findings.len())
+                })
+            }
+        };
+        let outcome = outcome.with_context(|| format!("Gate '{}' failed to run", gate.name))?;
+        outcomes.push(Outcome {
+            name: gate.name.clone(),
+            ..outcome
+        });
+    }
+    let passed = passes(requirement.as_ref(), &outcomes);
+    Ok((outcomes, passed))
+}
+
+/// Which gates must pass, from `--pass`
+#[derive(Debug, PartialEq, Eq)]
+pub enum Requirement {
+    Gate(String),
+    Not(Box<Requirement>),
+    And(Box<Requirement>, Box<Requirement>),
+    Or(Box<Requirement>, Box<Requirement>),
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    names: &'a [&'a str],
+}
+
+impl Parser<'_> {
+    fn eat(&mut self, op: &str) -> bool {
+        let found =
+            matches!(self.tokens.get(self.position), Some(Token::Op(found)) if *found == op);
+        self.position += found as usize;
+        found
+    }
+
+    fn or(&mut self) -> Result<Requirement> {
+        let mut left = self.and()?;
+        while self.eat("||") {
+            left = Requirement::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Requirement> {
+        let mut left = self.unary()?;
+        while self.eat("&&") {
+            left = Requirement::And(Box::new(left), Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Requirement> {
+        if self.eat("!") {
+            return Ok(Requirement::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("(") {
+            let inner = self.or()?;
+            if !self.eat(")") {
+                bail!("expected ')'");
+            }
+            return Ok(inner);
+        }
+        match self.tokens.get(self.position) {
+            Some(Token::Ident(name)) if self.names.contains(&name.as_str()) => {
+                self.position += 1;
+                Ok(Requirement::Gate(name.clone()))
+            }
+            Some(Token::Ident(name)) => bail!("no gate is named '{}'", name),
+            _ => bail!("expected a gate name"),
+        }
+    }
+}
+
+impl Requirement {
+    /// Parses `text`, whose gate names must all be in `names`
+    pub fn parse(text: &str, names: &[&str]) -> Result<Self> {
+        let parse = || {
+            let mut parser = Parser {
+                tokens: tokenize(text)?,
+                position: 0,
+                names,
+            };
+            let requirement = parser.or()?;
+            if parser.position < parser.tokens.len() {
+                bail!("unexpected {:?}", parser.tokens[parser.position]);
+            }
+            Ok(requirement)
+        };
+        parse().with_context(|| format!("Invalid --pass '{}'", text))
+    }
+
+    pub fn passes(&self, passed: &HashMap<&str, bool>) -> bool {
+        match self {
+            Requirement::Gate(name) => passed[name.as_str()],
+            Requirement::Not(inner) => !inner.passes(passed),
+            Requirement::And(left, right) => left.passes(passed) && right.passes(passed),
+            Requirement::Or(left, right) => left.passes(passed) || right.passes(passed),
+        }
+    }
+}
+
+/// Whether the run passes with `outcomes`, by `--pass` or with every gate passing
+pub fn passes(requirement: Option<&Requirement>, outcomes: &[Outcome]) -> bool {
+    let passed: HashMap<_, _> = outcomes
+        .iter()
+        .map(|outcome| (outcome.name.as_str(), !outcome.failed))
+        .collect();
+    match requirement {
+        Some(requirement) => requirement.passes(&passed),
+        None => passed.values().all(|&passed| passed),
+    }
+}
+
+/// A table with a row per gate and the combined result, in `lang`
+pub fn render(outcomes: &[Outcome], passed: bool, lang: Lang) -> String {
+    let (gate, findings) = (lang.text(Message::Gate), lang.text(Message::Findings));
+    let width = outcomes
+        .iter()
+        .map(|outcome| i18n::display_width(&outcome.name))
+        .chain([i18n::display_width(gate)])
+        .max()
+        .unwrap_or_default();
+    let line = |name: &str, count: &str, result: &str| {
+        let count = i18n::pad(count, i18n::display_width(findings), true);
+        format!("{}  {}  {}\n", i18n::pad(name, width, false), count, result)
+    };
+    let mut text = line(gate, findings, lang.text(Message::Result));
+    for outcome in outcomes {
+        let result = match outcome.failed {
+            true => Message::Failed,
+            false => Message::Passed,
+        };
+        text += &line(
+            &outcome.name,
+            &outcome.findings.to_string(),
+            lang.text(result),
+        );
+    }
+    text += lang.text(match passed {
+        true => Message::GatesPassed,
+        false => Message::GatesFailed,
+    });
+    text + "\n"
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::Config;
+    use crate::gates::{passes, render, Check, Outcome, Requirement};
+    use crate::i18n::Lang;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_config() {
+        let config: Config = toml::from_str(
+            r#"
+            [[gate]]
+            name = "lint"
+            kind = "lint"
+            report = "ruff.txt"
+            format = ["ruff"]
+            fail_on = "error"
+
+            [[gate]]
+            name = "todos"
+            kind = "todos"
+            markers = ["TODO"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.gate.len(), 2);
+        assert!(matches!(&config.gate[0].check, Check::Lint(lint) if lint.format == ["ruff"]));
+        assert!(matches!(config.gate[1].check, Check::Todos(_)));
+        assert!(toml::from_str::<Config>(
+            "[[gate]]\nname = \"todos\"\nkind = \"todos\"\nmarker = [\"TODO\"]\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_requirement() {
+        let names = ["lint", "todos", "license"];
+        let requirement = Requirement::parse("lint && !(todos || license)", &names).unwrap();
+        let passed: HashMap<_, _> = vec![("lint", true), ("todos", false), ("license", false)]
+            .into_iter()
+            .collect();
+        assert!(requirement.passes(&passed));
+        let requirement = Requirement::parse("todos || !lint && license", &names).unwrap();
+        assert!(!requirement.passes(&passed));
+        assert!(Requirement::parse("lint && coverage", &names).is_err());
+        assert!(Requirement::parse("lint todos", &names).is_err());
+        assert!(Requirement::parse("(lint", &names).is_err());
+
+        let outcome = |name: &str, findings| Outcome {
+            name: name.to_string(),
+            findings,
+            failed: findings > 0,
+        };
+        let outcomes = [outcome("lint", 0), outcome("todos", 2)];
+        assert!(!passes(None, &outcomes));
+        let lint_only = Requirement::parse("lint", &["lint", "todos"]).unwrap();
+        assert!(passes(Some(&lint_only), &outcomes));
+        assert_eq!(
+            render(&outcomes, true, Lang::En),
+            "\
+gate   findings  result
+lint          0  passed
+todos         2  failed
+gates passed
+"
+        );
+        assert!(
+            render(&outcomes, false, Lang::De).ends_with("fehlgeschlagen\nGates fehlgeschlagen\n")
+        );
+    }
+}
@@ -0,0 +1,387 @@
+//! `generate`: files that wire diff-format into a repository's tooling, printed for
+//! the repository to commit. The command they run carries the `--config`, `--format`
+//! and `--input-format` of the `generate` invocation, and runs the config's
+//! `[[gate]]`s when no linter is given.
+
+use crate::config::Config;
+use crate::input::InputFormat;
+use crate::output::OutputFormat;
+use crate::parsers::FormatSpec;
+use anyhow::{bail, Result};
+use clap::{Args, Subcommand, ValueEnum};
+use serde_json::{json, Value};
+use std::fmt::Write;
+use std::path::Path;
+
+/// Where releases are downloaded from, as `<RELEASES>/download/<tag>/<asset>`
+const RELEASES: &str = "https://github.com/shustinm/diff-format/releases";
+
+#[derive(Args, Debug)]
+pub struct GenerateArgs {
+    #[command(subcommand)]
+    target: Target,
+}
+
+#[derive(Subcommand, Debug)]
+enum Target {
+    /// A `.pre-commit-hooks.yaml` with a hook filtering the linter's output to the
+    /// staged changes
+    PreCommitConfig(Wiring),
+    /// An `action.yml` composite GitHub Action installing a release and filtering the
+    /// linter's output to the changes of the pull request
+    Action(Wiring),
+    /// A GitHub Actions problem matcher annotating the findings of an --output-format,
+    /// with the workflow step registering it printed to stderr
+    ProblemMatcher(Matcher),
+}
+
+#[derive(Args, Debug)]
+struct Matcher {
+    /// The --output-format the matcher reads: vscode-problems or porcelain
+    #[arg(long, value_enum, default_value = "vscode-problems")]
+    format: OutputFormat,
+
+    /// Where the repository keeps the matcher, for the registration step
+    #[arg(
+        long,
+        value_name = "PATH",
+        default_value = ".github/diff-format-matcher.json"
+    )]
+    matcher_path: String,
+}
+
+#[derive(Args, Debug)]
+struct Wiring {
+    /// Lint command whose output is filtered, e.g. `ruff check .`; without one, the
+    /// config's `[[gate]]`s are run
+    #[arg(long, value_name = "COMMAND")]
+    linter: Option<String>,
+
+    /// The hook's id, or the action's name
+    #[arg(long, default_value = "diff-format")]
+    id: String,
+}
+
+/// The options of the `generate` invocation that are passed on
+pub struct Setup<'a> {
+    pub config_path: Option<&'a Path>,
+    pub config: &'a Config,
+    pub formats: &'a [FormatSpec],
+    pub input_format: InputFormat,
+}
+
+/// `text` as a single-quoted YAML scalar
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+/// `text` as a single-quoted shell word, unless it needs no quoting
+fn shell_word(text: &str) -> String {
+    let plain = !text.is_empty()
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=,:+".contains(c));
+    if plain {
+        text.to_string()
+    } else {
+        format!("'{}'", text.replace('\'', r"'\''"))
+    }
+}
+
+impl Wiring {
+    /// The diff-format command line, after the options given by `before`
+    fn command(&self, setup: &Setup, before: &[&str]) -> Result<String> {
+        let mut words: Vec<String> = vec!["diff-format".to_string()];
+        words.extend(before.iter().map(|word| word.to_string()));
+        if let Some(path) = setup.config_path {
+            words.push("--config".to_string());
+            words.push(shell_word(&path.to_string_lossy()));
+        }
+        let formats: Vec<_> = setup.formats.iter().map(FormatSpec::name).collect();
+        if formats != ["python"] {
+            words.push(format!("--format={}", shell_word(&formats.join(","))));
+        }
+        if setup.input_format != InputFormat::default() {
+            let value = setup.input_format.to_possible_value().unwrap();
+            words.push(format!("--input-format={}", value.get_name()));
+        }
+        match &self.linter {
+            Some(linter) => {
+                words.push("--".to_string());
+                words.push(linter.clone());
+            }
+            None if !setup.config.gate.is_empty() => words.push("gates".to_string()),
+            None => bail!("Give the --linter to run, or add [[gate]]s to the config"),
+        }
+        Ok(words.join(" "))
+    }
+
+    fn name(&self) -> String {
+        match &self.linter {
+            Some(linter) => format!("{} ({})", self.id, linter),
+            None => format!("{} (gates)", self.id),
+        }
+    }
+
+    fn pre_commit_config(&self, setup: &Setup) -> Result<String> {
+        let mut yaml = String::from("# Generated by `diff-format generate pre-commit-config`\n");
+        writeln!(yaml, "- id: {}", quote(&self.id))?;
+        writeln!(yaml, "  name: {}", quote(&self.name()))?;
+        writeln!(
+            yaml,
+            "  description: Lint findings on the staged changes only"
+        )?;
+        writeln!(
+            yaml,
+            "  entry: {}",
+            quote(&self.command(setup, &["--staged"])?)
+        )?;
+        yaml.push_str(concat!(
+            "  language: system\n",
+            "  pass_filenames: false\n",
+            "  always_run: true\n",
+            "  require_serial: true\n",
+            "  stages: [pre-commit]\n",
+        ));
+        Ok(yaml)
+    }
+
+    fn action(&self, setup: &Setup) -> Result<String> {
+        // Without a `ci` profile to say how to report, pull requests get annotations
+        let mut before = vec!["--gitref", "\"$gitref\""];
+        if !setup.config.profile.contains_key("ci") {
+            before.push("--output-format=github");
+        }
+        let command = self.command(setup, &before)?;
+        let mut yaml = String::from("# Generated by `diff-format generate action`\n");
+        writeln!(yaml, "name: {}", quote(&self.name()))?;
+        yaml.push_str(
+            "description: Report the lint findings on the lines a pull request changes\n",
+        );
+        yaml.push_str(concat!(
+            "inputs:\n",
+            "  version:\n",
+            "    description: The diff-format release to install, e.g. v0.1.0\n",
+            "    default: latest\n",
+            "  token:\n",
+            "    description: The token publishers post with\n",
+            "    default: ${{ github.token }}\n",
+            "runs:\n",
+            "  using: composite\n",
+            "  steps:\n",
+            "    - name: Install diff-format\n",
+            "      shell: bash\n",
+            "      env:\n",
+            "        VERSION: ${{ inputs.version }}\n",
+            "      run: |\n",
+            "        case \"$RUNNER_ARCH\" in\n",
+            "          X64) arch=x86_64 ;;\n",
+            "          ARM64) arch=aarch64 ;;\n",
+            "          *) echo \"No diff-format release for $RUNNER_ARCH\" >&2; exit 1 ;;\n",
+            "        esac\n",
+            "        case \"$RUNNER_OS\" in\n",
+            "          Linux) os=linux ;;\n",
+            "          macOS) os=macos ;;\n",
+            "          *) echo \"No diff-format release for $RUNNER_OS\" >&2; exit 1 ;;\n",
+            "        esac\n",
+        ));
+        writeln!(
+            yaml,
+            "        if [ \"$VERSION\" = latest ]; then url={0}/latest/download; \
+             else url={0}/download/$VERSION; fi",
+            RELEASES
+        )?;
+        yaml.push_str(concat!(
+            "        mkdir -p \"$RUNNER_TEMP/diff-format\"\n",
+            "        curl -fsSL \"$url/diff-format-$arch-$os\" -o \"$RUNNER_TEMP/diff-format/diff-format\"\n",
+            "        chmod +x \"$RUNNER_TEMP/diff-format/diff-format\"\n",
+            "        echo \"$RUNNER_TEMP/diff-format\" >> \"$GITHUB_PATH\"\n",
+            "    - name: Run diff-format\n",
+            "      shell: bash\n",
+            "      env:\n",
+            "        GITHUB_TOKEN: ${{ inputs.token }}\n",
+            "      run: |\n",
+            "        if [ -n \"$GITHUB_BASE_REF\" ]; then\n",
+            "          git fetch --no-tags --depth=1 origin \"$GITHUB_BASE_REF\"\n",
+            "          gitref=\"origin/$GITHUB_BASE_REF\"\n",
+            "        else\n",
+            "          gitref=auto\n",
+            "        fi\n",
+        ));
+        writeln!(yaml, "        {}", command)?;
+        Ok(yaml)
+    }
+}
+
+impl Matcher {
+    /// The pattern of the matcher, checked against the layout of the format
+    fn pattern(&self) -> Result<Value> {
+        match self.format {
+            OutputFormat::VscodeProblems => {
+                let vscode: Value =
+                    serde_json::from_str(include_str!("output/vscode-problem-matcher.json"))?;
+                Ok(vscode["pattern"].clone())
+            }
+            OutputFormat::Porcelain => Ok(json!({
+                "regexp": "^([^\\t]+)\\t(\\d+)\\t([^\\t]*)\\t(.*)$",
+                "file": 1,
+                "line": 2,
+                "code": 3,
+                "message": 4,
+            })),
+            OutputFormat::Github => bail!("--output-format=github annotates without a matcher"),
+            format => bail!(
+                "No problem matcher reads --output-format={}, use vscode-problems or porcelain",
+                format.to_possible_value().unwrap().get_name()
+            ),
+        }
+    }
+
+    fn render(&self) -> Result<String> {
+        let matcher = json!({
+            "problemMatcher": [{
+                "owner": "diff-format",
+                "severity": "error",
+                "pattern": [self.pattern()?],
+            }],
+        });
+        Ok(serde_json::to_string_pretty(&matcher)? + "\n")
+    }
+
+    fn registration(&self) -> String {
+        let format = self.format.to_possible_value().unwrap();
+        format!(
+            concat!(
+                "# Save the matcher as {0}, then register it before diff-format runs:\n",
+                "- name: Annotate diff-format findings\n",
+                "  run: echo \"::add-matcher::{0}\"\n",
+                "- run: diff-format --output-format={1} ...\n",
+            ),
+            self.matcher_path,
+            format.get_name()
+        )
+    }
+}
+
+impl GenerateArgs {
+    pub fn render(&self, setup: &Setup) -> Result<String> {
+        match &self.target {
+            Target::PreCommitConfig(wiring) => wiring.pre_commit_config(setup),
+            Target::Action(wiring) => wiring.action(setup),
+            Target::ProblemMatcher(matcher) => matcher.render(),
+        }
+    }
+
+    /// How to use what [`render`](Self::render) generates, when it is not a file of its
+    /// own kind that says
+    pub fn instructions(&self) -> Option<String> {
+        match &self.target {
+            Target::ProblemMatcher(matcher) => Some(matcher.registration()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::Config;
+    use crate::diagnostic::{Diagnostic, Severity};
+    use crate::generate::{shell_word, GenerateArgs, Matcher, Setup, Target, Wiring};
+    use crate::input::InputFormat;
+    use crate::output::{OutputFormat, RenderContext};
+    use regex::Regex;
+    use serde_json::Value;
+    use std::path::Path;
+
+    #[test]
+    fn test_generate() {
+        let config = Config::default();
+        let formats = ["ruff".parse().unwrap(), "typos".parse().unwrap()];
+        let setup = Setup {
+            config_path: Some(Path::new("ci/diff format.toml")),
+            config: &config,
+            formats: &formats,
+            input_format: InputFormat::Text,
+        };
+        let wiring = Wiring {
+            linter: Some("ruff check --output-format concise .".to_string()),
+            id: "lint".to_string(),
+        };
+        let hooks = GenerateArgs {
+            target: Target::PreCommitConfig(wiring),
+        }
+        .render(&setup)
+        .unwrap();
+        assert!(hooks.contains("- id: 'lint'\n"));
+        assert!(hooks.contains(
+            "  entry: 'diff-format --staged --config ''ci/diff format.toml'' \
+             --format=ruff,typos -- ruff check --output-format concise .'\n"
+        ));
+
+        let action = GenerateArgs {
+            target: Target::Action(Wiring {
+                linter: None,
+                id: "diff-format".to_string(),
+            }),
+        };
+        assert!(action.render(&setup).is_err());
+        let config: Config = toml::from_str(
+            "[[gate]]\nname = 'lint'\nkind = 'lint'\nreport = 'lint.txt'\n[profile.ci]\nquiet = true\n",
+        )
+        .unwrap();
+        let setup = Setup {
+            config_path: None,
+            config: &config,
+            formats: &["python".parse().unwrap()],
+            input_format: InputFormat::Sarif,
+        };
+        let yaml = action.render(&setup).unwrap();
+        assert!(
+            yaml.contains("        diff-format --gitref \"$gitref\" --input-format=sarif gates\n")
+        );
+        assert_eq!(shell_word("a,b"), "a,b");
+        assert_eq!(shell_word("it's"), r"'it'\''s'");
+    }
+
+    #[test]
+    fn test_problem_matcher() {
+        let config = Config::default();
+        let setup = Setup {
+            config_path: None,
+            config: &config,
+            formats: &["python".parse().unwrap()],
+            input_format: InputFormat::Text,
+        };
+        let mut diagnostic = Diagnostic::new("src/a b.py", Some(7));
+        diagnostic.column = Some(3);
+        diagnostic.rule = Some("W605".to_string());
+        diagnostic.severity = Some(Severity::Warning);
+        diagnostic.message = Some("invalid escape".to_string());
+        let generate = |format| GenerateArgs {
+            target: Target::ProblemMatcher(Matcher {
+                format,
+                matcher_path: ".github/matcher.json".to_string(),
+            }),
+        };
+        for format in [OutputFormat::VscodeProblems, OutputFormat::Porcelain] {
+            let matcher: Value =
+                serde_json::from_str(&generate(format).render(&setup).unwrap()).unwrap();
+            let pattern = &matcher["problemMatcher"][0]["pattern"][0];
+            let regex = Regex::new(pattern["regexp"].as_str().unwrap()).unwrap();
+            let output = format
+                .render(
+                    std::slice::from_ref(&diagnostic),
+                    &RenderContext::new(Path::new(".")),
+                )
+                .unwrap();
+            let captures = regex.captures(output.trim_end()).unwrap();
+            let group = |name: &str| &captures[pattern[name].as_u64().unwrap() as usize];
+            assert_eq!((group("file"), group("line")), ("src/a b.py", "7"));
+            assert!(group("message").contains("invalid escape"));
+        }
+        let instructions = generate(OutputFormat::Porcelain).instructions().unwrap();
+        assert!(instructions.contains("echo \"::add-matcher::.github/matcher.json\"\n"));
+        assert!(instructions.contains("--output-format=porcelain"));
+        assert!(generate(OutputFormat::Junit).render(&setup).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+//! Throwaway git repositories for end-to-end tests of the filter, behind the
+//! `test-harness` feature, for this crate and for CI glue built on it.
+//!
+//! ```
+//! use diff_format::harness::{Change, TestRepo};
+//! use diff_format::parsers::Format;
+//!
+//! let repo = TestRepo::new()?;
+//! repo.apply(&[Change::Write("src/a.py", "a = 1\nb = 2\n")])?;
+//! repo.commit("base")?;
+//! repo.apply(&[Change::Write("src/a.py", "a = 1\nb = 3\n")])?;
+//!
+//! let run = repo.run("master", &[Format::Python], "src/a.py:1:1: E1 old\nsrc/a.py:2:1: E2 new\n")?;
+//! run.assert_kept(&[("src/a.py", 2)]);
+//! assert_eq!(run.output, "src/a.py:2:1: E2 new\n");
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use crate::config::Config;
+use crate::diagnostic::Diagnostic;
+use crate::fixtures::TempDir;
+use crate::parsers::{Format, Parsers};
+use crate::session::{Decision, FilterSession};
+use crate::DiffFilter;
+use anyhow::{Context, Result};
+use git2::{IndexAddOption, Oid, Repository, RepositoryInitOptions, Signature};
+use std::fs;
+use std::path::Path;
+
+/// An edit to the workdir of a [`TestRepo`], with `/`-separated paths
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change<'a> {
+    /// Creates or overwrites a file, and its directories
+    Write(&'a str, &'a str),
+    Remove(&'a str),
+    Rename(&'a str, &'a str),
+}
+
+/// A repository in a temporary directory, removed on drop, whose first branch is the
+/// `master` the tool diffs against by default
+pub struct TestRepo {
+    repo: Repository,
+    dir: TempDir,
+}
+
+impl TestRepo {
+    pub fn new() -> Result<Self> {
+        let dir = TempDir::new("harness").context("Can't create test repository")?;
+        let repo = Repository::init_opts(&dir, RepositoryInitOptions::new().initial_head("master"))
+            .context("Can't create test repository")?;
+        Ok(TestRepo { repo, dir })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn repo(&self) -> &Repository {
+        &self.repo
+    }
+
+    pub fn apply(&self, changes: &[Change]) -> Result<&Self> {
+        for change in changes {
+            match *change {
+                Change::Write(path, contents) => {
+                    let path = self.dir.join(path);
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::write(&path, contents)
+                        .with_context(|| format!("Can't write {}", path.display()))?;
+                }
+                Change::Remove(path) => fs::remove_file(self.dir.join(path))
+                    .with_context(|| format!("Can't remove {}", path))?,
+                Change::Rename(from, to) => {
+                    let to = self.dir.join(to);
+                    if let Some(parent) = to.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::rename(self.dir.join(from), &to)
+                        .with_context(|| format!("Can't rename {}", from))?;
+                }
+            }
+        }
+        Ok(self)
+    }
+
+    /// Stages every change in the workdir, deletions included
+    pub fn stage(&self) -> Result<&Self> {
+        let mut index = self.repo.index()?;
+        index.add_all(["*"], IndexAddOption::DEFAULT, None)?;
+        index.update_all(["*"], None)?;
+        index.write()?;
+        Ok(self)
+    }
+
+    /// Stages everything and commits it on the current branch
+    pub fn commit(&self, message: &str) -> Result<Oid> {
+        self.stage()?;
+        let tree = self.repo.find_tree(self.repo.index()?.write_tree()?)?;
+        let signature = Signature::now("diff-format", "harness@example.com")?;
+        let parent = match self.repo.head() {
+            Ok(head) => Some(head.peel_to_commit()?),
+            Err(_) => None,
+        };
+        let parents: Vec<_> = parent.iter().collect();
+        let id = self.repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )?;
+        Ok(id)
+    }
+
+    /// Creates branch `name` at HEAD, e.g. to diff against once HEAD moves on
+    pub fn branch(&self, name: &str) -> Result<&Self> {
+        let head = self.repo.head()?.peel_to_commit()?;
+        self.repo.branch(name, &head, false)?;
+        Ok(self)
+    }
+
+    /// Filters `lint_output` against the workdir's changes relative to `gitref`, with
+    /// the repository's config if it has one. Like the tool, this ignores untracked
+    /// files, so [`stage`](Self::stage) added ones first
+    pub fn run(&self, gitref: &str, formats: &[Format], lint_output: &str) -> Result<Run> {
+        let config = Config::discover(None, &self.dir, false)?;
+        let parsers = Parsers::new(formats, config.extension_formats());
+        let mut session = FilterSession::new(DiffFilter::from_repo(&self.dir, gitref)?, parsers)
+            .with_config(&config);
+        let mut run = Run::default();
+        for line in lint_output.lines() {
+            let mut kept = false;
+            session.push_line(line, |decision| {
+                kept |= decision.is_kept();
+                run.decisions.push(decision);
+            });
+            if kept {
+                run.output.push_str(line);
+                run.output.push('\n');
+            }
+        }
+        Ok(run)
+    }
+}
+
+/// What a [`TestRepo::run`] decided
+#[derive(Debug, Default)]
+pub struct Run {
+    pub decisions: Vec<Decision>,
+    /// The lint lines the tool would echo, those with a kept finding
+    pub output: String,
+}
+
+impl Run {
+    pub fn kept(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.decisions
+            .iter()
+            .filter(|decision| decision.is_kept())
+            .map(Decision::diagnostic)
+    }
+
+    /// Panics unless the kept findings are exactly at `expected` paths and lines, in order
+    pub fn assert_kept(&self, expected: &[(&str, u32)]) {
+        let kept: Vec<_> = self
+            .kept()
+            .map(|diagnostic| (diagnostic.path.as_str(), diagnostic.line.unwrap_or(0)))
+            .collect();
+        assert_eq!(kept, expected, "kept findings differ");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::harness::{Change, TestRepo};
+    use crate::parsers::Format;
+
+    #[test]
+    fn test_harness() {
+        let repo = TestRepo::new().unwrap();
+        repo.apply(&[
+            Change::Write("a.py", "a\nb\nc\n"),
+            Change::Write("old.py", "x\n"),
+            Change::Write(".diff-format.toml", "[tool.python]\nmatch = \"file\"\n"),
+        ])
+        .unwrap();
+        repo.commit("base").unwrap();
+        repo.branch("base").unwrap();
+        repo.apply(&[Change::Write("a.py", "a\nB\nc\n")]).unwrap();
+        repo.commit("edit").unwrap();
+        repo.apply(&[
+            Change::Rename("old.py", "pkg/new.py"),
+            Change::Write("pkg/new.py", "x\ny\n"),
+            Change::Remove("a.py"),
+        ])
+        .unwrap()
+        .stage()
+        .unwrap();
+
+        let lint = "pkg/new.py:1:1: E1 x\na.py:3:1: E2 y\nc.py:1:1: E3 z\n";
+        // The file match policy from the config keeps the edited file's finding
+        let run = repo.run("base", &[Format::Python], lint).unwrap();
+        run.assert_kept(&[("pkg/new.py", 1)]);
+        assert_eq!(run.decisions.len(), 3);
+
+        let path = repo.path().to_path_buf();
+        drop(repo);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,357 @@
+//! Changed lines computed from git diffs.
+
+use crate::error::{git, retried, Error, Result};
+use crate::ranges::{self, HunkMap};
+use crate::HunkRange;
+use git2::{
+    Delta, Diff, DiffFile, DiffFindOptions, DiffHunk, DiffOptions, Patch, Repository, Tree,
+};
+use log::{debug, info};
+use std::collections::HashMap;
+
+pub fn get_tree<'a>(repo: &'a Repository, gitref: &str) -> Result<Tree<'a>> {
+    let gitref = repo
+        .revparse_single(gitref)
+        .map_err(git("Unable to parse gitref"))?;
+    gitref.peel_to_tree().map_err(git("Gitref is not a tree"))
+}
+
+/// The index as a tree, to diff only what is staged. Written like `git write-tree`
+/// does, which fails while the index has conflicts, but into a backend kept in memory
+/// ahead of `.git/objects`, which later writes through `repo` go to as well
+pub fn index_tree(repo: &Repository) -> Result<Tree<'_>> {
+    let odb = repo
+        .odb()
+        .map_err(git("Unable to open the object database"))?;
+    odb.add_new_mempack_backend(1000)
+        .map_err(git("Unable to keep the index tree in memory"))?;
+    let id = retried(
+        "Unable to write the index as a tree, does it have unresolved conflicts?",
+        || repo.index().and_then(|mut index| index.write_tree()),
+    )?;
+    repo.find_tree(id)
+        .map_err(git("Unable to read the index tree"))
+}
+
+/// Diffs `tree` against `head`, or the workdir if not given, limited to `pathspecs` when given.
+/// With `ignore_eol`, lines whose only change is their line ending or trailing
+/// whitespace don't count as changed
+pub fn get_diff<'a>(
+    repo: &'a Repository,
+    tree: &Tree<'a>,
+    head: Option<&Tree<'a>>,
+    pathspecs: Option<&[String]>,
+    ignore_eol: bool,
+) -> Result<Diff<'a>> {
+    // Prevent errors on untouched lines by disabling context lines
+    let mut options = DiffOptions::new();
+    options.context_lines(0);
+    // A CR is whitespace to xdiff, so this covers CRLF and LF flips too
+    options.ignore_whitespace_eol(ignore_eol);
+    if let Some(pathspecs) = pathspecs {
+        // Paths come from lint output, not the user, so don't treat them as globs
+        options.disable_pathspec_match(true);
+        for pathspec in pathspecs {
+            options.pathspec(pathspec);
+        }
+    }
+    let mut diff = retried("Unable to compute the diff", || match head {
+        Some(head) => repo.diff_tree_to_tree(Some(tree), Some(head), Some(&mut options)),
+        None => repo.diff_tree_to_workdir_with_index(Some(tree), Some(&mut options)),
+    })?;
+    // Pair deleted and added files, so a moved file's hunks are those of its edits
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))
+        .map_err(git("Unable to detect renames"))?;
+    Ok(diff)
+}
+
+/// The path of `file`, which findings name as a string
+fn file_path<'a>(file: &DiffFile<'a>) -> Result<&'a str> {
+    let path = file.path_bytes().unwrap_or_default();
+    std::str::from_utf8(path).map_err(|_| Error::NonUtf8Path(String::from_utf8_lossy(path).into()))
+}
+
+/// New-side line ranges of the hunks of each modified, renamed or added file, by its
+/// new path; an added file's single hunk covers all of it
+pub fn generate_hunkmap(diff: &Diff) -> Result<HunkMap> {
+    generate_hunkmap_with(diff, |path| info!("Analyzing '{}'", path))
+}
+
+/// [`generate_hunkmap`], calling `on_file` with the path of each file as it gets to it
+pub fn generate_hunkmap_with(diff: &Diff, mut on_file: impl FnMut(&str)) -> Result<HunkMap> {
+    let mut hunkmap = HashMap::new();
+    // Callbacks can only stop the iteration, so the error is kept for after it
+    let mut error = None;
+    let iterated = diff.foreach(
+        &mut |file, _| match file_path(&file.new_file()) {
+            Ok(path) => {
+                on_file(path);
+                true
+            }
+            Err(err) => {
+                error = Some(err);
+                false
+            }
+        },
+        None, // Ignore binary files
+        Some(&mut |file, hunk| match file.status() {
+            Delta::Modified | Delta::Renamed | Delta::Added => {
+                // The file callback checked the path
+                let path = file_path(&file.new_file()).unwrap_or_default();
+                let hunk_edges = hunk_range(&hunk);
+                debug!("Changes in lines {}..{}", hunk_edges.0, hunk_edges.1);
+                hunkmap
+                    .entry(path.into())
+                    .or_insert_with(Vec::new)
+                    .push((hunk_edges.0, hunk_edges.1));
+                true
+            }
+            _ => true,
+        }),
+        None, // Extrapolating line information from hunks is enough, no need for line callback
+    );
+    if let Some(err) = error {
+        return Err(err);
+    }
+    iterated.map_err(git("Issue when iterating over diff"))?;
+
+    Ok(hunkmap)
+}
+
+/// New paths of the files `diff` adds, renames excluded
+pub fn added_files(diff: &Diff) -> Vec<String> {
+    diff.deltas()
+        .filter(|delta| delta.status() == Delta::Added)
+        .filter_map(|delta| delta.new_file().path()?.to_str().map(str::to_string))
+        .collect()
+}
+
+fn hunk_range(hunk: &DiffHunk) -> HunkRange {
+    (hunk.new_start(), hunk.new_start() + hunk.new_lines())
+}
+
+/// Like [`generate_hunkmap`] for the diff between two trees, diffing blobs read
+/// straight from the object database. libgit2 can't otherwise produce patches for a
+/// repository without a directory, e.g. one held in memory
+pub fn tree_hunkmap<'a>(repo: &'a Repository, base: &Tree<'a>, head: &Tree<'a>) -> Result<HunkMap> {
+    let mut diff = repo
+        .diff_tree_to_tree(Some(base), Some(head), None)
+        .map_err(git("Unable to compute the diff"))?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))
+        .map_err(git("Unable to detect renames"))?;
+    let contents = |file: DiffFile| -> Result<Vec<u8>> {
+        if file.id().is_zero() {
+            return Ok(Vec::new());
+        }
+        let blob = repo
+            .find_blob(file.id())
+            .map_err(git("Unable to read a blob of the diff"))?;
+        Ok(blob.content().to_vec())
+    };
+    let mut hunkmap = HashMap::new();
+    for delta in diff.deltas() {
+        if !matches!(
+            delta.status(),
+            Delta::Modified | Delta::Renamed | Delta::Added
+        ) {
+            continue;
+        }
+        let (old, new) = (contents(delta.old_file())?, contents(delta.new_file())?);
+        let mut options = DiffOptions::new();
+        options.context_lines(0);
+        let patch = Patch::from_buffers(
+            &old,
+            delta.old_file().path(),
+            &new,
+            delta.new_file().path(),
+            Some(&mut options),
+        )
+        .map_err(git("Unable to diff the blobs"))?;
+        let path = file_path(&delta.new_file())?;
+        let ranges: Vec<_> = (0..patch.num_hunks())
+            .map(|index| patch.hunk(index).map(|(hunk, _)| hunk_range(&hunk)))
+            .collect::<std::result::Result<_, _>>()
+            .map_err(git("Unable to diff the blobs"))?;
+        if !ranges.is_empty() {
+            hunkmap.insert(path.to_string(), ranges);
+        }
+    }
+    Ok(hunkmap)
+}
+
+/// Hunks of the diff from `tree`, none when limited to an empty set of paths
+pub fn diff_hunks<'a>(
+    repo: &'a Repository,
+    tree: &Tree<'a>,
+    head: Option<&Tree<'a>>,
+    pathspecs: Option<&[String]>,
+    ignore_eol: bool,
+) -> Result<HunkMap> {
+    match pathspecs {
+        Some([]) => Ok(HashMap::new()),
+        _ => generate_hunkmap(&get_diff(repo, tree, head, pathspecs, ignore_eol)?),
+    }
+}
+
+/// Applies the extra --gitref and --exclude-ref trees to the hunks of the first one
+pub fn combine_refs<'a>(
+    repo: &'a Repository,
+    extra_trees: &[Tree<'a>],
+    excluded_trees: &[Tree<'a>],
+    head: Option<&Tree<'a>>,
+    pathspecs: Option<&[String]>,
+    ignore_eol: bool,
+    file_hunks: &mut HunkMap,
+) -> Result<()> {
+    for tree in extra_trees {
+        let hunks = diff_hunks(repo, tree, head, pathspecs, ignore_eol)?;
+        ranges::union(file_hunks, hunks);
+    }
+    for tree in excluded_trees {
+        let hunks = diff_hunks(repo, tree, head, pathspecs, ignore_eol)?;
+        ranges::exclude(file_hunks, &hunks);
+    }
+    Ok(())
+}
+
+pub fn ref_trees<'a>(repo: &'a Repository, refs: &[String]) -> Result<Vec<Tree<'a>>> {
+    refs.iter().map(|gitref| get_tree(repo, gitref)).collect()
+}
+
+/// Like `generate_hunkmap`, but with the exact old-side lines each hunk replaced
+pub fn generate_old_hunkmap(diff: &Diff) -> Result<HunkMap> {
+    let mut hunkmap = HashMap::new();
+    let mut error = None;
+    let iterated = diff.foreach(
+        &mut |_, _| true,
+        None,
+        Some(&mut |file, hunk| {
+            let status = file.status();
+            if matches!(status, Delta::Modified | Delta::Renamed) && hunk.old_lines() > 0 {
+                let path = match file_path(&file.old_file()) {
+                    Ok(path) => path,
+                    Err(err) => {
+                        error = Some(err);
+                        return false;
+                    }
+                };
+                hunkmap
+                    .entry(path.into())
+                    .or_insert_with(Vec::new)
+                    .push((hunk.old_start(), hunk.old_start() + hunk.old_lines() - 1));
+            }
+            true
+        }),
+        None,
+    );
+    if let Some(err) = error {
+        return Err(err);
+    }
+    iterated.map_err(git("Issue when iterating over diff"))?;
+    Ok(hunkmap)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::Error;
+    use crate::fixtures::TempDir;
+    use crate::hunks::{added_files, generate_hunkmap, get_diff, index_tree};
+    use git2::{Diff, Repository, Signature};
+    use std::fs;
+
+    #[test]
+    fn test_added_and_renamed() {
+        let patch = "\
+diff --git a/new.py b/new.py
+new file mode 100644
+index 0000000..2c0e5b4
+--- /dev/null
++++ b/new.py
+@@ -0,0 +1,3 @@
++a
++b
++c
+diff --git a/old.py b/moved.py
+similarity index 80%
+rename from old.py
+rename to moved.py
+index 1b2c3d4..5e6f7a8 100644
+--- a/old.py
++++ b/moved.py
+@@ -2 +2 @@
+-x
++y
+diff --git a/gone.py b/gone.py
+deleted file mode 100644
+index 1b2c3d4..0000000
+--- a/gone.py
++++ /dev/null
+@@ -1 +0,0 @@
+-x
+";
+        let diff = Diff::from_buffer(patch.as_bytes()).unwrap();
+        let hunks = generate_hunkmap(&diff).unwrap();
+        assert_eq!(hunks["new.py"], [(1, 4)]);
+        assert_eq!(hunks["moved.py"], [(2, 3)]);
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(added_files(&diff), ["new.py"]);
+
+        let patch =
+            b"diff --git a/\xff.py b/\xff.py\n--- a/\xff.py\n+++ b/\xff.py\n@@ -1 +1 @@\n-x\n+y\n";
+        let err = generate_hunkmap(&Diff::from_buffer(patch).unwrap()).unwrap_err();
+        assert!(matches!(err, Error::NonUtf8Path(path) if path == "\u{fffd}.py"));
+    }
+
+    #[test]
+    fn test_ignore_eol() {
+        let dir = TempDir::new("eol").unwrap();
+        let repo = Repository::init(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "a\nb\nc\nd\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path("a.txt".as_ref()).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("a", "a@example.com").unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "base", &tree, &[])
+            .unwrap();
+
+        fs::write(dir.join("a.txt"), "a\r\nB\r\nc  \r\nd\r\n").unwrap();
+        let hunks = |ignore_eol| {
+            generate_hunkmap(&get_diff(&repo, &tree, None, None, ignore_eol).unwrap()).unwrap()
+        };
+        assert_eq!(hunks(false)["a.txt"], [(1, 5)]);
+        assert_eq!(hunks(true)["a.txt"], [(2, 3)]);
+    }
+
+    #[test]
+    fn test_index_tree() {
+        let dir = TempDir::new("index").unwrap();
+        let repo = Repository::init(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "a\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path("a.txt".as_ref()).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        // As another process would see them
+        let objects = || {
+            let mut count = 0;
+            let repo = Repository::open(&dir).unwrap();
+            repo.odb()
+                .unwrap()
+                .foreach(|_| {
+                    count += 1;
+                    true
+                })
+                .unwrap();
+            count
+        };
+        let stored = objects();
+
+        fs::write(dir.join("a.txt"), "a\nb\n").unwrap();
+        index.add_path("a.txt".as_ref()).unwrap();
+        index.write().unwrap();
+        let staged = index_tree(&repo).unwrap();
+        let diff = get_diff(&repo, &tree, Some(&staged), None, false).unwrap();
+        assert_eq!(generate_hunkmap(&diff).unwrap()["a.txt"], [(2, 3)]);
+        // Only the staged blob was stored, by `add_path`
+        assert_eq!(objects(), stored + 1);
+    }
+}
@@ -0,0 +1,186 @@
+//! Translations of the summaries people read in CI logs, picked with `--lang` or from
+//! the locale. Findings, logs and machine-readable outputs stay as they are.
+
+use clap::ValueEnum;
+use std::env;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Lang {
+    #[default]
+    En,
+    Ja,
+    De,
+}
+
+/// A string with `{0}`, `{1}`... placeholders, in each language
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    File,
+    Shown,
+    PreExisting,
+    Waived,
+    Stale,
+    Total,
+    /// The run ID, and when it started
+    RunStarted,
+    /// The lines kept and read
+    TruncatedHead,
+    TruncatedTail,
+    TruncatedSample,
+    RaiseMaxInputLines,
+    /// The number of files matched as a whole
+    Degraded,
+    /// The score and the most it may be
+    Score,
+    Gate,
+    Findings,
+    Result,
+    Passed,
+    Failed,
+    GatesPassed,
+    GatesFailed,
+    /// The number of findings fixed
+    Fixed,
+}
+
+impl Message {
+    /// The English, Japanese and German texts
+    fn texts(self) -> [&'static str; 3] {
+        match self {
+            Message::File => ["file", "ファイル", "Datei"],
+            Message::Shown => ["shown", "表示", "gezeigt"],
+            Message::PreExisting => ["pre-existing", "既存", "bestehend"],
+            Message::Waived => ["waived", "免除", "ausgenommen"],
+            Message::Stale => ["stale", "範囲外", "veraltet"],
+            Message::Total => ["total", "合計", "gesamt"],
+            Message::RunStarted => [
+                "run {0} started {1}",
+                "実行 {0} 開始 {1}",
+                "Lauf {0} gestartet {1}",
+            ],
+            Message::TruncatedHead => [
+                "input truncated to the first {0} of {1} lines",
+                "入力を全 {1} 行のうち先頭の {0} 行に切り詰めました",
+                "Eingabe auf die ersten {0} von {1} Zeilen gekürzt",
+            ],
+            Message::TruncatedTail => [
+                "input truncated to the last {0} of {1} lines",
+                "入力を全 {1} 行のうち末尾の {0} 行に切り詰めました",
+                "Eingabe auf die letzten {0} von {1} Zeilen gekürzt",
+            ],
+            Message::TruncatedSample => [
+                "input truncated to a sample of {0} of {1} lines",
+                "入力を全 {1} 行から抽出した {0} 行に切り詰めました",
+                "Eingabe auf eine Stichprobe von {0} von {1} Zeilen gekürzt",
+            ],
+            Message::RaiseMaxInputLines => [
+                "raise --max-input-lines to filter all of it",
+                "すべてを絞り込むには --max-input-lines を増やしてください",
+                "--max-input-lines erhöhen, um alles zu filtern",
+            ],
+            Message::Degraded => [
+                "{0} file(s) over --max-files or --max-hunks-per-file matched as a whole",
+                "--max-files または --max-hunks-per-file を超えた {0} 件のファイルを全体で照合しました",
+                "{0} Datei(en) über --max-files oder --max-hunks-per-file als Ganzes abgeglichen",
+            ],
+            Message::Score => [
+                "score {0}, at most {1}",
+                "スコア {0}（上限 {1}）",
+                "Punktzahl {0}, höchstens {1}",
+            ],
+            Message::Gate => ["gate", "ゲート", "Gate"],
+            Message::Findings => ["findings", "指摘", "Befunde"],
+            Message::Result => ["result", "結果", "Ergebnis"],
+            Message::Passed => ["passed", "合格", "bestanden"],
+            Message::Failed => ["failed", "不合格", "fehlgeschlagen"],
+            Message::GatesPassed => ["gates passed", "ゲート合格", "Gates bestanden"],
+            Message::GatesFailed => ["gates failed", "ゲート不合格", "Gates fehlgeschlagen"],
+            Message::Fixed => [
+                "{0} existing issue(s) fixed on changed lines",
+                "変更行で既存の問題を {0} 件修正しました",
+                "{0} bestehende(s) Problem(e) auf geänderten Zeilen behoben",
+            ],
+        }
+    }
+}
+
+impl Lang {
+    /// The language of `LC_ALL`, `LC_MESSAGES` or `LANG`, the first one set, as in
+    /// `ja_JP.UTF-8`; English for any other
+    pub fn from_env() -> Self {
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        Self::from_locale(&locale)
+    }
+
+    fn from_locale(locale: &str) -> Self {
+        match locale.split(['_', '.', '@', '-']).next() {
+            Some("ja") => Lang::Ja,
+            Some("de") => Lang::De,
+            _ => Lang::En,
+        }
+    }
+
+    pub fn text(self, message: Message) -> &'static str {
+        message.texts()[self as usize]
+    }
+
+    /// `message` with its placeholders replaced by `values`, in order
+    pub fn format(self, message: Message, values: &[&dyn ToString]) -> String {
+        let mut text = self.text(message).to_string();
+        for (index, value) in values.iter().enumerate() {
+            text = text.replace(&format!("{{{}}}", index), &value.to_string());
+        }
+        text
+    }
+}
+
+/// Columns `text` takes in a terminal, with CJK characters twice as wide as others
+pub fn display_width(text: &str) -> usize {
+    text.chars()
+        .map(|c| match c as u32 {
+            0x1100..=0x115f
+            | 0x2e80..=0xa4cf
+            | 0xac00..=0xd7a3
+            | 0xf900..=0xfaff
+            | 0xfe30..=0xfe4f
+            | 0xff00..=0xff60
+            | 0xffe0..=0xffe6 => 2,
+            _ => 1,
+        })
+        .sum()
+}
+
+/// `text` padded with spaces to `width` columns, on the right unless `right_aligned`
+pub fn pad(text: &str, width: usize, right_aligned: bool) -> String {
+    let padding = " ".repeat(width.saturating_sub(display_width(text)));
+    if right_aligned {
+        padding + text
+    } else {
+        text.to_string() + &padding
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::i18n::{display_width, pad, Lang, Message};
+
+    #[test]
+    fn test_lang() {
+        assert_eq!(Lang::from_locale("ja_JP.UTF-8"), Lang::Ja);
+        assert_eq!(Lang::from_locale("de"), Lang::De);
+        assert_eq!(Lang::from_locale("C.UTF-8"), Lang::En);
+        assert_eq!(Lang::from_locale(""), Lang::En);
+        assert_eq!(
+            Lang::Ja.format(Message::TruncatedTail, &[&4, &10]),
+            "入力を全 10 行のうち末尾の 4 行に切り詰めました"
+        );
+        assert_eq!(Lang::De.text(Message::Total), "gesamt");
+        assert_eq!(display_width("合計"), 4);
+        assert_eq!(pad("合計", 5, true), " 合計");
+        assert_eq!(pad("a", 3, false), "a  ");
+    }
+}
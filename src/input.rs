@@ -0,0 +1,531 @@
+use crate::checkstyle;
+use crate::sanitize::sanitize;
+use crate::sarif::percent_decode;
+use clap::ValueEnum;
+use log::debug;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{self, BufRead};
+use std::sync::mpsc;
+use std::thread;
+
+/// Container the lint output arrives in, unwrapped before line parsing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InputFormat {
+    /// Plain lint output; the progress bars and spinners redrawn on its lines are
+    /// dropped
+    #[default]
+    Text,
+    /// Bazel Build Event Protocol JSON stream (`--build_event_json_file`)
+    BazelBep,
+    /// Unified diff of a formatter run on the workdir, e.g. `black --diff`; only the
+    /// fixes touching changed lines are kept
+    FormatterDiff,
+    /// A SARIF log, e.g. `eslint -f @microsoft/eslint-formatter-sarif`; re-emitted with
+    /// only the results on changed lines
+    Sarif,
+    /// A Checkstyle XML report, as also written by ktlint, detekt and
+    /// `eslint -f checkstyle`; re-emitted with only the errors on changed lines
+    Checkstyle,
+    /// A JSON array, such as a gitleaks report, parsed as one line per element
+    JsonArray,
+    /// lychee's `--format json` report, parsed as one line per broken link for the
+    /// `link-check` format
+    Lychee,
+    /// A jscpd JSON or PMD CPD XML report, parsed as one line per location of each
+    /// duplicated block for the `duplicates` format
+    Duplicates,
+    /// clang-tidy's `--export-fixes` YAML, parsed as one line per diagnostic for the
+    /// `clang-tidy-fixes` format
+    ClangTidyFixes,
+}
+
+pub type Lines<'a> = Box<dyn Iterator<Item = io::Result<String>> + 'a>;
+
+impl InputFormat {
+    pub fn lines<'a>(self, reader: impl BufRead + 'a) -> Lines<'a> {
+        match self {
+            InputFormat::Text => Box::new(reader.lines().flat_map(|line| {
+                let lines: Vec<io::Result<String>> = match line {
+                    Ok(line) => sanitize(line).into_iter().map(Ok).collect(),
+                    Err(err) => vec![Err(err)],
+                };
+                lines
+            })),
+            InputFormat::FormatterDiff | InputFormat::Sarif | InputFormat::Checkstyle => {
+                Box::new(reader.lines())
+            }
+            InputFormat::BazelBep => Box::new(reader.lines().flat_map(|line| {
+                let lines: Vec<io::Result<String>> = match line {
+                    Ok(line) => bep_lines(&line)
+                        .into_iter()
+                        .flat_map(sanitize)
+                        .map(Ok)
+                        .collect(),
+                    Err(err) => vec![Err(err)],
+                };
+                lines
+            })),
+            InputFormat::JsonArray => Box::new(json_array_lines(reader).into_iter()),
+            InputFormat::Lychee => Box::new(lychee_lines(reader).into_iter()),
+            InputFormat::Duplicates => Box::new(duplicates_lines(reader).into_iter()),
+            InputFormat::ClangTidyFixes => Box::new(clang_tidy_lines(reader).into_iter()),
+        }
+    }
+
+    /// Reads stdin on its own thread, buffering up to `capacity` lines, so the linter
+    /// writing to it isn't blocked while the diff is computed or slow outputs run
+    pub fn read_stdin(self, capacity: usize) -> Lines<'static> {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        thread::spawn(move || {
+            for line in self.lines(io::stdin().lock()) {
+                // The receiver is gone once matching is done or failed
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Box::new(receiver.into_iter())
+    }
+}
+
+/// The elements of the JSON array `reader` holds, each as compact JSON
+fn json_array_lines(mut reader: impl BufRead) -> Vec<io::Result<String>> {
+    let mut text = String::new();
+    if let Err(err) = reader.read_to_string(&mut text) {
+        return vec![Err(err)];
+    }
+    // An empty report is nothing found, like an empty text output
+    if text.trim().is_empty() {
+        return Vec::new();
+    }
+    match serde_json::from_str::<Vec<Value>>(&text) {
+        Ok(elements) => elements
+            .iter()
+            .map(|element| Ok(element.to_string()))
+            .collect(),
+        Err(err) => vec![Err(io::Error::new(io::ErrorKind::InvalidData, err))],
+    }
+}
+
+/// The broken links of the lychee report `reader` holds, each as compact JSON naming its
+/// source
+fn lychee_lines(mut reader: impl BufRead) -> Vec<io::Result<String>> {
+    let mut text = String::new();
+    if let Err(err) = reader.read_to_string(&mut text) {
+        return vec![Err(err)];
+    }
+    if text.trim().is_empty() {
+        return Vec::new();
+    }
+    let report: Value = match serde_json::from_str(&text) {
+        Ok(report) => report,
+        Err(err) => return vec![Err(io::Error::new(io::ErrorKind::InvalidData, err))],
+    };
+    let mut lines = Vec::new();
+    // Newer lychee versions name the map of broken links `error_map`
+    for map in ["fail_map", "error_map"] {
+        for (source, links) in report[map].as_object().into_iter().flatten() {
+            for link in links.as_array().into_iter().flatten() {
+                if let Some(link) = link.as_object() {
+                    let mut link = link.clone();
+                    link.insert("source".to_string(), Value::String(source.clone()));
+                    lines.push(Ok(Value::Object(link).to_string()));
+                }
+            }
+        }
+    }
+    lines
+}
+
+/// A copy of a duplicated block: its file and first and last lines
+type Location = (String, u64, u64);
+
+/// The copies of each block in a jscpd `--reporters json` report
+fn jscpd_blocks(text: &str) -> Result<Vec<(u64, Vec<Location>)>, String> {
+    let report: Value = serde_json::from_str(text).map_err(|err| err.to_string())?;
+    let copy = |file: &Value| -> Option<Location> {
+        let start = file["start"]
+            .as_u64()
+            .or_else(|| file["startLoc"]["line"].as_u64())?;
+        let end = file["end"]
+            .as_u64()
+            .or_else(|| file["endLoc"]["line"].as_u64())?;
+        Some((file["name"].as_str()?.to_string(), start, end))
+    };
+    let mut blocks = Vec::new();
+    for duplicate in report["duplicates"].as_array().into_iter().flatten() {
+        let copies: Vec<_> = [&duplicate["firstFile"], &duplicate["secondFile"]]
+            .iter()
+            .filter_map(|&file| copy(file))
+            .collect();
+        let lines = duplicate["lines"]
+            .as_u64()
+            .or_else(|| copies.first().map(|(_, start, end)| end + 1 - start))
+            .unwrap_or(0);
+        blocks.push((lines, copies));
+    }
+    Ok(blocks)
+}
+
+/// The copies of each block in a PMD CPD `--format xml` report
+fn cpd_blocks(text: &str) -> Result<Vec<(u64, Vec<Location>)>, String> {
+    let number = |attributes: &checkstyle::Attributes, name| {
+        checkstyle::attribute(attributes, name).and_then(|value| value.parse::<u64>().ok())
+    };
+    let mut blocks: Vec<(u64, Vec<Location>)> = Vec::new();
+    for tag in checkstyle::tags(text).map_err(|err| format!("{:#}", err))? {
+        match (tag.name, tag.closing) {
+            ("duplication", false) => {
+                blocks.push((number(&tag.attributes, "lines").unwrap_or(0), Vec::new()))
+            }
+            ("file", false) => {
+                let (Some((lines, copies)), Some(path), Some(start)) = (
+                    blocks.last_mut(),
+                    checkstyle::attribute(&tag.attributes, "path"),
+                    number(&tag.attributes, "line"),
+                ) else {
+                    continue;
+                };
+                // Older CPD versions only give the length of the block
+                let end = number(&tag.attributes, "endline")
+                    .unwrap_or_else(|| start + lines.saturating_sub(1));
+                copies.push((path.to_string(), start, end));
+            }
+            _ => {}
+        }
+    }
+    Ok(blocks)
+}
+
+/// Each copy of the duplicated blocks of the jscpd or CPD report `reader` holds, as
+/// compact JSON naming the other copies, so a copy on changed lines is reported with
+/// where it was duplicated from
+fn duplicates_lines(mut reader: impl BufRead) -> Vec<io::Result<String>> {
+    let mut text = String::new();
+    if let Err(err) = reader.read_to_string(&mut text) {
+        return vec![Err(err)];
+    }
+    let text = text.trim();
+    if text.is_empty() {
+        return Vec::new();
+    }
+    let blocks = if text.starts_with('<') {
+        cpd_blocks(text)
+    } else {
+        jscpd_blocks(text)
+    };
+    let blocks = match blocks {
+        Ok(blocks) => blocks,
+        Err(err) => return vec![Err(io::Error::new(io::ErrorKind::InvalidData, err))],
+    };
+    let mut lines = Vec::new();
+    for (length, copies) in blocks {
+        for (index, (file, start, end)) in copies.iter().enumerate() {
+            let counterparts: Vec<_> = copies
+                .iter()
+                .enumerate()
+                .filter(|&(other, _)| other != index)
+                .map(|(_, (file, start, end))| json!({"file": file, "start": start, "end": end}))
+                .collect();
+            let line = json!({
+                "file": file,
+                "start": start,
+                "end": end,
+                "lines": length,
+                "counterparts": counterparts,
+            });
+            lines.push(Ok(line.to_string()));
+        }
+    }
+    lines
+}
+
+/// A YAML scalar as clang-tidy writes one: plain, or quoted with `''` or backslash
+/// escapes
+fn yaml_scalar(value: &str) -> Value {
+    let value = value.trim();
+    if let Some(quoted) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+        return Value::String(quoted.replace("''", "'"));
+    }
+    if value.starts_with('"') {
+        if let Ok(value) = serde_json::from_str(value) {
+            return value;
+        }
+    }
+    match value.parse::<u64>() {
+        Ok(number) => Value::from(number),
+        Err(_) => Value::String(value.to_string()),
+    }
+}
+
+/// The diagnostics of the `--export-fixes` file `reader` holds, each as compact JSON
+/// with its name, level and message, file and offset.
+///
+/// Only the block layout clang-tidy writes is read: the message fields are those
+/// nested under `DiagnosticMessage`, or of the diagnostic itself before clang-tidy 9,
+/// and the replacements and notes are skipped.
+fn clang_tidy_lines(mut reader: impl BufRead) -> Vec<io::Result<String>> {
+    let mut text = String::new();
+    if let Err(err) = reader.read_to_string(&mut text) {
+        return vec![Err(err)];
+    }
+    let mut diagnostics: Vec<serde_json::Map<String, Value>> = Vec::new();
+    // The indentation of the current diagnostic's fields, and of its message's fields
+    let mut fields = None;
+    let mut message_fields = None;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        let mut indent = line.len() - trimmed.len();
+        let mut entry = trimmed;
+        if let Some(item) = trimmed.strip_prefix("- ") {
+            entry = item.trim_start();
+            indent = line.len() - entry.len();
+            if entry.starts_with("DiagnosticName:") {
+                diagnostics.push(serde_json::Map::new());
+                fields = Some(indent);
+                message_fields = None;
+            }
+        }
+        let (Some(diagnostic), Some(field_indent), Some((key, value))) =
+            (diagnostics.last_mut(), fields, entry.split_once(':'))
+        else {
+            continue;
+        };
+        if indent < field_indent {
+            // Past the diagnostics list
+            fields = None;
+        } else if indent == field_indent {
+            message_fields = None;
+            match key {
+                "DiagnosticMessage" => message_fields = Some(indent + 2),
+                "DiagnosticName" | "Level" | "Message" | "FilePath" | "FileOffset" => {
+                    diagnostic.insert(key.to_string(), yaml_scalar(value));
+                }
+                _ => {}
+            }
+        } else if Some(indent) == message_fields {
+            if let "Message" | "FilePath" | "FileOffset" = key {
+                diagnostic.insert(key.to_string(), yaml_scalar(value));
+            }
+        }
+    }
+    diagnostics
+        .into_iter()
+        .map(|diagnostic| Ok(Value::Object(diagnostic).to_string()))
+        .collect()
+}
+
+/// Extracts the compiler and linter output carried by one BEP event
+fn bep_lines(event: &str) -> Vec<String> {
+    let event: Value = match serde_json::from_str(event) {
+        Ok(event) => event,
+        Err(err) => {
+            debug!("Skipping malformed build event: {}", err);
+            return Vec::new();
+        }
+    };
+    // Split apart, as a chunk needn't end with a newline
+    let mut chunks = Vec::new();
+    // Progress events carry the console output of actions inline
+    for stream in ["stderr", "stdout"] {
+        if let Some(output) = event["progress"][stream].as_str() {
+            chunks.push(output.to_string());
+        }
+    }
+    // Completed actions point to files holding their output
+    for stream in ["stderr", "stdout"] {
+        if let Some(uri) = event["action"][stream]["uri"].as_str() {
+            let uri = percent_decode(uri);
+            match uri.strip_prefix("file://") {
+                Some(path) => match fs::read_to_string(path) {
+                    Ok(output) => chunks.push(output),
+                    Err(err) => debug!("Can't read action output {}: {}", path, err),
+                },
+                None => debug!("Skipping non-local action output {}", uri),
+            }
+        }
+    }
+    chunks
+        .iter()
+        .flat_map(|chunk| chunk.lines())
+        .map(|line| line.trim_end_matches('\r').to_string())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fixtures::TempDir;
+    use crate::input::InputFormat;
+    use serde_json::{json, Value};
+    use std::fs;
+
+    #[test]
+    fn test_bazel_bep() {
+        let stream = concat!(
+            r#"{"id":{"started":{}},"started":{"uuid":"x"}}"#,
+            "\n",
+            r#"{"id":{"progress":{"opaqueCount":1}},"progress":{"stderr":"\u001b[31mERROR:\u001b[0m build failed\nsrc/main.cc:10:5: error: unused variable 'x'\n"}}"#,
+            "\n",
+            "not json\n",
+        );
+        let lines: Vec<String> = InputFormat::BazelBep
+            .lines(stream.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            lines,
+            [
+                "\x1b[31mERROR:\x1b[0m build failed",
+                "src/main.cc:10:5: error: unused variable 'x'"
+            ]
+        );
+
+        // Chunks without a final newline, and an output file whose URI is escaped
+        let dir = TempDir::new("bep").unwrap();
+        fs::write(dir.join("my log.txt"), "c.cc:3:1: error: z").unwrap();
+        let uri = format!("file://{}/my%20log.txt", dir.display());
+        let event = json!({
+            "progress": {"stderr": "a.cc:1:1: error: x", "stdout": "b.cc:2:1: error: y"},
+            "action": {"stderr": {"uri": uri}},
+        });
+        let lines: Vec<String> = InputFormat::BazelBep
+            .lines(format!("{}\n", event).as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            lines,
+            [
+                "a.cc:1:1: error: x",
+                "b.cc:2:1: error: y",
+                "c.cc:3:1: error: z"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_json_array() {
+        let report = "[\n  {\n    \"File\": \"a.py\",\n    \"StartLine\": 2\n  },\n  {}\n]\n";
+        let lines: Vec<String> = InputFormat::JsonArray
+            .lines(report.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(lines, [r#"{"File":"a.py","StartLine":2}"#, "{}"]);
+        assert_eq!(InputFormat::JsonArray.lines(&b""[..]).count(), 0);
+        assert!(InputFormat::JsonArray
+            .lines(&b"{}"[..])
+            .next()
+            .unwrap()
+            .is_err());
+    }
+
+    #[test]
+    fn test_lychee() {
+        let report = r#"{"total": 2, "errors": 1, "fail_map": {"README.md": [
+            {"url": "https://x.test/gone", "status": {"text": "404 Not Found", "code": 404}}
+        ]}, "success_map": {"README.md": [{"url": "https://x.test"}]}}"#;
+        let lines: Vec<Value> = InputFormat::Lychee
+            .lines(report.as_bytes())
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            [serde_json::json!({
+                "url": "https://x.test/gone",
+                "status": {"text": "404 Not Found", "code": 404},
+                "source": "README.md"
+            })]
+        );
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let jscpd = r#"{"duplicates": [{"format": "javascript", "lines": 11,
+            "firstFile": {"name": "src/a.js", "start": 1, "end": 11},
+            "secondFile": {"name": "src/b.js", "start": 20, "end": 30}}],
+            "statistics": {}}"#;
+        let lines: Vec<Value> = InputFormat::Duplicates
+            .lines(jscpd.as_bytes())
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                serde_json::json!({"file": "src/a.js", "start": 1, "end": 11, "lines": 11,
+                    "counterparts": [{"file": "src/b.js", "start": 20, "end": 30}]}),
+                serde_json::json!({"file": "src/b.js", "start": 20, "end": 30, "lines": 11,
+                    "counterparts": [{"file": "src/a.js", "start": 1, "end": 11}]}),
+            ]
+        );
+
+        let cpd = r#"<?xml version="1.0" encoding="UTF-8"?>
+<pmd-cpd>
+   <duplication lines="4" tokens="30">
+      <file column="1" endcolumn="2" endline="13" line="10" path="A.java"/>
+      <file column="1" line="5" path="B.java"/>
+      <codefragment><![CDATA[if (a > b) { <b> }]]></codefragment>
+   </duplication>
+</pmd-cpd>"#;
+        let lines: Vec<Value> = InputFormat::Duplicates
+            .lines(cpd.as_bytes())
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[1],
+            serde_json::json!({"file": "B.java", "start": 5, "end": 8, "lines": 4,
+                "counterparts": [{"file": "A.java", "start": 10, "end": 13}]})
+        );
+        assert_eq!(InputFormat::Duplicates.lines(&b"\n"[..]).count(), 0);
+        assert!(InputFormat::Duplicates
+            .lines(&b"<pmd-cpd"[..])
+            .next()
+            .unwrap()
+            .is_err());
+    }
+
+    #[test]
+    fn test_clang_tidy_fixes() {
+        let fixes = r#"---
+MainSourceFile:  '/src/a.cpp'
+Diagnostics:
+  - DiagnosticName:  misc-unused-parameters
+    DiagnosticMessage:
+      Message:         'parameter ''x'' is unused'
+      FilePath:        '/src/a.cpp'
+      FileOffset:      12
+      Replacements:
+        - FilePath:        '/src/a.cpp'
+          Offset:          12
+          Length:          5
+          ReplacementText: ''
+    Level:           Warning
+    BuildDirectory:  '/src'
+  - DiagnosticName:  clang-diagnostic-error
+    Message:         "use of undeclared identifier \"y\""
+    FilePath:        b.cpp
+    FileOffset:      3
+    Replacements:    []
+...
+"#;
+        let lines: Vec<Value> = InputFormat::ClangTidyFixes
+            .lines(fixes.as_bytes())
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                serde_json::json!({"DiagnosticName": "misc-unused-parameters",
+                    "Message": "parameter 'x' is unused", "FilePath": "/src/a.cpp",
+                    "FileOffset": 12, "Level": "Warning"}),
+                serde_json::json!({"DiagnosticName": "clang-diagnostic-error",
+                    "Message": "use of undeclared identifier \"y\"", "FilePath": "b.cpp",
+                    "FileOffset": 3}),
+            ]
+        );
+        assert_eq!(InputFormat::ClangTidyFixes.lines(&b""[..]).count(), 0);
+    }
+}
@@ -0,0 +1,126 @@
+//! Changed lines that no diagnostic landed on, to spot linters that silently skipped
+//! new code.
+
+use crate::bounds::{Deletions, HunkBounds};
+use crate::diagnostic::Diagnostic;
+use crate::HunkRange;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Lines each tool reported any finding on, changed or not
+#[derive(Default)]
+pub struct Coverage {
+    lines: BTreeMap<String, HashMap<String, BTreeSet<u32>>>,
+}
+
+impl Coverage {
+    /// File-level findings don't show which lines the tool looked at, so they cover none
+    pub fn record(&mut self, diagnostic: &Diagnostic) {
+        if let Some(line) = diagnostic.line {
+            let tool = diagnostic.tool.clone().unwrap_or_default();
+            self.lines
+                .entry(tool)
+                .or_default()
+                .entry(diagnostic.path.clone())
+                .or_default()
+                .insert(line);
+        }
+    }
+
+    /// Prints one line per changed range without findings, for each of `tools` and
+    /// every tool that reported something. `file_hunks` are those of the diff, before
+    /// --hunk-bounds, of which only the lines the hunks add or modify are listed, as
+    /// with `--hunk-bounds exclusive --deletions none`
+    pub fn render(&self, tools: &[&str], file_hunks: &HashMap<String, Vec<HunkRange>>) -> String {
+        let mut all_tools: BTreeSet<&str> = tools.iter().copied().collect();
+        all_tools.extend(self.lines.keys().map(String::as_str));
+        let mut paths: Vec<_> = file_hunks.keys().collect();
+        paths.sort();
+
+        let empty = BTreeSet::new();
+        let mut rendered = String::new();
+        for tool in all_tools {
+            let files = self.lines.get(tool);
+            for path in &paths {
+                let covered = files.and_then(|files| files.get(*path)).unwrap_or(&empty);
+                let changed: Vec<_> = file_hunks[*path]
+                    .iter()
+                    .filter_map(|&range| HunkBounds::Exclusive.resolve(Deletions::None, range))
+                    .collect();
+                for (start, end) in uncovered(&changed, covered) {
+                    let lines = if start == end {
+                        start.to_string()
+                    } else {
+                        format!("{}-{}", start, end)
+                    };
+                    rendered.push_str(&format!("{}:{}: no {} diagnostics\n", path, lines, tool));
+                }
+            }
+        }
+        rendered
+    }
+}
+
+/// Splits `ranges` around the `covered` lines
+fn uncovered(ranges: &[HunkRange], covered: &BTreeSet<u32>) -> Vec<HunkRange> {
+    let mut result = Vec::new();
+    for &(start, end) in ranges {
+        let mut next = Some(start);
+        for &line in covered.range(start..=end) {
+            if let Some(next) = next.filter(|&next| line > next) {
+                result.push((next, line - 1));
+            }
+            next = line.checked_add(1);
+        }
+        if let Some(next) = next.filter(|&next| next <= end) {
+            result.push((next, end));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::Diagnostic;
+    use crate::invert::Coverage;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_render_uncovered() {
+        let file_hunks: HashMap<_, _> = vec![
+            ("a.py".to_string(), vec![(1, 6), (10, 11), (14, 14)]),
+            ("b.py".to_string(), vec![(3, 4)]),
+        ]
+        .into_iter()
+        .collect();
+        let mut coverage = Coverage::default();
+        for line in &[1, 3, 7] {
+            let mut diagnostic = Diagnostic::new("a.py", Some(*line));
+            diagnostic.tool = Some("python".to_string());
+            coverage.record(&diagnostic);
+        }
+        assert_eq!(
+            coverage.render(&["python", "typos"], &file_hunks),
+            "a.py:2: no python diagnostics\n\
+             a.py:4-5: no python diagnostics\n\
+             a.py:10: no python diagnostics\n\
+             b.py:3: no python diagnostics\n\
+             a.py:1-5: no typos diagnostics\n\
+             a.py:10: no typos diagnostics\n\
+             b.py:3: no typos diagnostics\n"
+        );
+
+        // The line after a hunk, and those of one-line and deleted hunks, aren't changed
+        let file_hunks: HashMap<_, _> = vec![
+            ("a.py".to_string(), vec![(2, 3)]),
+            ("new.py".to_string(), vec![(1, 2)]),
+            ("gone.py".to_string(), vec![(4, 4)]),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(
+            Coverage::default().render(&["typos"], &file_hunks),
+            "a.py:2: no typos diagnostics\n\
+             new.py:1: no typos diagnostics\n"
+        );
+    }
+}
@@ -0,0 +1,264 @@
+//! The language of each changed file, for `language` in `--policy` expressions and
+//! the `files` subcommand.
+//!
+//! A `linguist-language` attribute in `.gitattributes` wins, as on GitHub; otherwise
+//! a shebang names the interpreter of extensionless scripts, and well-known file names
+//! and extensions the rest. Names are spelled as linguist spells them, e.g. `C++`.
+
+use crate::ranges::HunkMap;
+use clap::Args;
+use git2::{AttrCheckFlags, Repository};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// The language of changed files, by path
+pub type Languages = HashMap<String, String>;
+
+/// How much of a file is read to find its shebang
+const SHEBANG_LENGTH: u64 = 256;
+
+const FILE_NAMES: &[(&str, &str)] = &[
+    ("BUILD", "Starlark"),
+    ("BUILD.bazel", "Starlark"),
+    ("CMakeLists.txt", "CMake"),
+    ("Dockerfile", "Dockerfile"),
+    ("GNUmakefile", "Makefile"),
+    ("Gemfile", "Ruby"),
+    ("Jenkinsfile", "Groovy"),
+    ("Makefile", "Makefile"),
+    ("Rakefile", "Ruby"),
+    ("WORKSPACE", "Starlark"),
+    ("makefile", "Makefile"),
+];
+
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("bash", "Shell"),
+    ("bzl", "Starlark"),
+    ("c", "C"),
+    ("cc", "C++"),
+    ("cjs", "JavaScript"),
+    ("cmake", "CMake"),
+    ("cpp", "C++"),
+    ("cs", "C#"),
+    ("css", "CSS"),
+    ("cxx", "C++"),
+    ("dart", "Dart"),
+    ("ex", "Elixir"),
+    ("exs", "Elixir"),
+    ("go", "Go"),
+    ("groovy", "Groovy"),
+    ("h", "C"),
+    ("hh", "C++"),
+    ("hpp", "C++"),
+    ("hs", "Haskell"),
+    ("htm", "HTML"),
+    ("html", "HTML"),
+    ("java", "Java"),
+    ("js", "JavaScript"),
+    ("json", "JSON"),
+    ("jsx", "JavaScript"),
+    ("kt", "Kotlin"),
+    ("kts", "Kotlin"),
+    ("lua", "Lua"),
+    ("markdown", "Markdown"),
+    ("md", "Markdown"),
+    ("mjs", "JavaScript"),
+    ("php", "PHP"),
+    ("pl", "Perl"),
+    ("pm", "Perl"),
+    ("proto", "Protocol Buffer"),
+    ("py", "Python"),
+    ("pyi", "Python"),
+    ("r", "R"),
+    ("rb", "Ruby"),
+    ("rs", "Rust"),
+    ("scala", "Scala"),
+    ("scss", "SCSS"),
+    ("sh", "Shell"),
+    ("sql", "SQL"),
+    ("swift", "Swift"),
+    ("tf", "HCL"),
+    ("toml", "TOML"),
+    ("ts", "TypeScript"),
+    ("tsx", "TSX"),
+    ("vue", "Vue"),
+    ("xml", "XML"),
+    ("yaml", "YAML"),
+    ("yml", "YAML"),
+    ("zsh", "Shell"),
+];
+
+const INTERPRETERS: &[(&str, &str)] = &[
+    ("bash", "Shell"),
+    ("dash", "Shell"),
+    ("deno", "TypeScript"),
+    ("ksh", "Shell"),
+    ("lua", "Lua"),
+    ("node", "JavaScript"),
+    ("nodejs", "JavaScript"),
+    ("perl", "Perl"),
+    ("php", "PHP"),
+    ("python", "Python"),
+    ("Rscript", "R"),
+    ("ruby", "Ruby"),
+    ("sh", "Shell"),
+    ("zsh", "Shell"),
+];
+
+fn lookup(table: &[(&str, &'static str)], key: &str) -> Option<&'static str> {
+    table
+        .iter()
+        .find(|(name, _)| *name == key)
+        .map(|&(_, language)| language)
+}
+
+/// The language a `#!` line runs its script with, ignoring `env` and versions as in
+/// `#!/usr/bin/env -S python3.12 -u`
+fn shebang_language(head: &str) -> Option<&'static str> {
+    let line = head.strip_prefix("#!")?.lines().next()?;
+    let mut words = line.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = words.find(|word| !word.starts_with('-'))?;
+    }
+    lookup(
+        INTERPRETERS,
+        program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.'),
+    )
+}
+
+/// The language of `path` by its shebang, read from `head`, its name or its extension
+pub fn detect(path: &str, head: &str) -> Option<&'static str> {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let extension = name
+        .rsplit_once('.')
+        .filter(|(stem, _)| !stem.is_empty())
+        .map(|(_, extension)| extension.to_ascii_lowercase());
+    shebang_language(head)
+        .or_else(|| lookup(FILE_NAMES, name))
+        .or_else(|| lookup(EXTENSIONS, extension.as_deref()?))
+}
+
+/// The start of the file at `path`, when it may be a script
+fn head(path: &Path) -> String {
+    let mut head = Vec::new();
+    let read = File::open(path).and_then(|file| file.take(SHEBANG_LENGTH).read_to_end(&mut head));
+    match read {
+        Ok(_) if head.starts_with(b"#!") => String::from_utf8_lossy(&head).into_owned(),
+        _ => String::new(),
+    }
+}
+
+/// The language of each changed file in `file_hunks`, read from `root`; `repo` gives
+/// the `linguist-language` attributes
+pub fn detect_all(repo: Option<&Repository>, root: &Path, file_hunks: &HunkMap) -> Languages {
+    file_hunks
+        .keys()
+        .filter_map(|path| {
+            let attribute = repo.and_then(|repo| {
+                let flags = AttrCheckFlags::FILE_THEN_INDEX;
+                repo.get_attr(Path::new(path), "linguist-language", flags)
+                    .ok()
+                    .flatten()
+                    .map(str::to_string)
+            });
+            let language =
+                attribute.or_else(|| detect(path, &head(&root.join(path))).map(str::to_string))?;
+            Some((path.clone(), language))
+        })
+        .collect()
+}
+
+#[derive(Args, Debug)]
+pub struct FilesArgs {
+    /// Print JSON lines instead of text
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Serialize)]
+struct ListedFile<'a> {
+    path: &'a str,
+    language: Option<&'a str>,
+    hunks: usize,
+}
+
+/// Lists the changed files of `file_hunks` with their language and number of hunks
+pub fn list(args: &FilesArgs, file_hunks: &HunkMap, languages: &Languages) -> String {
+    let mut paths: Vec<_> = file_hunks.keys().collect();
+    paths.sort();
+    let mut output = String::new();
+    for path in paths {
+        let file = ListedFile {
+            path,
+            language: languages.get(path).map(String::as_str),
+            hunks: file_hunks[path].len(),
+        };
+        if args.json {
+            output.push_str(&serde_json::to_string(&file).unwrap());
+            output.push('\n');
+            continue;
+        }
+        writeln!(
+            output,
+            "{}\t{}\t{} hunk(s)",
+            path,
+            file.language.unwrap_or("unknown"),
+            file.hunks
+        )
+        .unwrap();
+    }
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fixtures::TempDir;
+    use crate::language::{detect, detect_all, list, FilesArgs};
+    use std::collections::HashMap;
+    use std::fs;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect("src/main.rs", ""), Some("Rust"));
+        assert_eq!(detect("include/a.HPP", ""), Some("C++"));
+        assert_eq!(detect("docker/Dockerfile", ""), Some("Dockerfile"));
+        assert_eq!(
+            detect("bin/tool", "#!/usr/bin/env -S python3.12 -u\n"),
+            Some("Python")
+        );
+        assert_eq!(
+            detect("run.py", "#!/bin/bash\nexec python\n"),
+            Some("Shell")
+        );
+        assert_eq!(detect(".rs", ""), None);
+        assert_eq!(detect("LICENSE", ""), None);
+        assert_eq!(detect("bin/tool", "#!/usr/bin/weird\n"), None);
+    }
+
+    #[test]
+    fn test_detect_all() {
+        let dir = TempDir::new("language").unwrap();
+        fs::create_dir_all(dir.join("bin")).unwrap();
+        fs::write(dir.join("bin/tool"), "#!/bin/sh\necho\n").unwrap();
+        let file_hunks: HashMap<_, _> = vec![
+            ("bin/tool".to_string(), vec![(1, 2)]),
+            ("a.py".to_string(), vec![(1, 1), (4, 5)]),
+            ("NOTICE".to_string(), vec![(3, 3)]),
+        ]
+        .into_iter()
+        .collect();
+        let languages = detect_all(None, &dir, &file_hunks);
+        assert_eq!(languages["bin/tool"], "Shell");
+        assert_eq!(languages["a.py"], "Python");
+        assert!(!languages.contains_key("NOTICE"));
+        assert_eq!(
+            list(&FilesArgs { json: false }, &file_hunks, &languages),
+            "NOTICE\tunknown\t1 hunk(s)\na.py\tPython\t2 hunk(s)\nbin/tool\tShell\t1 hunk(s)\n"
+        );
+    }
+}
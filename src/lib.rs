@@ -0,0 +1,202 @@
+//! Diff-aware filtering of lint output: keep only the findings on lines a change
+//! touched.
+//!
+//! The `diff-format` binary wraps this with a command line; other tools can compute
+//! the changed lines themselves and ask about individual locations:
+//!
+//! ```no_run
+//! use diff_format::DiffFilter;
+//!
+//! let filter = DiffFilter::from_repo(".", "origin/main")?;
+//! if filter.contains("src/main.rs", 42) {
+//!     println!("line 42 changed");
+//! }
+//! # Ok::<(), diff_format::Error>(())
+//! ```
+//!
+//! [`FilterSession`] takes lint output a line at a time, for embedders that own the
+//! input loop.
+
+pub mod attribution;
+pub mod audit;
+pub mod autofix;
+pub mod baseline;
+pub mod bisect;
+pub mod bounds;
+pub mod cache;
+pub mod checkstyle;
+pub mod cherry;
+pub mod ci;
+pub mod codeowners;
+pub mod config;
+pub mod contains;
+pub mod demux;
+#[cfg(feature = "dependency-rules")]
+pub mod deps;
+pub mod drift;
+pub mod editorconfig;
+pub mod error;
+pub mod exec;
+#[cfg(feature = "tree-sitter")]
+pub mod expand;
+pub mod export;
+pub mod expression;
+pub mod filter;
+pub mod fixed;
+pub mod fixtures;
+pub mod fold;
+pub mod formatter;
+pub mod gates;
+pub mod generate;
+#[cfg(feature = "test-harness")]
+pub mod harness;
+pub mod hunks;
+pub mod i18n;
+pub mod input;
+pub mod invert;
+pub mod language;
+pub mod license;
+pub mod limits;
+pub mod links;
+pub mod location;
+pub mod merge;
+pub mod metrics;
+pub mod normalize;
+pub mod notes;
+pub mod notify;
+pub mod output;
+pub mod parallel;
+pub mod partial_clone;
+pub mod progress;
+pub mod proximity;
+pub mod publish;
+pub mod pytest;
+pub mod redact;
+pub mod regression;
+pub mod remote_base;
+pub mod remote_pr;
+pub mod resume;
+pub mod rollout;
+pub mod rule_docs;
+pub mod run;
+pub mod sanitize;
+pub mod sarif;
+pub mod score;
+pub mod serve;
+pub mod session;
+pub mod show;
+pub mod shuffle;
+pub mod sign;
+pub mod since;
+pub mod staging;
+pub mod stale;
+pub mod state;
+#[cfg(feature = "store")]
+pub mod store;
+pub mod submodules;
+pub mod summary;
+pub mod todos;
+pub mod totals;
+#[cfg(feature = "store")]
+pub mod trends;
+pub mod truncate;
+pub mod unified_diff;
+pub mod update;
+pub mod waiver;
+
+pub use diff_format_core::{
+    diagnostic, embedded, fingerprint, manifest, matcher, parsers, paths, ranges,
+};
+pub use diff_format_core::{
+    is_changed, is_number_in_sorted_ranges, overlaps_sorted_ranges, HunkMap, HunkRange,
+};
+pub use error::Error;
+pub use filter::DiffFilter;
+pub use session::{Decision, FilterSession};
+
+const ESC: char = '\x1b';
+const BEL: char = '\x07';
+/// 8-bit forms of `ESC [`, `ESC ]` and `ESC \`
+const CSI: char = '\u{9b}';
+const OSC: char = '\u{9d}';
+const ST: char = '\u{9c}';
+
+/// Skips a control string up to and including its terminator: BEL, `ESC \` or ST
+fn skip_control_string(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    while let Some(c) = chars.next() {
+        match c {
+            BEL | ST => return,
+            ESC if chars.peek() == Some(&'\\') => {
+                chars.next();
+                return;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Skips the parameters and intermediates of a CSI sequence up to its final byte
+fn skip_csi(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    for c in chars.by_ref() {
+        if ('\x40'..='\x7e').contains(&c) {
+            return;
+        }
+    }
+}
+
+/// Strips the terminal escapes linters emit when forced to color their output: colors
+/// and cursor moves (CSI), hyperlinks and titles (OSC, e.g. the OSC 8 links ruff and
+/// eslint print around paths) and other control strings, in both their `ESC` and 8-bit
+/// forms. The text of a hyperlink is kept
+pub fn remove_ansi_colors(text: &str) -> String {
+    if !text.contains([ESC, CSI, OSC]) {
+        return text.to_string();
+    }
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            CSI => skip_csi(&mut chars),
+            OSC => skip_control_string(&mut chars),
+            ESC => match chars.next() {
+                Some('[') => skip_csi(&mut chars),
+                // OSC, DCS, SOS, PM and APC
+                Some(']' | 'P' | 'X' | '^' | '_') => skip_control_string(&mut chars),
+                // Intermediates, e.g. the `(` of a charset selection, then a final byte
+                Some(' '..='/') => {
+                    while chars.next_if(|c| (' '..='/').contains(c)).is_some() {}
+                    chars.next();
+                }
+                _ => {}
+            },
+            _ => stripped.push(c),
+        }
+    }
+    stripped
+}
+
+#[cfg(test)]
+mod test {
+    use crate::remove_ansi_colors;
+
+    #[test]
+    fn test_remove_ansi_colors() {
+        assert_eq!(
+            remove_ansi_colors("\x1b[1ma.py\x1b[0m:\x1b[36m3\x1b[0m: E1"),
+            "a.py:3: E1"
+        );
+        // OSC 8 hyperlinks terminated by ST or BEL
+        assert_eq!(
+            remove_ansi_colors(
+                "\x1b]8;;file:///src/a.py\x1b\\a.py\x1b]8;;\x1b\\:1:2: \x1b]8;id=1;https://x\x07F401\x1b]8;;\x07"
+            ),
+            "a.py:1:2: F401"
+        );
+        // Cursor moves, line erasing, charset selection and 8-bit forms
+        assert_eq!(
+            remove_ansi_colors("\x1b[2K\x1b[1G\x1b[?25la.py\x1b(B:\u{9b}31m1\u{9d}0;title\u{9c}"),
+            "a.py:1"
+        );
+        assert_eq!(remove_ansi_colors("a.py:1: plain"), "a.py:1: plain");
+    }
+}
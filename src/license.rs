@@ -0,0 +1,160 @@
+//! The `license` gate: files the diff adds must start with the header of a template,
+//! reported as file-level findings like lint output.
+
+use crate::config::compile_glob;
+use crate::diagnostic::{Diagnostic, Severity};
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use globset::GlobMatcher;
+use log::debug;
+use regex::Regex;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock as Lazy;
+
+/// Lines the header may start below, for shebangs, encoding declarations and the like
+const HEADER_OFFSET: usize = 5;
+
+static PLACEHOLDER: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{(\w+)\}").unwrap());
+
+/// Options of the gate, as flags or a `[[gate]]` table of `kind = "license"`
+#[derive(Args, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LicenseArgs {
+    /// The required header, without comment markers. `{year}` stands for a year or a
+    /// range of years, any other `{name}` for any text
+    #[arg(long, value_name = "PATH")]
+    template: PathBuf,
+
+    /// Only check added files matching one of these globs
+    #[arg(long, value_name = "GLOB")]
+    #[serde(default)]
+    include: Vec<String>,
+}
+
+/// Comment markers around a line, so one template fits `#`, `//`, `/* */` and `<!-- -->`
+fn strip_comment(line: &str) -> &str {
+    line.trim()
+        .trim_start_matches(['/', '*', '#', '-', ';', '!', '<', '%'])
+        .trim_end_matches(['*', '/', '-', '>'])
+        .trim()
+}
+
+pub struct Template {
+    lines: Vec<Regex>,
+}
+
+impl Template {
+    pub fn parse(text: &str) -> Result<Self> {
+        let lines: Vec<_> = text
+            .trim_matches('\n')
+            .lines()
+            .map(|line| {
+                let line = strip_comment(line);
+                let mut pattern = String::from("^");
+                let mut last = 0;
+                for placeholder in PLACEHOLDER.captures_iter(line) {
+                    let whole = placeholder.get(0).unwrap();
+                    pattern += &regex::escape(&line[last..whole.start()]);
+                    pattern += match &placeholder[1] {
+                        "year" => r"\d{4}(?:\s*[-,]\s*\d{4})*",
+                        _ => ".+?",
+                    };
+                    last = whole.end();
+                }
+                pattern += &regex::escape(&line[last..]);
+                Regex::new(&(pattern + "$")).map_err(anyhow::Error::from)
+            })
+            .collect::<Result<_>>()?;
+        if lines.is_empty() {
+            bail!("The license template is empty");
+        }
+        Ok(Template { lines })
+    }
+
+    /// Whether the header is on consecutive lines near the start of `source`
+    pub fn matches(&self, source: &str) -> bool {
+        let head: Vec<_> = source
+            .lines()
+            .take(HEADER_OFFSET + self.lines.len())
+            .map(strip_comment)
+            .collect();
+        head.windows(self.lines.len())
+            .take(HEADER_OFFSET + 1)
+            .any(|window| {
+                window
+                    .iter()
+                    .zip(&self.lines)
+                    .all(|(line, pattern)| pattern.is_match(line))
+            })
+    }
+}
+
+/// Findings for the `added` files without the header
+pub fn check(args: &LicenseArgs, workdir: &Path, added: &[String]) -> Result<Vec<Diagnostic>> {
+    let text = fs::read_to_string(&args.template).with_context(|| {
+        format!(
+            "Unable to read license template {}",
+            args.template.display()
+        )
+    })?;
+    let template = Template::parse(&text)?;
+    let include: Vec<GlobMatcher> = args
+        .include
+        .iter()
+        .map(|glob| compile_glob(glob))
+        .collect::<Result<_>>()?;
+    let mut diagnostics = Vec::new();
+    for path in added {
+        if !include.is_empty() && !include.iter().any(|glob| glob.is_match(path)) {
+            continue;
+        }
+        let source = match fs::read(workdir.join(path)).map(String::from_utf8) {
+            Ok(Ok(source)) => source,
+            Ok(Err(_)) => {
+                debug!("Skipping '{}', it is not UTF-8 text", path);
+                continue;
+            }
+            Err(err) => return Err(err).with_context(|| format!("Unable to read '{}'", path)),
+        };
+        if template.matches(&source) {
+            continue;
+        }
+        let mut diagnostic = Diagnostic::new(path, None);
+        diagnostic.rule = Some("license-header".to_string());
+        diagnostic.tool = Some("license".to_string());
+        diagnostic.severity = Some(Severity::Error);
+        diagnostic.message = Some(format!(
+            "Missing the license header of {}",
+            args.template.display()
+        ));
+        diagnostic.raw = format!("{}: license-header: {}", path, diagnostic.text());
+        diagnostics.push(diagnostic);
+    }
+    Ok(diagnostics)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::license::Template;
+
+    #[test]
+    fn test_template() {
+        let template =
+            Template::parse("Copyright (c) {year} {holder}\nSPDX-License-Identifier: MIT\n")
+                .unwrap();
+        assert!(template.matches(
+            "#!/usr/bin/env python\n# Copyright (c) 2024-2026 Acme Corp.\n\
+             # SPDX-License-Identifier: MIT\nimport os\n"
+        ));
+        assert!(template
+            .matches("/*\n * Copyright (c) 2026 Acme\n * SPDX-License-Identifier: MIT\n */\n"));
+        assert!(!template.matches("// Copyright (c) Acme\n// SPDX-License-Identifier: MIT\n"));
+        assert!(!template.matches("# Copyright (c) 2026 Acme\nimport os\n"));
+        assert!(!template.matches(
+            "a\nb\nc\nd\ne\nf\n# Copyright (c) 2026 Acme\n# SPDX-License-Identifier: MIT\n"
+        ));
+        assert!(Template::parse("\n\n").is_err());
+    }
+}
@@ -0,0 +1,73 @@
+use crate::HunkRange;
+use clap::Args;
+use log::warn;
+use std::collections::HashMap;
+
+/// Range covering a whole file, for files matched without their hunks
+pub const WHOLE_FILE: HunkRange = (1, u32::MAX);
+
+/// Caps on the size of the hunk map, for pathological diffs such as large merges
+#[derive(Args, Debug, Clone, Copy, Default)]
+pub struct Limits {
+    /// Match only the first N changed files (by path) line by line, and any later ones as
+    /// a whole
+    #[arg(long, value_name = "N")]
+    max_files: Option<usize>,
+
+    /// Match files with more than N hunks as a whole
+    #[arg(long, value_name = "N")]
+    max_hunks_per_file: Option<usize>,
+}
+
+impl Limits {
+    /// Replaces the hunks of files beyond the limits with the whole file, returning how
+    /// many files were degraded
+    pub fn apply(self, file_hunks: &mut HashMap<String, Vec<HunkRange>>) -> usize {
+        let mut paths: Vec<_> = file_hunks.keys().cloned().collect();
+        paths.sort();
+        let mut degraded = 0;
+        for (index, path) in paths.iter().enumerate() {
+            let ranges = file_hunks.get_mut(path).unwrap();
+            let over_files = self.max_files.is_some_and(|max| index >= max);
+            let over_hunks = self
+                .max_hunks_per_file
+                .is_some_and(|max| ranges.len() > max);
+            if over_files || over_hunks {
+                *ranges = vec![WHOLE_FILE];
+                degraded += 1;
+            }
+        }
+        if degraded > 0 {
+            warn!(
+                "{} file(s) exceeded --max-files or --max-hunks-per-file and are matched as a whole",
+                degraded
+            );
+        }
+        degraded
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::limits::{Limits, WHOLE_FILE};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_limits() {
+        let mut file_hunks: HashMap<_, _> = vec![
+            ("a.py".to_string(), vec![(1, 2)]),
+            ("b.py".to_string(), vec![(1, 2), (5, 6), (9, 9)]),
+            ("c.py".to_string(), vec![(3, 4)]),
+        ]
+        .into_iter()
+        .collect();
+        let limits = Limits {
+            max_files: Some(2),
+            max_hunks_per_file: Some(2),
+        };
+        assert_eq!(limits.apply(&mut file_hunks), 2);
+        assert_eq!(file_hunks["a.py"], [(1, 2)]);
+        assert_eq!(file_hunks["b.py"], [WHOLE_FILE]);
+        assert_eq!(file_hunks["c.py"], [WHOLE_FILE]);
+    }
+}
@@ -0,0 +1,80 @@
+//! Places broken links that link checkers report without a line, so they match like
+//! any other finding: on the changed line linking to the URL, if there is one.
+
+use crate::diagnostic::Diagnostic;
+use crate::{is_number_in_sorted_ranges, HunkMap};
+use log::debug;
+use std::fs;
+use std::path::Path;
+
+/// Points a link finding without a line at the first changed line quoting its URL, or
+/// at the first line quoting it when none changed. A URL the file doesn't quote leaves
+/// the finding about the whole file
+pub fn locate(workdir: &Path, diagnostic: &mut Diagnostic, file_hunks: &HunkMap) {
+    let url = match (&diagnostic.excerpt, diagnostic.line) {
+        (Some(url), None) if !url.is_empty() => url,
+        _ => return,
+    };
+    let source = match fs::read_to_string(workdir.join(&diagnostic.path)) {
+        Ok(source) => source,
+        Err(err) => {
+            debug!("Can't read '{}' to find {}: {}", diagnostic.path, url, err);
+            return;
+        }
+    };
+    let ranges = file_hunks.get(&diagnostic.path);
+    let quoting = source
+        .lines()
+        .zip(1..)
+        .filter_map(|(text, line)| Some((line, text.find(url.as_str())?, text)));
+    let mut first = None;
+    for (line, start, text) in quoting {
+        let column = text[..start].chars().count() as u32 + 1;
+        if ranges.is_some_and(|ranges| is_number_in_sorted_ranges(ranges, line)) {
+            first = Some((line, column));
+            break;
+        }
+        first = first.or(Some((line, column)));
+    }
+    if let Some((line, column)) = first {
+        diagnostic.line = Some(line);
+        diagnostic.column = Some(column);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::Diagnostic;
+    use crate::fixtures::TempDir;
+    use crate::links::locate;
+    use std::collections::HashMap;
+    use std::fs;
+
+    #[test]
+    fn test_locate() {
+        let dir = TempDir::new("links").unwrap();
+        fs::write(
+            dir.join("README.md"),
+            "See [a](https://x.test/gone).\n\nÄlso [b](https://x.test/gone)\n",
+        )
+        .unwrap();
+        let link = |url: &str| {
+            let mut diagnostic = Diagnostic::new("README.md", None);
+            diagnostic.excerpt = Some(url.to_string());
+            diagnostic
+        };
+        let changed: HashMap<_, _> = vec![("README.md".to_string(), vec![(3, 3)])]
+            .into_iter()
+            .collect();
+
+        let mut diagnostic = link("https://x.test/gone");
+        locate(&dir, &mut diagnostic, &changed);
+        assert_eq!((diagnostic.line, diagnostic.column), (Some(3), Some(10)));
+        let mut diagnostic = link("https://x.test/gone");
+        locate(&dir, &mut diagnostic, &HashMap::new());
+        assert_eq!((diagnostic.line, diagnostic.column), (Some(1), Some(9)));
+        let mut diagnostic = link("https://x.test/other");
+        locate(&dir, &mut diagnostic, &changed);
+        assert_eq!(diagnostic.line, None);
+    }
+}
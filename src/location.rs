@@ -0,0 +1,356 @@
+//! Conversions between the position conventions used by lint tools.
+//!
+//! Diagnostics are matched and emitted with 1-based lines and 1-based columns counted in
+//! characters; parsers that receive byte offsets or UTF-16 columns record the unit and let
+//! [`LocationResolver`] convert them against the file contents.
+
+use crate::diagnostic::Diagnostic;
+use crate::embedded::{self, EmbeddedBlocks};
+use crate::language::Languages;
+use crate::paths::PathMapper;
+use crate::HunkRange;
+use log::debug;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub use crate::diagnostic::ColumnUnit;
+
+/// Excerpts shorter than this, in characters, only match whole words
+const SHORT_EXCERPT: usize = 12;
+
+/// Whether `text` holds `excerpt`, as whole words when it is short: `i` or `id` are
+/// in most lines, but not as a word
+fn quotes(text: &str, excerpt: &str) -> bool {
+    if excerpt.chars().count() >= SHORT_EXCERPT {
+        return text.contains(excerpt);
+    }
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    text.match_indices(excerpt).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + excerpt.len()..].chars().next();
+        let joined_before = excerpt.starts_with(is_word) && before.is_some_and(is_word);
+        let joined_after = excerpt.ends_with(is_word) && after.is_some_and(is_word);
+        !joined_before && !joined_after
+    })
+}
+
+/// Converts a 1-based column in `unit` to a 1-based character column within `line`
+pub fn to_char_column(line: &str, column: u32, unit: ColumnUnit) -> u32 {
+    let target = column.saturating_sub(1) as usize;
+    let mut consumed = 0;
+    let mut chars = 0;
+    for c in line.chars() {
+        if consumed >= target {
+            break;
+        }
+        consumed += match unit {
+            ColumnUnit::Char => 1,
+            ColumnUnit::Utf8Byte => c.len_utf8(),
+            ColumnUnit::Utf16 => c.len_utf16(),
+        };
+        chars += 1;
+    }
+    // Columns past the end of the line keep their overshoot
+    chars + target.saturating_sub(consumed) as u32 + 1
+}
+
+/// Maps byte offsets within a file to lines
+pub struct LineIndex {
+    text: String,
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(text: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        LineIndex { text, line_starts }
+    }
+
+    /// Returns the 1-based line and character column of a byte offset
+    pub fn position(&self, offset: usize) -> (u32, u32) {
+        let offset = offset.min(self.text.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let prefix = &self.text[self.line_starts[line]..offset];
+        (line as u32 + 1, prefix.chars().count() as u32 + 1)
+    }
+
+    /// Lines in the file, not counting an empty one after the final line ending
+    pub fn line_count(&self) -> u32 {
+        let ends_with_newline = self.text.is_empty() || self.text.ends_with('\n');
+        (self.line_starts.len() - usize::from(ends_with_newline)) as u32
+    }
+
+    /// Returns a 1-based line without its terminator
+    pub fn line(&self, line: u32) -> Option<&str> {
+        let index = line.checked_sub(1)? as usize;
+        let start = *self.line_starts.get(index)?;
+        let end = self
+            .line_starts
+            .get(index + 1)
+            .map_or(self.text.len(), |&next| next - 1);
+        Some(self.text[start..end].trim_end_matches('\r'))
+    }
+}
+
+fn cached_index<'a>(
+    files: &'a mut HashMap<String, Option<LineIndex>>,
+    root: &Path,
+    path: &str,
+) -> Option<&'a LineIndex> {
+    files
+        .entry(path.to_string())
+        .or_insert_with(|| match fs::read_to_string(root.join(path)) {
+            Ok(text) => Some(LineIndex::new(text)),
+            Err(err) => {
+                debug!("Can't read '{}' to resolve positions: {}", path, err);
+                None
+            }
+        })
+        .as_ref()
+}
+
+/// Normalizes diagnostic positions, reading (and caching) files from the working directory
+pub struct LocationResolver {
+    root: PathBuf,
+    files: HashMap<String, Option<LineIndex>>,
+    /// Embedded block rules, keyed by file extension
+    embedded: HashMap<String, EmbeddedBlocks>,
+    paths: Option<PathMapper>,
+    languages: Languages,
+}
+
+impl LocationResolver {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        LocationResolver {
+            root: root.into(),
+            files: HashMap::new(),
+            embedded: HashMap::new(),
+            paths: None,
+            languages: Languages::new(),
+        }
+    }
+
+    /// Maps reported paths with `paths` before resolving positions
+    pub fn with_paths(mut self, paths: PathMapper) -> Self {
+        self.paths = Some(paths);
+        self
+    }
+
+    /// Tags findings in changed files with their language
+    pub fn with_languages(mut self, languages: Languages) -> Self {
+        self.languages = languages;
+        self
+    }
+
+    pub fn with_embedded(mut self, embedded: HashMap<String, EmbeddedBlocks>) -> Self {
+        self.embedded = embedded;
+        self
+    }
+
+    fn index(&mut self, path: &str) -> Option<&LineIndex> {
+        cached_index(&mut self.files, &self.root, path)
+    }
+
+    /// Text of line `line` of `path`, without its line ending
+    pub fn source_line(&mut self, path: &str, line: u32) -> Option<&str> {
+        self.index(path)?.line(line)
+    }
+
+    /// The number of lines of the finding's file, if its line is past them
+    pub fn past_end(&mut self, diagnostic: &Diagnostic) -> Option<u32> {
+        let line = diagnostic.line?;
+        let lines = self.index(&diagnostic.path)?.line_count();
+        (line > lines).then_some(lines)
+    }
+
+    pub fn resolve(&mut self, diagnostic: &mut Diagnostic) {
+        if let Some(paths) = &mut self.paths {
+            diagnostic.path = paths.map(&diagnostic.path);
+            for related in &mut diagnostic.related {
+                related.path = paths.map(&related.path);
+            }
+        }
+        self.resolve_position(diagnostic);
+        self.resolve_embedded(diagnostic);
+        diagnostic.language = self.languages.get(&diagnostic.path).cloned();
+    }
+
+    /// The changed line of `path` quoting `excerpt` that is closest to `near`, see
+    /// [`quotes`]
+    pub fn find_excerpt(
+        &mut self,
+        path: &str,
+        excerpt: &str,
+        near: u32,
+        hunk_ranges: &[HunkRange],
+    ) -> Option<u32> {
+        let excerpt = excerpt.trim();
+        if excerpt.is_empty() {
+            return None;
+        }
+        let index = self.index(path)?;
+        hunk_ranges
+            .iter()
+            .flat_map(|&(start, end)| {
+                (start..=end).map_while(|line| Some((line, index.line(line)?)))
+            })
+            .filter(|(_, text)| quotes(text, excerpt))
+            .map(|(line, _)| line)
+            .min_by_key(|&line| (line as i64 - near as i64).abs())
+    }
+
+    fn resolve_embedded(&mut self, diagnostic: &mut Diagnostic) {
+        let embedded = &self.embedded;
+        let blocks = match embedded::extension(&diagnostic.path)
+            .and_then(|extension| embedded.get(extension))
+        {
+            Some(blocks) if blocks.applies_to(diagnostic.tool.as_deref()) => blocks,
+            _ => return,
+        };
+        let (relative, index) = match (
+            diagnostic.line,
+            cached_index(&mut self.files, &self.root, &diagnostic.path),
+        ) {
+            (Some(line), Some(index)) => (line, index),
+            _ => return,
+        };
+        let lines = (1..).map_while(|line| index.line(line));
+        if let Some(line) = blocks.absolute_line(lines, relative) {
+            debug!(
+                "Mapped block line {} of '{}' to {}",
+                relative, diagnostic.path, line
+            );
+            diagnostic.line = Some(line);
+            // Spans stay within their block
+            diagnostic.end_line = diagnostic
+                .end_line
+                .map(|end| end.saturating_sub(relative) + line);
+        }
+    }
+
+    fn resolve_position(&mut self, diagnostic: &mut Diagnostic) {
+        let needs_offset = diagnostic.line.is_none() && diagnostic.offset.is_some();
+        let needs_column = (diagnostic.column.is_some() || diagnostic.end_column.is_some())
+            && diagnostic.column_unit != ColumnUnit::Char;
+        if !needs_offset && !needs_column {
+            return;
+        }
+        let index = match self.index(&diagnostic.path) {
+            Some(index) => index,
+            None => return,
+        };
+        if needs_offset {
+            let (line, column) = index.position(diagnostic.offset.unwrap());
+            diagnostic.line = Some(line);
+            diagnostic.column = Some(column);
+        } else if let Some(line) = diagnostic.line {
+            let unit = diagnostic.column_unit;
+            if let (Some(column), Some(text)) = (diagnostic.column, index.line(line)) {
+                diagnostic.column = Some(to_char_column(text, column, unit));
+            }
+            let end_line = diagnostic.end_line.unwrap_or(line);
+            if let (Some(column), Some(text)) = (diagnostic.end_column, index.line(end_line)) {
+                diagnostic.end_column = Some(to_char_column(text, column, unit));
+            }
+        }
+        diagnostic.offset = None;
+        diagnostic.column_unit = ColumnUnit::Char;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fixtures::TempDir;
+    use crate::input::InputFormat;
+    use crate::location::{quotes, to_char_column, ColumnUnit, LineIndex, LocationResolver};
+    use crate::parsers::Format;
+    use std::fs;
+
+    #[test]
+    fn test_column_units() {
+        // 'é' is 2 UTF-8 bytes and 1 UTF-16 unit, '😀' is 4 bytes and 2 units
+        let line = "é😀x = 1";
+        assert_eq!(to_char_column(line, 7, ColumnUnit::Utf8Byte), 3);
+        assert_eq!(to_char_column(line, 4, ColumnUnit::Utf16), 3);
+        assert_eq!(to_char_column(line, 3, ColumnUnit::Char), 3);
+        // Past the end of the line
+        assert_eq!(to_char_column("ab", 5, ColumnUnit::Utf8Byte), 5);
+    }
+
+    #[test]
+    fn test_line_index() {
+        let index = LineIndex::new("first\r\nsécond\nthird".to_string());
+        assert_eq!(index.position(0), (1, 1));
+        assert_eq!(index.position(7), (2, 1));
+        assert_eq!(index.position(10), (2, 3));
+        assert_eq!(index.position(100), (3, 6));
+        assert_eq!(index.line(1), Some("first"));
+        assert_eq!(index.line(3), Some("third"));
+        assert_eq!(index.line(4), None);
+        assert_eq!(index.line_count(), 3);
+        assert_eq!(LineIndex::new("a\nb\n".to_string()).line_count(), 2);
+        assert_eq!(LineIndex::new(String::new()).line_count(), 0);
+    }
+
+    #[test]
+    fn test_resolve_offset() {
+        let dir = TempDir::new("offset").unwrap();
+        fs::write(dir.join("a.cpp"), "int f();\nint g(int é, int x) {}\n").unwrap();
+        let fixes = "Diagnostics:\n  - DiagnosticName: misc-unused-parameters\n    \
+            DiagnosticMessage:\n      Message: 'parameter ''x'' is unused'\n      \
+            FilePath: a.cpp\n      FileOffset: 27\n    Level: Warning\n";
+        let line = InputFormat::ClangTidyFixes
+            .lines(fixes.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+        let mut diagnostic = Format::ClangTidyFixes.parser().parse(&line).unwrap();
+        LocationResolver::new(dir.path()).resolve(&mut diagnostic);
+        // Byte 27 is the `x` after the 2-byte 'é', the 18th character of line 2
+        assert_eq!(
+            (diagnostic.line, diagnostic.column, diagnostic.offset),
+            (Some(2), Some(18), None)
+        );
+        assert_eq!(diagnostic.column_unit, ColumnUnit::Char);
+    }
+
+    #[test]
+    fn test_find_excerpt() {
+        assert!(quotes("if id == 0:", "id"));
+        assert!(quotes("x = f(a, b)", "f(a"));
+        assert!(!quotes("valid = True", "id"));
+        assert!(!quotes("width = 1", "i"));
+        // Longer ones match anywhere
+        assert!(!quotes("return compute_total(x)", "compute_tot"));
+        assert!(quotes("return compute_total(x)", "compute_tota"));
+
+        let dir = TempDir::new("excerpt").unwrap();
+        fs::write(
+            dir.join("a.py"),
+            "import os\nvalid = True\nx = 1\nimport os\nid = 2\n",
+        )
+        .unwrap();
+        let mut resolver = LocationResolver::new(dir.path());
+        // A finding on a stale line moves to the closest changed line quoting it
+        assert_eq!(
+            resolver.find_excerpt("a.py", " import os ", 1, &[(2, 4)]),
+            Some(4)
+        );
+        assert_eq!(
+            resolver.find_excerpt("a.py", "import os", 3, &[(1, 1), (4, 5)]),
+            Some(4)
+        );
+        // Short excerpts only match whole words, and unchanged lines never
+        assert_eq!(resolver.find_excerpt("a.py", "id", 1, &[(2, 4)]), None);
+        assert_eq!(resolver.find_excerpt("a.py", "id", 1, &[(2, 5)]), Some(5));
+        assert_eq!(
+            resolver.find_excerpt("a.py", "import sys", 1, &[(1, 5)]),
+            None
+        );
+        assert_eq!(resolver.find_excerpt("a.py", "  ", 1, &[(1, 5)]), None);
+        assert_eq!(resolver.find_excerpt("b.py", "x", 1, &[(1, 5)]), None);
+    }
+}
@@ -1,150 +1,2697 @@
-use anyhow::{Context, Result};
-use clap::Parser;
-use env_logger;
+use anyhow::{bail, Context, Result};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use diff_format::attribution::{Attributor, CommitRange};
+use diff_format::audit::Audit;
+use diff_format::autofix;
+use diff_format::baseline::{Baseline, BaselineAction, BaselineArgs};
+use diff_format::bisect::BisectArgs;
+use diff_format::bounds::{Deletions, HunkBounds, HunksArgs};
+use diff_format::cache::ResultCache;
+use diff_format::checkstyle::CheckstyleReport;
+use diff_format::ci::Ci;
+use diff_format::codeowners::CodeOwners;
+use diff_format::config::{Config, ConfigArgs, FailOn, Policies, ProfileConfig};
+use diff_format::contains::{self, ContainsArgs};
+use diff_format::demux::{Demux, Route};
+#[cfg(feature = "dependency-rules")]
+use diff_format::deps::{self, DepsArgs};
+use diff_format::diagnostic::{Diagnostic, Severity};
+use diff_format::drift::DriftMapper;
+use diff_format::embedded::EmbeddedBlocks;
+use diff_format::exec::Linter;
+#[cfg(feature = "tree-sitter")]
+use diff_format::expand;
+use diff_format::export::ExportArgs;
+use diff_format::expression::Expression;
+use diff_format::fingerprint::FingerprintArgs;
+use diff_format::fixed::FindingKey;
+use diff_format::fold::Folder;
+use diff_format::formatter::FormatterDiff;
+use diff_format::gates::{self, GatesArgs};
+use diff_format::generate::{self, GenerateArgs};
+use diff_format::hunks::{
+    added_files, combine_refs, generate_old_hunkmap, get_diff, get_tree, index_tree, ref_trees,
+};
+use diff_format::i18n::Lang;
+use diff_format::input::{InputFormat, Lines};
+use diff_format::invert::Coverage;
+use diff_format::language::{self, FilesArgs};
+use diff_format::license::{self, LicenseArgs};
+use diff_format::limits::Limits;
+use diff_format::location::LocationResolver;
+use diff_format::metrics::MetricsArgs;
+use diff_format::notes;
+use diff_format::notify::{self, WebhookPayload};
+use diff_format::output::buffer::{SortKey, Streams};
+use diff_format::output::{
+    CsvColumn, Destination, Output, OutputFormat, RenderContext, SplitOutput,
+};
+use diff_format::parallel::{Parsed, ParsedLines};
+use diff_format::parsers::{Format, FormatSpec, ParserStrategy, Parsers};
+use diff_format::paths::{self, PathMapper, SymlinkPolicy};
+use diff_format::progress;
+use diff_format::proximity::Proximity;
+use diff_format::publish::{GhArtifactArgs, Pacing, PublishArgs, PublishTarget, Undelivered};
+use diff_format::redact::Redaction;
+use diff_format::regression::{PairTotals, RuleTotals};
+use diff_format::remote_base::{self, RemoteBase};
+use diff_format::remote_pr::RemotePrArgs;
+use diff_format::resume::Resume;
+use diff_format::rollout::Rollouts;
+use diff_format::run::{self, RunInfo};
+use diff_format::sarif::{self, SarifLog};
+use diff_format::score::Score;
+use diff_format::serve::ServeArgs;
+use diff_format::show::ShowArgs;
+use diff_format::sign::{Revisions, SignArgs};
+use diff_format::staging::Staging;
+use diff_format::stale::OnStaleLine;
+use diff_format::state::{self, CheckoutError};
+#[cfg(feature = "store")]
+use diff_format::store::{self, QueryArgs, Store};
+use diff_format::summary::Summary;
+use diff_format::todos::{self, TodoArgs};
+#[cfg(feature = "store")]
+use diff_format::trends::ReportArgs;
+use diff_format::truncate::{InputLimit, Tally};
+use diff_format::update::UpdateArgs;
+use diff_format::waiver::Waivers;
+use diff_format::{
+    attribution, baseline, bounds, cherry, diagnostic, export, fixed, links, manifest, merge,
+    metrics, output, partial_clone, publish, pytest, redact, regression, serve, show, shuffle,
+    since, submodules, totals, unified_diff, waiver,
+};
+use diff_format::{is_changed, remove_ansi_colors, HunkMap, HunkRange};
 use env_logger::Env;
-use git2::Delta;
-use git2::Diff;
-use git2::DiffOptions;
-use git2::Repository;
-use log::{debug, info};
-use regex::Regex;
-use std::collections::HashMap;
-use std::io::{self, BufRead};
-use std::path::PathBuf;
+use git2::Tree;
+use git2::{Oid, Repository};
+use log::{debug, info, warn};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io::{self, BufReader, IsTerminal};
+use std::path::{Path, PathBuf};
 use std::process;
-
-type HunkRange = (u32, u32);
+use std::sync::Arc;
+use toml::value::Date;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Linter to run, e.g. `-- flake8 src/`, filtering its stdout and stderr instead of
+    /// stdin; its exit code is passed on only if it fails without reporting findings
+    #[arg(last = true, value_name = "LINTER")]
+    exec: Vec<String>,
+
+    /// Replay the lint output of changed files whose content and rule set were linted
+    /// before, keeping it in this directory, and lint only the others: the linter after
+    /// -- gets them in place of a `{files}` word
+    #[arg(
+        long,
+        value_name = "DIR",
+        requires = "exec",
+        conflicts_with_all = ["diff_file", "fail_fast", "lazy_diff", "max_input_lines", "resume"]
+    )]
+    cache: Option<PathBuf>,
+
+    /// A file --cache reads as part of the rule set, e.g. the linter's config, so
+    /// editing it lints everything again; can be given several times
+    #[arg(long, value_name = "PATH", requires = "cache")]
+    cache_key_file: Vec<PathBuf>,
+
     /// Path to repository
-    #[arg(short, long, default_value = ".")]
+    #[arg(short, long, global = true, default_value = ".")]
     path: PathBuf,
 
-    #[arg(short, long, default_value = "origin")]
+    /// Remote whose default branch `--gitref auto` picks
+    #[arg(short, long, global = true, default_value = "origin")]
     remote: String,
 
-    #[arg(short, long, default_value = "master")]
-    gitref: String,
-}
-
-fn is_number_in_sorted_ranges(ranges: &[(u32, u32)], number: u32) -> bool {
-    let mut low = 0;
-    let mut high = ranges.len();
-
-    while low < high {
-        let mid = low + (high - low) / 2;
-        match (number >= ranges[mid].0, number <= ranges[mid].1) {
-            (true, true) => return true,    // Number is within the current range
-            (true, false) => low = mid + 1, // Number is greater than the current range, search in the right half
-            (false, _) => high = mid, // Number is less than the current range, search in the left half
-        }
-    }
-
-    false
-}
-
-fn get_diff<'a>(repo: &'a Repository, gitref: &str) -> Result<Diff<'a>> {
-    let gitref = repo
-        .revparse_single(gitref)
-        .context("Unable to parse gitref")?;
-    let gitref_tree = gitref.peel_to_tree().context("Gitref is not a tree")?;
-    Ok(repo.diff_tree_to_workdir_with_index(
-        Some(&gitref_tree),
-        // Prevent errors on untouched lines by disabling context lines
-        Some(DiffOptions::new().context_lines(0)),
-    )?)
-}
-
-fn generate_hunkmap(diff: &Diff) -> Result<HashMap<String, Vec<HunkRange>>> {
-    let mut hunkmap = HashMap::new();
-
-    diff.foreach(
-        &mut |file, _| {
-            let path = file.new_file().path().unwrap().to_str().unwrap();
-            info!("Analyzing '{}'", path);
-            true
-        },
-        None, // Ignore binary files
-        Some(&mut |file, hunk| match file.status() {
-            Delta::Modified => {
-                let path = file.new_file().path().unwrap().to_str().unwrap();
-                let hunk_edges = (hunk.new_start(), hunk.new_start() + hunk.new_lines());
-                debug!("Changes in lines {}..{}", hunk_edges.0, hunk_edges.1);
-                hunkmap
-                    .entry(path.into())
-                    .or_insert_with(Vec::new)
-                    .push((hunk_edges.0, hunk_edges.1));
-                true
-            }
-            _ => true,
-        }),
-        None, // Extrapolating line information from hunks is enough, no need for line callback
+    /// Ref to diff against; when given several times, lines changed relative to any of them
+    /// count. `auto` is the --remote's default branch, as `<remote>/HEAD` points to
+    #[arg(short, long, global = true, default_value = "master")]
+    gitref: Vec<String>,
+
+    /// Diff against where HEAD's first-parent line forked from --gitref, so a merge
+    /// commit only counts the changes it merged in
+    #[arg(long, global = true)]
+    first_parent: bool,
+
+    /// Diff against the merge base of HEAD and --gitref, so changes the target branch
+    /// gained after HEAD branched off don't count
+    #[arg(long, global = true, conflicts_with = "first_parent")]
+    merge_base: bool,
+
+    /// Drop hunks identical to a change on --gitref that HEAD doesn't have, such as a
+    /// commit cherry-picked between long-lived branches
+    #[arg(long)]
+    cherry_pick_aware: bool,
+
+    /// Drop changed lines that are unchanged relative to this ref, e.g. a long-lived branch
+    /// the change was cherry-picked to; can be given several times
+    #[arg(long, global = true, value_name = "REF")]
+    exclude_ref: Vec<String>,
+
+    /// Read the changed lines from this patch, e.g. from `git format-patch`, instead of
+    /// diffing the repository, which need not exist then; `-` reads it from stdin, which
+    /// needs the linter given after --
+    #[arg(
+        long,
+        value_name = "PATCH",
+        conflicts_with_all = [
+            "base_url", "per_commit", "since", "exclude_ref", "first_parent", "merge_base",
+            "cherry_pick_aware", "require_staged", "lint_rev", "fixed_summary",
+            "regression_check", "invert", "debounce", "emit_trends"
+        ]
+    )]
+    diff_file: Option<PathBuf>,
+
+    /// Also remove terminal escapes, such as colors and hyperlinks, from the lint lines
+    /// echoed to the output; they are always ignored when parsing
+    #[arg(long)]
+    strip_escapes: bool,
+
+    /// Echo lint lines in this order once the input ends, rather than as they are
+    /// matched. The lines are held until then, spilling to temporary files past
+    /// --buffer-limit
+    #[arg(long, value_enum, value_name = "KEY")]
+    sort: Option<SortKey>,
+
+    /// Bytes of lines --sort holds in memory before spilling them to a temporary file
+    #[arg(long, value_name = "BYTES", default_value_t = 64 << 20, requires = "sort")]
+    buffer_limit: usize,
+
+    /// Report a finding once when several tools flag the same rule on the same line,
+    /// noting the agreeing tools. Rule codes are matched across tools with built-in
+    /// aliases (e.g. pylint's C0301 for E501) and those under [fold] in the config
+    #[arg(long)]
+    fold_equivalent: bool,
+
+    /// Ignore hunks whose changes are all unstaged, so a pre-commit hook only gates what
+    /// is about to be committed
+    #[arg(long, conflicts_with_all = ["per_commit", "base_url"])]
+    require_staged: bool,
+
+    /// Diff against the state before this date (YYYY-MM-DD[THH:MM]) or revision instead
+    /// of --gitref, covering every commit since then
+    #[arg(long, global = true)]
+    since: Option<String>,
+
+    /// Diff the commit range base..head and report which commit introduced each finding
+    #[arg(long, value_name = "RANGE")]
+    per_commit: Option<String>,
+
+    /// Diff the commit range base..head instead of --gitref against the workdir, e.g. to
+    /// check what a merge brought in when the workdir is clean
+    #[arg(
+        long,
+        value_name = "RANGE",
+        conflicts_with_all = ["per_commit", "since", "require_staged", "staged", "diff_file"]
+    )]
+    range: Option<String>,
+
+    /// Only count staged changes, diffing --gitref against the index, for pre-commit hooks
+    /// that lint what is about to be committed
+    #[arg(long, conflicts_with_all = ["per_commit", "require_staged", "diff_file"])]
+    staged: bool,
+
+    /// Fetch the base from this remote instead of reading --gitref from the repository,
+    /// for a workdir mounted without its `.git`; only the blobs of files in the lint
+    /// output are downloaded
+    #[arg(
+        long,
+        value_name = "URL",
+        requires = "base_ref",
+        conflicts_with_all = [
+            "exclude_ref", "since", "per_commit", "range", "staged", "first_parent", "merge_base",
+            "cherry_pick_aware"
+        ]
+    )]
+    base_url: Option<String>,
+
+    /// Branch, tag or commit of --base-url to diff against
+    #[arg(long, value_name = "REF", requires = "base_url")]
+    base_ref: Option<String>,
+
+    /// Whether the line after each hunk also counts as changed
+    #[arg(long, value_enum, global = true, default_value = "inclusive")]
+    hunk_bounds: HunkBounds,
+
+    /// Which lines count as changed where lines were only removed
+    #[arg(long, value_enum, global = true, default_value = "bounds")]
+    deletions: Deletions,
+
+    /// Print diagnostics next to hunk edges with how each --hunk-bounds mode treats them
+    #[arg(long)]
+    debug_bounds: bool,
+
+    /// Fail unless the changed lines have the digest `hunks --checksum` printed, so a
+    /// job doesn't filter against ranges the workdir has drifted from since
+    #[arg(long, value_name = "DIGEST")]
+    expect_checksum: Option<String>,
+
+    /// Config file [default: .diff-format.toml in the repository, if present]
+    #[arg(short, long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Config `[profile.NAME]` whose settings apply where the command line sets none
+    /// [default: `ci` in CI jobs and `local` elsewhere, if the config has it]
+    #[arg(long, global = true, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Lines of lint output read ahead of matching; the linter only blocks once this many
+    /// are waiting
+    #[arg(long, value_name = "LINES", default_value_t = 10_000)]
+    input_buffer: usize,
+
+    /// How lint output is wrapped on stdin
+    #[arg(long, value_enum, default_value = "text")]
+    input_format: InputFormat,
+
+    /// Lint output formats, tried in order on each line: python, typos, typos-json,
+    /// codespell, pytest, actionlint, actionlint-json, kubeconform, kubeval, black,
+    /// rustfmt, gofmt, gitleaks, trufflehog, link-check, duplicates, auto, or the name
+    /// of a `[parsers.<name>]` config profile
+    #[arg(short, long, value_delimiter = ',', default_value = "python")]
+    format: Vec<FormatSpec>,
+
+    /// Which diagnostics to keep when several formats accept a line
+    #[arg(long, value_enum, default_value = "first")]
+    parser_strategy: ParserStrategy,
+
+    /// Parse the lines starting with PREFIX with FORMAT, taking the prefix off, e.g.
+    /// `--demux '[eslint] =>eslint'` for a wrapper running several linters; sections
+    /// after a `##tool:FORMAT` line are parsed with FORMAT without one
+    #[arg(long, value_name = "PREFIX=>FORMAT")]
+    demux: Vec<Route>,
+
+    /// How to print matched diagnostics [default: text]
+    #[arg(long, value_enum)]
+    output_format: Option<OutputFormat>,
+
+    /// Shorthand for --output-format porcelain
+    #[arg(long, conflicts_with = "output_format")]
+    porcelain: bool,
+
+    /// Print nothing and only report through the exit code; reports are still written
+    /// to files and publishers still run
+    #[arg(short, long, conflicts_with_all = ["output_format", "porcelain"])]
+    quiet: bool,
+
+    /// Write an output to its own destination, as FORMAT=stdout|stderr|PATH; can be
+    /// given several times and replaces --output-format on stdout
+    #[arg(long, value_name = "FORMAT=DEST")]
+    out: Vec<Output>,
+
+    /// Write the --output-format output to this file instead of stdout, replacing it
+    /// atomically
+    #[arg(long, value_name = "PATH", conflicts_with = "out")]
+    output: Option<PathBuf>,
+
+    /// Append to the --output file under a lock instead of replacing it, so parallel
+    /// steps can share one report
+    #[arg(long, requires = "output")]
+    append: bool,
+
+    /// Columns of the csv output, in order, from file, line, col, severity, rule,
+    /// message, tool, hunk_start and hunk_end, the changed hunk the finding falls in
+    /// [default: all of them]
+    #[arg(long, value_enum, value_delimiter = ',')]
+    csv_columns: Vec<CsvColumn>,
+
+    /// Write how many findings were shown and how many were dropped as pre-existing,
+    /// per file and overall, to stdout, stderr (the default) or a file
+    #[arg(
+        long,
+        visible_alias = "stats",
+        value_name = "DEST",
+        num_args = 0..=1,
+        default_missing_value = "stderr"
+    )]
+    summary: Option<Destination>,
+
+    /// Write the --summary as JSON, for dashboards
+    #[arg(long, requires = "summary")]
+    summary_json: bool,
+
+    /// Write the totals of the reported findings by rule, with the commits and branch
+    /// diffed, as JSON for dashboards
+    #[arg(long, value_name = "DEST")]
+    emit_trends: Option<Destination>,
+
+    /// POST the summary of the run, with its exit code, to this URL once it is decided
+    #[arg(long, value_name = "URL")]
+    notify_webhook: Option<String>,
+
+    /// What --notify-webhook posts: the summary as JSON, or a Slack message
+    #[arg(long, value_enum, default_value = "json", requires = "notify_webhook")]
+    webhook_payload: WebhookPayload,
+
+    /// Language of the --summary, the gates table, the --fixed-summary and truncation
+    /// warnings [default: from LC_ALL, LC_MESSAGES or LANG, else en]
+    #[arg(long, value_enum, default_value_t = Lang::from_env(), hide_default_value = true)]
+    lang: Lang,
+
+    /// Identify the run by this ID in the --summary and the --store instead of a new
+    /// ULID, so the shards of one CI job can be correlated
+    #[arg(long, value_name = "ID")]
+    run_id: Option<String>,
+
+    /// Write an output per group of findings, as FORMAT=TEMPLATE where the template path
+    /// holds `{file}`, `{file_dir}` or `{owner}`, the CODEOWNERS owners of the file, e.g.
+    /// `junit=reports/{owner}.xml`; can be given several times
+    #[arg(long, value_name = "FORMAT=TEMPLATE")]
+    split_out: Vec<SplitOutput>,
+
+    /// Append the reported findings, with the branch, commit and time of the run, to
+    /// this SQLite database, for `diff-format query`
+    #[cfg(feature = "store")]
+    #[arg(long, value_name = "PATH")]
+    store: Option<PathBuf>,
+
+    /// Rewrite what matches PATTERN, a regex, in the paths and messages of findings
+    /// before they are reported or published, e.g. `--redact 'acme-\w+\.internal=>HOST'`;
+    /// applied in the order given. CODEOWNERS and --split-out see the redacted paths
+    #[arg(long, value_name = "PATTERN=>REPLACEMENT")]
+    redact: Vec<Redaction>,
+
+    /// Also report matched diagnostics to these services
+    #[arg(long, value_enum, value_delimiter = ',')]
+    publish: Vec<PublishTarget>,
+
+    /// PEM bundle of CA certificates to trust instead of the bundled roots when
+    /// publishing over HTTPS, e.g. behind a TLS-intercepting proxy
+    #[arg(long, value_name = "PATH")]
+    ca_cert: Option<PathBuf>,
+
+    /// When a publisher fails, e.g. on a network error, append its findings to this
+    /// file for `publish --drain` instead of failing the run
+    #[arg(long, value_name = "QUEUE")]
+    publish_queue: Option<PathBuf>,
+
+    #[command(flatten)]
+    pacing: Pacing,
+
+    /// Use the default outputs, publishers and artifact paths of a CI provider
+    #[arg(long, value_enum)]
+    ci: Option<Ci>,
+
+    /// Take config values literally instead of expanding `${VAR}` from the environment
+    #[arg(long, global = true)]
+    no_env_interp: bool,
+
+    /// Validate the config file and exit, without reading the repository or stdin
+    #[arg(long)]
+    check_config: bool,
+
+    /// Lint output of the base revision, parsed with the same formats
+    #[arg(long)]
+    base_report: Option<PathBuf>,
+
+    /// Suppress findings recorded by `diff-format baseline`, even on changed lines
+    #[arg(long, value_name = "FILE")]
+    baseline: Option<PathBuf>,
+
+    /// Rules whose findings on changed lines are only reported when their count in the
+    /// file grew compared to --base-report, e.g. duplicate-code detectors
+    #[arg(
+        long,
+        value_name = "RULE",
+        value_delimiter = ',',
+        requires = "base_report"
+    )]
+    debounce: Vec<String>,
+
+    /// Only fail for matched findings at least this severe; findings without a severity
+    /// get one from their rule code, or count as errors. `[policy."<glob>"]` config
+    /// tables override this per path
+    #[arg(long, value_enum)]
+    fail_on: Option<Severity>,
+
+    /// Fail only if the matched findings score more than N, with the `[score]` weights
+    /// of each severity (by default error = 5, warning = 1 and info = 0), so a few
+    /// warnings pass but a pile of them doesn't
+    #[arg(long, value_name = "N", conflicts_with_all = ["fail_on", "policy", "regression_check"])]
+    max_score: Option<u64>,
+
+    /// Stop reading the lint output at the first finding that fails the run, stopping
+    /// the linter given after -- along with the processes it started. On Windows only
+    /// the linter itself is stopped, and what it started keeps running. Notes aren't
+    /// waited for, so they are reported as findings of their own
+    #[arg(
+        long,
+        conflicts_with_all = ["max_score", "debounce", "regression_check", "invert", "fixed_summary"]
+    )]
+    fail_fast: bool,
+
+    /// Fail only for matched findings this expression holds for, e.g.
+    /// `severity >= "error" || (rule.startsWith("SEC") && file.contains("src/"))`.
+    /// Fields are file, rule, message, tool, line, column and severity
+    #[arg(long, value_name = "EXPR", conflicts_with_all = ["fail_on", "regression_check"])]
+    policy: Option<Expression>,
+
+    /// Fail only if a rule's total count grew compared to --base-report,
+    /// for rules whose line attribution is unreliable
+    #[arg(long, requires = "base_report")]
+    regression_check: bool,
+
+    #[command(flatten)]
+    limits: Limits,
+
+    #[command(flatten)]
+    input_limit: InputLimit,
+
+    #[command(flatten)]
+    proximity: Proximity,
+
+    #[command(flatten)]
+    sign: SignArgs,
+
+    /// Save progress through the lint output to this file, and when it exists skip the
+    /// lines a killed run already processed; feed it the same output again
+    #[arg(
+        long,
+        value_name = "STATE",
+        conflicts_with_all = ["debounce", "regression_check", "fixed_summary", "invert"]
+    )]
+    resume: Option<PathBuf>,
+
+    /// Remove this prefix from reported paths, leaving them relative to the repository
+    /// root, for linters that ran where the checkout is at another path, e.g. `/workspace/`
+    /// in a container; can be given several times
+    #[arg(long, value_name = "PREFIX")]
+    strip_prefix: Vec<String>,
+
+    /// Whether reported paths through symlinks are resolved to their targets before
+    /// matching them to the diff, or kept as the linter spelled them
+    #[arg(long, value_enum, default_value_t)]
+    symlink_policy: SymlinkPolicy,
+
+    /// Silently ignore findings whose path is outside the repository instead of failing
+    #[arg(long)]
+    allow_external_paths: bool,
+
+    /// Report findings wherever they are, not only on changed lines; the baseline and
+    /// waivers still apply. Defaults to on for the gitleaks and trufflehog formats only,
+    /// as a leaked secret is a problem anywhere
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+    always_report: Option<bool>,
+
+    /// Fail instead of warning when the diff has no changed lines, which usually means a
+    /// wrong --gitref rather than an empty change
+    #[arg(long)]
+    fail_on_empty_diff: bool,
+
+    /// Write the fixes tools suggested for findings on changed lines into the working
+    /// tree, indented and terminated as `.editorconfig` sets for each file
+    #[arg(long)]
+    apply_fixes: bool,
+
+    /// Once fixes are applied, write the changed lines of the working tree, the findings
+    /// the fixes resolved and where the others moved to PATH as JSON, for a second run
+    /// of the linter to confirm they converged
+    #[arg(long, value_name = "PATH", requires = "apply_fixes")]
+    fixes_report: Option<PathBuf>,
+
+    /// Hand the findings to the publishers, and run the publishers, in an order shuffled
+    /// with SEED, a random one printed when not given, to find publishers depending on
+    /// the order; printed outputs keep theirs
+    #[arg(long, value_name = "SEED")]
+    shuffle: Option<Option<u64>>,
+
+    /// What to do with findings in changed files on lines past the end of the file, as
+    /// from a stale report; they are counted in the --summary either way
+    #[arg(long, value_enum, default_value_t)]
+    on_stale_line: OnStaleLine,
+
+    /// Revision the lint output was produced from, when files changed since (e.g. by an
+    /// autoformatter); diagnostic lines are mapped to the current workdir lines
+    #[arg(long, value_name = "REV")]
+    lint_rev: Option<String>,
+
+    /// When a finding's line misses every hunk, match it anyway if the source excerpt
+    /// it quotes is on a changed line of the file
+    #[arg(long)]
+    match_excerpt: bool,
+
+    /// Instead of the findings, print the changed line ranges each tool reported nothing
+    /// on, to check that the linter actually scanned the changed files
+    #[arg(long, conflicts_with_all = ["output_format", "porcelain", "out", "quiet", "ci"])]
+    invert: bool,
+
+    #[command(flatten)]
+    audit: Audit,
+
+    /// Write findings from --base-report that were on changed lines and are now gone to
+    /// this file, as JSON if it ends in .json and Markdown otherwise
+    #[arg(long, requires = "base_report")]
+    fixed_summary: Option<PathBuf>,
+
+    /// Read all lint output first and only diff the files it mentions.
+    /// Always on for partial clones
+    #[arg(long)]
+    lazy_diff: bool,
+
+    /// Threads parsing lint output; with more than one, lines are read ahead in batches,
+    /// which speeds up very large logs but delays echoing a live linter's findings.
+    /// Findings still come out in input order, unless --unordered
+    #[arg(short, long, value_name = "N", default_value_t = 1)]
+    jobs: usize,
+
+    /// With --jobs, take findings as the threads parse them rather than in input order,
+    /// for the most throughput; echoed lines and outputs then vary in order between runs
+    #[arg(long, conflicts_with = "resume")]
+    unordered: bool,
+
+    /// Don't count lines as changed when only their line ending (CRLF or LF) or
+    /// trailing whitespace changed, e.g. after a formatter flipped line endings
+    #[arg(long)]
+    ignore_eol_only_changes: bool,
+
+    /// Also diff checked out submodules, recursively, against the commits the base
+    /// records for them, so findings in their files can match
+    #[arg(long)]
+    recurse_submodules: bool,
+
+    /// A nested repository, relative to --path, to diff against its own --gitref as
+    /// well, for workspaces of several repositories. May be given multiple times
+    #[arg(long = "repo", value_name = "PATH")]
+    repos: Vec<PathBuf>,
+
+    /// On partial clones, skip files whose base blobs are missing instead of fetching them
+    #[arg(long)]
+    no_lazy_fetch: bool,
+
+    /// Fail before doing anything if an option, the config or the CI environment would
+    /// make the run write files, fetch, run a linter or publish; implies --no-lazy-fetch,
+    /// and --sort holds every line in memory rather than spilling
+    #[arg(long)]
+    assert_read_only: bool,
+
+    /// Widen each hunk to the enclosing syntactic construct before matching
+    #[cfg(feature = "tree-sitter")]
+    #[arg(long, value_enum)]
+    expand_to: Option<expand::ExpandTo>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check complexity and length of the functions touched by the diff
+    Metrics(MetricsArgs),
+    /// Fail on TODO, FIXME or HACK markers added by the diff
+    Todos(TodoArgs),
+    /// Report files the diff adds without the license header, through the usual outputs
+    /// and publishers
+    License(LicenseArgs),
+    /// Filter lint output like --diff-file, against the diff of a pull request read from
+    /// the provider's API, without a checkout of the repository
+    RemotePr(RemotePrArgs),
+    /// Report the violations of architectural rules in a dependency-cruiser,
+    /// import-linter or jdeps report whose import is on changed lines
+    #[cfg(feature = "dependency-rules")]
+    Deps(DepsArgs),
+    /// Run the `[[gate]]` checks of the config against the same diff, with a summary and
+    /// exit code for all of them
+    Gates(GatesArgs),
+    /// Keep the hunk map in memory and filter lines sent over a socket
+    Serve(ServeArgs),
+    /// Maintain a baseline of known findings
+    Baseline(BaselineArgs),
+    /// Maintain the config file
+    Config(ConfigArgs),
+    /// Find the commit a finding first appeared in
+    Bisect(BisectArgs),
+    /// Print a file with the lines considered changed marked
+    Show(ShowArgs),
+    /// Exit 0 if all the given PATH:LINE locations are on changed lines and 1 otherwise,
+    /// for scripts
+    Contains(ContainsArgs),
+    /// Print the hunks of the diff and the lines each counts as changed, with pure
+    /// deletions listed apart
+    Hunks(HunksArgs),
+    /// Print the files the diff changes with their language and number of hunks
+    Files(FilesArgs),
+    /// Print the stable fingerprint of a finding, as used in reports
+    Fingerprint(FingerprintArgs),
+    /// Filter stdin like the default command, but write changed ranges and findings for
+    /// editor extensions instead of printing them
+    Export(ExportArgs),
+    /// Deliver findings that publishers failed to send earlier
+    Publish(PublishArgs),
+    /// Hand the findings of a pull request from a fork over to a `workflow_run` job to
+    /// publish, as runs for forks have no token to publish with
+    GhArtifact(GhArtifactArgs),
+    /// Count the findings in a --store database by week, rule, team, tool or file
+    #[cfg(feature = "store")]
+    Query(QueryArgs),
+    /// Write a Markdown or HTML trend report from a --store database
+    #[cfg(feature = "store")]
+    Report(ReportArgs),
+    /// Install the latest signed release over this binary, and optionally shell
+    /// completions and a pre-commit hook
+    SelfUpdate(UpdateArgs),
+    /// Print a pre-commit hook definition or a GitHub Action running diff-format with
+    /// this config, formats and linter
+    Generate(GenerateArgs),
+}
+
+/// The tree to diff against, with the commit it is of unless --gitref names a tree
+fn gitref_tree<'a>(repo: &'a Repository, args: &Args) -> Result<(Tree<'a>, Option<Oid>)> {
+    let object = state::resolve(repo, &args.gitref[0], &args.remote)?;
+    let commit = match object.peel_to_commit() {
+        Ok(commit) => commit,
+        Err(_) => return Ok((object.peel_to_tree().context("Gitref is not a tree")?, None)),
+    };
+    let base = if args.first_parent {
+        merge::first_parent_base(repo, &commit)?
+    } else if args.merge_base {
+        merge::merge_base(repo, &commit)?
+    } else {
+        merge::warn_if_merge(repo, commit.id());
+        commit
+    };
+    Ok((base.tree()?, Some(base.id())))
+}
+
+/// Collects the files mentioned in the lint output, to limit the diff to them
+fn lint_pathspecs(lines: &[String], parsers: &Parsers, mapper: &mut PathMapper) -> Vec<String> {
+    let mut paths: Vec<String> = lines
+        .iter()
+        .flat_map(|line| parsers.parse(&remove_ansi_colors(line)))
+        .map(|diagnostic| mapper.map(&diagnostic.path))
+        .collect();
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// Fetches base blobs a partial clone lacks, or drops their paths with `--no-lazy-fetch`
+fn ensure_blobs(
+    repo: &Repository,
+    tree: &Tree,
+    remote: &str,
+    paths: &mut Vec<String>,
+    no_lazy_fetch: bool,
+) -> Result<()> {
+    let missing = partial_clone::missing_blobs(repo, tree, paths)?;
+    if missing.is_empty() {
+        return Ok(());
+    }
+    if no_lazy_fetch {
+        for (path, _) in &missing {
+            warn!(
+                "Skipping '{}': its base blob is not available locally",
+                path
+            );
+        }
+        paths.retain(|path| !missing.iter().any(|(missing_path, _)| missing_path == path));
+        Ok(())
+    } else {
+        let oids: Vec<_> = missing.iter().map(|(_, oid)| *oid).collect();
+        partial_clone::fetch_blobs(repo, remote, &oids)
+    }
+}
+
+/// Splits the lint output of several tools by --demux and `##tool:` sections
+fn demux<'a>(args: &Args, config: &'a Config) -> Result<Demux<'a>> {
+    Demux::new(
+        &args.demux,
+        &config.parsers,
+        config.extension_formats(),
+        args.parser_strategy,
     )
-    .context("Issue when iterating over diff")?;
+}
 
-    Ok(hunkmap)
+/// Echoes a matched lint line to the streaming outputs, followed by a fixup note when
+/// attributing findings to commits
+fn stream_match(
+    streams: &mut Streams,
+    attributor: &mut Option<Attributor>,
+    line: &str,
+    diagnostic: &Diagnostic,
+) -> Result<()> {
+    if streams.is_empty() {
+        return Ok(());
+    }
+    let mut text = format!("{}\n", line);
+    let commit = match (attributor, diagnostic.line) {
+        (Some(attributor), Some(line_num)) => attributor.commit_for(&diagnostic.path, line_num),
+        _ => None,
+    };
+    if let Some(commit) = commit {
+        text.push_str(&format!("{}\n", attribution::fixup_note(&commit)));
+    }
+    streams.write(diagnostic, text)
 }
 
-fn remove_ansi_colors(text: &str) -> String {
-    let re = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
-    re.replace_all(text, "").to_string()
+/// Writes the --summary, if requested, once `matched` holds every reported finding
+fn write_summary(args: &Args, summary: &mut Summary, matched: &[Diagnostic]) -> Result<()> {
+    summary.shown(matched);
+    let destination = match &args.summary {
+        Some(destination) => destination,
+        None => return Ok(()),
+    };
+    let text = if args.summary_json {
+        summary.render_json()?
+    } else {
+        summary.render_text(args.lang)
+    };
+    destination.write(&text)
+}
+
+/// Adds the hunks of submodules with --recurse-submodules and of the --repo repositories
+fn add_nested_hunks(
+    repo: &Repository,
+    args: &Args,
+    tree: &Tree,
+    head_tree: Option<&Tree>,
+    file_hunks: &mut HunkMap,
+) -> Result<()> {
+    let ignore_eol = args.ignore_eol_only_changes;
+    if args.recurse_submodules {
+        file_hunks.extend(submodules::submodule_hunks(
+            repo, tree, head_tree, ignore_eol,
+        )?);
+    }
+    if !args.repos.is_empty() {
+        let workdir = repo.workdir().unwrap_or_else(|| repo.path());
+        file_hunks.extend(submodules::nested_repo_hunks(
+            workdir,
+            &args.repos,
+            &args.gitref[0],
+            &args.remote,
+            ignore_eol,
+        )?);
+    }
+    Ok(())
+}
+
+/// Recomputes the hunk map from scratch, as the server does on reload
+fn build_hunks(repo: &Repository, args: &Args) -> Result<HashMap<String, Vec<HunkRange>>> {
+    let (tree, extra_trees) = match &args.since {
+        Some(since) => (since::since_tree(repo, since)?.0, Vec::new()),
+        None => (
+            gitref_tree(repo, args)?.0,
+            ref_trees(repo, &args.gitref[1..])?,
+        ),
+    };
+    let ignore_eol = args.ignore_eol_only_changes;
+    let diff = progress::diff_with_spinner(repo, &tree, None, None, ignore_eol)?;
+    let mut file_hunks = progress::hunkmap_with_bar(&diff)?;
+    let excluded_trees = ref_trees(repo, &args.exclude_ref)?;
+    combine_refs(
+        repo,
+        &extra_trees,
+        &excluded_trees,
+        None,
+        None,
+        ignore_eol,
+        &mut file_hunks,
+    )?;
+    add_nested_hunks(repo, args, &tree, None, &mut file_hunks)?;
+    args.hunk_bounds.apply(args.deletions, &mut file_hunks);
+    args.proximity.apply(
+        repo.workdir().unwrap_or_else(|| repo.path()),
+        &mut file_hunks,
+    );
+    #[cfg(feature = "tree-sitter")]
+    if let Some(expand_to) = args.expand_to {
+        let workdir = repo
+            .workdir()
+            .context("Repository has no working directory")?;
+        expand::expand_hunks(expand_to, workdir, &mut file_hunks)?;
+    }
+    args.limits.apply(&mut file_hunks);
+    Ok(file_hunks)
+}
+
+/// Fills in what the command line left unset from a config profile
+fn apply_profile(args: &mut Args, profile: &ProfileConfig) -> Result<()> {
+    if args.out.is_empty() && !args.porcelain && !args.quiet {
+        args.output_format = args.output_format.or(profile.output_format);
+        args.quiet = profile.quiet.unwrap_or(false) && args.output_format.is_none();
+    }
+    if args.publish.is_empty() {
+        args.publish = profile.publish.clone();
+    }
+    args.ci = args.ci.or(profile.ci);
+    if args.fail_on.is_none()
+        && args.policy.is_none()
+        && !args.regression_check
+        && args.max_score.is_none()
+    {
+        args.fail_on = profile.fail_on;
+        args.policy = profile
+            .policy
+            .as_deref()
+            .map(Expression::parse)
+            .transpose()?;
+    }
+    Ok(())
 }
 
-fn parse_lint_location(line: &str, regex: &Regex) -> Option<(String, u32)> {
-    regex.captures(line).and_then(|captures| {
-        let filename = captures.get(1)?.as_str().to_string();
-        let line_num = captures.get(2)?.as_str().parse().ok()?;
-        Some((filename, line_num))
+fn render_context<'a>(
+    args: &'a Args,
+    config: &'a Config,
+    workdir: &'a Path,
+    file_hunks: &'a HunkMap,
+) -> RenderContext<'a> {
+    RenderContext::new(workdir)
+        .with_hunks(file_hunks)
+        .with_csv_columns(&args.csv_columns)
+        .with_rule_docs(config.rule_docs())
+}
+
+/// Writes the --split-out documents, returning their paths
+fn emit_split(
+    args: &Args,
+    config: &Config,
+    matched: &[Diagnostic],
+    workdir: &Path,
+    file_hunks: &HunkMap,
+) -> Result<Vec<PathBuf>> {
+    let owners = if args.split_out.iter().any(SplitOutput::uses_owners) {
+        CodeOwners::discover(workdir)?
+    } else {
+        CodeOwners::default()
+    };
+    let mut paths = Vec::new();
+    for split in &args.split_out {
+        let written = split.emit(
+            matched,
+            &render_context(args, config, workdir, file_hunks),
+            &owners,
+        )?;
+        info!(
+            "Wrote {} {:?} report(s) to {}",
+            written.len(),
+            split.format,
+            split.template
+        );
+        paths.extend(written);
+    }
+    Ok(paths)
+}
+
+/// Reports a diff without changed lines, naming the trees it compared, as every finding
+/// is then dropped and a misconfigured base would go unnoticed
+fn check_empty_diff(
+    args: &Args,
+    repo: &Repository,
+    tree: &Tree,
+    head_tree: Option<&Tree>,
+) -> Result<()> {
+    let head = match (head_tree, repo.head().ok().and_then(|head| head.target())) {
+        (Some(head), _) => format!("tree {}", head.id()),
+        (None, Some(commit)) => format!("the working tree at {}", commit),
+        (None, None) => "the working tree".to_string(),
+    };
+    let message = format!(
+        "No changed lines between {} (tree {}) and {}, so every finding is dropped; \
+         check --gitref",
+        args.gitref.join(", "),
+        tree.id(),
+        head
+    );
+    if args.fail_on_empty_diff {
+        bail!(message);
+    }
+    warn!("{}", message);
+    Ok(())
+}
+
+/// Whether --always-report keeps `diagnostic` off the diff
+fn always_reported(args: &Args, diagnostic: &Diagnostic) -> bool {
+    args.always_report.unwrap_or_else(|| {
+        diagnostic
+            .tool
+            .as_deref()
+            .and_then(|tool| Format::from_str(tool, false).ok())
+            .is_some_and(Format::is_secret_scanner)
     })
 }
 
+/// Appends the findings of the run to the --store database
+#[cfg(feature = "store")]
+fn store_run(
+    args: &Args,
+    run_info: &RunInfo,
+    repo: Option<&Repository>,
+    matched: &[Diagnostic],
+    workdir: &Path,
+) -> Result<()> {
+    if let Some(path) = &args.store {
+        let owners = CodeOwners::discover(workdir)?;
+        let run = store::Run::current(run_info, repo);
+        Store::open(path)?.record(&run, matched, &owners)?;
+        info!("Stored {} finding(s) in {}", matched.len(), path.display());
+    }
+    Ok(())
+}
+
+/// The findings that can fail the run, as those of rules still rolling out never do
+fn gating<'a>(
+    rollouts: &'a Rollouts,
+    today: Date,
+    matched: &'a [Diagnostic],
+) -> impl Iterator<Item = &'a Diagnostic> {
+    matched
+        .iter()
+        .filter(move |diagnostic| rollouts.active(diagnostic, today).is_none())
+}
+
+/// The score of the findings that can fail the run, with --max-score
+fn score(
+    args: &Args,
+    config: &Config,
+    rollouts: &Rollouts,
+    today: Date,
+    matched: &[Diagnostic],
+) -> Option<Score> {
+    let max = args.max_score?;
+    let value = config.score.score(gating(rollouts, today, matched));
+    if value > max {
+        warn!(
+            "The findings score {}, more than --max-score {}",
+            value, max
+        );
+    }
+    Some(Score { value, max })
+}
+
+/// Whether the findings that are not still rolling out fail the run, by --max-score,
+/// by --policy or by --fail-on and the per-path policies
+fn gate_fails(
+    args: &Args,
+    policies: &Policies,
+    rollouts: &Rollouts,
+    today: Date,
+    matched: &[Diagnostic],
+    score: Option<Score>,
+) -> bool {
+    if let Some(score) = score {
+        return score.exceeded();
+    }
+    gating(rollouts, today, matched).any(|diagnostic| fails(args, policies, diagnostic))
+}
+
+/// Whether `diagnostic` fails the run by the --policy or --fail-on, unless rolling out
+fn fails(args: &Args, policies: &Policies, diagnostic: &Diagnostic) -> bool {
+    match &args.policy {
+        Some(policy) => policy.evaluate(diagnostic),
+        None => policies
+            .fail_on(&diagnostic.path)
+            .unwrap_or(args.fail_on.map_or(FailOn::Info, FailOn::from))
+            .fails(diagnostic.effective_severity()),
+    }
+}
+
+/// Whether --fail-fast stops reading the lint output at `diagnostic`, just reported
+fn stops_at(
+    args: &Args,
+    policies: &Policies,
+    rollouts: &Rollouts,
+    today: Date,
+    diagnostic: &Diagnostic,
+) -> bool {
+    args.fail_fast
+        && rollouts.active(diagnostic, today).is_none()
+        && fails(args, policies, diagnostic)
+}
+
+/// Stops the linter once --fail-fast stopped reading at `stopped`, the line of the
+/// finding, after `lines` lines of lint output
+fn stop_early(linter: &mut Option<Linter>, stopped: &str, lines: usize) -> Result<()> {
+    if let Some(linter) = linter {
+        linter.terminate()?;
+    }
+    eprintln!(
+        "--fail-fast: stopped after {} line(s) of lint output at {}",
+        lines, stopped
+    );
+    Ok(())
+}
+
+/// The blocks of other languages embedded in files, by extension, from `[embedded]`
+fn embedded_blocks(config: &Config) -> Result<HashMap<String, EmbeddedBlocks>> {
+    config
+        .embedded
+        .iter()
+        .map(|(extension, embedded)| Ok((extension.clone(), EmbeddedBlocks::new(embedded)?)))
+        .collect::<Result<_>>()
+        .context("Invalid [embedded] config")
+}
+
+/// The names of the formats given, for --invert to list the lines each reported nothing on
+fn tool_names(args: &Args) -> Vec<&str> {
+    args.format
+        .iter()
+        .filter(|&format| *format != FormatSpec::Builtin(Format::Auto))
+        .map(FormatSpec::name)
+        .collect()
+}
+
+/// How each finding of the lint output is filtered, alike for the diff of a repository
+/// and for a patch: resolved and normalized, located, matched against the hunks, then
+/// checked against the baseline and waivers, and folded and streamed if reported
+struct Pipeline<'a> {
+    args: &'a Args,
+    config: &'a Config,
+    policies: &'a Policies,
+    waivers: &'a Waivers,
+    rollouts: &'a Rollouts,
+    today: Date,
+    workdir: &'a Path,
+    file_hunks: &'a HunkMap,
+    /// The hunks before --hunk-bounds, for --debug-bounds
+    raw_hunks: Option<&'a HunkMap>,
+    resolver: LocationResolver,
+    baseline: Option<Baseline>,
+    folder: Option<Folder<'a>>,
+    drift: Option<DriftMapper<'a>>,
+    attributor: Option<Attributor<'a>>,
+    summary: Summary,
+    /// The findings reported, in order
+    matched: Vec<Diagnostic>,
+    /// Whether the linter's output held any findings, changed or not
+    reported: bool,
+    /// Whether a finding --fail-fast stops at was reported
+    stopped: bool,
+    waived: BTreeMap<String, usize>,
+    rolling_out: BTreeMap<String, usize>,
+    coverage: Coverage,
+    rule_totals: RuleTotals,
+    current_findings: HashMap<FindingKey, usize>,
+    base_pairs: PairTotals,
+    current_pairs: PairTotals,
+    /// Debounced findings on the change, decided once all findings of their file are
+    /// counted
+    deferred: Vec<(String, Diagnostic)>,
+}
+
+impl<'a> Pipeline<'a> {
+    fn new(
+        args: &'a Args,
+        config: &'a Config,
+        (policies, waivers, rollouts): (&'a Policies, &'a Waivers, &'a Rollouts),
+        workdir: &'a Path,
+        file_hunks: &'a HunkMap,
+        resolver: LocationResolver,
+        run_info: &RunInfo,
+    ) -> Result<Self> {
+        Ok(Pipeline {
+            args,
+            config,
+            policies,
+            waivers,
+            rollouts,
+            today: waiver::today(),
+            workdir,
+            file_hunks,
+            raw_hunks: None,
+            resolver,
+            baseline: args.baseline.as_deref().map(Baseline::load).transpose()?,
+            folder: args.fold_equivalent.then(|| Folder::new(&config.fold)),
+            drift: None,
+            attributor: None,
+            summary: Summary::new(run_info.clone()),
+            matched: Vec::new(),
+            reported: false,
+            stopped: false,
+            waived: BTreeMap::new(),
+            rolling_out: BTreeMap::new(),
+            coverage: Coverage::default(),
+            rule_totals: RuleTotals::new(),
+            current_findings: HashMap::new(),
+            base_pairs: PairTotals::new(),
+            current_pairs: PairTotals::new(),
+            deferred: Vec::new(),
+        })
+    }
+
+    /// Counts the debounced findings of the --base-report, which those of the lint
+    /// output are reported past
+    fn with_base_report(mut self, base_report: &[Diagnostic]) -> Self {
+        let debounced = base_report
+            .iter()
+            .filter(|diagnostic| self.is_debounced(diagnostic));
+        let mut base_pairs = PairTotals::new();
+        for diagnostic in debounced {
+            regression::count_pair(&mut base_pairs, diagnostic);
+        }
+        self.base_pairs = base_pairs;
+        self
+    }
+
+    fn is_debounced(&self, diagnostic: &Diagnostic) -> bool {
+        diagnostic
+            .rule
+            .as_ref()
+            .is_some_and(|rule| self.args.debounce.contains(rule))
+    }
+
+    /// Feeds the lint output through the pipeline, recording progress for --resume and
+    /// stopping the linter at the first finding --fail-fast stops at
+    fn filter_lines(
+        &mut self,
+        parsed: impl Iterator<Item = Result<Parsed, diff_format::Error>>,
+        streams: &mut Streams,
+        resume: &mut Option<Resume>,
+        linter: &mut Option<Linter>,
+    ) -> Result<()> {
+        for (index, parsed) in parsed.enumerate() {
+            // Escapes are dropped for parsing regardless, --strip-escapes only changes
+            // what is echoed
+            let (line, diagnostics) = parsed.context("Could not read lint output")?;
+            if let Some(resume) = resume {
+                if resume.skip(index, &line)? {
+                    continue;
+                }
+            }
+            self.reported |= !diagnostics.is_empty();
+            for diagnostic in diagnostics {
+                self.filter(&line, diagnostic, streams)?;
+            }
+            if let Some(resume) = resume {
+                resume.processed(index, &line, &self.matched)?;
+            }
+            if self.stopped {
+                stop_early(linter, &line, index + 1)?;
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Filters a finding parsed from `line`
+    fn filter(
+        &mut self,
+        line: &str,
+        mut diagnostic: Diagnostic,
+        streams: &mut Streams,
+    ) -> Result<()> {
+        let (args, config, workdir, file_hunks) =
+            (self.args, self.config, self.workdir, self.file_hunks);
+        self.resolver.resolve(&mut diagnostic);
+        config.normalize(&mut diagnostic);
+        if let (None, Some(rule)) = (diagnostic.severity, &diagnostic.rule) {
+            diagnostic.severity = diagnostic::infer_severity(rule, &config.severity);
+        }
+        self.policies.escalate(&mut diagnostic);
+        if paths::escapes_root(&diagnostic.path, workdir) {
+            if args.allow_external_paths {
+                return Ok(());
+            }
+            bail!(
+                "'{}' is outside the repository, check the paths the linter reports \
+                 (or pass --allow-external-paths)",
+                diagnostic.path
+            );
+        }
+        // Only counted when used, as huge logs have millions of findings
+        if args.regression_check {
+            regression::count_rule(&mut self.rule_totals, &diagnostic);
+        }
+        let debounced = self.is_debounced(&diagnostic);
+        if debounced {
+            regression::count_pair(&mut self.current_pairs, &diagnostic);
+        }
+        if args.fixed_summary.is_some() {
+            *self
+                .current_findings
+                .entry(fixed::finding_key(&diagnostic))
+                .or_insert(0) += 1;
+        }
+        if let (Some(drift), Some(line_num)) = (&mut self.drift, diagnostic.line) {
+            if let Some(end) = diagnostic.end_line {
+                diagnostic.end_line = drift.map(&diagnostic.path, end);
+            }
+            match drift.map(&diagnostic.path, line_num) {
+                Some(current) => diagnostic.line = Some(current),
+                None => {
+                    debug!("{}:{} was deleted since linting", diagnostic.path, line_num);
+                    return Ok(());
+                }
+            }
+        }
+        if file_hunks.contains_key(&diagnostic.path) {
+            if let Some(lines) = self.resolver.past_end(&diagnostic) {
+                self.summary.stale(&diagnostic);
+                if !args.on_stale_line.apply(&mut diagnostic, lines) {
+                    return Ok(());
+                }
+            }
+        }
+        if args.invert {
+            self.coverage.record(&diagnostic);
+        }
+        if let (Some(raw_hunks), Some(line_num)) = (self.raw_hunks, diagnostic.line) {
+            let near_miss = raw_hunks
+                .get(&diagnostic.path)
+                .and_then(|ranges| bounds::near_miss(ranges, line_num));
+            if let Some(near_miss) = near_miss {
+                eprintln!(
+                    "{}:{}: near hunk {}..{}: inclusive={} exclusive={}",
+                    diagnostic.path,
+                    line_num,
+                    near_miss.hunk.0,
+                    near_miss.hunk.1,
+                    near_miss.inclusive,
+                    near_miss.exclusive
+                );
+            }
+        }
+        if diagnostic.symbol.is_some() {
+            if manifest::is_manifest(&diagnostic.path) {
+                manifest::locate(workdir, &mut diagnostic);
+            } else {
+                pytest::locate(workdir, &mut diagnostic, file_hunks);
+            }
+        }
+        if diagnostic.tool.as_deref() == Some(Format::LinkCheck.name()) {
+            links::locate(workdir, &mut diagnostic, file_hunks);
+        }
+        let policy = config.match_policy(diagnostic.tool.as_deref());
+        let mut changed =
+            is_changed(file_hunks, &diagnostic, policy) || always_reported(args, &diagnostic);
+        if diagnostic.tool.as_deref() == Some(Format::Pytest.name()) {
+            let note = if changed {
+                Some("your change touched this failing test".to_string())
+            } else {
+                pytest::tested_module_changed(&diagnostic.path, file_hunks).map(|module| {
+                    changed = true;
+                    format!("your change touched {}, which this test covers", module)
+                })
+            };
+            if let Some(note) = note {
+                diagnostic.message = Some(format!("{} ({})", diagnostic.text(), note));
+            }
+        }
+        if let (false, true, Some(excerpt), Some(line_num), Some(hunk_ranges)) = (
+            changed,
+            args.match_excerpt,
+            &diagnostic.excerpt,
+            diagnostic.line,
+            file_hunks.get(&diagnostic.path),
+        ) {
+            if let Some(found) =
+                self.resolver
+                    .find_excerpt(&diagnostic.path, excerpt, line_num, hunk_ranges)
+            {
+                debug!(
+                    "{}:{}: excerpt found on changed line {}",
+                    diagnostic.path, line_num, found
+                );
+                diagnostic.line = Some(found);
+                changed = true;
+            }
+        }
+        if let (true, Some(baseline)) = (changed, &self.baseline) {
+            if baseline.contains(&diagnostic, &mut self.resolver) {
+                debug!("{}: in the baseline", line);
+                changed = false;
+            }
+        }
+        if !changed {
+            self.summary.pre_existing(&diagnostic);
+        }
+        let waiver = self
+            .waivers
+            .find(&diagnostic, self.today)
+            .filter(|waiver| !waiver.expired(self.today));
+        if let (true, Some(waiver)) = (changed, waiver) {
+            debug!("{}: waived by {}", line, waiver);
+            *self.waived.entry(waiver.to_string()).or_insert(0) += 1;
+            self.summary.waived(&diagnostic);
+            return Ok(());
+        }
+        if changed && debounced {
+            self.deferred.push((line.to_string(), diagnostic));
+        } else if args.audit.reports(changed) {
+            if let Some(folder) = &mut self.folder {
+                if folder.fold(&mut self.matched, &diagnostic) {
+                    debug!("{}: folded into an equivalent finding", line);
+                    return Ok(());
+                }
+            }
+            let line = args.audit.label(line, changed);
+            stream_match(streams, &mut self.attributor, &line, &diagnostic)?;
+            self.stopped |= stops_at(args, self.policies, self.rollouts, self.today, &diagnostic);
+            self.matched.push(diagnostic);
+        } else if args.audit.annotate {
+            let line = args.audit.label(line, false);
+            stream_match(streams, &mut None, &line, &diagnostic)?;
+        }
+        Ok(())
+    }
+
+    /// Filters the SARIF log or Checkstyle report `input` holds, as --input-format
+    /// says. The filtered Checkstyle report is written instead of echoed lines, the
+    /// SARIF log is returned to be written once the exit code is known
+    fn filter_report(
+        &mut self,
+        input: impl Iterator<Item = io::Result<String>>,
+        outputs: &[Output],
+    ) -> Result<Option<SarifLog>> {
+        let lines = input
+            .collect::<io::Result<Vec<_>>>()
+            .context("Could not read lines from stdin")?;
+        let workdir = self.workdir;
+        if self.args.input_format == InputFormat::Sarif {
+            let mut sarif = SarifLog::parse(&lines.join("\n"))?;
+            let diagnostics = sarif.diagnostics(workdir);
+            self.reported |= !diagnostics.is_empty();
+            let mut kept = self.filter_document(diagnostics)?.into_iter();
+            sarif.retain(workdir, |_| kept.next().unwrap_or(true));
+            return Ok(Some(sarif));
+        }
+        let mut report = CheckstyleReport::parse(&lines.join("\n"))?;
+        let diagnostics = report.diagnostics(workdir);
+        self.reported |= !diagnostics.is_empty();
+        let mut kept = self.filter_document(diagnostics)?.into_iter();
+        report.retain(workdir, |_| kept.next().unwrap_or(true));
+        // The filtered report takes the place of echoed lint lines
+        for output in outputs.iter().filter(|output| output.is_streaming()) {
+            output.destination.write(&report.render())?;
+        }
+        Ok(None)
+    }
+
+    /// Filters the findings of a SARIF log or Checkstyle report like those of lint
+    /// lines, returning which of them are reported: only those stay in the re-emitted
+    /// document, which is written instead of echoed lines
+    fn filter_document(&mut self, diagnostics: Vec<Diagnostic>) -> Result<Vec<bool>> {
+        let mut streams = Streams::new(&[], None, 0);
+        let mut reported = Vec::with_capacity(diagnostics.len());
+        let mut deferred = Vec::new();
+        for diagnostic in diagnostics {
+            let (matched, pending) = (self.matched.len(), self.deferred.len());
+            let line = diagnostic.raw.clone();
+            self.filter(&line, diagnostic, &mut streams)?;
+            if self.deferred.len() > pending {
+                deferred.push(reported.len());
+            }
+            reported.push(self.matched.len() > matched);
+        }
+        // The document is the whole input, so its debounced findings are all counted
+        for (index, increased) in deferred.into_iter().zip(self.settle(&mut streams)?) {
+            reported[index] = increased;
+        }
+        Ok(reported)
+    }
+
+    /// Reports the debounced findings whose count increased in their file, once all
+    /// are counted, returning which were reported
+    fn settle(&mut self, streams: &mut Streams) -> Result<Vec<bool>> {
+        let args = self.args;
+        let mut reported = Vec::with_capacity(self.deferred.len());
+        for (line, diagnostic) in std::mem::take(&mut self.deferred) {
+            let increased =
+                regression::pair_increased(&self.base_pairs, &self.current_pairs, &diagnostic);
+            if !increased {
+                debug!(
+                    "{}: {:?} did not increase in the file, not reporting",
+                    line, diagnostic.rule
+                );
+                self.summary.pre_existing(&diagnostic);
+            }
+            let echoed = args.audit.label(&line, increased);
+            reported.push(args.audit.reports(increased));
+            if args.audit.reports(increased) {
+                stream_match(streams, &mut self.attributor, &echoed, &diagnostic)?;
+                self.matched.push(diagnostic);
+            } else if args.audit.annotate {
+                stream_match(streams, &mut None, &echoed, &diagnostic)?;
+            }
+        }
+        Ok(reported)
+    }
+
+    /// Reports the debounced findings left, and applies the rollouts to the findings
+    /// reported
+    fn finish(&mut self, mut streams: Streams) -> Result<()> {
+        self.settle(&mut streams)?;
+        streams.finish()?;
+        for diagnostic in &mut self.matched {
+            if let Some(rollout) = self.rollouts.apply(diagnostic, self.today) {
+                *self.rolling_out.entry(rollout.to_string()).or_insert(0) += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Prints how many findings each waiver and rollout spared
+    fn print_tallies(&self) {
+        for (waiver, count) in &self.waived {
+            eprintln!("{} finding(s) waived: {}", count, waiver);
+        }
+        for (rollout, count) in &self.rolling_out {
+            eprintln!("{} finding(s) not failing yet: {}", count, rollout);
+        }
+    }
+
+    /// Warns once of each expired waiver covering a reported finding, which fail the
+    /// run, and returns them
+    fn expired_waivers(&self) -> Vec<String> {
+        let mut expired: Vec<_> = self
+            .matched
+            .iter()
+            .filter_map(|diagnostic| self.waivers.find(diagnostic, self.today))
+            .filter(|waiver| waiver.expired(self.today))
+            .map(ToString::to_string)
+            .collect();
+        expired.sort();
+        expired.dedup();
+        for waiver in &expired {
+            warn!(
+                "Waiver for {} has expired, its findings fail the run",
+                waiver
+            );
+        }
+        expired
+    }
+}
+
+/// Keeps the fixes of the formatter diff `input` holds that touch `file_hunks`, writing
+/// them instead of echoed lines, and returns whether it held any and their findings
+fn filter_formatter_diff(
+    input: impl Iterator<Item = io::Result<String>>,
+    file_hunks: &HunkMap,
+    outputs: &[Output],
+) -> Result<(bool, Vec<Diagnostic>)> {
+    let lines = input
+        .collect::<io::Result<Vec<_>>>()
+        .context("Could not read lines from stdin")?;
+    let mut formatter_diff = FormatterDiff::parse(lines.iter().map(String::as_str));
+    let reported = formatter_diff.paths().next().is_some();
+    formatter_diff.filter(file_hunks);
+    // The filtered patch takes the place of echoed lint lines
+    for output in outputs.iter().filter(|output| output.is_streaming()) {
+        output.destination.write(&formatter_diff.render())?;
+    }
+    Ok((reported, formatter_diff.diagnostics()))
+}
+
+/// Writes the filtered SARIF log, if the input was one, recording the invocation that
+/// exits with `exit_code`
+fn emit_sarif(
+    args: &Args,
+    outputs: &[Output],
+    run_info: &RunInfo,
+    provenance: Option<&sarif::Provenance>,
+    sarif: Option<SarifLog>,
+    exit_code: i32,
+) -> Result<()> {
+    let mut sarif = match sarif {
+        Some(sarif) => sarif,
+        None => return Ok(()),
+    };
+    let invocation = sarif::Invocation {
+        arguments: env::args().skip(1).collect(),
+        run: run_info.clone(),
+        ended: run::now(),
+        exit_code,
+    };
+    sarif.annotate(provenance, &invocation);
+    sarif.redact(&args.redact);
+    // The filtered log takes the place of echoed lint lines
+    for output in outputs.iter().filter(|output| output.is_streaming()) {
+        output.destination.write(&sarif.render())?;
+    }
+    Ok(())
+}
+
+/// Filters lint output against the hunks of a patch, for --diff-file and remote-pr.
+/// Only what needs no repository applies: locations are resolved against --path and
+/// findings go through the same pipeline as against a repository's diff
+fn filter_patch(
+    args: &Args,
+    config: &Config,
+    patch: &str,
+    outputs: &[Output],
+    (input, tally): (Lines, Tally),
+    mut linter: Option<Linter>,
+) -> Result<()> {
+    let mut file_hunks = unified_diff::hunkmap(patch);
+    debug!("Patch changes {} file(s)", file_hunks.len());
+    if let Some(expected) = &args.expect_checksum {
+        bounds::expect_checksum(expected, &file_hunks)?;
+    }
+    let raw_hunks = args.debug_bounds.then(|| file_hunks.clone());
+    args.hunk_bounds.apply(args.deletions, &mut file_hunks);
+    args.proximity.apply(&args.path, &mut file_hunks);
+    let degraded = args.limits.apply(&mut file_hunks);
+
+    let parsers = Arc::new(
+        Parsers::with_profiles(&args.format, &config.parsers, config.extension_formats())?
+            .with_strategy(args.parser_strategy),
+    );
+    let policies = config.policies()?;
+    let waivers = config.waivers()?;
+    let rollouts = config.rollouts()?;
+    let paths = PathMapper::new(&args.path, &env::current_dir()?)
+        .with_strip_prefixes(&args.strip_prefix)
+        .with_symlink_policy(args.symlink_policy);
+    let repo = Repository::discover(&args.path).ok();
+    let languages = language::detect_all(repo.as_ref(), &args.path, &file_hunks);
+    let resolver = LocationResolver::new(&args.path)
+        .with_embedded(embedded_blocks(config)?)
+        .with_paths(paths)
+        .with_languages(languages);
+    let run_info = RunInfo::new(args.run_id.as_deref());
+    let mut pipeline = Pipeline::new(
+        args,
+        config,
+        (&policies, &waivers, &rollouts),
+        &args.path,
+        &file_hunks,
+        resolver,
+        &run_info,
+    )?;
+    pipeline.raw_hunks = raw_hunks.as_ref();
+    let mut resume = args.resume.as_deref().map(Resume::load).transpose()?;
+    if let Some(resume) = &resume {
+        pipeline.matched.extend_from_slice(resume.matched());
+        pipeline.reported |= !pipeline.matched.is_empty();
+    }
+    // Documents are filtered whole, like against a repository's diff
+    let mut sarif_log = None;
+    let input: Lines = match args.input_format {
+        InputFormat::FormatterDiff => {
+            let (reported, matched) = filter_formatter_diff(input, &file_hunks, outputs)?;
+            pipeline.reported |= reported;
+            pipeline.matched.extend(matched);
+            Box::new(std::iter::empty())
+        }
+        InputFormat::Sarif | InputFormat::Checkstyle => {
+            sarif_log = pipeline.filter_report(input, outputs)?;
+            Box::new(std::iter::empty())
+        }
+        _ => input,
+    };
+    let mut streams =
+        Streams::new(outputs, args.sort, args.buffer_limit).with_redactions(&args.redact);
+    let parsed = ParsedLines::new(input, parsers.clone(), args.jobs, args.strip_escapes)
+        .with_unordered(args.unordered)
+        .with_demux(demux(args, config)?);
+    pipeline.filter_lines(
+        notes::attach(parsed, !args.unordered && !args.fail_fast),
+        &mut streams,
+        &mut resume,
+        &mut linter,
+    )?;
+    pipeline.finish(streams)?;
+    let truncation = args.input_limit.truncation(&tally, args.lang);
+    pipeline.summary.truncated(truncation);
+    pipeline.summary.degraded(degraded);
+    pipeline.print_tallies();
+    let expired = pipeline.expired_waivers();
+    let Pipeline {
+        today,
+        mut summary,
+        matched,
+        reported,
+        ..
+    } = pipeline;
+
+    let score = score(args, config, &rollouts, today, &matched);
+    summary.scored(score);
+    let redacted = redact::diagnostics(&args.redact, &matched);
+    write_summary(args, &mut summary, &redacted)?;
+    for output in outputs.iter().filter(|output| !output.is_streaming()) {
+        output.emit(
+            &redacted,
+            &render_context(args, config, &args.path, &file_hunks),
+        )?;
+    }
+    emit_split(args, config, &redacted, &args.path, &file_hunks)?;
+    #[cfg(feature = "store")]
+    store_run(args, &run_info, repo.as_ref(), &redacted, &args.path)?;
+    if let Some(resume) = resume {
+        resume.finish()?;
+    }
+
+    let failed = !expired.is_empty()
+        || args.input_limit.fails(truncation)
+        || gate_fails(args, &policies, &rollouts, today, &matched, score);
+    let code = linter
+        .map(|linter| linter.finish(reported))
+        .transpose()?
+        .flatten()
+        .or(failed.then_some(1));
+    emit_sarif(args, outputs, &run_info, None, sarif_log, code.unwrap_or(0))?;
+    notify(args, &summary, code)?;
+    match code {
+        Some(code) => exit(code),
+        None => Ok(()),
+    }
+}
+
+/// Posts the summary of the run exiting with `code` to the --notify-webhook
+fn notify(args: &Args, summary: &Summary, code: Option<i32>) -> Result<()> {
+    if let Some(url) = &args.notify_webhook {
+        let http = publish::agent(args.ca_cert.as_deref())?;
+        let code = code.unwrap_or(0);
+        notify::notify(&http, url, args.webhook_payload, summary, code);
+    }
+    Ok(())
+}
+
+fn read_report(
+    path: &Path,
+    input_format: InputFormat,
+    parsers: &Parsers,
+    root: &Path,
+    mapper: &mut PathMapper,
+) -> Result<Vec<Diagnostic>> {
+    if input_format == InputFormat::Sarif {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        return Ok(SarifLog::parse(&text)?.diagnostics(root));
+    }
+    if input_format == InputFormat::Checkstyle {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        return Ok(CheckstyleReport::parse(&text)?.diagnostics(root));
+    }
+    let file = File::open(path).with_context(|| format!("Can't open {}", path.display()))?;
+    let mut diagnostics = Vec::new();
+    for line in input_format.lines(BufReader::new(file)) {
+        let line = line.with_context(|| format!("Could not read {}", path.display()))?;
+        for mut diagnostic in parsers.parse(&remove_ansi_colors(&line)) {
+            diagnostic.path = mapper.map(&diagnostic.path);
+            diagnostics.push(diagnostic);
+        }
+    }
+    Ok(diagnostics)
+}
+
+/// The options given that would make the run write, fetch or run something, which
+/// --assert-read-only refuses
+fn writing_options(args: &Args) -> Vec<&'static str> {
+    let command = match &args.command {
+        Some(Command::Export(_)) => Some("export"),
+        Some(Command::Publish(_)) => Some("publish"),
+        Some(Command::GhArtifact(_)) => Some("gh-artifact"),
+        Some(Command::Serve(_)) => Some("serve"),
+        Some(Command::SelfUpdate(_)) => Some("self-update"),
+        Some(Command::Baseline(baseline_args)) if baseline_args.action().is_some() => {
+            Some("baseline migrate")
+        }
+        Some(Command::Config(_)) => Some("config migrate"),
+        Some(Command::Bisect(bisect_args)) if bisect_args.runs_linter() => Some("bisect --linter"),
+        #[cfg(feature = "store")]
+        Some(Command::Report(report_args)) if report_args.writes_file() => Some("report --out"),
+        _ => None,
+    };
+    #[allow(unused_mut)]
+    let mut options = vec![
+        (!args.exec.is_empty(), "-- <LINTER>"),
+        (args.output.is_some(), "--output"),
+        (
+            args.out
+                .iter()
+                .any(|output| !output.destination.is_stream()),
+            "--out",
+        ),
+        (
+            args.summary
+                .as_ref()
+                .is_some_and(|summary| !summary.is_stream()),
+            "--summary",
+        ),
+        (
+            args.emit_trends
+                .as_ref()
+                .is_some_and(|trends| !trends.is_stream()),
+            "--emit-trends",
+        ),
+        (!args.split_out.is_empty(), "--split-out"),
+        (!args.publish.is_empty(), "--publish"),
+        (args.publish_queue.is_some(), "--publish-queue"),
+        (args.sign.enabled(), "--sign"),
+        (args.resume.is_some(), "--resume"),
+        (args.apply_fixes, "--apply-fixes"),
+        (args.fixes_report.is_some(), "--fixes-report"),
+        (args.fixed_summary.is_some(), "--fixed-summary"),
+        (args.base_url.is_some(), "--base-url"),
+        (args.notify_webhook.is_some(), "--notify-webhook"),
+        (args.cache.is_some(), "--cache"),
+    ];
+    #[cfg(feature = "store")]
+    options.push((args.store.is_some(), "--store"));
+    command
+        .into_iter()
+        .chain(
+            options
+                .iter()
+                .filter(|(given, _)| *given)
+                .map(|&(_, option)| option),
+        )
+        .collect()
+}
+
+/// Exits once buffered output is written, which `process::exit` would lose
+fn exit(code: i32) -> ! {
+    if let Err(err) = output::flush_stdout() {
+        warn!("Unable to write to stdout: {}", err);
+    }
+    process::exit(code)
+}
+
 fn main() -> Result<()> {
-    env_logger::Builder::from_env(Env::default().default_filter_or("warn")).init();
-    let args = Args::parse();
+    let result = run().inspect_err(|err| {
+        if err.downcast_ref::<CheckoutError>().is_some() {
+            eprintln!("Error: {:#}", err);
+            exit(state::EXIT_CODE);
+        }
+        if let Some(library) = err.downcast_ref::<diff_format::Error>() {
+            eprintln!("Error: {:#}", err);
+            exit(library.exit_code());
+        }
+    });
+    output::flush_stdout().context("Unable to write to stdout")?;
+    result
+}
+
+fn run() -> Result<()> {
+    // Logs never share a stream with results
+    env_logger::Builder::from_env(Env::default().default_filter_or("warn"))
+        .target(env_logger::Target::Stderr)
+        .init();
+    let mut args = Args::parse();
+    if args.assert_read_only {
+        let writing = writing_options(&args);
+        if !writing.is_empty() {
+            bail!(
+                "--assert-read-only, but these would write: {}",
+                writing.join(", ")
+            );
+        }
+        args.no_lazy_fetch = true;
+        args.buffer_limit = usize::MAX;
+    }
+
+    if args.check_config {
+        let config = Config::discover(args.config.as_deref(), &args.path, !args.no_env_interp)?;
+        let problems = config.validate();
+        for problem in &problems {
+            eprintln!("{}", problem);
+        }
+        if !problems.is_empty() {
+            exit(1);
+        }
+        eprintln!("Config OK");
+        return Ok(());
+    }
+    if let Some(Command::Config(config_args)) = &args.command {
+        return config_args.run(args.config.as_deref(), &args.path);
+    }
+    if let Some(Command::Fingerprint(fingerprint_args)) = &args.command {
+        println!("{}", fingerprint_args.fingerprint(&args.path)?);
+        return Ok(());
+    }
+    if let Some(Command::Publish(publish_args)) = &args.command {
+        return publish_args.drain(&args.path, args.ca_cert.as_deref(), args.pacing);
+    }
+    if let Some(Command::GhArtifact(artifact_args)) = &args.command {
+        if !artifact_args.uploads() {
+            return artifact_args.download(args.ca_cert.as_deref());
+        }
+    }
+    if let Some(Command::SelfUpdate(update_args)) = &args.command {
+        return update_args.run(&args.path, args.ca_cert.as_deref(), Args::command());
+    }
+    #[cfg(feature = "store")]
+    if let Some(Command::Query(query_args)) = &args.command {
+        return query_args.run();
+    }
+    #[cfg(feature = "store")]
+    if let Some(Command::Report(report_args)) = &args.command {
+        return report_args.run();
+    }
+    let config = Config::discover(args.config.as_deref(), &args.path, !args.no_env_interp)?;
+    if let Some(Command::Generate(generate_args)) = &args.command {
+        let setup = generate::Setup {
+            config_path: args.config.as_deref(),
+            config: &config,
+            formats: &args.format,
+            input_format: args.input_format,
+        };
+        print!("{}", generate_args.render(&setup)?);
+        if let Some(instructions) = generate_args.instructions() {
+            eprint!("{}", instructions);
+        }
+        return Ok(());
+    }
+    if let Some((name, profile)) = config.profile(args.profile.as_deref())? {
+        info!("Using [profile.{}]", name);
+        apply_profile(&mut args, profile).with_context(|| format!("Invalid [profile.{}]", name))?;
+    }
+    let ci_plan = args.ci.map(Ci::plan).unwrap_or_default();
+    let mut outputs = if args.out.is_empty() {
+        vec![Output {
+            format: args
+                .output_format
+                .or(args.porcelain.then_some(OutputFormat::Porcelain))
+                .or(ci_plan.output_format)
+                .unwrap_or(OutputFormat::Text),
+            destination: match &args.output {
+                Some(path) if args.append => Destination::Append(path.clone()),
+                Some(path) => Destination::File(path.clone()),
+                None => Destination::Stdout,
+            },
+        }]
+    } else {
+        args.out.clone()
+    };
+    outputs.extend(ci_plan.reports.iter().map(|(format, path)| Output {
+        format: *format,
+        destination: Destination::File(path.clone()),
+    }));
+    if args.assert_read_only {
+        // The command line was checked, but the profile and --ci can add to it
+        let mut writing = Vec::new();
+        if outputs.iter().any(|output| !output.destination.is_stream()) {
+            writing.push("the reports of --ci");
+        }
+        if !args.publish.is_empty() || !ci_plan.publish.is_empty() {
+            writing.push("the publishers of the profile or --ci");
+        }
+        if !writing.is_empty() {
+            bail!(
+                "--assert-read-only, but these would write: {}",
+                writing.join(", ")
+            );
+        }
+    }
+    if args.quiet || args.invert || matches!(args.command, Some(Command::Export(_))) {
+        outputs.retain(|output| !output.destination.is_stream());
+    }
+
+    match (&args.diff_file, &args.command) {
+        (Some(patch), None) if patch == Path::new("-") && args.exec.is_empty() => {
+            bail!("--diff-file - reads the patch from stdin, give the linter to run after --")
+        }
+        (Some(_), Some(_)) => bail!("--diff-file only filters lint output"),
+        _ => {}
+    }
 
-    let repo = Repository::open(args.path).context("Can't open repository")?;
-    let diff = get_diff(&repo, &args.gitref)?;
+    // Start draining stdin before the slow parts so the linter can finish writing
+    let filters_stdin = match &args.command {
+        None | Some(Command::Export(_)) | Some(Command::RemotePr(_)) => true,
+        Some(Command::Baseline(baseline_args)) => baseline_args.action().is_none(),
+        Some(Command::GhArtifact(artifact_args)) => artifact_args.uploads(),
+        _ => false,
+    };
+    let mut linter = None;
+    if args.cache.is_some() && args.input_format != InputFormat::Text {
+        bail!("--cache stores lint output by line, so it needs --input-format text");
+    }
+    // With --cache, the linter is run once the files missing from it are known
+    let input = if filters_stdin && !args.exec.is_empty() && args.cache.is_none() {
+        let (spawned, lines) = Linter::spawn(
+            &args.exec,
+            args.input_format,
+            args.input_buffer,
+            args.fail_fast,
+        )?;
+        linter = Some(spawned);
+        lines
+    } else if filters_stdin && args.exec.is_empty() {
+        args.input_format.read_stdin(args.input_buffer)
+    } else {
+        Box::new(std::iter::empty())
+    };
+    let (mut input, tally) = args.input_limit.apply(input);
 
-    let file_hunks = generate_hunkmap(&diff)?;
+    if let Some(Command::RemotePr(pr_args)) = &args.command {
+        let patch = pr_args.fetch_diff(&publish::agent(args.ca_cert.as_deref())?)?;
+        return filter_patch(&args, &config, &patch, &outputs, (input, tally), linter);
+    }
+    if let Some(patch) = &args.diff_file {
+        let patch = if patch == Path::new("-") {
+            io::read_to_string(io::stdin()).context("Could not read the patch from stdin")?
+        } else {
+            fs::read_to_string(patch)
+                .with_context(|| format!("Unable to read patch {}", patch.display()))?
+        };
+        return filter_patch(&args, &config, &patch, &outputs, (input, tally), linter);
+    }
 
-    let python_regex = Regex::new(r#"(.+?):(\d+)"#).expect("Failed to create python regex");
+    let (remote_base, repo) = match (&args.base_url, &args.base_ref) {
+        (Some(url), Some(gitref)) => {
+            let (base, repo) = RemoteBase::fetch(url, gitref, &args.path)?;
+            args.gitref = vec![remote_base::BASE_REF.to_string()];
+            (Some(base), repo)
+        }
+        _ => (
+            None,
+            Repository::open(&args.path).context("Can't open repository")?,
+        ),
+    };
+    for gitref in args.gitref.iter_mut().filter(|gitref| *gitref == "auto") {
+        *gitref = merge::default_branch(&repo, &args.remote)?;
+    }
+    let workdir = repo.workdir().unwrap_or_else(|| repo.path());
+    let mut paths = PathMapper::new(workdir, &env::current_dir()?)
+        .with_strip_prefixes(&args.strip_prefix)
+        .with_symlink_policy(args.symlink_policy);
+    let parsers = Arc::new(
+        Parsers::with_profiles(&args.format, &config.parsers, config.extension_formats())?
+            .with_strategy(args.parser_strategy),
+    );
+    if let Some(Command::Bisect(bisect_args)) = &args.command {
+        print!("{}", bisect_args.run(&repo, &parsers)?);
+        return Ok(());
+    }
+    let policies = config.policies()?;
+    let waivers = config.waivers()?;
+    let rollouts = config.rollouts()?;
+    let range = match &args.per_commit {
+        Some(range) => Some(CommitRange::parse(&repo, range)?),
+        None => None,
+    };
+    let commit_range = match &args.range {
+        Some(range) => Some(CommitRange::parse(&repo, range)?),
+        None => None,
+    };
+    let commit_range = range.as_ref().or(commit_range.as_ref());
+    let index_tree = if args.staged {
+        Some(index_tree(&repo)?)
+    } else {
+        None
+    };
+    let ((tree, base_commit), head_tree) = match (commit_range, &args.since) {
+        (Some(range), _) => (
+            (range.base.tree()?, Some(range.base.id())),
+            Some(range.head.tree()?),
+        ),
+        (None, Some(since)) => (since::since_tree(&repo, since)?, index_tree),
+        (None, None) => (gitref_tree(&repo, &args)?, index_tree),
+    };
+    if head_tree.is_none() {
+        state::require_workdir(&repo)?;
+    }
+    // Further --gitref values only make sense when diffing against refs
+    let extra_trees = match (commit_range, &args.since) {
+        (None, None) => ref_trees(&repo, &args.gitref[1..])?,
+        _ => Vec::new(),
+    };
+    let excluded_trees = ref_trees(&repo, &args.exclude_ref)?;
 
-    let mut failed = false;
-    let stdin = io::stdin();
-    for line in stdin.lock().lines() {
-        let line = line.expect("Could not read line from stdin");
+    if let Some(Command::Baseline(baseline_args)) = &args.command {
+        match baseline_args.action() {
+            Some(BaselineAction::Migrate { file }) => {
+                let diff = get_diff(
+                    &repo,
+                    &tree,
+                    head_tree.as_ref(),
+                    None,
+                    args.ignore_eol_only_changes,
+                )?;
+                baseline::migrate_file(file, &diff)?;
+            }
+            None => {
+                let mut resolver = LocationResolver::new(workdir).with_paths(paths);
+                let mut baseline = Baseline::default();
+                for line in input {
+                    let line = line.context("Could not read lint output")?;
+                    for mut diagnostic in parsers.parse(&remove_ansi_colors(&line)) {
+                        resolver.resolve(&mut diagnostic);
+                        baseline.insert(&diagnostic, &mut resolver);
+                    }
+                }
+                print!("{}", baseline.render());
+            }
+        }
+        return Ok(());
+    }
 
-        if let Some((filename, line_num)) =
-            parse_lint_location(&remove_ansi_colors(&line), &python_regex)
-        {
-            if let Some(hunk_ranges) = file_hunks.get(&filename) {
-                if is_number_in_sorted_ranges(hunk_ranges, line_num) {
-                    println!("{}", line);
-                    failed = true;
+    let base_report = match &args.base_report {
+        Some(path) => read_report(path, args.input_format, &parsers, workdir, &mut paths)?,
+        None => Vec::new(),
+    };
+
+    let promisor = partial_clone::promisor_remote(&repo);
+    let lazy_diff = filters_stdin && (args.lazy_diff || promisor.is_some());
+    let pathspecs = if lazy_diff {
+        if promisor.is_some() {
+            info!("Partial clone detected, limiting diff to files in lint output");
+        }
+        let lines = input
+            .collect::<io::Result<Vec<_>>>()
+            .context("Could not read lines from stdin")?;
+        let mut pathspecs = if args.input_format == InputFormat::FormatterDiff {
+            let diff = FormatterDiff::parse(lines.iter().map(String::as_str));
+            diff.paths().map(str::to_string).collect()
+        } else if args.input_format == InputFormat::Sarif {
+            let sarif = SarifLog::parse(&lines.join("\n"))?;
+            let diagnostics = sarif.diagnostics(workdir).into_iter();
+            diagnostics.map(|diagnostic| diagnostic.path).collect()
+        } else if args.input_format == InputFormat::Checkstyle {
+            let report = CheckstyleReport::parse(&lines.join("\n"))?;
+            let diagnostics = report.diagnostics(workdir).into_iter();
+            diagnostics.map(|diagnostic| diagnostic.path).collect()
+        } else {
+            lint_pathspecs(&lines, &parsers, &mut paths)
+        };
+        // Findings only in the base report may have been fixed
+        pathspecs.extend(base_report.iter().map(|diagnostic| diagnostic.path.clone()));
+        pathspecs.sort();
+        pathspecs.dedup();
+        if let Some(remote) = &promisor {
+            for tree in std::iter::once(&tree)
+                .chain(&extra_trees)
+                .chain(&excluded_trees)
+            {
+                ensure_blobs(&repo, tree, remote, &mut pathspecs, args.no_lazy_fetch)?;
+            }
+        }
+        debug!("Limiting diff to {} file(s)", pathspecs.len());
+        input = Box::new(lines.into_iter().map(Ok));
+        Some(pathspecs)
+    } else {
+        None
+    };
+    let diff = match &pathspecs {
+        Some(pathspecs) if pathspecs.is_empty() => None,
+        _ => Some(progress::diff_with_spinner(
+            &repo,
+            &tree,
+            head_tree.as_ref(),
+            pathspecs.as_deref(),
+            args.ignore_eol_only_changes,
+        )?),
+    };
+
+    #[allow(unused_mut)]
+    let mut file_hunks = match &diff {
+        Some(diff) => progress::hunkmap_with_bar(diff)?,
+        None => HashMap::new(),
+    };
+    // Only the workdir diff has an index between its sides
+    let staging = match (&head_tree, &remote_base) {
+        (None, None) if args.require_staged || matches!(args.command, Some(Command::Export(_))) => {
+            Some(Staging::new(&repo, &tree, pathspecs.as_deref())?)
+        }
+        _ => None,
+    };
+    if let (true, Some(staging)) = (args.require_staged, &staging) {
+        staging.require_staged(&mut file_hunks);
+    }
+    combine_refs(
+        &repo,
+        &extra_trees,
+        &excluded_trees,
+        head_tree.as_ref(),
+        pathspecs.as_deref(),
+        args.ignore_eol_only_changes,
+        &mut file_hunks,
+    )?;
+    if remote_base.is_none() {
+        add_nested_hunks(&repo, &args, &tree, head_tree.as_ref(), &mut file_hunks)?;
+    }
+    if let (true, Some(diff)) = (args.cherry_pick_aware, &diff) {
+        let upstream = state::resolve(&repo, &args.gitref[0], &args.remote)?
+            .peel_to_commit()
+            .context("Unable to resolve --gitref to a commit")?;
+        let head = repo
+            .head()
+            .and_then(|head| head.peel_to_commit())
+            .context("HEAD does not point to a commit")?;
+        let upstream_ids = cherry::upstream_hunk_ids(&repo, upstream.id(), head.id())?;
+        cherry::drop_equivalent(diff, &upstream_ids, &mut file_hunks)?;
+    }
+    // A diff limited to the linted files is empty whenever they are unchanged
+    if file_hunks.is_empty() && pathspecs.is_none() {
+        check_empty_diff(&args, &repo, &tree, head_tree.as_ref())?;
+    }
+    if let Some(expected) = &args.expect_checksum {
+        bounds::expect_checksum(expected, &file_hunks)?;
+    }
+    let raw_hunks =
+        (args.debug_bounds || args.invert || matches!(args.command, Some(Command::Gates(_))))
+            .then(|| file_hunks.clone());
+    let languages = language::detect_all(Some(&repo), workdir, &file_hunks);
+    if let Some(Command::Files(files_args)) = &args.command {
+        print!("{}", language::list(files_args, &file_hunks, &languages));
+        return Ok(());
+    }
+    if let Some(Command::Hunks(hunks_args)) = &args.command {
+        print!(
+            "{}",
+            bounds::list(hunks_args, args.hunk_bounds, args.deletions, &file_hunks)
+        );
+        return Ok(());
+    }
+    // Markers are only introduced on the added lines, whatever --hunk-bounds counts
+    if let Some(Command::Todos(todo_args)) = &args.command {
+        let workdir = repo
+            .workdir()
+            .context("Repository has no working directory")?;
+        if todos::check(todo_args, workdir, &file_hunks)? > 0 {
+            drop(remote_base);
+            exit(1);
+        }
+        return Ok(());
+    }
+    args.hunk_bounds.apply(args.deletions, &mut file_hunks);
+
+    if let Some(Command::Metrics(metrics_args)) = &args.command {
+        let workdir = repo
+            .workdir()
+            .context("Repository has no working directory")?;
+        if metrics::check(metrics_args, workdir, &file_hunks)? > 0 {
+            drop(remote_base);
+            exit(1);
+        }
+        return Ok(());
+    }
+    // Checked here, while added files are known, and filtered like lint output below
+    let checked = match (&args.command, &diff) {
+        (Some(Command::License(license_args)), Some(diff)) => {
+            license::check(license_args, workdir, &added_files(diff))?
+        }
+        #[cfg(feature = "dependency-rules")]
+        (Some(Command::Deps(deps_args)), _) => deps::check(deps_args, workdir)?,
+        _ => Vec::new(),
+    };
+
+    args.proximity.apply(workdir, &mut file_hunks);
+    #[cfg(feature = "tree-sitter")]
+    if let Some(expand_to) = args.expand_to {
+        let workdir = repo
+            .workdir()
+            .context("Repository has no working directory")?;
+        expand::expand_hunks(expand_to, workdir, &mut file_hunks)?;
+    }
+    let degraded = args.limits.apply(&mut file_hunks);
+
+    if let Some(Command::Contains(contains_args)) = &args.command {
+        if !contains::check(contains_args, &file_hunks) {
+            drop(remote_base);
+            exit(1);
+        }
+        return Ok(());
+    }
+
+    let mut resolver = LocationResolver::new(workdir)
+        .with_embedded(embedded_blocks(&config)?)
+        .with_paths(paths.clone())
+        .with_languages(languages);
+
+    if let Some(Command::Gates(gates_args)) = &args.command {
+        let added = diff.as_ref().map(added_files).unwrap_or_default();
+        let context = gates::Shared {
+            config: &config,
+            workdir,
+            file_hunks: &file_hunks,
+            raw_hunks: raw_hunks.as_ref().unwrap(),
+            added: &added,
+        };
+        let (outcomes, passed) = gates::run(gates_args, &context, &mut resolver, |gate| {
+            let specs: Vec<FormatSpec> = match gate.format.is_empty() {
+                true => vec![FormatSpec::Builtin(Format::Auto)],
+                false => gate
+                    .format
+                    .iter()
+                    .map(|name| name.parse())
+                    .collect::<Result<_, _>>()?,
+            };
+            let parsers =
+                Parsers::with_profiles(&specs, &config.parsers, config.extension_formats())?
+                    .with_strategy(args.parser_strategy);
+            read_report(
+                &gate.report,
+                gate.input_format,
+                &parsers,
+                workdir,
+                &mut paths,
+            )
+        })?;
+        eprint!("{}", gates::render(&outcomes, passed, args.lang));
+        if !passed {
+            drop(remote_base);
+            exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Show(show_args)) = &args.command {
+        let source = fs::read_to_string(workdir.join(&show_args.file))
+            .with_context(|| format!("Unable to read '{}'", show_args.file))?;
+        let findings = match &show_args.report {
+            Some(report) => {
+                let mut lines = Vec::new();
+                for mut diagnostic in
+                    read_report(report, args.input_format, &parsers, workdir, &mut paths)?
+                {
+                    resolver.resolve(&mut diagnostic);
+                    if diagnostic.path == show_args.file {
+                        lines.extend(diagnostic.line);
+                    }
+                }
+                Some(lines)
+            }
+            None => None,
+        };
+        let ranges = file_hunks
+            .get(&show_args.file)
+            .map_or(&[][..], Vec::as_slice);
+        print!(
+            "{}",
+            show::render(
+                &source,
+                ranges,
+                findings.as_deref(),
+                show_args.context,
+                io::stdout().is_terminal()
+            )
+        );
+        return Ok(());
+    }
+
+    if let Some(Command::Serve(serve_args)) = &args.command {
+        return serve::serve(serve_args.socket(), |request| match request {
+            serve::Request::Reload => {
+                file_hunks = build_hunks(&repo, &args)?;
+                Ok("reloaded".to_string())
+            }
+            serve::Request::Line(line) => {
+                let kept =
+                    parsers
+                        .parse(&remove_ansi_colors(line))
+                        .into_iter()
+                        .any(|mut diagnostic| {
+                            resolver.resolve(&mut diagnostic);
+                            let policy = config.match_policy(diagnostic.tool.as_deref());
+                            is_changed(&file_hunks, &diagnostic, policy)
+                        });
+                Ok(if kept { "kept" } else { "dropped" }.to_string())
+            }
+        });
+    }
+
+    let drift = match &args.lint_rev {
+        Some(rev) => Some(DriftMapper::new(&repo, get_tree(&repo, rev)?, workdir)),
+        None => None,
+    };
+    let mut matched = Vec::new();
+    // Whether the linter's output held any findings, changed or not
+    let mut reported = false;
+    // Written once the exit code it records is known
+    let mut sarif_log = None;
+    let mut cached_run = None;
+    if let (Some(dir), true) = (&args.cache, filters_stdin) {
+        let cache = ResultCache::new(dir, &args.exec, &args.format, &args.cache_key_file)?;
+        let cwd = env::current_dir()?;
+        let (cached, run) = cache.plan(workdir, &cwd, file_hunks.keys(), &args.exec)?;
+        let cached = cached.into_iter().map(Ok);
+        input = match run.command() {
+            Some(command) => {
+                let (spawned, lines) =
+                    Linter::spawn(command, args.input_format, args.input_buffer, false)?;
+                linter = Some(spawned);
+                Box::new(cached.chain(run.tee(lines)))
+            }
+            None => Box::new(cached),
+        };
+        cached_run = Some(run);
+    }
+    if args.input_format == InputFormat::FormatterDiff {
+        (reported, matched) = filter_formatter_diff(input, &file_hunks, &outputs)?;
+        input = Box::new(std::iter::empty());
+    }
+    let run_info = RunInfo::new(args.run_id.as_deref());
+    let mut resume = args.resume.as_deref().map(Resume::load).transpose()?;
+    if let Some(resume) = &resume {
+        matched.extend_from_slice(resume.matched());
+        reported |= !matched.is_empty();
+    }
+    let mut pipeline = Pipeline::new(
+        &args,
+        &config,
+        (&policies, &waivers, &rollouts),
+        workdir,
+        &file_hunks,
+        resolver,
+        &run_info,
+    )?
+    .with_base_report(&base_report);
+    pipeline.raw_hunks = raw_hunks.as_ref().filter(|_| args.debug_bounds);
+    pipeline.drift = drift;
+    pipeline.attributor = range.as_ref().map(|range| Attributor::new(&repo, range));
+    pipeline.matched = matched;
+    pipeline.reported = reported;
+    if matches!(
+        args.input_format,
+        InputFormat::Sarif | InputFormat::Checkstyle
+    ) {
+        sarif_log = pipeline.filter_report(input, &outputs)?;
+        input = Box::new(std::iter::empty());
+    }
+    let mut streams =
+        Streams::new(&outputs, args.sort, args.buffer_limit).with_redactions(&args.redact);
+    let parsed = ParsedLines::new(input, parsers.clone(), args.jobs, args.strip_escapes)
+        .with_unordered(args.unordered)
+        .with_demux(demux(&args, &config)?);
+    let parsed = notes::attach(parsed, !args.unordered && !args.fail_fast).chain(
+        checked
+            .into_iter()
+            .map(|diagnostic| Ok((diagnostic.raw.clone(), vec![diagnostic]))),
+    );
+    pipeline.filter_lines(parsed, &mut streams, &mut resume, &mut linter)?;
+    pipeline.finish(streams)?;
+    let truncation = args.input_limit.truncation(&tally, args.lang);
+    pipeline.summary.truncated(truncation);
+    pipeline.summary.degraded(degraded);
+
+    if args.invert {
+        let raw_hunks = raw_hunks.as_ref().unwrap();
+        print!(
+            "{}",
+            pipeline.coverage.render(&tool_names(&args), raw_hunks)
+        );
+        return Ok(());
+    }
+
+    if args.sign.enabled()
+        && args.split_out.is_empty()
+        && !outputs
+            .iter()
+            .any(|output| output.destination.path().is_some())
+    {
+        warn!("--sign only signs reports written to files, see --out");
+    }
+    pipeline.print_tallies();
+    let expired = pipeline.expired_waivers();
+    let Pipeline {
+        today,
+        mut summary,
+        matched,
+        reported,
+        rule_totals,
+        current_findings,
+        ..
+    } = pipeline;
+    let score = score(&args, &config, &rollouts, today, &matched);
+    summary.scored(score);
+    let mut redacted = redact::diagnostics(&args.redact, &matched);
+    write_summary(&args, &mut summary, &redacted)?;
+
+    let revisions = Revisions {
+        base: tree.id(),
+        head: head_tree.as_ref().map(Tree::id),
+    };
+    for output in outputs.iter().filter(|output| !output.is_streaming()) {
+        output.emit(
+            &redacted,
+            &render_context(&args, &config, workdir, &file_hunks),
+        )?;
+        if let Some(path) = output.destination.path() {
+            info!("Wrote {:?} report to {}", output.format, path.display());
+            args.sign.sign(path, &revisions)?;
+        }
+    }
+    for path in emit_split(&args, &config, &redacted, workdir, &file_hunks)? {
+        args.sign.sign(&path, &revisions)?;
+    }
+    #[cfg(feature = "store")]
+    store_run(&args, &run_info, Some(&repo), &redacted, workdir)?;
+    if let Some(destination) = &args.emit_trends {
+        let current = repo.head().ok();
+        let range_head = commit_range.map(|range| range.head.id());
+        let base = totals::Revision {
+            sha: base_commit.map(|oid| oid.to_string()),
+            name: Some(match (&args.per_commit, &args.range, &args.since) {
+                (Some(range), _, _) | (None, Some(range), _) => range.clone(),
+                (None, None, Some(since)) => since.clone(),
+                (None, None, None) => args.gitref[0].clone(),
+            }),
+        };
+        let head = totals::Revision {
+            sha: range_head
+                .or_else(|| current.as_ref()?.target())
+                .map(|oid| oid.to_string()),
+            // A range's head needn't be the checked out branch
+            name: current
+                .filter(|current| current.is_branch() && range_head.is_none())
+                .and_then(|current| current.shorthand().map(str::to_string)),
+        };
+        destination.write(&totals::Trends::new(&run_info, base, head, &matched).render())?;
+    }
+    if args.apply_fixes {
+        match head_tree {
+            Some(_) => {
+                warn!("Not applying fixes: findings are about a commit, not the working tree")
+            }
+            None => {
+                let applied = autofix::apply_fixes(workdir, &matched, &file_hunks)?;
+                eprintln!("Applied {} fix(es)", applied.count());
+                if let Some(path) = &args.fixes_report {
+                    let report = applied.report(&matched, &build_hunks(&repo, &args)?);
+                    let text = serde_json::to_string_pretty(&report)? + "\n";
+                    output::write_atomic(path, text.as_bytes())
+                        .with_context(|| format!("Unable to write {}", path.display()))?;
                 }
             }
         }
     }
-    if failed {
-        process::exit(1);
+    if let Some(resume) = resume {
+        resume.finish()?;
+    }
+
+    let provenance = sarif_log.as_ref().and_then(|_| {
+        let head = commit_range.map(|range| range.head.id());
+        sarif::Provenance::new(&repo, &args.remote, base_commit, head)
+    });
+    let emit_sarif = |sarif, exit_code| {
+        emit_sarif(
+            &args,
+            &outputs,
+            &run_info,
+            provenance.as_ref(),
+            sarif,
+            exit_code,
+        )
+    };
+
+    if let Some(Command::Export(export_args)) = &args.command {
+        export::write(
+            export_args,
+            workdir,
+            &file_hunks,
+            staging.as_ref(),
+            &matched,
+        )?;
+        return emit_sarif(sarif_log, 0);
+    }
+    if let Some(Command::GhArtifact(artifact_args)) = &args.command {
+        artifact_args.upload(workdir, &redacted)?;
+        return emit_sarif(sarif_log, 0);
+    }
+
+    let mut publish = args.publish.clone();
+    publish.extend(ci_plan.publish.iter().filter(|t| !args.publish.contains(t)));
+    if let Some(seed) = args.shuffle {
+        let seed = seed.unwrap_or_else(shuffle::seed);
+        eprintln!(
+            "Shuffling with seed {}, rerun with --shuffle {} to reproduce",
+            seed, seed
+        );
+        shuffle::shuffle(redacted.to_mut(), seed);
+        shuffle::shuffle(&mut publish, seed);
+    }
+    if !publish.is_empty() {
+        let http = publish::agent(args.ca_cert.as_deref())?;
+        let mut pacer = args.pacing.pacer();
+        for &target in &publish {
+            let publisher = target.publisher(workdir, &http);
+            match (
+                pacer.publish(publisher.as_ref(), &redacted),
+                &args.publish_queue,
+            ) {
+                (Ok(()), _) => {}
+                (Err(Undelivered { error, diagnostics }), Some(queue)) => {
+                    warn!(
+                        "{:#}, queued for `diff-format publish --drain {}`",
+                        error,
+                        queue.display()
+                    );
+                    publish::enqueue(queue, target, diagnostics)?;
+                }
+                (Err(Undelivered { error, .. }), None) => return Err(error),
+            }
+        }
+    }
+
+    if let Some(path) = &args.fixed_summary {
+        let old_hunks = match &diff {
+            Some(diff) => generate_old_hunkmap(diff)?,
+            None => HashMap::new(),
+        };
+        let fixed = fixed::find_fixed(&base_report, &old_hunks, &current_findings);
+        info!("{} finding(s) fixed on changed lines", fixed.len());
+        let summary = if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            fixed::render_json(&fixed)?
+        } else {
+            fixed::render_markdown(&fixed, args.lang)
+        };
+        output::write_atomic(path, summary.as_bytes())
+            .with_context(|| format!("Unable to write summary to {}", path.display()))?;
+    }
+
+    let failed = if !expired.is_empty() {
+        true
+    } else if args.regression_check {
+        let mut base_totals = RuleTotals::new();
+        for diagnostic in &base_report {
+            regression::count_rule(&mut base_totals, diagnostic);
+        }
+        let regressions = regression::find_regressions(&base_totals, &rule_totals);
+        for regression in &regressions {
+            warn!(
+                "{} increased from {} to {}",
+                regression.rule, regression.base, regression.current
+            );
+        }
+        !regressions.is_empty()
     } else {
-        Ok(())
+        gate_fails(&args, &policies, &rollouts, today, &matched, score)
+    };
+    let failed = failed || args.input_limit.fails(truncation);
+
+    let linter_code = linter
+        .map(|linter| linter.finish(reported))
+        .transpose()?
+        .flatten();
+    if let (Some(run), None) = (cached_run, linter_code) {
+        let mut paths = PathMapper::new(workdir, &env::current_dir()?)
+            .with_strip_prefixes(&args.strip_prefix)
+            .with_symlink_policy(args.symlink_policy);
+        run.store(&parsers, &mut paths)?;
+    }
+    let code = linter_code.or(failed.then_some(1));
+    notify(&args, &summary, code)?;
+    emit_sarif(sarif_log, code.unwrap_or(0))?;
+    match code {
+        Some(code) => {
+            // Exiting skips destructors
+            drop(remote_base);
+            exit(code);
+        }
+        None => Ok(()),
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::parse_lint_location;
-    use regex::Regex;
+    use crate::{lint_pathspecs, writing_options, Args, Pipeline};
+    use clap::Parser;
+    use diff_format::config::Config;
+    use diff_format::diagnostic::Diagnostic;
+    use diff_format::fixtures::TempDir;
+    use diff_format::location::LocationResolver;
+    use diff_format::output::buffer::Streams;
+    use diff_format::parsers::{Format, Parsers};
+    use diff_format::paths::PathMapper;
+    use diff_format::run::RunInfo;
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_lint_pathspecs() {
+        let parsers = Parsers::new(&[Format::Python], HashMap::new());
+        let mut paths = PathMapper::new(Path::new("."), Path::new("/"));
+        let lines = [
+            "b.py:1:1: E1 x".to_string(),
+            "\x1b[1ma.py\x1b[0m:2: E2 y".to_string(),
+            "1 error found".to_string(),
+            "b.py:3:1: E3 z".to_string(),
+        ];
+        assert_eq!(
+            lint_pathspecs(&lines, &parsers, &mut paths),
+            ["a.py", "b.py"]
+        );
+    }
+
+    #[test]
+    fn test_writing_options() {
+        let writing = |line: &[&str]| writing_options(&Args::parse_from(line));
+        assert!(writing(&[
+            "diff-format",
+            "--out",
+            "junit=stdout",
+            "--summary",
+            "stderr"
+        ])
+        .is_empty());
+        assert_eq!(
+            writing(&[
+                "diff-format",
+                "--out",
+                "junit=report.xml",
+                "--",
+                "ruff",
+                "check"
+            ]),
+            ["-- <LINTER>", "--out"]
+        );
+        assert_eq!(
+            writing(&["diff-format", "--apply-fixes", "serve", "--socket", "s"]),
+            ["serve", "--apply-fixes"]
+        );
+    }
 
     #[test]
-    fn test_python_regex() {
-        let python_regex = Regex::new(r#"(.+?):(\d+)"#).expect("Failed to create python regex");
-        let (filename, line) =
-            parse_lint_location("pysrc/main.py:753:89: E501 Line too long", &python_regex).unwrap();
-        assert_eq!(filename, "pysrc/main.py");
-        assert_eq!(line, 753);
+    fn test_match_excerpt() {
+        let dir = TempDir::new("pipeline").unwrap();
+        fs::write(dir.join("a.py"), "import os\nimport os, sys\nvalid = 1\n").unwrap();
+        let args = Args::parse_from(["diff-format", "--match-excerpt"]);
+        let config = Config::default();
+        let (policies, waivers, rollouts) = (
+            config.policies().unwrap(),
+            config.waivers().unwrap(),
+            config.rollouts().unwrap(),
+        );
+        let file_hunks = vec![("a.py".to_string(), vec![(2, 3)])]
+            .into_iter()
+            .collect();
+        let run_info = RunInfo::new(None);
+        let mut pipeline = Pipeline::new(
+            &args,
+            &config,
+            (&policies, &waivers, &rollouts),
+            &dir,
+            &file_hunks,
+            LocationResolver::new(dir.path()),
+            &run_info,
+        )
+        .unwrap();
+        let mut streams = Streams::new(&[], None, usize::MAX);
+        let quoting = |excerpt: &str| {
+            let mut diagnostic = Diagnostic::new("a.py", Some(1));
+            diagnostic.excerpt = Some(excerpt.to_string());
+            diagnostic
+        };
+        for excerpt in ["import os, sys", "id", "import re"] {
+            pipeline
+                .filter("a.py:1: E1", quoting(excerpt), &mut streams)
+                .unwrap();
+        }
+        pipeline.finish(streams).unwrap();
+        // Only the finding quoting a changed line, moved to it
+        assert_eq!(pipeline.matched.len(), 1);
+        assert_eq!(pipeline.matched[0].line, Some(2));
+        assert_eq!(pipeline.summary.total().pre_existing, 2);
     }
 }
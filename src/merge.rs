@@ -0,0 +1,80 @@
+use anyhow::{bail, Context, Result};
+use git2::{Commit, Oid, Repository};
+use log::{info, warn};
+
+fn head_commit(repo: &Repository) -> Result<Commit<'_>> {
+    repo.head()
+        .and_then(|head| head.peel_to_commit())
+        .context("HEAD does not point to a commit")
+}
+
+/// Where the line HEAD was committed on forked from `base`: merges are followed through
+/// their first parent, treating the other parents as the change being merged
+pub fn first_parent_base<'a>(repo: &'a Repository, base: &Commit<'a>) -> Result<Commit<'a>> {
+    let head = head_commit(repo)?;
+    let line = if head.parent_count() > 1 {
+        head.parent_id(0)?
+    } else {
+        head.id()
+    };
+    let fork = repo
+        .merge_base(base.id(), line)
+        .context("HEAD has no common history with --gitref")?;
+    info!("Diffing against first-parent merge base {}", fork);
+    Ok(repo.find_commit(fork)?)
+}
+
+/// Where HEAD and `base` last shared history, so changes `base` gained since HEAD
+/// branched off don't count as HEAD's
+pub fn merge_base<'a>(repo: &'a Repository, base: &Commit<'a>) -> Result<Commit<'a>> {
+    let head = head_commit(repo)?;
+    let fork = repo
+        .merge_base(base.id(), head.id())
+        .context("HEAD has no common history with --gitref")?;
+    info!("Diffing against merge base {}", fork);
+    Ok(repo.find_commit(fork)?)
+}
+
+/// The branch `<remote>/HEAD` points to, falling back to `<remote>/main` and
+/// `<remote>/master` for clones where it was never set
+pub fn default_branch(repo: &Repository, remote: &str) -> Result<String> {
+    let head = format!("refs/remotes/{}/HEAD", remote);
+    if let Some(target) = repo
+        .find_reference(&head)
+        .ok()
+        .and_then(|reference| reference.symbolic_target().map(str::to_string))
+    {
+        let branch = target.trim_start_matches("refs/remotes/").to_string();
+        info!("Using {}'s default branch {}", remote, branch);
+        return Ok(branch);
+    }
+    for name in ["main", "master"] {
+        let branch = format!("{}/{}", remote, name);
+        if repo.revparse_single(&branch).is_ok() {
+            info!("{} is not set, using {}", head, branch);
+            return Ok(branch);
+        }
+    }
+    bail!(
+        "Can't detect {}'s default branch; run `git remote set-head {} --auto`",
+        remote,
+        remote
+    )
+}
+
+/// Warns when HEAD is a merge and `base` is not where its first parent forked
+pub fn warn_if_merge(repo: &Repository, base: Oid) {
+    let head = match head_commit(repo) {
+        Ok(head) if head.parent_count() > 1 => head,
+        _ => return,
+    };
+    let fork = head
+        .parent_id(0)
+        .and_then(|first_parent| repo.merge_base(base, first_parent));
+    if fork.ok() != Some(base) {
+        warn!(
+            "HEAD is a merge commit and --gitref is not where its first parent forked, so \
+             changes from more than one parent are likely counted; consider --first-parent"
+        );
+    }
+}
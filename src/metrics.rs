@@ -0,0 +1,335 @@
+use crate::HunkRange;
+use anyhow::{Context, Result};
+use clap::Args;
+use log::debug;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock as Lazy;
+
+/// Options of the gate, as flags or a `[[gate]]` table of `kind = "metrics"`
+#[derive(Args, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MetricsArgs {
+    /// Fail when a changed function's cyclomatic complexity exceeds this
+    #[arg(long)]
+    max_cyclomatic: Option<u32>,
+
+    /// Fail when a changed function is longer than this many lines
+    #[arg(long)]
+    max_length: Option<u32>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Function {
+    pub name: String,
+    pub start: u32,
+    pub end: u32,
+    pub complexity: u32,
+}
+
+impl Function {
+    pub fn length(&self) -> u32 {
+        self.end - self.start + 1
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Language {
+    Python,
+    Rust,
+    /// C, C++, Java, Go, JavaScript and other brace-delimited languages
+    CLike,
+}
+
+fn language(path: &str) -> Option<Language> {
+    let extension = Path::new(path).extension()?.to_str()?;
+    match extension {
+        "py" | "pyi" => Some(Language::Python),
+        "rs" => Some(Language::Rust),
+        "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "java" | "kt" | "go" | "js" | "jsx"
+        | "ts" | "tsx" | "cs" | "swift" | "scala" => Some(Language::CLike),
+        _ => None,
+    }
+}
+
+static PYTHON_DEF: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(?P<indent>\s*)(?:async\s+)?def\s+(?P<name>\w+)").unwrap());
+static RUST_FN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\s*(?:[\w()]+\s+)*fn\s+(?P<name>\w+)").unwrap());
+static C_LIKE_FN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^\s*(?:(?:func|function)\s*(?:\([^)]*\)\s*)?|[\w:<>,\*&\[\]\s]+?\s)\*?(?P<name>\w+)\s*\([^;]*$")
+        .unwrap()
+});
+const C_LIKE_KEYWORDS: &[&str] = &[
+    "if", "for", "while", "switch", "catch", "return", "else", "do", "sizeof", "new",
+];
+
+static PYTHON_BRANCHES: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\b(?:if|elif|for|while|except|and|or|case)\b").unwrap());
+static RUST_BRANCHES: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\b(?:if|for|while|loop)\b|&&|\|\||=>").unwrap());
+static RUST_MATCH: Lazy<Regex> = Lazy::new(|| Regex::new(r"\bmatch\b").unwrap());
+static C_LIKE_BRANCHES: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\b(?:if|for|while|case|catch)\b|&&|\|\||\?").unwrap());
+
+/// Drops string literals and line comments so they don't affect brace or branch counting
+fn strip_code_line(line: &str, comment: &str) -> String {
+    let mut code = String::with_capacity(line.len());
+    let mut quote = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match quote {
+            Some(q) => {
+                if c == '\\' {
+                    chars.next();
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None if c == '"' || (c == '\'' && comment == "#") => quote = Some(c),
+            None => {
+                code.push(c);
+                if code.ends_with(comment) {
+                    code.truncate(code.len() - comment.len());
+                    break;
+                }
+            }
+        }
+    }
+    code
+}
+
+fn python_functions(lines: &[&str]) -> Vec<Function> {
+    let mut functions = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let captures = match PYTHON_DEF.captures(line) {
+            Some(captures) => captures,
+            None => continue,
+        };
+        let indent = captures["indent"].len();
+        // The body ends before the first non-blank line that is not indented deeper
+        let mut end = index;
+        for (offset, body_line) in lines[index + 1..].iter().enumerate() {
+            if body_line.trim().is_empty() {
+                continue;
+            }
+            if body_line.len() - body_line.trim_start().len() <= indent {
+                break;
+            }
+            end = index + 1 + offset;
+        }
+        let complexity = 1 + lines[index..=end]
+            .iter()
+            .map(|line| {
+                PYTHON_BRANCHES
+                    .find_iter(&strip_code_line(line, "#"))
+                    .count() as u32
+            })
+            .sum::<u32>();
+        functions.push(Function {
+            name: captures["name"].to_string(),
+            start: index as u32 + 1,
+            end: end as u32 + 1,
+            complexity,
+        });
+    }
+    functions
+}
+
+fn brace_functions(lines: &[&str], language: Language) -> Vec<Function> {
+    let header = match language {
+        Language::Rust => &RUST_FN,
+        _ => &C_LIKE_FN,
+    };
+    let mut functions = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let name = match header.captures(line) {
+            Some(captures) => captures["name"].to_string(),
+            None => continue,
+        };
+        if C_LIKE_KEYWORDS.contains(&name.as_str()) {
+            continue;
+        }
+
+        let mut depth = 0i32;
+        let mut opened = false;
+        let mut complexity = 1i32;
+        let mut end = None;
+        'body: for (offset, body_line) in lines[index..].iter().enumerate() {
+            let code = strip_code_line(body_line, "//");
+            complexity += match language {
+                Language::Rust => {
+                    RUST_BRANCHES.find_iter(&code).count() as i32
+                        - RUST_MATCH.find_iter(&code).count() as i32
+                }
+                _ => C_LIKE_BRANCHES.find_iter(&code).count() as i32,
+            };
+            for c in code.chars() {
+                match c {
+                    // A prototype or trait method without a body
+                    ';' if !opened => break 'body,
+                    '{' => {
+                        depth += 1;
+                        opened = true;
+                    }
+                    '}' => depth -= 1,
+                    _ => continue,
+                }
+                if opened && depth == 0 {
+                    end = Some(index + offset);
+                    break 'body;
+                }
+            }
+        }
+        if let Some(end) = end {
+            functions.push(Function {
+                name,
+                start: index as u32 + 1,
+                end: end as u32 + 1,
+                complexity: complexity.max(1) as u32,
+            });
+        }
+    }
+    functions
+}
+
+pub fn find_functions(path: &str, source: &str) -> Vec<Function> {
+    let lines: Vec<&str> = source.lines().collect();
+    match language(path) {
+        Some(Language::Python) => python_functions(&lines),
+        Some(language) => brace_functions(&lines, language),
+        None => Vec::new(),
+    }
+}
+
+/// Whether any of `hunk_ranges` overlaps `function`
+pub fn touches(function: &Function, hunk_ranges: &[HunkRange]) -> bool {
+    hunk_ranges
+        .iter()
+        .any(|&(start, end)| start <= function.end && end >= function.start)
+}
+
+/// Prints changed functions that exceed the thresholds, returning how many times they
+/// did
+pub fn check(
+    args: &MetricsArgs,
+    workdir: &Path,
+    file_hunks: &HashMap<String, Vec<HunkRange>>,
+) -> Result<usize> {
+    let mut exceeded = 0;
+    let mut paths: Vec<_> = file_hunks.keys().collect();
+    paths.sort();
+    for path in paths {
+        if language(path).is_none() {
+            debug!("Skipping metrics for '{}'", path);
+            continue;
+        }
+        let source = fs::read_to_string(workdir.join(path))
+            .with_context(|| format!("Unable to read '{}'", path))?;
+        for function in find_functions(path, &source) {
+            if !touches(&function, &file_hunks[path]) {
+                continue;
+            }
+            if let Some(max) = args.max_cyclomatic.filter(|&max| function.complexity > max) {
+                println!(
+                    "{}:{}: function '{}' has cyclomatic complexity {} (max {})",
+                    path, function.start, function.name, function.complexity, max
+                );
+                exceeded += 1;
+            }
+            if let Some(max) = args.max_length.filter(|&max| function.length() > max) {
+                println!(
+                    "{}:{}: function '{}' is {} lines long (max {})",
+                    path,
+                    function.start,
+                    function.name,
+                    function.length(),
+                    max
+                );
+                exceeded += 1;
+            }
+        }
+    }
+    Ok(exceeded)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::metrics::{find_functions, Function};
+
+    #[test]
+    fn test_python_functions() {
+        let source = "\
+import os
+
+def simple():
+    return 1
+
+class A:
+    async def method(self, x):
+        # if this comment counted, complexity would be off
+        if x and os.path.exists(x):
+            return 'if'
+        for y in x:
+            pass
+
+    def other(self):
+        pass
+";
+        let functions = find_functions("a.py", source);
+        assert_eq!(
+            functions[..2],
+            [
+                Function {
+                    name: "simple".to_string(),
+                    start: 3,
+                    end: 4,
+                    complexity: 1
+                },
+                Function {
+                    name: "method".to_string(),
+                    start: 7,
+                    end: 12,
+                    complexity: 4
+                },
+            ]
+        );
+        assert_eq!(functions[2].start, 14);
+    }
+
+    #[test]
+    fn test_brace_functions() {
+        let source = "\
+trait T {
+    fn declared(&self);
+}
+
+pub(crate) fn parse(x: Option<u32>) -> u32 {
+    let s = \"{ if\";
+    match x {
+        Some(v) if v > 1 => v,
+        _ => 0,
+    }
+}
+";
+        assert_eq!(
+            find_functions("lib.rs", source),
+            [Function {
+                name: "parse".to_string(),
+                start: 5,
+                end: 11,
+                complexity: 3
+            }]
+        );
+
+        let source =
+            "int main(int argc, char **argv)\n{\n    return argc > 1 && argv ? 0 : 1;\n}\n";
+        let functions = find_functions("main.c", source);
+        assert_eq!(functions.len(), 1);
+        assert_eq!((functions[0].start, functions[0].end), (1, 4));
+        assert_eq!(functions[0].complexity, 3);
+    }
+}
@@ -0,0 +1,126 @@
+//! `normalize = true`: findings of tools that put their severity in the message, as in
+//! `error: error: unused variable`, are given it on the canonical scale and their
+//! messages stripped of it and of other redundant prefixes, so the same finding reads,
+//! folds and fingerprints alike whichever tool or wrapper printed it.
+//!
+//! It is off by default as it changes the messages baselines fingerprinted; a
+//! `[tool.<name>]` table can turn it on or off for one format, map the tool's own
+//! labels with `severities` and strip other prefixes with `strip_prefixes`.
+
+use crate::config::ToolConfig;
+use crate::diagnostic::{Diagnostic, Severity};
+
+/// The severity label `rest` starts with, as `error:` or `[warning]`, and what follows it
+fn leading_label<'a>(rest: &'a str, tool: &ToolConfig) -> Option<(Severity, &'a str)> {
+    let (label, after) = match rest.strip_prefix('[') {
+        Some(bracketed) => bracketed.split_once(']')?,
+        None => rest.split_once(':')?,
+    };
+    if label.is_empty()
+        || !label
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return None;
+    }
+    let severity = tool
+        .severities
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(label))
+        .map(|(_, &severity)| severity)
+        .or_else(|| Severity::from_label(label))?;
+    Some((severity, after.strip_prefix(':').unwrap_or(after)))
+}
+
+/// Strips the severity labels and `strip_prefixes` of `tool` the message of
+/// `diagnostic` starts with, taking the first label as its severity unless it has one
+pub fn normalize(diagnostic: &mut Diagnostic, tool: &ToolConfig) {
+    let message = match &diagnostic.message {
+        Some(message) => message,
+        None => return,
+    };
+    let mut rest = message.trim_start();
+    let mut severity = None;
+    loop {
+        if let Some(prefix) = tool
+            .strip_prefixes
+            .iter()
+            .find(|prefix| !prefix.is_empty() && rest.starts_with(prefix.as_str()))
+        {
+            rest = rest[prefix.len()..].trim_start();
+            continue;
+        }
+        match leading_label(rest, tool) {
+            Some((label, after)) => {
+                severity = severity.or(Some(label));
+                rest = after.trim_start();
+            }
+            None => break,
+        }
+    }
+    // A message that is nothing but a label keeps it
+    if rest.is_empty() || rest.len() == message.len() {
+        return;
+    }
+    diagnostic.message = Some(rest.to_string());
+    diagnostic.severity = diagnostic.severity.or(severity);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::{Config, ToolConfig};
+    use crate::diagnostic::{Diagnostic, Severity};
+    use crate::normalize::normalize;
+
+    fn normalized(message: &str, tool: &ToolConfig) -> (String, Option<Severity>) {
+        let mut diagnostic = Diagnostic::new("a.py", Some(1));
+        diagnostic.message = Some(message.to_string());
+        normalize(&mut diagnostic, tool);
+        (diagnostic.message.unwrap(), diagnostic.severity)
+    }
+
+    #[test]
+    fn test_normalize() {
+        let plain = ToolConfig::default();
+        assert_eq!(
+            normalized("error: error: unused variable 'x'", &plain),
+            ("unused variable 'x'".to_string(), Some(Severity::Error))
+        );
+        assert_eq!(
+            normalized("[WARNING] shadowed name", &plain),
+            ("shadowed name".to_string(), Some(Severity::Warning))
+        );
+        // Rule codes and other words aren't labels
+        for message in ["E501 line too long", "C0114: Missing docstring", "error:"] {
+            assert_eq!(normalized(message, &plain), (message.to_string(), None));
+        }
+
+        let tool: ToolConfig = toml::from_str(
+            "severities = { blocker = \"error\", style = \"warning\" }\nstrip_prefixes = [\"mylint: \"]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            normalized("mylint: BLOCKER: style: bad name", &tool),
+            ("bad name".to_string(), Some(Severity::Error))
+        );
+        assert_eq!(
+            normalized("style: bad name", &tool).1,
+            Some(Severity::Warning)
+        );
+
+        // Off unless enabled, and per format
+        let config: Config =
+            toml::from_str("normalize = true\n[tool.typos]\nnormalize = false\n").unwrap();
+        let mut diagnostic = Diagnostic::new("a.c", Some(1));
+        diagnostic.message = Some("warning: unused".to_string());
+        let mut typo = diagnostic.clone();
+        typo.tool = Some("typos".to_string());
+        config.normalize(&mut diagnostic);
+        config.normalize(&mut typo);
+        assert_eq!(diagnostic.message.as_deref(), Some("unused"));
+        assert_eq!(typo.message.as_deref(), Some("warning: unused"));
+        let mut unset = typo.clone();
+        Config::default().normalize(&mut unset);
+        assert_eq!(unset, typo);
+    }
+}
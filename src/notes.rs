@@ -0,0 +1,149 @@
+//! The `note:` lines compilers print after a finding to point at other locations, such
+//! as the earlier definition a redefinition conflicts with. Each is kept with the
+//! finding before it as a related location rather than matched on its own, so the
+//! finding is reported by its own location and still explains itself when the note's
+//! location didn't change. The source snippets compilers print in between are passed
+//! on as they are.
+
+use crate::diagnostic::Diagnostic;
+use crate::error::Result;
+use crate::parallel::Parsed;
+use std::collections::VecDeque;
+
+/// Lines without findings held after a finding while waiting for its notes
+const HELD_LINES: usize = 64;
+
+/// Parsed lint output with notes folded into the findings they follow
+pub struct WithNotes<I> {
+    input: I,
+    enabled: bool,
+    /// The last line with findings, which later notes are about
+    primary: Option<Parsed>,
+    /// Lines without findings after `primary`
+    between: Vec<Parsed>,
+    ready: VecDeque<Result<Parsed>>,
+}
+
+/// Folds the notes of `input` into the findings before them, unless not `enabled`, as
+/// when lines don't come in order
+pub fn attach<I: Iterator<Item = Result<Parsed>>>(input: I, enabled: bool) -> WithNotes<I> {
+    WithNotes {
+        input,
+        enabled,
+        primary: None,
+        between: Vec::new(),
+        ready: VecDeque::new(),
+    }
+}
+
+fn is_notes(diagnostics: &[Diagnostic]) -> bool {
+    !diagnostics.is_empty() && diagnostics.iter().all(Diagnostic::is_note)
+}
+
+impl<I> WithNotes<I> {
+    fn flush(&mut self) {
+        self.ready.extend(self.primary.take().map(Ok));
+        self.ready.extend(self.between.drain(..).map(Ok));
+    }
+}
+
+impl<I: Iterator<Item = Result<Parsed>>> Iterator for WithNotes<I> {
+    type Item = Result<Parsed>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.enabled {
+            return self.input.next();
+        }
+        loop {
+            if let Some(parsed) = self.ready.pop_front() {
+                return Some(parsed);
+            }
+            let parsed = match self.input.next() {
+                Some(parsed) => parsed,
+                None => {
+                    self.flush();
+                    return self.ready.pop_front();
+                }
+            };
+            match (&mut self.primary, parsed) {
+                (Some((primary_line, findings)), Ok((line, notes))) if is_notes(&notes) => {
+                    primary_line.push('\n');
+                    primary_line.push_str(&line);
+                    // With several formats accepting the lines, each keeps its own note
+                    for finding in findings.iter_mut() {
+                        let note = notes
+                            .iter()
+                            .find(|note| note.tool == finding.tool)
+                            .unwrap_or(&notes[0]);
+                        finding.relate(note);
+                    }
+                }
+                (Some(_), Ok((line, diagnostics)))
+                    if diagnostics.is_empty() && self.between.len() < HELD_LINES =>
+                {
+                    self.between.push((line, diagnostics));
+                }
+                (_, parsed) => {
+                    self.flush();
+                    match parsed {
+                        Ok(parsed) if !parsed.1.is_empty() && !is_notes(&parsed.1) => {
+                            self.primary = Some(parsed)
+                        }
+                        parsed => self.ready.push_back(parsed),
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::notes::attach;
+    use crate::parallel::parse_line;
+    use crate::parsers::{Format, Parsers};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_attach() {
+        let parsers = Parsers::new(&[Format::Python], HashMap::new());
+        let output = [
+            "b.c:1:1: note: orphan",
+            "a.c:10:5: error: redefinition of 'x'",
+            "   10 | int x;",
+            "a.c:3:5: note: previous definition is here",
+            "a.c:12:1: warning: unused",
+        ];
+        let parsed = output
+            .iter()
+            .map(|line| Ok(parse_line(&parsers, line.to_string(), false)));
+        let grouped: Vec<_> = attach(parsed, true).map(Result::unwrap).collect();
+        let lines: Vec<_> = grouped.iter().map(|(line, _)| line.as_str()).collect();
+        assert_eq!(
+            lines,
+            [
+                "b.c:1:1: note: orphan",
+                "a.c:10:5: error: redefinition of 'x'\na.c:3:5: note: previous definition is here",
+                "   10 | int x;",
+                "a.c:12:1: warning: unused",
+            ]
+        );
+        assert_eq!(grouped[0].1.len(), 1);
+        let related = &grouped[1].1[0].related;
+        assert_eq!(related.len(), 1);
+        assert_eq!(
+            (related[0].path.as_str(), related[0].line),
+            ("a.c", Some(3))
+        );
+        assert_eq!(
+            related[0].message.as_deref(),
+            Some("previous definition is here")
+        );
+        assert!(grouped[3].1[0].related.is_empty());
+
+        let parsed = output
+            .iter()
+            .map(|line| Ok(parse_line(&parsers, line.to_string(), false)));
+        assert_eq!(attach(parsed, false).count(), output.len());
+    }
+}
@@ -0,0 +1,98 @@
+//! `--notify-webhook`: the summary of a run POSTed to a URL once its exit code is
+//! decided, so chat notifications don't need a CI step parsing the output. A webhook
+//! that can't be reached is warned about rather than failing the run.
+
+use crate::publish::Agent;
+use crate::summary::Summary;
+use anyhow::Result;
+use clap::ValueEnum;
+use log::{info, warn};
+use serde_json::{json, Value};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum WebhookPayload {
+    /// The `--summary-json` summary, with the exit code and whether the run failed
+    #[default]
+    Json,
+    /// A Slack incoming webhook message
+    Slack,
+}
+
+/// What is posted for `summary`, the run exiting with `code`
+pub fn payload(kind: WebhookPayload, summary: &Summary, code: i32) -> Value {
+    let mut value = summary.to_json();
+    match kind {
+        WebhookPayload::Json => {
+            value["exit_code"] = json!(code);
+            value["failed"] = json!(code != 0);
+            value
+        }
+        WebhookPayload::Slack => {
+            let total = summary.total();
+            let run = value["run"]["id"]
+                .as_str()
+                .map_or_else(String::new, |id| format!(" {}", id));
+            json!({
+                "text": format!(
+                    "diff-format run{} {}: {} new finding(s) on changed lines, {} pre-existing, {} waived",
+                    run,
+                    if code == 0 { "passed" } else { "failed" },
+                    total.shown,
+                    total.pre_existing,
+                    total.waived
+                ),
+            })
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+fn post(http: &Agent, url: &str, payload: &Value) -> Result<()> {
+    http.post(url)
+        .send_json(payload)
+        .map_err(|err| anyhow::anyhow!("Unable to notify {}: {}", url, err))?;
+    Ok(())
+}
+
+#[cfg(not(feature = "http"))]
+fn post(_http: &Agent, _url: &str, _payload: &Value) -> Result<()> {
+    anyhow::bail!("--notify-webhook needs diff-format built with the `http` feature")
+}
+
+/// Posts the summary of the run exiting with `code` to `url`, warning when it can't
+pub fn notify(http: &Agent, url: &str, kind: WebhookPayload, summary: &Summary, code: i32) {
+    match post(http, url, &payload(kind, summary, code)) {
+        Ok(()) => info!("Notified {}", url),
+        Err(err) => warn!("{:#}", err),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::Diagnostic;
+    use crate::notify::{payload, WebhookPayload};
+    use crate::run::RunInfo;
+    use crate::summary::Summary;
+
+    #[test]
+    fn test_payload() {
+        let mut summary = Summary::new(RunInfo::new(Some("run-7")));
+        summary.pre_existing(&Diagnostic::new("a.py", Some(1)));
+        summary.shown(&[Diagnostic::new("b.py", Some(2))]);
+
+        let json = payload(WebhookPayload::Json, &summary, 1);
+        assert_eq!(json["run"]["id"], "run-7");
+        assert_eq!(json["total"]["shown"], 1);
+        assert_eq!(
+            (json["exit_code"].as_i64(), json["failed"].as_bool()),
+            (Some(1), Some(true))
+        );
+
+        let slack = payload(WebhookPayload::Slack, &summary, 0);
+        assert_eq!(
+            slack["text"],
+            "diff-format run run-7 passed: 1 new finding(s) on changed lines, 1 pre-existing, 0 waived"
+        );
+        assert_eq!(slack.as_object().unwrap().len(), 1);
+    }
+}
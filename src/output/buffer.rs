@@ -0,0 +1,345 @@
+//! How the lines of streaming outputs are emitted: echoed as they are matched, or, once
+//! an order is asked for with `--sort`, held until the input ends and written sorted.
+//!
+//! Sorting holds every echoed line on top of the matched findings reports keep anyway.
+//! Past `--buffer-limit` bytes the held lines are sorted and spilled to a temporary
+//! file, and the files merged at the end, so the lines take bounded memory however
+//! much lint output there is. Once [`MAX_RUNS`] files pile up they are merged into one,
+//! so the merge never needs more open files than that.
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::output::Output;
+use crate::redact::{self, Redaction};
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Numbers the runs of every buffer of the process, so their files don't collide
+static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+/// Spilled files merged at once, well under common open file limits
+pub const MAX_RUNS: usize = 64;
+
+/// The order streaming outputs are written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortKey {
+    /// By file, then line and column
+    File,
+    /// Errors first, then by file
+    Severity,
+    /// By rule, then by file
+    Rule,
+}
+
+/// What lines sort by; the sequence number keeps equal ones in input order
+type Key = (String, String, u32, u32, u64);
+
+fn severity_rank(severity: Option<Severity>) -> &'static str {
+    match severity {
+        Some(Severity::Error) => "0",
+        Some(Severity::Warning) => "1",
+        Some(Severity::Info) => "2",
+        None => "3",
+    }
+}
+
+/// Lines held until the input ends, spilled to sorted temporary files past a limit
+pub struct SortedBuffer {
+    sort: SortKey,
+    limit: usize,
+    entries: Vec<(Key, String)>,
+    bytes: usize,
+    runs: Vec<PathBuf>,
+    sequence: u64,
+}
+
+/// The lines of a spilled run, in order
+type Run = Lines<BufReader<File>>;
+
+fn next_entry(run: &mut Run) -> Result<Option<(Key, String)>> {
+    match run.next() {
+        Some(line) => Ok(Some(
+            serde_json::from_str(&line?).context("Corrupt sort run")?,
+        )),
+        None => Ok(None),
+    }
+}
+
+fn open_runs(paths: &[PathBuf]) -> Result<Vec<Run>> {
+    paths
+        .iter()
+        .map(|path| {
+            File::open(path)
+                .map(|file| BufReader::new(file).lines())
+                .with_context(|| format!("Unable to read {}", path.display()))
+        })
+        .collect()
+}
+
+/// Hands the entries of the sorted `runs` and `held` to `write`, in order
+fn merge(
+    mut runs: Vec<Run>,
+    mut held: impl Iterator<Item = (Key, String)>,
+    mut write: impl FnMut((Key, String)) -> Result<()>,
+) -> Result<()> {
+    // The held entries make one more run, indexed past the spilled ones
+    let mut heap = BinaryHeap::new();
+    for (index, run) in runs.iter_mut().enumerate() {
+        if let Some(entry) = next_entry(run)? {
+            heap.push(Reverse((entry, index)));
+        }
+    }
+    if let Some(entry) = held.next() {
+        heap.push(Reverse((entry, runs.len())));
+    }
+    while let Some(Reverse((entry, index))) = heap.pop() {
+        write(entry)?;
+        let next = match runs.get_mut(index) {
+            Some(run) => next_entry(run)?,
+            None => held.next(),
+        };
+        if let Some(entry) = next {
+            heap.push(Reverse((entry, index)));
+        }
+    }
+    Ok(())
+}
+
+fn run_path() -> PathBuf {
+    env::temp_dir().join(format!(
+        "diff-format-sort-{}-{}",
+        std::process::id(),
+        RUNS.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Writes one entry of a run
+fn write_entry(file: &mut impl Write, entry: &(Key, String)) -> Result<()> {
+    serde_json::to_writer(&mut *file, entry)?;
+    file.write_all(b"\n")?;
+    Ok(())
+}
+
+impl SortedBuffer {
+    pub fn new(sort: SortKey, limit: usize) -> Self {
+        SortedBuffer {
+            sort,
+            limit,
+            entries: Vec::new(),
+            bytes: 0,
+            runs: Vec::new(),
+            sequence: 0,
+        }
+    }
+
+    fn key(&mut self, diagnostic: &Diagnostic) -> Key {
+        let primary = match self.sort {
+            SortKey::File => String::new(),
+            SortKey::Severity => severity_rank(diagnostic.severity).to_string(),
+            SortKey::Rule => diagnostic.rule.clone().unwrap_or_default(),
+        };
+        self.sequence += 1;
+        (
+            primary,
+            diagnostic.path.clone(),
+            diagnostic.line.unwrap_or(0),
+            diagnostic.column.unwrap_or(0),
+            self.sequence,
+        )
+    }
+
+    pub fn push(&mut self, diagnostic: &Diagnostic, text: String) -> Result<()> {
+        let key = self.key(diagnostic);
+        self.bytes += text.len() + key.1.len();
+        self.entries.push((key, text));
+        if self.bytes > self.limit {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the held entries, sorted, to a new run; the runs are merged into one
+    /// first when there are [`MAX_RUNS`] of them
+    fn spill(&mut self) -> Result<()> {
+        if self.runs.len() >= MAX_RUNS {
+            self.compact()?;
+        }
+        let path = run_path();
+        // Removed on drop even if writing fails
+        self.runs.push(path.clone());
+        self.entries.sort_unstable();
+        let mut file = BufWriter::new(
+            File::create(&path).with_context(|| format!("Unable to create {}", path.display()))?,
+        );
+        for entry in self.entries.drain(..) {
+            write_entry(&mut file, &entry)?;
+        }
+        file.flush()
+            .with_context(|| format!("Unable to write {}", path.display()))?;
+        self.bytes = 0;
+        Ok(())
+    }
+
+    /// Merges the runs into one
+    fn compact(&mut self) -> Result<()> {
+        let path = run_path();
+        let runs = std::mem::replace(&mut self.runs, vec![path.clone()]);
+        let mut file = BufWriter::new(
+            File::create(&path).with_context(|| format!("Unable to create {}", path.display()))?,
+        );
+        let merged = merge(open_runs(&runs)?, std::iter::empty(), |entry| {
+            write_entry(&mut file, &entry)
+        })
+        .and_then(|()| Ok(file.flush()?))
+        .with_context(|| format!("Unable to write {}", path.display()));
+        for run in &runs {
+            let _ = fs::remove_file(run);
+        }
+        merged
+    }
+
+    /// Hands every line to `write`, in order
+    pub fn finish(mut self, mut write: impl FnMut(&str) -> Result<()>) -> Result<()> {
+        self.entries.sort_unstable();
+        let held = std::mem::take(&mut self.entries).into_iter();
+        merge(open_runs(&self.runs)?, held, |(_, text)| write(&text))
+    }
+}
+
+impl Drop for SortedBuffer {
+    fn drop(&mut self) {
+        for path in &self.runs {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// The outputs that echo lint lines, written to as findings are matched or, when
+/// sorting, once the input ends
+pub struct Streams<'a> {
+    outputs: Vec<&'a Output>,
+    buffer: Option<SortedBuffer>,
+    redactions: &'a [Redaction],
+}
+
+impl<'a> Streams<'a> {
+    /// Streaming unless `sort` asks for an order, buffering up to `limit` bytes of lines
+    pub fn new(outputs: &'a [Output], sort: Option<SortKey>, limit: usize) -> Self {
+        let outputs: Vec<_> = outputs
+            .iter()
+            .filter(|output| output.is_streaming())
+            .collect();
+        let buffer = match sort {
+            Some(sort) if !outputs.is_empty() => Some(SortedBuffer::new(sort, limit)),
+            _ => None,
+        };
+        Streams {
+            outputs,
+            buffer,
+            redactions: &[],
+        }
+    }
+
+    /// Redacts the lines echoed with `redactions`
+    pub fn with_redactions(mut self, redactions: &'a [Redaction]) -> Self {
+        self.redactions = redactions;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.outputs.is_empty()
+    }
+
+    /// Echoes `text`, the lines of `diagnostic`, now or once sorted
+    pub fn write(&mut self, diagnostic: &Diagnostic, mut text: String) -> Result<()> {
+        if let Cow::Owned(redacted) = redact::text(self.redactions, &text) {
+            text = redacted;
+        }
+        match &mut self.buffer {
+            Some(buffer) => buffer.push(diagnostic, text),
+            None => self.write_all(&text),
+        }
+    }
+
+    fn write_all(&self, text: &str) -> Result<()> {
+        for output in &self.outputs {
+            output.destination.write(text)?;
+        }
+        Ok(())
+    }
+
+    /// Writes what was held for sorting
+    pub fn finish(mut self) -> Result<()> {
+        match self.buffer.take() {
+            Some(buffer) => buffer.finish(|text| self.write_all(text)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::{Diagnostic, Severity};
+    use crate::output::buffer::{SortKey, SortedBuffer, MAX_RUNS};
+
+    fn sorted(sort: SortKey, limit: usize) -> Vec<String> {
+        let mut buffer = SortedBuffer::new(sort, limit);
+        for (path, line, severity) in [
+            ("b.py", 2, Severity::Warning),
+            ("a.py", 9, Severity::Info),
+            ("b.py", 1, Severity::Error),
+            ("a.py", 3, Severity::Warning),
+        ] {
+            let mut diagnostic = Diagnostic::new(path, Some(line));
+            diagnostic.severity = Some(severity);
+            buffer
+                .push(&diagnostic, format!("{}:{}\n", path, line))
+                .unwrap();
+        }
+        let mut lines = Vec::new();
+        buffer
+            .finish(|text| {
+                lines.push(text.trim_end().to_string());
+                Ok(())
+            })
+            .unwrap();
+        lines
+    }
+
+    #[test]
+    fn test_sorted_buffer() {
+        let by_file = ["a.py:3", "a.py:9", "b.py:1", "b.py:2"];
+        assert_eq!(sorted(SortKey::File, 1 << 20), by_file);
+        // Every line spilled to its own run
+        assert_eq!(sorted(SortKey::File, 0), by_file);
+        // Two runs and lines still held
+        assert_eq!(sorted(SortKey::File, 20), by_file);
+        // The runs are merged into one as they pile up
+        let mut buffer = SortedBuffer::new(SortKey::File, 0);
+        for line in (1..=MAX_RUNS as u32 + 2).rev() {
+            buffer
+                .push(&Diagnostic::new("a.py", Some(line)), line.to_string())
+                .unwrap();
+        }
+        assert_eq!(buffer.runs.len(), 3);
+        let mut lines = Vec::new();
+        buffer
+            .finish(|text| {
+                lines.push(text.parse::<u32>().unwrap());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(lines, (1..=MAX_RUNS as u32 + 2).collect::<Vec<_>>());
+        assert_eq!(
+            sorted(SortKey::Severity, 20),
+            ["b.py:1", "a.py:3", "b.py:2", "a.py:9"]
+        );
+    }
+}
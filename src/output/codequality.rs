@@ -0,0 +1,147 @@
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::fingerprint::{finding_fingerprint, fingerprint};
+use crate::location::LocationResolver;
+use crate::rule_docs::RuleDocs;
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(Serialize)]
+struct Issue<'a> {
+    description: &'a str,
+    check_name: &'a str,
+    fingerprint: String,
+    severity: &'static str,
+    location: Location<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: Option<Content>,
+}
+
+/// Markdown GitLab shows with the issue
+#[derive(Serialize)]
+struct Content {
+    body: String,
+}
+
+#[derive(Serialize)]
+struct Location<'a> {
+    path: &'a str,
+    lines: Lines,
+}
+
+#[derive(Serialize)]
+struct Lines {
+    begin: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    end: Option<u32>,
+}
+
+fn severity(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "major",
+        Severity::Warning => "minor",
+        Severity::Info => "info",
+    }
+}
+
+/// The GitLab Code Quality report, a JSON array of issues.
+///
+/// Fingerprints are [`finding_fingerprint`]s of the content of the finding's line in
+/// place of its message, so they survive edits that only move the line. Findings that
+/// would share one get their occurrence number hashed in too, as GitLab drops duplicate
+/// fingerprints. Rules with documentation link to it in the issue's content.
+pub fn render(diagnostics: &[Diagnostic], root: &Path, rule_docs: RuleDocs) -> Result<String> {
+    let mut sources = LocationResolver::new(root);
+    let mut seen = HashMap::new();
+    let issues: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let rule = diagnostic.rule.as_deref().unwrap_or_default();
+            let content = diagnostic
+                .line
+                .and_then(|line| sources.source_line(&diagnostic.path, line))
+                .map_or_else(
+                    || diagnostic.text().to_string(),
+                    |text| text.trim().to_string(),
+                );
+            let occurrence = seen
+                .entry((diagnostic.path.clone(), rule.to_string(), content.clone()))
+                .or_insert(0);
+            let fingerprint = match *occurrence {
+                0 => finding_fingerprint(&diagnostic.path, Some(rule), &content),
+                n => fingerprint(&[&diagnostic.path, rule, &content, &n.to_string()]),
+            };
+            *occurrence += 1;
+            Issue {
+                description: diagnostic.text(),
+                check_name: diagnostic
+                    .rule
+                    .as_deref()
+                    .or(diagnostic.tool.as_deref())
+                    .unwrap_or("diff-format"),
+                fingerprint,
+                severity: severity(diagnostic.effective_severity()),
+                location: Location {
+                    path: &diagnostic.path,
+                    lines: Lines {
+                        begin: diagnostic.line.unwrap_or(1),
+                        end: diagnostic.span().map(|(_, end)| end),
+                    },
+                },
+                content: rule_docs.url(diagnostic).map(|url| Content {
+                    body: format!("[{}]({})", rule, url),
+                }),
+            }
+        })
+        .collect();
+    Ok(serde_json::to_string_pretty(&issues)? + "\n")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::{Diagnostic, Severity};
+    use crate::fingerprint::finding_fingerprint;
+    use crate::fixtures::TempDir;
+    use crate::output::codequality::render;
+    use crate::rule_docs::RuleDocs;
+    use serde_json::Value;
+    use std::fs;
+
+    #[test]
+    fn test_codequality() {
+        let root = TempDir::new("codequality").unwrap();
+        fs::write(root.join("a.py"), "x = 1\n    long_line = 2\n").unwrap();
+
+        let mut diagnostic = Diagnostic::new("a.py", Some(2));
+        diagnostic.rule = Some("E501".to_string());
+        diagnostic.severity = Some(Severity::Warning);
+        diagnostic.message = Some("E501 Line too long".to_string());
+        let render = |diagnostics: &[Diagnostic]| -> Value {
+            serde_json::from_str(&render(diagnostics, &root, RuleDocs::builtin()).unwrap()).unwrap()
+        };
+        let report = render(&[diagnostic.clone(), diagnostic.clone()]);
+        assert_eq!(report[0]["check_name"], "E501");
+        assert_eq!(report[0]["severity"], "minor");
+        assert_eq!(report[0]["location"]["path"], "a.py");
+        assert_eq!(report[0]["location"]["lines"]["begin"], 2);
+        assert_ne!(report[0]["fingerprint"], report[1]["fingerprint"]);
+        assert_eq!(
+            report[0]["content"]["body"],
+            "[E501](https://www.flake8rules.com/rules/E501.html)"
+        );
+        // What `fingerprint --line 2` prints
+        assert_eq!(
+            report[0]["fingerprint"],
+            finding_fingerprint("a.py", Some("E501"), "long_line = 2")
+        );
+
+        // The same line content elsewhere in the file keeps the fingerprint
+        fs::write(root.join("a.py"), "# moved\nx = 1\n  long_line = 2\n").unwrap();
+        diagnostic.line = Some(3);
+        assert_eq!(
+            render(&[diagnostic])[0]["fingerprint"],
+            report[0]["fingerprint"]
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use crate::diagnostic::Diagnostic;
+use crate::{HunkMap, HunkRange};
+use clap::ValueEnum;
+
+/// A column of the CSV output, selected with `--csv-columns`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Column {
+    File,
+    Line,
+    Col,
+    Severity,
+    Rule,
+    Message,
+    Tool,
+    /// First line of the changed hunk the finding falls in
+    #[value(name = "hunk_start")]
+    HunkStart,
+    /// Last line of that hunk
+    #[value(name = "hunk_end")]
+    HunkEnd,
+}
+
+pub const DEFAULT_COLUMNS: &[Column] = &[
+    Column::File,
+    Column::Line,
+    Column::Col,
+    Column::Severity,
+    Column::Rule,
+    Column::Message,
+    Column::Tool,
+    Column::HunkStart,
+    Column::HunkEnd,
+];
+
+/// Quotes `text` if it holds a separator, a quote or a line break, doubling its quotes
+fn field(text: &str) -> String {
+    if text.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+fn number(value: Option<u32>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+/// The hunk closest to the finding's lines, overlapping them unless it only matched
+/// through the `hunk` or `file` policy
+fn hunk(file_hunks: Option<&HunkMap>, diagnostic: &Diagnostic) -> Option<HunkRange> {
+    let (start, end) = diagnostic.span()?;
+    let distance = |&(hunk_start, hunk_end): &HunkRange| {
+        hunk_start
+            .saturating_sub(end)
+            .max(start.saturating_sub(hunk_end))
+    };
+    file_hunks?
+        .get(&diagnostic.path)?
+        .iter()
+        .copied()
+        .min_by_key(distance)
+}
+
+/// A header row naming `columns`, then a row per finding with empty fields for missing
+/// parts, quoted as RFC 4180 describes
+pub fn render(
+    diagnostics: &[Diagnostic],
+    file_hunks: Option<&HunkMap>,
+    columns: &[Column],
+) -> String {
+    let header: Vec<_> = columns
+        .iter()
+        .map(|column| column.to_possible_value().unwrap().get_name().to_string())
+        .collect();
+    let mut csv = header.join(",") + "\n";
+    for diagnostic in diagnostics {
+        let hunk = hunk(file_hunks, diagnostic);
+        let row: Vec<_> = columns
+            .iter()
+            .map(|column| match column {
+                Column::File => field(&diagnostic.path),
+                Column::Line => number(diagnostic.line),
+                Column::Col => number(diagnostic.column),
+                Column::Severity => diagnostic.effective_severity().as_str().to_string(),
+                Column::Rule => field(diagnostic.rule.as_deref().unwrap_or_default()),
+                Column::Message => field(diagnostic.text()),
+                Column::Tool => field(diagnostic.tool.as_deref().unwrap_or_default()),
+                Column::HunkStart => number(hunk.map(|(start, _)| start)),
+                Column::HunkEnd => number(hunk.map(|(_, end)| end)),
+            })
+            .collect();
+        csv += &row.join(",");
+        csv.push('\n');
+    }
+    csv
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::Diagnostic;
+    use crate::output::csv::{render, Column, DEFAULT_COLUMNS};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_render() {
+        let mut diagnostic = Diagnostic::new("src/a.py", Some(12));
+        diagnostic.column = Some(5);
+        diagnostic.rule = Some("E501".to_string());
+        diagnostic.message = Some("Line too long, \"120\" > 79".to_string());
+        diagnostic.tool = Some("python".to_string());
+        let file_level = Diagnostic::new("src/b.py", None);
+        let file_hunks = HashMap::from([("src/a.py".to_string(), vec![(2, 4), (10, 11)])]);
+        assert_eq!(
+            render(
+                &[diagnostic.clone(), file_level],
+                Some(&file_hunks),
+                DEFAULT_COLUMNS
+            ),
+            "file,line,col,severity,rule,message,tool,hunk_start,hunk_end\n\
+             src/a.py,12,5,error,E501,\"Line too long, \"\"120\"\" > 79\",python,10,11\n\
+             src/b.py,,,error,,,,,\n"
+        );
+        diagnostic.message = Some("one\ntwo".to_string());
+        assert_eq!(
+            render(&[diagnostic], None, &[Column::Message, Column::HunkStart]),
+            "message,hunk_start\n\"one\ntwo\",\n"
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use crate::diagnostic::{Diagnostic, Severity};
+
+/// Escapes the message of a workflow command
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escapes a `key=value` property of a workflow command
+fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// GitHub Actions `::error file=...,line=...::message` workflow commands, which show up
+/// as annotations on the pull request diff
+pub fn render(diagnostics: &[Diagnostic]) -> String {
+    diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let command = match diagnostic.effective_severity() {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+                Severity::Info => "notice",
+            };
+            let mut properties = vec![format!("file={}", escape_property(&diagnostic.path))];
+            if let Some(line) = diagnostic.line {
+                properties.push(format!("line={}", line));
+                properties.extend(diagnostic.column.map(|column| format!("col={}", column)));
+                properties.extend(diagnostic.end_line.map(|end| format!("endLine={}", end)));
+                properties.extend(
+                    diagnostic
+                        .end_column
+                        .map(|column| format!("endColumn={}", column)),
+                );
+            }
+            if let Some(rule) = &diagnostic.rule {
+                properties.push(format!("title={}", escape_property(rule)));
+            }
+            format!(
+                "::{} {}::{}\n",
+                command,
+                properties.join(","),
+                escape_data(diagnostic.text())
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::{Diagnostic, Severity};
+    use crate::output::github::render;
+
+    #[test]
+    fn test_workflow_commands() {
+        let mut diagnostic = Diagnostic::new("src/a,b.py", Some(7));
+        diagnostic.column = Some(3);
+        diagnostic.severity = Some(Severity::Warning);
+        diagnostic.rule = Some("W605".to_string());
+        diagnostic.message = Some("invalid escape: 100%\nsee docs".to_string());
+        let mut file_level = Diagnostic::new("src/teh.rs", None);
+        file_level.severity = Some(Severity::Info);
+        file_level.raw = "src/teh.rs: `teh` -> `the`".to_string();
+        assert_eq!(
+            render(&[diagnostic, file_level]),
+            "::warning file=src/a%2Cb.py,line=7,col=3,title=W605::invalid escape: 100%25%0Asee docs\n\
+             ::notice file=src/teh.rs::src/teh.rs: `teh` -> `the`\n"
+        );
+    }
+}
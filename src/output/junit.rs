@@ -0,0 +1,70 @@
+use crate::diagnostic::Diagnostic;
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters are not allowed in XML 1.0
+            c if c.is_control() && c != '\n' && c != '\t' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// One failing test case per finding, so CI test summaries list them individually
+pub fn render(diagnostics: &[Diagnostic]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites>\n  <testsuite name=\"diff-format\" tests=\"{}\" failures=\"{}\">\n",
+        diagnostics.len().max(1),
+        diagnostics.len()
+    ));
+    if diagnostics.is_empty() {
+        // An empty suite renders as "no tests" rather than a pass
+        xml.push_str("    <testcase classname=\"diff-format\" name=\"changed lines\"/>\n");
+    }
+    for diagnostic in diagnostics {
+        let name = match diagnostic.line {
+            Some(line) => format!("{}:{}", diagnostic.path, line),
+            None => diagnostic.path.clone(),
+        };
+        xml.push_str(&format!(
+            "    <testcase classname=\"{}\" name=\"{}\" file=\"{}\">\n      \
+             <failure type=\"{}\" message=\"{}\">{}</failure>\n    </testcase>\n",
+            escape(diagnostic.tool.as_deref().unwrap_or("diff-format")),
+            escape(&name),
+            escape(&diagnostic.path),
+            diagnostic.effective_severity().as_str(),
+            escape(diagnostic.text()),
+            escape(&diagnostic.raw)
+        ));
+    }
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::Diagnostic;
+    use crate::output::junit::render;
+
+    #[test]
+    fn test_junit_escapes_findings() {
+        let mut diagnostic = Diagnostic::new("src/a.cc", Some(4));
+        diagnostic.message = Some("use \"nullptr\" instead of <NULL>".to_string());
+        diagnostic.raw = "src/a.cc:4: use \"nullptr\" instead of <NULL>".to_string();
+
+        let xml = render(&[diagnostic]);
+        assert!(xml.contains("tests=\"1\" failures=\"1\""));
+        assert!(xml.contains("name=\"src/a.cc:4\""));
+        assert!(xml.contains("message=\"use &quot;nullptr&quot; instead of &lt;NULL&gt;\""));
+
+        assert!(render(&[]).contains("failures=\"0\""));
+    }
+}
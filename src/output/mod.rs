@@ -0,0 +1,308 @@
+pub mod buffer;
+mod codequality;
+mod csv;
+mod github;
+mod junit;
+mod porcelain;
+mod split;
+mod vscode;
+mod warnings_ng;
+
+use crate::diagnostic::Diagnostic;
+use crate::rule_docs::RuleDocs;
+use crate::HunkMap;
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufWriter, IsTerminal, Stdout, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{LazyLock as Lazy, Mutex};
+
+pub use csv::Column as CsvColumn;
+pub use split::SplitOutput;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    /// Echo matching lint lines unchanged
+    Text,
+    /// Jenkins Warnings NG native `issues.json`
+    WarningsNg,
+    /// JUnit XML with a failing test case per finding
+    Junit,
+    /// Stable tab separated `file line rule message` records for scripts
+    Porcelain,
+    /// `file:line:column: severity: message` lines for the problem matcher in
+    /// `src/output/vscode-problem-matcher.json`
+    VscodeProblems,
+    /// GitHub Actions workflow commands, annotating the findings on the pull request
+    Github,
+    /// GitLab Code Quality JSON, for a `codequality` report artifact
+    Codequality,
+    /// Comma separated values with a header row, in the `--csv-columns` order
+    Csv,
+}
+
+/// What renders draw on besides the findings
+#[derive(Debug, Clone, Copy)]
+pub struct RenderContext<'a> {
+    /// The directory paths are relative to, for formats that quote the source
+    root: &'a Path,
+    /// The changed lines the findings were matched against
+    file_hunks: Option<&'a HunkMap>,
+    csv_columns: &'a [CsvColumn],
+    /// Where reports that link rules link them to
+    rule_docs: RuleDocs<'a>,
+}
+
+impl<'a> RenderContext<'a> {
+    pub fn new(root: &'a Path) -> Self {
+        RenderContext {
+            root,
+            file_hunks: None,
+            csv_columns: csv::DEFAULT_COLUMNS,
+            rule_docs: RuleDocs::builtin(),
+        }
+    }
+
+    pub fn with_hunks(self, file_hunks: &'a HunkMap) -> Self {
+        RenderContext {
+            file_hunks: Some(file_hunks),
+            ..self
+        }
+    }
+
+    /// Keeps the default columns when `columns` is empty
+    pub fn with_csv_columns(self, columns: &'a [CsvColumn]) -> Self {
+        if columns.is_empty() {
+            return self;
+        }
+        RenderContext {
+            csv_columns: columns,
+            ..self
+        }
+    }
+
+    pub fn with_rule_docs(self, rule_docs: RuleDocs<'a>) -> Self {
+        RenderContext { rule_docs, ..self }
+    }
+}
+
+impl OutputFormat {
+    /// Whether lines are echoed as they are matched instead of rendered once input ends
+    pub fn is_streaming(self) -> bool {
+        self == OutputFormat::Text
+    }
+
+    pub fn render(self, diagnostics: &[Diagnostic], context: &RenderContext) -> Result<String> {
+        match self {
+            OutputFormat::Text => Ok(diagnostics
+                .iter()
+                .map(|diagnostic| format!("{}\n", diagnostic.raw))
+                .collect()),
+            OutputFormat::WarningsNg => warnings_ng::render(diagnostics),
+            OutputFormat::Junit => Ok(junit::render(diagnostics)),
+            OutputFormat::Porcelain => Ok(porcelain::render(diagnostics)),
+            OutputFormat::VscodeProblems => Ok(vscode::render(diagnostics)),
+            OutputFormat::Github => Ok(github::render(diagnostics)),
+            OutputFormat::Codequality => {
+                codequality::render(diagnostics, context.root, context.rule_docs)
+            }
+            OutputFormat::Csv => Ok(csv::render(
+                diagnostics,
+                context.file_hunks,
+                context.csv_columns,
+            )),
+        }
+    }
+}
+
+/// Writes to a temporary file next to `path` and renames it over `path`, so a killed
+/// run leaves either the previous file or the complete new one
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(format!(".tmp{}", std::process::id()));
+    let temporary = PathBuf::from(temporary);
+    let written = fs::write(&temporary, contents).and_then(|()| fs::rename(&temporary, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    written
+}
+
+/// Appends to `path` under an exclusive lock, so runs writing the same file at once add
+/// their results whole rather than interleaved
+pub fn append_locked(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.lock()?;
+    file.write_all(contents)
+}
+
+/// Stdout behind a buffer, as echoing millions of lines one flush at a time is slow.
+/// On a terminal every write is flushed, so findings still show up as they are matched
+static STDOUT: Lazy<Mutex<BufWriter<Stdout>>> =
+    Lazy::new(|| Mutex::new(BufWriter::with_capacity(1 << 16, io::stdout())));
+static STDOUT_IS_TERMINAL: Lazy<bool> = Lazy::new(|| io::stdout().is_terminal());
+
+/// Writes out what the `stdout` destination buffered; needed before exiting
+pub fn flush_stdout() -> io::Result<()> {
+    STDOUT.lock().unwrap().flush()
+}
+
+/// Where an output is written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Destination {
+    Stdout,
+    Stderr,
+    File(PathBuf),
+    /// A file added to rather than replaced
+    Append(PathBuf),
+}
+
+impl Destination {
+    /// Whether streaming formats can echo lines here as they are matched
+    pub fn is_stream(&self) -> bool {
+        self.path().is_none()
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Destination::File(path) | Destination::Append(path) => Some(path),
+            Destination::Stdout | Destination::Stderr => None,
+        }
+    }
+
+    pub fn write(&self, text: &str) -> Result<()> {
+        match self {
+            Destination::Stdout => {
+                let mut stdout = STDOUT.lock().unwrap();
+                stdout.write_all(text.as_bytes())?;
+                if *STDOUT_IS_TERMINAL {
+                    stdout.flush()?;
+                }
+            }
+            Destination::Stderr => io::stderr().write_all(text.as_bytes())?,
+            Destination::File(path) | Destination::Append(path) => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)
+                        .with_context(|| format!("Unable to create {}", parent.display()))?;
+                }
+                let written = match self {
+                    Destination::Append(_) => append_locked(path, text.as_bytes()),
+                    _ => write_atomic(path, text.as_bytes()),
+                };
+                written.with_context(|| format!("Unable to write report to {}", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An output format and its destination, given as `FORMAT=DEST` where `DEST` is
+/// `stdout`, `stderr` or a file path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Output {
+    pub format: OutputFormat,
+    pub destination: Destination,
+}
+
+impl Output {
+    /// Whether lines are echoed as they are matched
+    pub fn is_streaming(&self) -> bool {
+        self.format.is_streaming() && self.destination.is_stream()
+    }
+
+    pub fn emit(&self, diagnostics: &[Diagnostic], context: &RenderContext) -> Result<()> {
+        self.destination
+            .write(&self.format.render(diagnostics, context)?)
+    }
+}
+
+impl FromStr for Output {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let (format, destination) = match value.split_once('=') {
+            Some((format, destination)) if !destination.is_empty() => (format, destination),
+            _ => bail!("Expected FORMAT=DEST, got '{}'", value),
+        };
+        let format = OutputFormat::from_str(format, true).map_err(anyhow::Error::msg)?;
+        Ok(Output {
+            format,
+            destination: destination.parse()?,
+        })
+    }
+}
+
+impl FromStr for Destination {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        Ok(match value {
+            "stdout" | "-" => Destination::Stdout,
+            "stderr" => Destination::Stderr,
+            path => Destination::File(path.into()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::Diagnostic;
+    use crate::fixtures::TempDir;
+    use crate::output::{Destination, Output, OutputFormat, RenderContext};
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_parse_output() {
+        assert_eq!(
+            "junit=stderr".parse::<Output>().unwrap(),
+            Output {
+                format: OutputFormat::Junit,
+                destination: Destination::Stderr
+            }
+        );
+        let output: Output = "text=out/lint.txt".parse().unwrap();
+        assert_eq!(output.destination, Destination::File("out/lint.txt".into()));
+        assert!(!output.is_streaming());
+        assert!("text".parse::<Output>().is_err());
+        assert!("sarif=stdout".parse::<Output>().is_err());
+    }
+
+    #[test]
+    fn test_append() {
+        let dir = TempDir::new("append").unwrap();
+        let path = dir.join("lint.txt");
+        for text in ["a.py:1: E1\n", "b.py:2: E2\n"] {
+            Destination::Append(path.clone()).write(text).unwrap();
+        }
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "a.py:1: E1\nb.py:2: E2\n"
+        );
+        Destination::File(path.clone())
+            .write("c.py:3: E3\n")
+            .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "c.py:3: E3\n");
+    }
+
+    #[test]
+    fn test_porcelain() {
+        let mut diagnostic = Diagnostic::new("src/a.py", Some(3));
+        diagnostic.rule = Some("E501".to_string());
+        diagnostic.message = Some("E501 Line\ttoo long".to_string());
+        let file_level = Diagnostic::new("src/teh.rs", None);
+        assert_eq!(
+            OutputFormat::Porcelain
+                .render(
+                    &[diagnostic, file_level],
+                    &RenderContext::new(Path::new("."))
+                )
+                .unwrap(),
+            "src/a.py\t3\tE501\tE501 Line too long\nsrc/teh.rs\t\t\t\n"
+        );
+    }
+}
@@ -0,0 +1,27 @@
+use crate::diagnostic::Diagnostic;
+
+/// Tabs and line breaks would split fields or records
+fn field(text: &str) -> String {
+    text.replace(['\t', '\r', '\n'], " ")
+}
+
+/// `file<TAB>line<TAB>rule<TAB>message` per finding, with empty fields for missing parts.
+///
+/// This layout is a stable interface for scripts and must not change across versions.
+pub fn render(diagnostics: &[Diagnostic]) -> String {
+    diagnostics
+        .iter()
+        .map(|diagnostic| {
+            format!(
+                "{}\t{}\t{}\t{}\n",
+                field(&diagnostic.path),
+                diagnostic
+                    .line
+                    .map(|line| line.to_string())
+                    .unwrap_or_default(),
+                field(diagnostic.rule.as_deref().unwrap_or_default()),
+                field(diagnostic.text())
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,166 @@
+use crate::codeowners::CodeOwners;
+use crate::diagnostic::Diagnostic;
+use crate::output::{Destination, OutputFormat, RenderContext};
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::LazyLock as Lazy;
+
+static PLACEHOLDER: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{(\w*)\}").unwrap());
+
+/// Group of findings without a CODEOWNERS owner
+const UNOWNED: &str = "unowned";
+
+/// An output written as one document per group of findings, given as
+/// `FORMAT=TEMPLATE` where the template path holds `{file}`, `{file_dir}` or `{owner}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitOutput {
+    pub format: OutputFormat,
+    pub template: String,
+}
+
+/// An owner as a file name, e.g. `org-web` for `@org/web`
+fn owner_key(owner: &str) -> String {
+    owner.trim_start_matches('@').replace(['/', '\\'], "-")
+}
+
+impl SplitOutput {
+    pub fn uses_owners(&self) -> bool {
+        self.template.contains("{owner}")
+    }
+
+    /// Destinations of `diagnostic`, several when it has several owners
+    fn paths(&self, diagnostic: &Diagnostic, owners: &CodeOwners) -> Vec<PathBuf> {
+        let file_dir = match Path::new(&diagnostic.path).parent() {
+            Some(parent) if parent != Path::new("") => parent.to_string_lossy().into_owned(),
+            _ => ".".to_string(),
+        };
+        let mut keys = Vec::new();
+        if self.uses_owners() {
+            let owners = owners.owners(&diagnostic.path);
+            keys.extend(owners.iter().map(|owner| owner_key(owner)));
+        }
+        // A single group when the template doesn't split by owner
+        if keys.is_empty() {
+            keys.push(UNOWNED.to_string());
+        }
+        keys.iter()
+            .map(|owner| {
+                let path = PLACEHOLDER.replace_all(&self.template, |captures: &regex::Captures| {
+                    match &captures[1] {
+                        "file" => diagnostic.path.clone(),
+                        "file_dir" => file_dir.clone(),
+                        _ => owner.clone(),
+                    }
+                });
+                PathBuf::from(path.into_owned())
+            })
+            .collect()
+    }
+
+    /// Writes a document for each group that has findings, returning their paths
+    pub fn emit(
+        &self,
+        diagnostics: &[Diagnostic],
+        context: &RenderContext,
+        owners: &CodeOwners,
+    ) -> Result<Vec<PathBuf>> {
+        let mut groups: BTreeMap<PathBuf, Vec<Diagnostic>> = BTreeMap::new();
+        for diagnostic in diagnostics {
+            for path in self.paths(diagnostic, owners) {
+                groups.entry(path).or_default().push(diagnostic.clone());
+            }
+        }
+        for (path, diagnostics) in &groups {
+            Destination::File(path.clone()).write(&self.format.render(diagnostics, context)?)?;
+        }
+        Ok(groups.into_keys().collect())
+    }
+}
+
+impl FromStr for SplitOutput {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let (format, template) = match value.split_once('=') {
+            Some((format, template)) if !template.is_empty() => (format, template),
+            _ => bail!("Expected FORMAT=TEMPLATE, got '{}'", value),
+        };
+        let format = OutputFormat::from_str(format, true).map_err(anyhow::Error::msg)?;
+        let mut keys = PLACEHOLDER.captures_iter(template).peekable();
+        if keys.peek().is_none() {
+            bail!(
+                "'{}' has none of {{file}}, {{file_dir}} or {{owner}}",
+                template
+            );
+        }
+        for captures in keys {
+            if !matches!(&captures[1], "file" | "file_dir" | "owner") {
+                bail!("Unknown key {} in '{}'", &captures[0], template);
+            }
+        }
+        Ok(SplitOutput {
+            format,
+            template: template.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::codeowners::CodeOwners;
+    use crate::diagnostic::Diagnostic;
+    use crate::fixtures::TempDir;
+    use crate::output::{OutputFormat, RenderContext, SplitOutput};
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_split_output() {
+        assert!("porcelain=reports.txt".parse::<SplitOutput>().is_err());
+        assert!("porcelain={team}.txt".parse::<SplitOutput>().is_err());
+
+        let root = TempDir::new("split").unwrap();
+        let split: SplitOutput = format!("porcelain={}/{{owner}}.txt", root.display())
+            .parse()
+            .unwrap();
+        assert_eq!(split.format, OutputFormat::Porcelain);
+        let owners = CodeOwners::parse("*.py @org/py @alice\nweb/ @org/web\n").unwrap();
+        let diagnostics = [
+            Diagnostic::new("a.py", Some(1)),
+            Diagnostic::new("web/a.ts", Some(2)),
+            Diagnostic::new("Makefile", Some(3)),
+        ];
+        let paths = split
+            .emit(&diagnostics, &RenderContext::new(Path::new(".")), &owners)
+            .unwrap();
+        let names: Vec<_> = paths
+            .iter()
+            .map(|path| path.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            ["alice.txt", "org-py.txt", "org-web.txt", "unowned.txt"]
+        );
+        assert!(fs::read_to_string(root.join("org-web.txt"))
+            .unwrap()
+            .starts_with("web/a.ts\t2"));
+
+        let split: SplitOutput = "porcelain={file_dir}/lint.txt".parse().unwrap();
+        let paths: Vec<_> = diagnostics
+            .iter()
+            .flat_map(|diagnostic| split.paths(diagnostic, &owners))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                Path::new("./lint.txt"),
+                Path::new("web/lint.txt"),
+                Path::new("./lint.txt")
+            ]
+        );
+    }
+}
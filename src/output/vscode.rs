@@ -0,0 +1,51 @@
+use crate::diagnostic::Diagnostic;
+
+/// `file:line:column: severity: message`, with findings without a position on `1:1`.
+///
+/// Parsed by the VS Code problem matcher in `vscode-problem-matcher.json`, kept next to
+/// this layout and checked against it so they never drift.
+pub fn render(diagnostics: &[Diagnostic]) -> String {
+    diagnostics
+        .iter()
+        .map(|diagnostic| {
+            format!(
+                "{}:{}:{}: {}: {}\n",
+                diagnostic.path,
+                diagnostic.line.unwrap_or(1),
+                diagnostic.column.unwrap_or(1),
+                diagnostic.effective_severity().as_str(),
+                diagnostic.text().replace(['\r', '\n'], " ")
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::{Diagnostic, Severity};
+    use crate::output::vscode::render;
+    use regex::Regex;
+    use serde_json::Value;
+
+    #[test]
+    fn test_matches_problem_matcher() {
+        let matcher: Value =
+            serde_json::from_str(include_str!("vscode-problem-matcher.json")).unwrap();
+        let regex = Regex::new(matcher["pattern"]["regexp"].as_str().unwrap()).unwrap();
+
+        let mut diagnostic = Diagnostic::new("src/a b.py", Some(7));
+        diagnostic.column = Some(3);
+        diagnostic.severity = Some(Severity::Warning);
+        diagnostic.message = Some("W605 invalid escape: '\\d'".to_string());
+        let output = render(&[diagnostic, Diagnostic::new("src/teh.rs", None)]);
+        let lines: Vec<_> = output.lines().collect();
+
+        let captures = regex.captures(lines[0]).unwrap();
+        assert_eq!(&captures[1], "src/a b.py");
+        assert_eq!(&captures[2], "7");
+        assert_eq!(&captures[3], "3");
+        assert_eq!(&captures[4], "warning");
+        assert_eq!(&captures[5], "W605 invalid escape: '\\d'");
+        assert_eq!(&regex.captures(lines[1]).unwrap()[4], "error");
+    }
+}
@@ -0,0 +1,78 @@
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::fingerprint::finding_fingerprint;
+use anyhow::Result;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct Report<'a> {
+    issues: Vec<Issue<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Issue<'a> {
+    file_name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line_start: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line_end: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    column_start: Option<u32>,
+    severity: &'static str,
+    message: &'a str,
+    fingerprint: String,
+    origin: &'a str,
+}
+
+fn severity(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "ERROR",
+        Severity::Warning => "NORMAL",
+        Severity::Info => "LOW",
+    }
+}
+
+pub fn render(diagnostics: &[Diagnostic]) -> Result<String> {
+    let issues = diagnostics
+        .iter()
+        .map(|diagnostic| Issue {
+            file_name: &diagnostic.path,
+            line_start: diagnostic.line,
+            line_end: diagnostic.line,
+            column_start: diagnostic.column,
+            severity: severity(diagnostic.effective_severity()),
+            message: diagnostic.text(),
+            fingerprint: finding_fingerprint(
+                &diagnostic.path,
+                diagnostic.rule.as_deref(),
+                diagnostic.text(),
+            ),
+            origin: diagnostic.tool.as_deref().unwrap_or("diff-format"),
+        })
+        .collect();
+    Ok(serde_json::to_string_pretty(&Report { issues })? + "\n")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::Diagnostic;
+    use crate::output::warnings_ng::render;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_warnings_ng_report() {
+        let mut diagnostic = Diagnostic::new("src/a.py", Some(7));
+        diagnostic.column = Some(2);
+        diagnostic.message = Some("E225 missing whitespace".to_string());
+        diagnostic.tool = Some("python".to_string());
+
+        let report: Value = serde_json::from_str(&render(&[diagnostic]).unwrap()).unwrap();
+        let issue = &report["issues"][0];
+        assert_eq!(issue["fileName"], json!("src/a.py"));
+        assert_eq!(issue["lineStart"], json!(7));
+        assert_eq!(issue["columnStart"], json!(2));
+        assert_eq!(issue["severity"], json!("ERROR"));
+        assert_eq!(issue["origin"], json!("python"));
+        assert_eq!(issue["fingerprint"].as_str().unwrap().len(), 16);
+    }
+}
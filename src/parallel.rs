@@ -0,0 +1,258 @@
+//! Parsing lint output on several threads, for `--jobs`. Lines are sent to the threads
+//! in numbered batches and the batches put back in input order as they come back, so
+//! the stateful matching after parsing sees the same sequence as with a single thread;
+//! `--unordered` takes them as they come instead.
+
+use crate::demux::{Demux, Routed};
+use crate::diagnostic::Diagnostic;
+use crate::error::{Error, Result};
+use crate::input::Lines;
+use crate::parsers::Parsers;
+use crate::remove_ansi_colors;
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Lines sent to a thread at a time
+const BATCH: usize = 256;
+
+/// Batches read ahead per thread, being parsed or waiting for the ones before them
+const BATCHES_PER_JOB: u64 = 16;
+
+/// A line of lint output, as echoed, and the findings parsed from it
+pub type Parsed = (String, Vec<Diagnostic>);
+
+/// Strips escapes from `line` for echoing if asked, and always for parsing
+pub fn parse_line(parsers: &Parsers, line: String, strip_escapes: bool) -> Parsed {
+    let line = if strip_escapes {
+        remove_ansi_colors(&line)
+    } else {
+        line
+    };
+    let diagnostics = parsers.parse(&remove_ansi_colors(&line));
+    (line, diagnostics)
+}
+
+/// A batch of parsed lines, by its number, or the panic of the thread parsing it
+type Batch = (u64, Result<Vec<Parsed>>);
+
+/// The threads parsing batches, which exit once the batches stop coming
+struct Pool {
+    work: Sender<(u64, Vec<Routed>)>,
+    results: Receiver<Batch>,
+}
+
+impl Pool {
+    fn new(parsers: &Arc<Parsers>, jobs: usize, strip_escapes: bool) -> Self {
+        let (work, queue) = mpsc::channel::<(u64, Vec<Routed>)>();
+        let queue = Arc::new(Mutex::new(queue));
+        let (done, results) = mpsc::channel();
+        for _ in 0..jobs {
+            let (parsers, queue, done) = (parsers.clone(), queue.clone(), done.clone());
+            thread::spawn(move || loop {
+                // The lock is only held to receive, so is never poisoned
+                let (number, lines) = match queue.lock().unwrap().recv() {
+                    Ok(batch) => batch,
+                    Err(_) => return,
+                };
+                let parsed = panic::catch_unwind(AssertUnwindSafe(|| {
+                    lines
+                        .into_iter()
+                        .map(|(line, routed): Routed| {
+                            let parsers = routed.as_deref().unwrap_or(&parsers);
+                            parse_line(parsers, line, strip_escapes)
+                        })
+                        .collect()
+                }));
+                let parsed = parsed.map_err(|_| Error::ParserPanicked);
+                if done.send((number, parsed)).is_err() {
+                    return;
+                }
+            });
+        }
+        Pool { work, results }
+    }
+}
+
+pub struct ParsedLines<'a> {
+    input: Lines<'a>,
+    parsers: Arc<Parsers>,
+    jobs: usize,
+    strip_escapes: bool,
+    unordered: bool,
+    /// Splits the lines of several tools
+    demux: Option<Demux<'a>>,
+    /// Started with the first batch
+    pool: Option<Pool>,
+    /// Batches sent to the pool, and taken back from it
+    sent: u64,
+    taken: u64,
+    /// Batches back before the ones ahead of them
+    pending: BTreeMap<u64, Result<Vec<Parsed>>>,
+    ready: VecDeque<Parsed>,
+    /// A read error, returned once the lines before it are
+    error: Option<Error>,
+    ended: bool,
+}
+
+impl<'a> ParsedLines<'a> {
+    pub fn new(input: Lines<'a>, parsers: Arc<Parsers>, jobs: usize, strip_escapes: bool) -> Self {
+        ParsedLines {
+            input,
+            parsers,
+            jobs: jobs.max(1),
+            strip_escapes,
+            unordered: false,
+            demux: None,
+            pool: None,
+            sent: 0,
+            taken: 0,
+            pending: BTreeMap::new(),
+            ready: VecDeque::new(),
+            error: None,
+            ended: false,
+        }
+    }
+
+    /// Yields lines as their batches are parsed rather than in input order
+    pub fn with_unordered(mut self, unordered: bool) -> Self {
+        self.unordered = unordered;
+        self
+    }
+
+    /// Parses the lines of each tool with its format, see [`crate::demux`]
+    pub fn with_demux(mut self, demux: Demux<'a>) -> Self {
+        self.demux = Some(demux);
+        self
+    }
+
+    /// The next line with the parsers for it, skipping sentinel lines
+    fn next_routed(&mut self) -> Option<io::Result<Routed>> {
+        loop {
+            let line = match self.input.next()? {
+                Ok(line) => line,
+                Err(err) => return Some(Err(err)),
+            };
+            match &mut self.demux {
+                Some(demux) => match demux.route(line) {
+                    Some(routed) => return Some(Ok(routed)),
+                    None => continue,
+                },
+                None => return Some(Ok((line, None))),
+            }
+        }
+    }
+
+    /// Reads batches ahead until the pool has enough of them or the input ends
+    fn send(&mut self) {
+        if self.pool.is_none() {
+            self.pool = Some(Pool::new(&self.parsers, self.jobs, self.strip_escapes));
+        }
+        while !self.ended && self.sent - self.taken < BATCHES_PER_JOB * self.jobs as u64 {
+            let mut lines = Vec::with_capacity(BATCH);
+            while lines.len() < BATCH {
+                match self.next_routed() {
+                    Some(Ok(routed)) => lines.push(routed),
+                    Some(Err(err)) => {
+                        self.error = Some(Error::Io(err));
+                        break;
+                    }
+                    None => break,
+                }
+            }
+            self.ended = lines.len() < BATCH;
+            if lines.is_empty() {
+                break;
+            }
+            // The threads only exit once this sender is dropped
+            let pool = self.pool.as_ref().unwrap();
+            let _ = pool.work.send((self.sent, lines));
+            self.sent += 1;
+        }
+    }
+
+    /// The next batch to yield: the one after the last taken, or any when unordered
+    fn receive(&mut self) -> Result<Vec<Parsed>> {
+        let results = &self.pool.as_ref().unwrap().results;
+        loop {
+            if let Some(batch) = self.pending.remove(&self.taken) {
+                return batch;
+            }
+            // Every thread caught its panics, so they are all there while batches are
+            let (number, batch) = results.recv().map_err(|_| Error::ParserPanicked)?;
+            if self.unordered {
+                return batch;
+            }
+            self.pending.insert(number, batch);
+        }
+    }
+}
+
+impl Iterator for ParsedLines<'_> {
+    type Item = Result<Parsed>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.jobs == 1 {
+            // Nothing to gain from batches, and a linter's output stays live
+            let (line, routed) = match self.next_routed()? {
+                Ok(routed) => routed,
+                Err(err) => return Some(Err(Error::Io(err))),
+            };
+            let parsers = routed.as_deref().unwrap_or(&self.parsers);
+            return Some(Ok(parse_line(parsers, line, self.strip_escapes)));
+        }
+        loop {
+            if let Some(parsed) = self.ready.pop_front() {
+                return Some(Ok(parsed));
+            }
+            self.send();
+            if self.sent == self.taken {
+                return self.error.take().map(Err);
+            }
+            let batch = self.receive();
+            self.taken += 1;
+            match batch {
+                Ok(parsed) => self.ready.extend(parsed),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parallel::ParsedLines;
+    use crate::parsers::{Format, Parsers};
+    use std::collections::HashMap;
+    use std::io;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_order() {
+        let parsers = Arc::new(Parsers::new(&[Format::Python], HashMap::new()));
+        let lines: Vec<_> = (1..=10_000)
+            .map(|line| Ok(format!("a.py:{}: E1 \x1b[31mred\x1b[0m", line)))
+            .chain([Err(io::Error::other("closed"))])
+            .collect();
+        let parsed: Vec<_> =
+            ParsedLines::new(Box::new(lines.into_iter()), parsers.clone(), 3, true).collect();
+        assert_eq!(parsed.len(), 10_001);
+        for (index, parsed) in parsed[..10_000].iter().enumerate() {
+            let (line, diagnostics) = parsed.as_ref().unwrap();
+            assert_eq!(line, &format!("a.py:{}: E1 red", index + 1));
+            assert_eq!(diagnostics[0].line, Some(index as u32 + 1));
+        }
+        assert!(parsed[10_000].is_err());
+
+        let lines = (1..=10_000).map(|line| Ok(format!("a.py:{}: E1 x", line)));
+        let mut numbers: Vec<_> = ParsedLines::new(Box::new(lines), parsers, 4, false)
+            .with_unordered(true)
+            .map(|parsed| parsed.unwrap().1[0].line.unwrap())
+            .collect();
+        numbers.sort_unstable();
+        assert_eq!(numbers, (1..=10_000).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,91 @@
+//! Support for partial (`--filter=blob:none`) clones.
+//!
+//! libgit2 cannot fetch missing objects on demand, so diffing against a tree whose blobs were
+//! never downloaded fails. We restrict the diff to the files the lint output mentions and fetch
+//! just those blobs through the git CLI, or skip them with `--no-lazy-fetch`.
+
+use anyhow::{bail, Context, Result};
+use git2::{ObjectType, Oid, Repository, Tree};
+use log::{debug, info};
+use std::path::Path;
+use std::process::Command;
+
+/// Returns the remote missing objects can be fetched from, if this is a partial clone
+pub fn promisor_remote(repo: &Repository) -> Option<String> {
+    let config = repo.config().ok()?;
+    if let Ok(remote) = config.get_string("extensions.partialclone") {
+        return Some(remote);
+    }
+    let mut entries = config.entries(Some(r"remote\..*\.promisor")).ok()?;
+    while let Some(Ok(entry)) = entries.next() {
+        if entry.value() == Some("true") {
+            let name = entry.name()?;
+            let remote = name.strip_prefix("remote.")?.strip_suffix(".promisor")?;
+            return Some(remote.to_string());
+        }
+    }
+    None
+}
+
+/// Returns the base tree blobs of `paths` that are not present in the local object store
+pub fn missing_blobs(
+    repo: &Repository,
+    tree: &Tree,
+    paths: &[String],
+) -> Result<Vec<(String, Oid)>> {
+    let odb = repo.odb().context("Can't open object database")?;
+    let mut missing = Vec::new();
+    for path in paths {
+        let entry = match tree.get_path(Path::new(path)) {
+            Ok(entry) => entry,
+            // Added in the working tree, nothing to fetch
+            Err(_) => continue,
+        };
+        if entry.kind() == Some(ObjectType::Blob) && !odb.exists(entry.id()) {
+            debug!("Blob {} of '{}' is missing", entry.id(), path);
+            missing.push((path.clone(), entry.id()));
+        }
+    }
+    Ok(missing)
+}
+
+pub fn fetch_blobs(repo: &Repository, remote: &str, oids: &[Oid]) -> Result<()> {
+    info!("Fetching {} missing blob(s) from '{}'", oids.len(), remote);
+    // Blobs have no history to negotiate, and advertising commits of a shallow base
+    // makes the server leave the blobs out
+    let status = Command::new("git")
+        .args(["-c", "fetch.negotiationAlgorithm=noop", "--git-dir"])
+        .arg(repo.path())
+        .args([
+            "fetch",
+            "--no-tags",
+            "--no-write-fetch-head",
+            "--filter=blob:none",
+        ])
+        .arg(remote)
+        .args(oids.iter().map(Oid::to_string))
+        .status()
+        .context("Unable to run git fetch")?;
+    if !status.success() {
+        bail!("git fetch of missing blobs failed with {}", status);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fixtures::TempDir;
+    use crate::partial_clone::promisor_remote;
+    use git2::Repository;
+
+    #[test]
+    fn test_promisor_remote() {
+        let dir = TempDir::new("promisor").unwrap();
+        let repo = Repository::init(&dir).unwrap();
+        assert_eq!(promisor_remote(&repo), None);
+
+        let mut config = repo.config().unwrap();
+        config.set_bool("remote.upstream.promisor", true).unwrap();
+        assert_eq!(promisor_remote(&repo).as_deref(), Some("upstream"));
+    }
+}
@@ -0,0 +1,58 @@
+//! Progress indicators for slow steps on large repositories, drawn on stderr.
+//!
+//! indicatif hides them when stderr is not a terminal, so CI logs stay clean. Only the
+//! command line draws them; the library functions they wrap draw nothing.
+
+use crate::error::Result;
+use crate::hunks::{self, generate_hunkmap_with};
+use crate::HunkMap;
+use git2::{Diff, Repository, Tree};
+use indicatif::{ProgressBar, ProgressStyle};
+use log::info;
+use std::time::Duration;
+
+/// A spinner for a step with no known length, ticking on its own while the step blocks
+pub fn spinner(message: &'static str) -> ProgressBar {
+    let spinner = ProgressBar::new_spinner().with_message(message);
+    spinner.set_style(
+        ProgressStyle::with_template("{spinner} {msg} [{elapsed}]").expect("Invalid template"),
+    );
+    spinner.enable_steady_tick(Duration::from_millis(100));
+    spinner
+}
+
+/// A bar counting the files of a diff
+pub fn files(count: usize) -> ProgressBar {
+    let bar = ProgressBar::new(count as u64);
+    bar.set_style(
+        ProgressStyle::with_template("{msg} {bar:30} {pos}/{len} files [{elapsed}]")
+            .expect("Invalid template"),
+    );
+    bar.set_message("Analyzing diff");
+    bar
+}
+
+/// [`hunks::get_diff`] with a spinner while it runs
+pub fn diff_with_spinner<'a>(
+    repo: &'a Repository,
+    tree: &Tree<'a>,
+    head: Option<&Tree<'a>>,
+    pathspecs: Option<&[String]>,
+    ignore_eol: bool,
+) -> Result<Diff<'a>> {
+    let spinner = spinner("Computing diff");
+    let diff = hunks::get_diff(repo, tree, head, pathspecs, ignore_eol);
+    spinner.finish_and_clear();
+    diff
+}
+
+/// [`hunks::generate_hunkmap`] with a bar counting the files
+pub fn hunkmap_with_bar(diff: &Diff) -> Result<HunkMap> {
+    let bar = files(diff.deltas().len());
+    let hunkmap = generate_hunkmap_with(diff, |path| {
+        bar.suspend(|| info!("Analyzing '{}'", path));
+        bar.inc(1);
+    });
+    bar.finish_and_clear();
+    hunkmap
+}
@@ -0,0 +1,101 @@
+use crate::metrics;
+use crate::ranges::merge_ranges;
+use crate::HunkRange;
+use clap::Args;
+use log::debug;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Widening of hunks before matching, so findings a change caused right next to it
+/// (e.g. an import made unused one line above) are kept too
+#[derive(Args, Debug, Clone, Copy, Default)]
+pub struct Proximity {
+    /// Also match the N lines before and after each hunk
+    #[arg(long, value_name = "N")]
+    context: Option<u32>,
+
+    /// Also match the whole of each function a hunk touches, found the same way as
+    /// for the `metrics` command
+    #[arg(long)]
+    function_context: bool,
+}
+
+impl Proximity {
+    /// Widens the hunks of every file, reading sources from `workdir` for
+    /// --function-context. Files that can't be read keep their hunks
+    pub fn apply(self, workdir: &Path, file_hunks: &mut HashMap<String, Vec<HunkRange>>) {
+        if self.context.is_none() && !self.function_context {
+            return;
+        }
+        for (path, ranges) in file_hunks.iter_mut() {
+            let source = if self.function_context {
+                fs::read_to_string(workdir.join(path)).ok()
+            } else {
+                None
+            };
+            *ranges = self.widen(path, source.as_deref(), ranges);
+            debug!("Widened hunks of '{}' to {:?}", path, ranges);
+        }
+    }
+
+    fn widen(self, path: &str, source: Option<&str>, ranges: &[HunkRange]) -> Vec<HunkRange> {
+        let context = self.context.unwrap_or(0);
+        let mut widened: Vec<HunkRange> = ranges
+            .iter()
+            .map(|&(start, end)| {
+                (
+                    start.saturating_sub(context).max(1),
+                    end.saturating_add(context),
+                )
+            })
+            .collect();
+        if let Some(source) = source {
+            widened.extend(
+                metrics::find_functions(path, source)
+                    .into_iter()
+                    .filter(|function| metrics::touches(function, ranges))
+                    .map(|function| (function.start, function.end)),
+            );
+        }
+        merge_ranges(widened)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::proximity::Proximity;
+
+    const SOURCE: &str = "\
+import os
+
+def f(x):
+    y = x
+    return y
+
+def g():
+    pass
+";
+
+    #[test]
+    fn test_widen() {
+        let context = Proximity {
+            context: Some(2),
+            function_context: false,
+        };
+        assert_eq!(context.widen("a.py", None, &[(2, 2), (6, 9)]), [(1, 11)]);
+        assert_eq!(
+            context.widen("a.py", None, &[(1, 1), (9, 9)]),
+            [(1, 3), (7, 11)]
+        );
+
+        let function = Proximity {
+            context: None,
+            function_context: true,
+        };
+        assert_eq!(function.widen("a.py", Some(SOURCE), &[(4, 4)]), [(3, 5)]);
+        // Top-level lines and unknown languages keep their hunks
+        assert_eq!(function.widen("a.py", Some(SOURCE), &[(1, 1)]), [(1, 1)]);
+        assert_eq!(function.widen("a.txt", Some(SOURCE), &[(4, 4)]), [(4, 4)]);
+    }
+}
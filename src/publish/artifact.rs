@@ -0,0 +1,193 @@
+//! `gh-artifact`: reviews for pull requests from forks, whose runs get no token to post
+//! with.
+//!
+//! The `pull_request` job filters the lint output as usual, and `upload` writes the
+//! findings to a handoff file for `actions/upload-artifact` instead of publishing them.
+//! A `workflow_run` job, which has a token but must not run the fork's code, fetches
+//! the artifact with `actions/download-artifact` and `download` posts the review and
+//! status. The handoff is untrusted: its commit must be the one the triggering run was
+//! for and the head of the pull request it names, and the API URL and repository come
+//! from the `workflow_run` job's own environment.
+
+use crate::diagnostic::Diagnostic;
+use crate::output;
+use crate::publish::pull_request::PullRequest;
+use crate::publish::{self, GithubPublisher};
+use anyhow::{bail, Context, Result};
+use clap::{Args, Subcommand};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The handoff file in the artifact directory
+pub const FILE_NAME: &str = "diff-format-handoff.json";
+
+const VERSION: u32 = 1;
+
+#[derive(Args, Debug)]
+pub struct GhArtifactArgs {
+    #[command(subcommand)]
+    action: ArtifactAction,
+}
+
+#[derive(Subcommand, Debug)]
+enum ArtifactAction {
+    /// Filter the lint output on stdin like the default command, and write the findings
+    /// to a directory for `actions/upload-artifact` instead of publishing them
+    Upload {
+        #[arg(long, default_value = "diff-format-handoff")]
+        dir: PathBuf,
+    },
+    /// From a `workflow_run` job, post the review and status of the handoff that
+    /// `actions/download-artifact` put in a directory
+    Download {
+        #[arg(long, default_value = "diff-format-handoff")]
+        dir: PathBuf,
+    },
+}
+
+/// The findings of a run for a pull request, to be published by a later job
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Handoff {
+    version: u32,
+    pub number: u64,
+    pub head_sha: String,
+    pub diagnostics: Vec<Diagnostic>,
+    /// Fixed lines for suggestion blocks, by index in `diagnostics`, rendered before
+    /// the handoff as the publishing job doesn't check out the fork
+    #[serde(default)]
+    suggestions: BTreeMap<usize, String>,
+}
+
+impl Handoff {
+    pub fn new(pull_request: &PullRequest, workdir: &Path, diagnostics: &[Diagnostic]) -> Self {
+        let suggestions = diagnostics
+            .iter()
+            .enumerate()
+            .filter_map(|(index, diagnostic)| {
+                Some((index, publish::suggestion(workdir, diagnostic)?))
+            })
+            .collect();
+        Handoff {
+            version: VERSION,
+            number: pull_request.number,
+            head_sha: pull_request.head_sha.clone(),
+            diagnostics: diagnostics.to_vec(),
+            suggestions,
+        }
+    }
+
+    pub fn suggestion(&self, index: usize) -> Option<&str> {
+        self.suggestions.get(&index).map(String::as_str)
+    }
+
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(FILE_NAME);
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Unable to read handoff {}", path.display()))?;
+        let handoff: Handoff = serde_json::from_str(&text)
+            .with_context(|| format!("Malformed handoff {}", path.display()))?;
+        if handoff.version != VERSION {
+            bail!(
+                "Handoff {} is version {}, this diff-format reads version {}",
+                path.display(),
+                handoff.version,
+                VERSION
+            );
+        }
+        Ok(handoff)
+    }
+
+    /// Writes the handoff into `dir`, returning its path
+    pub fn save(&self, dir: &Path) -> Result<PathBuf> {
+        let path = dir.join(FILE_NAME);
+        fs::create_dir_all(dir).with_context(|| format!("Unable to create {}", dir.display()))?;
+        output::write_atomic(
+            &path,
+            (serde_json::to_string_pretty(self)? + "\n").as_bytes(),
+        )
+        .with_context(|| format!("Unable to write handoff {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+impl GhArtifactArgs {
+    /// Whether the lint output is filtered for an upload, rather than a handoff published
+    pub fn uploads(&self) -> bool {
+        matches!(self.action, ArtifactAction::Upload { .. })
+    }
+
+    /// Writes the handoff of the filtered `diagnostics` of the pull request being checked
+    pub fn upload(&self, workdir: &Path, diagnostics: &[Diagnostic]) -> Result<()> {
+        let dir = match &self.action {
+            ArtifactAction::Upload { dir } => dir,
+            ArtifactAction::Download { .. } => bail!("Only `gh-artifact upload` filters findings"),
+        };
+        let pull_request = PullRequest::from_env()?;
+        let path = Handoff::new(&pull_request, workdir, diagnostics).save(dir)?;
+        info!(
+            "Wrote {} finding(s) on pull request #{} to {}",
+            diagnostics.len(),
+            pull_request.number,
+            path.display()
+        );
+        Ok(())
+    }
+
+    /// Publishes the downloaded handoff to GitHub
+    pub fn download(&self, ca_cert: Option<&Path>) -> Result<()> {
+        let dir = match &self.action {
+            ArtifactAction::Download { dir } => dir,
+            ArtifactAction::Upload { .. } => bail!("`gh-artifact upload` reads lint output"),
+        };
+        let handoff = Handoff::load(dir)?;
+        let http = publish::agent(ca_cert)?;
+        // Nothing is read from the checkout, which may not be the fork's
+        GithubPublisher::new(Path::new("."), http).publish_handoff(&handoff)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::{Diagnostic, Fix};
+    use crate::fixtures::TempDir;
+    use crate::publish::artifact::{Handoff, FILE_NAME};
+    use crate::publish::pull_request::PullRequest;
+    use std::fs;
+
+    #[test]
+    fn test_handoff() {
+        let dir = TempDir::new("handoff").unwrap();
+        fs::write(dir.join("a.py"), "x = 1\nteh = 2\n").unwrap();
+        let pull_request = PullRequest {
+            api_url: "https://api.github.com".to_string(),
+            repository: "o/r".to_string(),
+            number: 7,
+            head_sha: "abc".to_string(),
+        };
+        let mut typo = Diagnostic::new("a.py", Some(2));
+        typo.column = Some(1);
+        typo.fix = Some(Fix {
+            length: 3,
+            replacement: "the".to_string(),
+        });
+        let diagnostics = [Diagnostic::new("a.py", Some(1)), typo];
+
+        let handoff = Handoff::new(&pull_request, &dir, &diagnostics);
+        assert_eq!(handoff.suggestion(0), None);
+        assert_eq!(handoff.suggestion(1), Some("the = 2"));
+        let artifact = dir.join("artifact");
+        handoff.save(&artifact).unwrap();
+        assert_eq!(Handoff::load(&artifact).unwrap(), handoff);
+
+        let text = fs::read_to_string(artifact.join(FILE_NAME)).unwrap();
+        fs::write(
+            artifact.join(FILE_NAME),
+            text.replace("\"version\": 1", "\"version\": 2"),
+        )
+        .unwrap();
+        assert!(Handoff::load(&artifact).is_err());
+    }
+}
@@ -0,0 +1,125 @@
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::publish::Publisher;
+use anyhow::{bail, Context, Result};
+use log::info;
+use std::collections::BTreeMap;
+use std::env;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+pub struct BuildkitePublisher {
+    /// Annotations with the same context replace each other, so use one per step
+    context: String,
+    step_label: Option<String>,
+}
+
+impl BuildkitePublisher {
+    pub fn from_env() -> Self {
+        let step = env::var("BUILDKITE_STEP_KEY")
+            .or_else(|_| env::var("BUILDKITE_STEP_ID"))
+            .ok();
+        BuildkitePublisher {
+            context: match step {
+                Some(step) => format!("diff-format-{}", step),
+                None => "diff-format".to_string(),
+            },
+            step_label: env::var("BUILDKITE_LABEL").ok(),
+        }
+    }
+}
+
+fn annotation_style(diagnostics: &[Diagnostic]) -> Severity {
+    diagnostics
+        .iter()
+        .map(Diagnostic::effective_severity)
+        .max()
+        .unwrap_or(Severity::Info)
+}
+
+fn render_markdown(diagnostics: &[Diagnostic], step_label: Option<&str>) -> String {
+    let mut markdown = format!(
+        "### diff-format: {} issue(s) on changed lines",
+        diagnostics.len()
+    );
+    if let Some(label) = step_label {
+        markdown.push_str(&format!(" in {}", label));
+    }
+    markdown.push('\n');
+
+    let mut by_file = BTreeMap::new();
+    for diagnostic in diagnostics {
+        by_file
+            .entry(&diagnostic.path)
+            .or_insert_with(Vec::new)
+            .push(diagnostic);
+    }
+    for (path, diagnostics) in by_file {
+        markdown.push_str(&format!("\n**`{}`**\n\n", path));
+        for diagnostic in diagnostics {
+            let location = match diagnostic.line {
+                Some(line) => format!("line {}", line),
+                None => "file".to_string(),
+            };
+            markdown.push_str(&format!(
+                "- {} ({}): {}\n",
+                location,
+                diagnostic.effective_severity().as_str(),
+                diagnostic.text()
+            ));
+        }
+    }
+    markdown
+}
+
+impl Publisher for BuildkitePublisher {
+    fn publish(&self, diagnostics: &[Diagnostic]) -> Result<()> {
+        if diagnostics.is_empty() {
+            info!("No diagnostics to annotate");
+            return Ok(());
+        }
+        let mut child = Command::new("buildkite-agent")
+            .args(["annotate", "--context", &self.context, "--style"])
+            .arg(annotation_style(diagnostics).as_str())
+            .stdin(Stdio::piped())
+            .spawn()
+            .context("Unable to run buildkite-agent")?;
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(render_markdown(diagnostics, self.step_label.as_deref()).as_bytes())
+            .context("Unable to write annotation to buildkite-agent")?;
+        let status = child.wait().context("buildkite-agent did not finish")?;
+        if !status.success() {
+            bail!("buildkite-agent annotate failed with {}", status);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::{Diagnostic, Severity};
+    use crate::publish::buildkite::{annotation_style, render_markdown};
+
+    #[test]
+    fn test_annotation_markdown() {
+        let mut warning = Diagnostic::new("src/a.py", Some(3));
+        warning.severity = Some(Severity::Warning);
+        warning.message = Some("W291 trailing whitespace".to_string());
+        let mut file_level = Diagnostic::new("src/teh.py", None);
+        file_level.raw = "src/teh.py: `teh` -> `the`".to_string();
+
+        assert_eq!(annotation_style(&[warning.clone()]), Severity::Warning);
+        assert_eq!(
+            annotation_style(&[warning.clone(), file_level.clone()]),
+            Severity::Error
+        );
+        assert_eq!(
+            render_markdown(&[warning, file_level], Some(":python: lint")),
+            "### diff-format: 2 issue(s) on changed lines in :python: lint\n\
+             \n**`src/a.py`**\n\n- line 3 (warning): W291 trailing whitespace\n\
+             \n**`src/teh.py`**\n\n- file (error): src/teh.py: `teh` -> `the`\n"
+        );
+    }
+}
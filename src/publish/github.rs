@@ -0,0 +1,276 @@
+use crate::diagnostic::Diagnostic;
+use crate::publish::artifact::Handoff;
+use crate::publish::pull_request::PullRequest;
+use crate::publish::{self, Publisher};
+use anyhow::{anyhow, bail, Context, Result};
+use log::info;
+use serde::Serialize;
+use serde_json::Value;
+use std::env;
+use std::path::{Path, PathBuf};
+use ureq::Agent;
+
+/// Posts matched diagnostics as a pull request review, one comment per line
+pub struct GithubPublisher {
+    workdir: PathBuf,
+    http: Agent,
+}
+
+impl GithubPublisher {
+    pub fn new(workdir: &Path, http: Agent) -> Self {
+        GithubPublisher {
+            workdir: workdir.to_path_buf(),
+            http,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Review<'a> {
+    commit_id: &'a str,
+    event: &'static str,
+    body: String,
+    comments: Vec<ReviewComment<'a>>,
+}
+
+/// A commit status, which branch protection can require without reading job logs
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct Status {
+    state: &'static str,
+    description: String,
+    context: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target_url: Option<String>,
+}
+
+impl Status {
+    /// Fails when there are any findings, linking to the review listing them
+    fn new(diagnostics: &[Diagnostic], target_url: Option<String>) -> Self {
+        let (state, description) = match diagnostics.len() {
+            0 => ("success", "No new issues".to_string()),
+            1 => ("failure", "1 new issue".to_string()),
+            count => ("failure", format!("{} new issues", count)),
+        };
+        Status {
+            state,
+            description,
+            context: "diff-format",
+            target_url,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ReviewComment<'a> {
+    path: &'a str,
+    line: u32,
+    side: &'static str,
+    body: String,
+}
+
+/// Renders a comment, with a one-click `suggestion` block of the fixed line when the
+/// tool proposed a fix
+fn comment_body(diagnostic: &Diagnostic, suggestion: Option<&str>) -> String {
+    let mut body = format!(
+        "**{}**: {}",
+        diagnostic.tool.as_deref().unwrap_or("diff-format"),
+        diagnostic.text()
+    );
+    if let Some(suggestion) = suggestion {
+        body.push_str(&format!("\n\n```suggestion\n{}\n```", suggestion));
+    }
+    body
+}
+
+fn review_body(diagnostics: &[Diagnostic]) -> String {
+    let mut body = format!(
+        "diff-format found {} issue(s) on changed lines",
+        diagnostics.len()
+    );
+    for diagnostic in diagnostics.iter().filter(|d| d.line.is_none()) {
+        body.push_str(&format!("\n- `{}`: {}", diagnostic.path, diagnostic.text()));
+    }
+    body
+}
+
+impl GithubPublisher {
+    /// Sets the `diff-format` status of the pull request's head commit
+    fn set_status(&self, pull_request: &PullRequest, token: &str, status: &Status) -> Result<()> {
+        let url = format!(
+            "{}/repos/{}/statuses/{}",
+            pull_request.api_url, pull_request.repository, pull_request.head_sha
+        );
+        self.http
+            .post(&url)
+            .header("Authorization", &format!("Bearer {}", token))
+            .header("Accept", "application/vnd.github+json")
+            .send_json(status)
+            .map_err(|err| anyhow!("Unable to set commit status: {}", err))?;
+        info!(
+            "Set commit status {}: {}",
+            status.context, status.description
+        );
+        Ok(())
+    }
+
+    /// Fails unless the pull request's head is still the commit the review is for, so a
+    /// handoff can't name someone else's pull request
+    fn check_head(&self, pull_request: &PullRequest, token: &str) -> Result<()> {
+        let url = format!(
+            "{}/repos/{}/pulls/{}",
+            pull_request.api_url, pull_request.repository, pull_request.number
+        );
+        let head: Value = self
+            .http
+            .get(&url)
+            .header("Authorization", &format!("Bearer {}", token))
+            .header("Accept", "application/vnd.github+json")
+            .call()
+            .map_err(|err| {
+                anyhow!(
+                    "Unable to read pull request #{}: {}",
+                    pull_request.number,
+                    err
+                )
+            })?
+            .body_mut()
+            .read_json()
+            .context("Malformed pull request")?;
+        match head["head"]["sha"].as_str() {
+            Some(sha) if sha == pull_request.head_sha => Ok(()),
+            sha => bail!(
+                "Pull request #{} is at {}, not {} as the handoff says",
+                pull_request.number,
+                sha.unwrap_or("an unknown commit"),
+                pull_request.head_sha
+            ),
+        }
+    }
+
+    /// Reviews the handoff of a run for a fork, from the `workflow_run` job it triggered
+    pub fn publish_handoff(&self, handoff: &Handoff) -> Result<()> {
+        let token = env::var("GITHUB_TOKEN").context("GITHUB_TOKEN is not set")?;
+        let pull_request = PullRequest::from_workflow_run(handoff.number, &handoff.head_sha)?;
+        self.check_head(&pull_request, &token)?;
+        self.review(&pull_request, &token, &handoff.diagnostics, |index| {
+            handoff.suggestion(index).map(str::to_string)
+        })
+    }
+
+    /// Posts `diagnostics` as a review and sets the status, passing it when there are
+    /// none; `suggestion` gives the fixed line for the diagnostic at an index
+    fn review(
+        &self,
+        pull_request: &PullRequest,
+        token: &str,
+        diagnostics: &[Diagnostic],
+        suggestion: impl Fn(usize) -> Option<String>,
+    ) -> Result<()> {
+        if diagnostics.is_empty() {
+            return self.set_status(pull_request, token, &Status::new(diagnostics, None));
+        }
+        let comments = diagnostics
+            .iter()
+            .enumerate()
+            .filter_map(|(index, diagnostic)| {
+                Some(ReviewComment {
+                    path: &diagnostic.path,
+                    line: diagnostic.line?,
+                    side: "RIGHT",
+                    body: comment_body(diagnostic, suggestion(index).as_deref()),
+                })
+            })
+            .collect();
+        let review = Review {
+            commit_id: &pull_request.head_sha,
+            event: "COMMENT",
+            body: review_body(diagnostics),
+            comments,
+        };
+
+        let url = format!(
+            "{}/repos/{}/pulls/{}/reviews",
+            pull_request.api_url, pull_request.repository, pull_request.number
+        );
+        let response = self
+            .http
+            .post(&url)
+            .header("Authorization", &format!("Bearer {}", token))
+            .header("Accept", "application/vnd.github+json")
+            .send_json(&review);
+        let review_url = match response {
+            Ok(mut response) => {
+                info!("Posted review on pull request #{}", pull_request.number);
+                let review: Value = response.body_mut().read_json().unwrap_or_default();
+                review["html_url"].as_str().map(str::to_string)
+            }
+            Err(err) => bail!("Unable to post pull request review: {}", err),
+        };
+        self.set_status(pull_request, token, &Status::new(diagnostics, review_url))
+    }
+}
+
+impl Publisher for GithubPublisher {
+    fn publish(&self, diagnostics: &[Diagnostic]) -> Result<()> {
+        if diagnostics.is_empty() {
+            info!("No diagnostics to review");
+            // Still pass the status check, when there is a pull request to set it on
+            return match (PullRequest::from_env(), env::var("GITHUB_TOKEN")) {
+                (Ok(pull_request), Ok(token)) => self.review(&pull_request, &token, &[], |_| None),
+                _ => Ok(()),
+            };
+        }
+        let pull_request = PullRequest::from_env()?;
+        let token = env::var("GITHUB_TOKEN").context("GITHUB_TOKEN is not set")?;
+        self.review(&pull_request, &token, diagnostics, |index| {
+            publish::suggestion(&self.workdir, &diagnostics[index])
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::Diagnostic;
+    use crate::publish::github::{comment_body, Status};
+
+    #[test]
+    fn test_suggestion_block() {
+        let mut diagnostic = Diagnostic::new("README.md", Some(3));
+        diagnostic.column = Some(5);
+        diagnostic.message = Some("`teh` -> `the`".to_string());
+        diagnostic.tool = Some("typos".to_string());
+        assert_eq!(comment_body(&diagnostic, None), "**typos**: `teh` -> `the`");
+        assert_eq!(
+            comment_body(&diagnostic, Some("Fix the typo")),
+            "**typos**: `teh` -> `the`\n\n```suggestion\nFix the typo\n```"
+        );
+    }
+
+    #[test]
+    fn test_status() {
+        let url = "https://github.com/o/r/pull/1#pullrequestreview-2";
+        let findings = [
+            Diagnostic::new("a.py", Some(1)),
+            Diagnostic::new("b.py", None),
+        ];
+        let status = Status::new(&findings, Some(url.to_string()));
+        assert_eq!(
+            serde_json::to_value(&status).unwrap(),
+            serde_json::json!({
+                "state": "failure",
+                "description": "2 new issues",
+                "context": "diff-format",
+                "target_url": url,
+            })
+        );
+        let status = Status::new(&[], None);
+        assert_eq!(
+            (status.state, status.description.as_str()),
+            ("success", "No new issues")
+        );
+        assert!(serde_json::to_value(&status)
+            .unwrap()
+            .get("target_url")
+            .is_none());
+    }
+}
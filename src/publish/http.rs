@@ -0,0 +1,52 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::Path;
+use ureq::tls::{PemItem, RootCerts, TlsConfig};
+use ureq::{Agent, Proxy};
+
+/// The client shared by publishers that talk HTTP.
+///
+/// Requests go through the proxy named by `HTTPS_PROXY` (or `ALL_PROXY`/`HTTP_PROXY`)
+/// except for hosts in `NO_PROXY`. A `ca_cert` PEM bundle replaces the bundled roots,
+/// for runners behind TLS-intercepting proxies.
+pub fn agent(ca_cert: Option<&Path>) -> Result<Agent> {
+    let mut config = Agent::config_builder().proxy(Proxy::try_from_env());
+    if let Some(path) = ca_cert {
+        let pem = fs::read(path)
+            .with_context(|| format!("Unable to read CA bundle {}", path.display()))?;
+        config = config.tls_config(
+            TlsConfig::builder()
+                .root_certs(RootCerts::from(
+                    parse_certificates(&pem)
+                        .with_context(|| format!("Invalid CA bundle {}", path.display()))?,
+                ))
+                .build(),
+        );
+    }
+    Ok(config.build().new_agent())
+}
+
+fn parse_certificates(pem: &[u8]) -> Result<Vec<ureq::tls::Certificate<'static>>> {
+    let mut certificates = Vec::new();
+    for item in ureq::tls::parse_pem(pem) {
+        if let PemItem::Certificate(certificate) = item? {
+            certificates.push(certificate);
+        }
+    }
+    if certificates.is_empty() {
+        bail!("No certificates found");
+    }
+    Ok(certificates)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::publish::http::parse_certificates;
+
+    #[test]
+    fn test_parse_certificates() {
+        assert!(parse_certificates(b"").is_err());
+        let pem = "-----BEGIN CERTIFICATE-----\nMAA=\n-----END CERTIFICATE-----\n";
+        assert_eq!(parse_certificates(pem.as_bytes()).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,72 @@
+mod artifact;
+mod buildkite;
+#[cfg(feature = "http")]
+mod github;
+#[cfg(feature = "http")]
+mod http;
+#[cfg(not(feature = "http"))]
+mod offline;
+mod pace;
+mod pull_request;
+mod queue;
+
+use crate::diagnostic::Diagnostic;
+use crate::editorconfig;
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+pub use artifact::{GhArtifactArgs, Handoff};
+
+#[cfg(feature = "http")]
+use github::GithubPublisher;
+#[cfg(feature = "http")]
+pub use http::agent;
+#[cfg(not(feature = "http"))]
+use offline::GithubPublisher;
+#[cfg(not(feature = "http"))]
+pub use offline::{agent, Agent};
+pub use pace::{Pacer, Pacing, Undelivered};
+pub use queue::{enqueue, PublishArgs, Queue};
+#[cfg(feature = "http")]
+pub use ureq::Agent;
+
+/// Destinations matched diagnostics can be reported to, besides stdout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PublishTarget {
+    /// `buildkite-agent annotate`
+    Buildkite,
+    /// Pull request review comments, with suggestions for available fixes, and a
+    /// `diff-format` commit status linking to the review
+    Github,
+}
+
+pub trait Publisher {
+    fn publish(&self, diagnostics: &[Diagnostic]) -> Result<()>;
+}
+
+/// The line of `diagnostic` with the tool's fix applied in the file's `.editorconfig`
+/// style, for a one-click suggestion, when the tool proposed a fix
+pub fn suggestion(workdir: &Path, diagnostic: &Diagnostic) -> Option<String> {
+    let (fix, line, column) = (
+        diagnostic.fix.as_ref()?,
+        diagnostic.line?,
+        diagnostic.column?,
+    );
+    let source = fs::read_to_string(workdir.join(&diagnostic.path)).ok()?;
+    let text = source.lines().nth(line.checked_sub(1)? as usize)?;
+    let style = editorconfig::style(workdir, &diagnostic.path).unwrap_or_default();
+    Some(style.apply(fix, text, column))
+}
+
+impl PublishTarget {
+    pub fn publisher(self, workdir: &Path, http: &Agent) -> Box<dyn Publisher> {
+        match self {
+            PublishTarget::Buildkite => Box::new(buildkite::BuildkitePublisher::from_env()),
+            PublishTarget::Github => Box::new(GithubPublisher::new(workdir, http.clone())),
+        }
+    }
+}
@@ -0,0 +1,33 @@
+//! Stand-ins for builds without the `http` feature: publishers that talk HTTP are still
+//! accepted on the command line and in config, but fail when they run.
+
+use crate::diagnostic::Diagnostic;
+use crate::publish::{Handoff, Publisher};
+use anyhow::{bail, Result};
+use std::path::Path;
+
+/// There is no HTTP client to share
+#[derive(Debug, Clone)]
+pub struct Agent;
+
+pub fn agent(_ca_cert: Option<&Path>) -> Result<Agent> {
+    Ok(Agent)
+}
+
+pub struct GithubPublisher;
+
+impl GithubPublisher {
+    pub fn new(_workdir: &Path, _http: Agent) -> Self {
+        GithubPublisher
+    }
+
+    pub fn publish_handoff(&self, _handoff: &Handoff) -> Result<()> {
+        bail!("Publishing to GitHub needs diff-format built with the `http` feature")
+    }
+}
+
+impl Publisher for GithubPublisher {
+    fn publish(&self, _diagnostics: &[Diagnostic]) -> Result<()> {
+        bail!("Publishing to GitHub needs diff-format built with the `http` feature")
+    }
+}
@@ -0,0 +1,166 @@
+//! Rate and batch size limits on publishing, to try a publisher against a staging API
+//! at a controlled request rate.
+
+use crate::diagnostic::Diagnostic;
+use crate::publish::Publisher;
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use std::num::NonZeroUsize;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Publishes per second, written `N/s` or `N/m`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rate(f64);
+
+impl FromStr for Rate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (count, per) = s
+            .split_once('/')
+            .with_context(|| format!("Expected a rate like 5/s or 30/m, got '{}'", s))?;
+        let count: f64 = count
+            .parse()
+            .with_context(|| format!("Invalid count '{}'", count))?;
+        if !(count > 0.0 && count.is_finite()) {
+            bail!("The rate must be positive, got '{}'", s);
+        }
+        match per {
+            "s" => Ok(Rate(count)),
+            "m" => Ok(Rate(count / 60.0)),
+            _ => bail!("Unknown rate unit '{}', expected s or m", per),
+        }
+    }
+}
+
+#[derive(Args, Debug, Clone, Copy, Default)]
+pub struct Pacing {
+    /// Call publishers at most this often, e.g. 5/s or 30/m, counting each batch
+    #[arg(long, value_name = "N/s")]
+    throttle: Option<Rate>,
+
+    /// Publish at most N findings per call, splitting larger sets into batches
+    #[arg(long, value_name = "N")]
+    batch_size: Option<NonZeroUsize>,
+}
+
+/// The findings a publisher didn't get, starting with the batch that failed
+pub struct Undelivered<'d> {
+    pub error: anyhow::Error,
+    pub diagnostics: &'d [Diagnostic],
+}
+
+/// Spaces out publishes across publishers, as [`Pacing`] asks
+pub struct Pacer {
+    interval: Option<Duration>,
+    batch_size: Option<NonZeroUsize>,
+    last: Option<Instant>,
+}
+
+impl Pacing {
+    pub fn pacer(self) -> Pacer {
+        Pacer {
+            interval: self
+                .throttle
+                .map(|Rate(rate)| Duration::from_secs_f64(1.0 / rate)),
+            batch_size: self.batch_size,
+            last: None,
+        }
+    }
+}
+
+impl Pacer {
+    fn wait(&mut self) {
+        if let (Some(interval), Some(last)) = (self.interval, self.last) {
+            let next = last + interval;
+            let now = Instant::now();
+            if next > now {
+                thread::sleep(next - now);
+            }
+        }
+        self.last = Some(Instant::now());
+    }
+
+    /// Publishes `diagnostics` in batches, stopping at the first batch that fails
+    pub fn publish<'d>(
+        &mut self,
+        publisher: &dyn Publisher,
+        diagnostics: &'d [Diagnostic],
+    ) -> Result<(), Undelivered<'d>> {
+        let size = match self.batch_size {
+            // An empty set is still published, e.g. to clear an earlier annotation
+            Some(size) if !diagnostics.is_empty() => size.get(),
+            _ => diagnostics.len().max(1),
+        };
+        let mut start = 0;
+        loop {
+            let end = diagnostics.len().min(start + size);
+            self.wait();
+            if let Err(error) = publisher.publish(&diagnostics[start..end]) {
+                return Err(Undelivered {
+                    error,
+                    diagnostics: &diagnostics[start..],
+                });
+            }
+            start = end;
+            if start >= diagnostics.len() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::Diagnostic;
+    use crate::publish::pace::{Pacing, Rate};
+    use crate::publish::Publisher;
+    use anyhow::{bail, Result};
+    use std::cell::RefCell;
+    use std::num::NonZeroUsize;
+    use std::time::{Duration, Instant};
+
+    struct Recorder(RefCell<Vec<usize>>);
+
+    impl Publisher for Recorder {
+        fn publish(&self, diagnostics: &[Diagnostic]) -> Result<()> {
+            let mut calls = self.0.borrow_mut();
+            calls.push(diagnostics.len());
+            if calls.len() == 3 {
+                bail!("429 Too Many Requests");
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_pacer() {
+        assert_eq!("30/m".parse::<Rate>().unwrap(), Rate(0.5));
+        assert!("0/s".parse::<Rate>().is_err());
+        assert!("5".parse::<Rate>().is_err());
+
+        let pacing = Pacing {
+            throttle: Some(Rate(100.0)),
+            batch_size: NonZeroUsize::new(2),
+        };
+        let mut pacer = pacing.pacer();
+        let recorder = Recorder(RefCell::new(Vec::new()));
+        let findings: Vec<_> = (1..=7)
+            .map(|line| Diagnostic::new("a.py", Some(line)))
+            .collect();
+        let started = Instant::now();
+        let undelivered = pacer.publish(&recorder, &findings).unwrap_err();
+        assert!(started.elapsed() >= Duration::from_millis(20));
+        assert_eq!(*recorder.0.borrow(), [2, 2, 2]);
+        assert_eq!(undelivered.diagnostics, &findings[4..]);
+
+        // Everything in one call without a batch size, even nothing
+        let recorder = Recorder(RefCell::new(Vec::new()));
+        let mut pacer = Pacing::default().pacer();
+        pacer.publish(&recorder, &findings).ok().unwrap();
+        pacer.publish(&recorder, &[]).ok().unwrap();
+        assert_eq!(*recorder.0.borrow(), [7, 0]);
+    }
+}
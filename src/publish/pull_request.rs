@@ -0,0 +1,91 @@
+//! The pull request a run reviews, from the GitHub Actions environment.
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::env;
+use std::fs;
+
+/// The pull request being checked, as described by the Actions environment
+pub struct PullRequest {
+    pub api_url: String,
+    pub repository: String,
+    pub number: u64,
+    pub head_sha: String,
+}
+
+/// The payload of the event that triggered the workflow
+fn event() -> Result<Value> {
+    let event_path = env::var("GITHUB_EVENT_PATH").context("GITHUB_EVENT_PATH is not set")?;
+    serde_json::from_str(&fs::read_to_string(&event_path).context("Unable to read GitHub event")?)
+        .context("Malformed GitHub event")
+}
+
+fn api_url() -> String {
+    env::var("GITHUB_API_URL").unwrap_or_else(|_| "https://api.github.com".to_string())
+}
+
+fn repository() -> Result<String> {
+    env::var("GITHUB_REPOSITORY").context("GITHUB_REPOSITORY is not set")
+}
+
+impl PullRequest {
+    /// The pull request of a `pull_request` or `pull_request_target` event
+    pub fn from_env() -> Result<Self> {
+        let event = event()?;
+        let pull_request = &event["pull_request"];
+        Ok(PullRequest {
+            api_url: api_url(),
+            repository: repository()?,
+            number: pull_request["number"]
+                .as_u64()
+                .context("Not running for a pull request")?,
+            head_sha: pull_request["head"]["sha"]
+                .as_str()
+                .context("Pull request event has no head sha")?
+                .to_string(),
+        })
+    }
+
+    /// Pull request `number` at `head_sha`, as claimed by the run a `workflow_run`
+    /// event follows. Only the commit is checked against the event here: runs for
+    /// forks don't list their pull requests, so the number must be checked against
+    /// the API before trusting it
+    pub fn from_workflow_run(number: u64, head_sha: &str) -> Result<Self> {
+        Self::check_workflow_run(&event()?, head_sha)?;
+        Ok(PullRequest {
+            api_url: api_url(),
+            repository: repository()?,
+            number,
+            head_sha: head_sha.to_string(),
+        })
+    }
+
+    fn check_workflow_run(event: &Value, head_sha: &str) -> Result<()> {
+        let run_sha = event["workflow_run"]["head_sha"]
+            .as_str()
+            .context("Not running for a workflow_run event")?;
+        if run_sha != head_sha {
+            bail!(
+                "The handoff is for commit {}, but the triggering run was for {}",
+                head_sha,
+                run_sha
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::publish::pull_request::PullRequest;
+    use serde_json::json;
+
+    #[test]
+    fn test_check_workflow_run() {
+        let event = json!({ "workflow_run": { "head_sha": "abc", "pull_requests": [] } });
+        assert!(PullRequest::check_workflow_run(&event, "abc").is_ok());
+        assert!(PullRequest::check_workflow_run(&event, "def").is_err());
+        let event = json!({ "pull_request": { "number": 1 } });
+        assert!(PullRequest::check_workflow_run(&event, "abc").is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use crate::diagnostic::Diagnostic;
+use crate::output;
+use crate::publish::{self, Pacing, PublishTarget};
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+#[derive(Args, Debug)]
+pub struct PublishArgs {
+    /// Deliver the payloads queued by --publish-queue, keeping those that fail again
+    #[arg(long, value_name = "QUEUE")]
+    drain: PathBuf,
+}
+
+/// Findings a publisher failed to deliver
+#[derive(Debug, Serialize, Deserialize)]
+struct Pending {
+    target: PublishTarget,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Payloads waiting for a later `publish --drain`, as a JSON file
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Queue {
+    pending: Vec<Pending>,
+}
+
+impl Queue {
+    /// An empty queue if the file doesn't exist yet
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .with_context(|| format!("Malformed publish queue {}", path.display())),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Queue::default()),
+            Err(err) => Err(err).with_context(|| format!("Unable to read {}", path.display())),
+        }
+    }
+
+    /// Writes the queue, removing the file once nothing is pending
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if self.pending.is_empty() {
+            return match fs::remove_file(path) {
+                Err(err) if err.kind() != ErrorKind::NotFound => {
+                    Err(err).with_context(|| format!("Unable to remove {}", path.display()))
+                }
+                _ => Ok(()),
+            };
+        }
+        output::write_atomic(
+            path,
+            (serde_json::to_string_pretty(self)? + "\n").as_bytes(),
+        )
+        .with_context(|| format!("Unable to write publish queue {}", path.display()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn push(&mut self, target: PublishTarget, diagnostics: &[Diagnostic]) {
+        self.pending.push(Pending {
+            target,
+            diagnostics: diagnostics.to_vec(),
+        });
+    }
+
+    /// Tries `publish` on each payload in order, keeping the ones it fails on. Returns
+    /// how many were delivered
+    pub fn drain(
+        &mut self,
+        mut publish: impl FnMut(PublishTarget, &[Diagnostic]) -> Result<()>,
+    ) -> usize {
+        let before = self.pending.len();
+        self.pending.retain(
+            |pending| match publish(pending.target, &pending.diagnostics) {
+                Ok(()) => false,
+                Err(err) => {
+                    warn!("Still unable to publish to {:?}: {:#}", pending.target, err);
+                    true
+                }
+            },
+        );
+        before - self.pending.len()
+    }
+}
+
+/// Appends a payload that failed to publish to the queue at `path`
+pub fn enqueue(path: &Path, target: PublishTarget, diagnostics: &[Diagnostic]) -> Result<()> {
+    let mut queue = Queue::load(path)?;
+    queue.push(target, diagnostics);
+    queue.save(path)
+}
+
+impl PublishArgs {
+    /// Delivers the queued payloads, failing if any are still pending afterwards
+    pub fn drain(&self, workdir: &Path, ca_cert: Option<&Path>, pacing: Pacing) -> Result<()> {
+        let mut queue = Queue::load(&self.drain)?;
+        if queue.is_empty() {
+            info!("Nothing queued in {}", self.drain.display());
+            return Ok(());
+        }
+        let http = publish::agent(ca_cert)?;
+        let mut pacer = pacing.pacer();
+        let delivered = queue.drain(|target, diagnostics| {
+            let publisher = target.publisher(workdir, &http);
+            pacer
+                .publish(publisher.as_ref(), diagnostics)
+                .map_err(|undelivered| undelivered.error)
+        });
+        info!("Delivered {} queued payload(s)", delivered);
+        queue.save(&self.drain)?;
+        if !queue.is_empty() {
+            bail!(
+                "{} payload(s) are still queued in {}",
+                queue.len(),
+                self.drain.display()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::Diagnostic;
+    use crate::fixtures::TempDir;
+    use crate::publish::queue::{enqueue, Queue};
+    use crate::publish::PublishTarget;
+    use anyhow::bail;
+
+    #[test]
+    fn test_queue() {
+        let dir = TempDir::new("queue").unwrap();
+        let path = dir.join("queue.json");
+        let findings = [Diagnostic::new("a.py", Some(1))];
+        enqueue(&path, PublishTarget::Github, &findings).unwrap();
+        enqueue(&path, PublishTarget::Buildkite, &findings).unwrap();
+
+        let mut queue = Queue::load(&path).unwrap();
+        assert_eq!(queue.len(), 2);
+        let delivered = queue.drain(|target, diagnostics| {
+            assert_eq!(diagnostics, findings);
+            match target {
+                PublishTarget::Github => bail!("503 Service Unavailable"),
+                PublishTarget::Buildkite => Ok(()),
+            }
+        });
+        assert_eq!(delivered, 1);
+        queue.save(&path).unwrap();
+        assert_eq!(queue.pending[0].target, PublishTarget::Github);
+
+        assert_eq!(Queue::load(&path).unwrap().drain(|_, _| Ok(())), 1);
+        Queue::default().save(&path).unwrap();
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,106 @@
+//! Ties pytest's short test summary to the change: failing tests whose function was
+//! touched, or whose module under test was.
+
+use crate::diagnostic::Diagnostic;
+use crate::metrics;
+use crate::HunkRange;
+use log::debug;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// The function name of a pytest node id, e.g. `test_y` for `TestX::test_y[case-1]`
+fn function_name(test: &str) -> &str {
+    let name = test.rsplit("::").next().unwrap_or(test);
+    name.split('[').next().unwrap_or(name)
+}
+
+/// Points a failing test at the first changed line of its function, or at its `def`
+/// line when the function is unchanged
+pub fn locate(
+    workdir: &Path,
+    diagnostic: &mut Diagnostic,
+    file_hunks: &HashMap<String, Vec<HunkRange>>,
+) {
+    let test = match (&diagnostic.symbol, diagnostic.line) {
+        (Some(test), None) => function_name(test),
+        _ => return,
+    };
+    let source = match fs::read_to_string(workdir.join(&diagnostic.path)) {
+        Ok(source) => source,
+        Err(err) => {
+            debug!("Can't read '{}' to find {}: {}", diagnostic.path, test, err);
+            return;
+        }
+    };
+    let function = match metrics::find_functions(&diagnostic.path, &source)
+        .into_iter()
+        .find(|function| function.name == test)
+    {
+        Some(function) => function,
+        None => return,
+    };
+    let changed = file_hunks.get(&diagnostic.path).and_then(|ranges| {
+        ranges
+            .iter()
+            .filter(|&&(start, end)| start <= function.end && end >= function.start)
+            .map(|&(start, _)| start.max(function.start))
+            .min()
+    });
+    diagnostic.line = Some(changed.unwrap_or(function.start));
+}
+
+/// Whether a module a test file exercises, by the `test_x.py`/`x_test.py` naming
+/// conventions, is among the changed files
+pub fn tested_module_changed(
+    test_path: &str,
+    file_hunks: &HashMap<String, Vec<HunkRange>>,
+) -> Option<String> {
+    let file_name = Path::new(test_path).file_name()?.to_str()?;
+    let module = match (
+        file_name.strip_prefix("test_"),
+        file_name.strip_suffix("_test.py"),
+    ) {
+        (Some(module), _) => module.to_string(),
+        (None, Some(stem)) => format!("{}.py", stem),
+        (None, None) => return None,
+    };
+    let mut changed: Vec<_> = file_hunks
+        .keys()
+        .filter(|path| path.as_str() != test_path)
+        .filter(|path| Path::new(path).file_name().and_then(|name| name.to_str()) == Some(&module))
+        .collect();
+    changed.sort();
+    changed.first().map(|path| path.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::pytest::{function_name, tested_module_changed};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_naming_conventions() {
+        assert_eq!(function_name("TestX::test_y[case-1]"), "test_y");
+        assert_eq!(function_name("test_z"), "test_z");
+
+        let file_hunks: HashMap<_, _> = vec![
+            ("src/pkg/parser.py".to_string(), vec![(1, 2)]),
+            ("tests/test_parser.py".to_string(), vec![(1, 2)]),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(
+            tested_module_changed("tests/test_parser.py", &file_hunks).as_deref(),
+            Some("src/pkg/parser.py")
+        );
+        assert_eq!(
+            tested_module_changed("tests/parser_test.py", &file_hunks).as_deref(),
+            Some("src/pkg/parser.py")
+        );
+        assert_eq!(
+            tested_module_changed("tests/test_lexer.py", &file_hunks),
+            None
+        );
+    }
+}
@@ -0,0 +1,135 @@
+//! `--redact`: rewrites the paths and messages of findings before they are reported, so
+//! internal host names or customer names in fixture paths don't leave the CI
+//! environment. Every output, split report, summary, publisher and the store get the
+//! redacted findings, and echoed lint lines are redacted the same way. Matching,
+//! `--apply-fixes`, the exit code and the `export` files editors open still use the
+//! paths as the linter gave them.
+
+use crate::diagnostic::Diagnostic;
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::str::FromStr;
+
+/// A `PATTERN=>REPLACEMENT` rule; the replacement may refer to groups as `$1` or `$name`
+#[derive(Debug, Clone)]
+pub struct Redaction {
+    pattern: Regex,
+    replacement: String,
+}
+
+impl FromStr for Redaction {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let (pattern, replacement) = match value.split_once("=>") {
+            Some((pattern, replacement)) if !pattern.is_empty() => (pattern, replacement),
+            _ => bail!("Expected PATTERN=>REPLACEMENT, got '{}'", value),
+        };
+        Ok(Redaction {
+            pattern: Regex::new(pattern)
+                .with_context(|| format!("Invalid --redact pattern '{}'", pattern))?,
+            replacement: replacement.to_string(),
+        })
+    }
+}
+
+/// `text` with each of `redactions` applied in turn
+pub fn text<'a>(redactions: &[Redaction], text: &'a str) -> Cow<'a, str> {
+    let mut text = Cow::Borrowed(text);
+    for redaction in redactions {
+        if let Cow::Owned(replaced) = redaction
+            .pattern
+            .replace_all(&text, redaction.replacement.as_str())
+        {
+            text = Cow::Owned(replaced);
+        }
+    }
+    text
+}
+
+fn redact(redactions: &[Redaction], value: &mut String) {
+    if let Cow::Owned(replaced) = text(redactions, value) {
+        *value = replaced;
+    }
+}
+
+/// Redacts the paths, messages, excerpt and echoed line of `diagnostic` and its related
+/// locations
+pub fn diagnostic(redactions: &[Redaction], diagnostic: &mut Diagnostic) {
+    redact(redactions, &mut diagnostic.path);
+    redact(redactions, &mut diagnostic.raw);
+    if let Some(message) = &mut diagnostic.message {
+        redact(redactions, message);
+    }
+    if let Some(excerpt) = &mut diagnostic.excerpt {
+        redact(redactions, excerpt);
+    }
+    for related in &mut diagnostic.related {
+        redact(redactions, &mut related.path);
+        if let Some(message) = &mut related.message {
+            redact(redactions, message);
+        }
+    }
+}
+
+/// Redacted copies of `diagnostics`, or `diagnostics` themselves without redactions
+pub fn diagnostics<'a>(
+    redactions: &[Redaction],
+    diagnostics: &'a [Diagnostic],
+) -> Cow<'a, [Diagnostic]> {
+    if redactions.is_empty() {
+        return Cow::Borrowed(diagnostics);
+    }
+    let mut diagnostics = diagnostics.to_vec();
+    for redacted in &mut diagnostics {
+        diagnostic(redactions, redacted);
+    }
+    Cow::Owned(diagnostics)
+}
+
+/// Redacts every string in `value`
+pub fn json(redactions: &[Redaction], value: &mut Value) {
+    match value {
+        Value::String(string) => redact(redactions, string),
+        Value::Array(values) => values.iter_mut().for_each(|value| json(redactions, value)),
+        Value::Object(object) => object
+            .values_mut()
+            .for_each(|value| json(redactions, value)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::Diagnostic;
+    use crate::redact::{self, Redaction};
+
+    #[test]
+    fn test_redact() {
+        let redactions: Vec<Redaction> = [r"acme-(\w+)\.internal=>host-$1", "Globex=>CUSTOMER"]
+            .iter()
+            .map(|rule| rule.parse().unwrap())
+            .collect();
+        assert!("no-arrow".parse::<Redaction>().is_err());
+        assert!("=>x".parse::<Redaction>().is_err());
+        assert!("(=>x".parse::<Redaction>().is_err());
+
+        let mut diagnostic = Diagnostic::new("fixtures/acme-db.internal/Globex.json", Some(3));
+        diagnostic.message = Some("can't reach acme-db.internal".to_string());
+        diagnostic.raw = "fixtures/acme-db.internal/Globex.json:3: can't reach".to_string();
+        let redacted = redact::diagnostics(&redactions, std::slice::from_ref(&diagnostic));
+        assert_eq!(redacted[0].path, "fixtures/host-db/CUSTOMER.json");
+        assert_eq!(redacted[0].message.as_deref(), Some("can't reach host-db"));
+        assert_eq!(
+            redacted[0].raw,
+            "fixtures/host-db/CUSTOMER.json:3: can't reach"
+        );
+        assert_eq!(redact::text(&redactions, "plain"), "plain");
+
+        let mut value = serde_json::json!({"uri": ["Globex"], "line": 1});
+        redact::json(&redactions, &mut value);
+        assert_eq!(value, serde_json::json!({"uri": ["CUSTOMER"], "line": 1}));
+    }
+}
@@ -0,0 +1,119 @@
+use crate::diagnostic::Diagnostic;
+use std::collections::BTreeMap;
+
+const NO_RULE: &str = "(no rule)";
+
+pub type RuleTotals = BTreeMap<String, usize>;
+
+pub fn count_rule(totals: &mut RuleTotals, diagnostic: &Diagnostic) {
+    let rule = diagnostic.rule.as_deref().unwrap_or(NO_RULE);
+    match totals.get_mut(rule) {
+        Some(total) => *total += 1,
+        None => {
+            totals.insert(rule.to_string(), 1);
+        }
+    }
+}
+
+/// Totals per `(path, rule)`, for rules that legitimately repeat within a file
+pub type PairTotals = BTreeMap<(String, String), usize>;
+
+pub fn pair_key(diagnostic: &Diagnostic) -> (String, String) {
+    let rule = diagnostic.rule.as_deref().unwrap_or(NO_RULE);
+    (diagnostic.path.clone(), rule.to_string())
+}
+
+pub fn count_pair(totals: &mut PairTotals, diagnostic: &Diagnostic) {
+    *totals.entry(pair_key(diagnostic)).or_insert(0) += 1;
+}
+
+/// Whether the file has more findings of the diagnostic's rule than in the base report
+pub fn pair_increased(base: &PairTotals, current: &PairTotals, diagnostic: &Diagnostic) -> bool {
+    let key = pair_key(diagnostic);
+    current.get(&key).copied().unwrap_or(0) > base.get(&key).copied().unwrap_or(0)
+}
+
+/// A rule whose total count grew compared to the base report
+#[derive(Debug, PartialEq, Eq)]
+pub struct Regression {
+    pub rule: String,
+    pub base: usize,
+    pub current: usize,
+}
+
+pub fn find_regressions(base: &RuleTotals, current: &RuleTotals) -> Vec<Regression> {
+    current
+        .iter()
+        .filter_map(|(rule, &current)| {
+            let base = base.get(rule).copied().unwrap_or(0);
+            if current > base {
+                Some(Regression {
+                    rule: rule.clone(),
+                    base,
+                    current,
+                })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::Diagnostic;
+    use crate::regression::{
+        count_pair, count_rule, find_regressions, pair_increased, PairTotals, Regression,
+        RuleTotals,
+    };
+
+    fn totals(rules: &[Option<&str>]) -> RuleTotals {
+        let mut totals = RuleTotals::new();
+        for rule in rules {
+            let mut diagnostic = Diagnostic::new("a.py", Some(1));
+            diagnostic.rule = rule.map(str::to_string);
+            count_rule(&mut totals, &diagnostic);
+        }
+        totals
+    }
+
+    #[test]
+    fn test_only_increases_are_regressions() {
+        let base = totals(&[Some("I001"), Some("I001"), Some("E501"), None]);
+        let current = totals(&[Some("I001"), Some("E501"), Some("E501"), Some("F401")]);
+        assert_eq!(
+            find_regressions(&base, &current),
+            vec![
+                Regression {
+                    rule: "E501".to_string(),
+                    base: 1,
+                    current: 2
+                },
+                Regression {
+                    rule: "F401".to_string(),
+                    base: 0,
+                    current: 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pair_increased() {
+        let finding = |path: &str| {
+            let mut diagnostic = Diagnostic::new(path, Some(1));
+            diagnostic.rule = Some("R0801".to_string());
+            diagnostic
+        };
+        let mut base = PairTotals::new();
+        let mut current = PairTotals::new();
+        for path in ["a.py", "a.py", "b.py"].iter() {
+            count_pair(&mut base, &finding(path));
+        }
+        for path in ["a.py", "a.py", "b.py", "b.py"].iter() {
+            count_pair(&mut current, &finding(path));
+        }
+        assert!(!pair_increased(&base, &current, &finding("a.py")));
+        assert!(pair_increased(&base, &current, &finding("b.py")));
+    }
+}
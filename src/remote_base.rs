@@ -0,0 +1,97 @@
+//! Base trees fetched straight from a remote, for workdirs mounted without their `.git`.
+//!
+//! The ref is fetched shallowly and without blobs into a throwaway bare repository,
+//! which is marked as a partial clone of the remote so that only the blobs of files in
+//! the lint output are fetched later. Its index is filled from the base tree so the
+//! workdir diff sees unmodified files as such.
+
+use anyhow::{bail, Context, Result};
+use git2::Repository;
+use log::{debug, info};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+/// Ref the fetched base is stored under
+pub const BASE_REF: &str = "refs/diff-format/base";
+
+/// The temporary repository, deleted when dropped
+pub struct RemoteBase {
+    dir: PathBuf,
+}
+
+impl Drop for RemoteBase {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_dir_all(&self.dir) {
+            debug!("Can't remove {}: {}", self.dir.display(), err);
+        }
+    }
+}
+
+fn git(dir: &Path, args: &[&OsStr]) -> Result<()> {
+    let status = Command::new("git")
+        .arg("--git-dir")
+        .arg(dir)
+        .args(args)
+        .status()
+        .context("Unable to run git")?;
+    if !status.success() {
+        bail!("git {:?} failed with {}", args, status);
+    }
+    Ok(())
+}
+
+impl RemoteBase {
+    /// Fetches `gitref` from `url`, returning a repository with `workdir` as its working
+    /// tree and the base at [`BASE_REF`]
+    pub fn fetch(url: &str, gitref: &str, workdir: &Path) -> Result<(Self, Repository)> {
+        let dir = std::env::temp_dir().join(format!("diff-format-base-{}", process::id()));
+        let base = RemoteBase { dir };
+        let status = Command::new("git")
+            .args(["init", "--quiet", "--bare"])
+            .arg(&base.dir)
+            .status()
+            .context("Unable to run git init")?;
+        if !status.success() {
+            bail!("git init of {} failed with {}", base.dir.display(), status);
+        }
+        for (key, value) in [
+            ("remote.origin.url", url),
+            ("remote.origin.promisor", "true"),
+            ("remote.origin.partialclonefilter", "blob:none"),
+        ] {
+            git(
+                &base.dir,
+                &["config".as_ref(), key.as_ref(), value.as_ref()],
+            )?;
+        }
+
+        info!("Fetching {} from {}", gitref, url);
+        let refspec = format!("+{}:{}", gitref, BASE_REF);
+        git(
+            &base.dir,
+            &[
+                "fetch",
+                "--quiet",
+                "--depth=1",
+                "--no-tags",
+                "--filter=blob:none",
+                "origin",
+                &refspec,
+            ]
+            .map(OsStr::new),
+        )
+        .with_context(|| format!("Unable to fetch {} from {}", gitref, url))?;
+
+        let repo = Repository::open_bare(&base.dir)?;
+        repo.set_workdir(workdir, false)?;
+        {
+            let tree = repo.revparse_single(BASE_REF)?.peel_to_tree()?;
+            let mut index = repo.index()?;
+            index.read_tree(&tree)?;
+            index.write()?;
+        }
+        Ok((base, repo))
+    }
+}
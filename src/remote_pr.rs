@@ -0,0 +1,112 @@
+//! `remote-pr`: the changed lines of a pull request read from the provider's API rather
+//! than a checkout, for bots that gate lint reports without cloning the repository.
+//! The report on stdin is filtered as with `--diff-file`.
+
+use crate::publish::Agent;
+use anyhow::{anyhow, bail, Result};
+use clap::{Args, ValueEnum};
+#[cfg(feature = "http")]
+use log::debug;
+use std::env;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Provider {
+    /// GitHub or GitHub Enterprise, at `GITHUB_API_URL`
+    Github,
+}
+
+#[derive(Args, Debug)]
+pub struct RemotePrArgs {
+    /// Where the pull request is hosted
+    #[arg(long, value_enum)]
+    provider: Provider,
+
+    /// Number of the pull request
+    #[arg(long)]
+    pr: u64,
+
+    /// The repository as `owner/name`; `GITHUB_REPOSITORY` by default
+    #[arg(long, value_name = "OWNER/NAME")]
+    repository: Option<String>,
+}
+
+impl RemotePrArgs {
+    /// The API URL of the pull request
+    fn url(&self) -> Result<String> {
+        let repository = match &self.repository {
+            Some(repository) => repository.clone(),
+            None => env::var("GITHUB_REPOSITORY").map_err(|_| {
+                anyhow!("Give the repository of the pull request with --repository")
+            })?,
+        };
+        if repository.split('/').count() != 2 {
+            bail!("--repository should be owner/name, not '{}'", repository);
+        }
+        let api_url = match self.provider {
+            Provider::Github => {
+                env::var("GITHUB_API_URL").unwrap_or_else(|_| "https://api.github.com".to_string())
+            }
+        };
+        Ok(format!(
+            "{}/repos/{}/pulls/{}",
+            api_url.trim_end_matches('/'),
+            repository,
+            self.pr
+        ))
+    }
+
+    /// The unified diff of the pull request, authenticated with `GITHUB_TOKEN` when set,
+    /// which private repositories need
+    #[cfg(feature = "http")]
+    pub fn fetch_diff(&self, http: &Agent) -> Result<String> {
+        let url = self.url()?;
+        let mut request = http
+            .get(&url)
+            .header("Accept", "application/vnd.github.diff");
+        if let Ok(token) = env::var("GITHUB_TOKEN") {
+            request = request.header("Authorization", &format!("Bearer {}", token));
+        }
+        let diff = request
+            .call()
+            .map_err(|err| match err {
+                // GitHub only renders diffs of up to 300 files
+                ureq::Error::StatusCode(406) => anyhow!(
+                    "Pull request #{} is too large for GitHub to render its diff",
+                    self.pr
+                ),
+                err => anyhow!(
+                    "Unable to read the diff of pull request #{}: {}",
+                    self.pr,
+                    err
+                ),
+            })?
+            .body_mut()
+            .read_to_string()?;
+        debug!("Read {} byte(s) of diff from {}", diff.len(), url);
+        Ok(diff)
+    }
+
+    #[cfg(not(feature = "http"))]
+    pub fn fetch_diff(&self, _http: &Agent) -> Result<String> {
+        self.url()?;
+        bail!("remote-pr needs diff-format built with the `http` feature")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::remote_pr::{Provider, RemotePrArgs};
+
+    #[test]
+    fn test_url() {
+        let args = |repository: &str| RemotePrArgs {
+            provider: Provider::Github,
+            pr: 123,
+            repository: Some(repository.to_string()),
+        };
+        let url = args("octo/app").url().unwrap();
+        assert!(url.ends_with("/repos/octo/app/pulls/123"), "{}", url);
+        assert!(args("octo").url().is_err());
+        assert!(args("octo/app/extra").url().is_err());
+    }
+}
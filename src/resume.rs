@@ -0,0 +1,113 @@
+//! Progress through the lint output, saved as it is consumed so a run killed halfway
+//! can be continued by feeding it the same output again.
+
+use crate::diagnostic::Diagnostic;
+use crate::output;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Input lines consumed between saves
+const SAVE_INTERVAL: usize = 100;
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct State {
+    /// Input lines already processed
+    lines: usize,
+    /// The last processed line, to detect a different input
+    last_line: Option<String>,
+    matched: Vec<Diagnostic>,
+}
+
+pub struct Resume {
+    path: PathBuf,
+    state: State,
+}
+
+impl Resume {
+    /// Continues from the state at `path`, or starts over if there is none
+    pub fn load(path: &Path) -> Result<Self> {
+        let state = match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .with_context(|| format!("Invalid resume state {}", path.display()))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => State::default(),
+            Err(err) => return Err(err).with_context(|| format!("Can't read {}", path.display())),
+        };
+        Ok(Resume {
+            path: path.to_path_buf(),
+            state,
+        })
+    }
+
+    /// Findings matched before the previous run stopped
+    pub fn matched(&self) -> &[Diagnostic] {
+        &self.state.matched
+    }
+
+    /// Whether input line `index` was processed already, failing if the input differs
+    pub fn skip(&self, index: usize, line: &str) -> Result<bool> {
+        if index + 1 == self.state.lines && self.state.last_line.as_deref() != Some(line) {
+            bail!(
+                "Input differs from the run {} was saved for, remove it to start over",
+                self.path.display()
+            );
+        }
+        Ok(index < self.state.lines)
+    }
+
+    /// Records that input line `index` was processed, saving every few lines
+    pub fn processed(&mut self, index: usize, line: &str, matched: &[Diagnostic]) -> Result<()> {
+        self.state.lines = index + 1;
+        if self.state.lines.is_multiple_of(SAVE_INTERVAL) {
+            self.state.last_line = Some(line.to_string());
+            self.state.matched = matched.to_vec();
+            let text = serde_json::to_string(&self.state)?;
+            output::write_atomic(&self.path, text.as_bytes())
+                .with_context(|| format!("Unable to write {}", self.path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Drops the state once all outputs are written
+    pub fn finish(self) -> Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                Err(err).with_context(|| format!("Unable to remove {}", self.path.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::Diagnostic;
+    use crate::fixtures::TempDir;
+    use crate::resume::Resume;
+
+    #[test]
+    fn test_resume() {
+        let dir = TempDir::new("resume").unwrap();
+        let path = dir.join("resume.json");
+        let lines: Vec<String> = (0..150).map(|i| format!("a.py:{}: E1 x", i)).collect();
+        let matched = vec![Diagnostic::new("a.py", Some(1))];
+
+        let mut resume = Resume::load(&path).unwrap();
+        for (index, line) in lines.iter().enumerate().take(120) {
+            assert!(!resume.skip(index, line).unwrap());
+            resume.processed(index, line, &matched).unwrap();
+        }
+
+        // Killed here; the state saved after line 100 is picked up
+        let resume = Resume::load(&path).unwrap();
+        assert_eq!(resume.matched(), &matched[..]);
+        assert!(resume.skip(99, &lines[99]).unwrap());
+        assert!(!resume.skip(100, &lines[100]).unwrap());
+        assert!(resume.skip(99, "other output").is_err());
+
+        resume.finish().unwrap();
+        assert!(!path.exists());
+    }
+}
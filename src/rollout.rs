@@ -0,0 +1,124 @@
+//! Staged enabling of rules, configured as `[[rollout]]`: findings of the rule are
+//! reported as warnings that don't fail the run until its `warn_until` date.
+
+use crate::config::RolloutConfig;
+use crate::diagnostic::{Diagnostic, Severity};
+use anyhow::{Context, Result};
+use std::fmt;
+use toml::value::Date;
+
+pub struct Rollout {
+    pub rule: String,
+    /// First day the rule's findings fail the run
+    pub warn_until: Date,
+}
+
+impl Rollout {
+    pub fn warns(&self, today: Date) -> bool {
+        (today.year, today.month, today.day)
+            < (
+                self.warn_until.year,
+                self.warn_until.month,
+                self.warn_until.day,
+            )
+    }
+}
+
+impl fmt::Display for Rollout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (warning until {})", self.rule, self.warn_until)
+    }
+}
+
+pub struct Rollouts {
+    rollouts: Vec<Rollout>,
+}
+
+impl Rollouts {
+    pub fn new(configs: &[RolloutConfig]) -> Result<Self> {
+        let rollouts = configs
+            .iter()
+            .map(|config| {
+                let datetime = config.warn_until;
+                let warn_until = Some(datetime)
+                    .filter(|datetime| datetime.time.is_none())
+                    .and_then(|datetime| datetime.date)
+                    .with_context(|| {
+                        format!("[[rollout]] warn_until {} is not a date", config.warn_until)
+                    })?;
+                Ok(Rollout {
+                    rule: config.rule.clone(),
+                    warn_until,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Rollouts { rollouts })
+    }
+
+    /// The rollout still downgrading the finding's rule on `today`
+    pub fn active(&self, diagnostic: &Diagnostic, today: Date) -> Option<&Rollout> {
+        let rule = diagnostic.rule.as_deref()?;
+        self.rollouts
+            .iter()
+            .find(|rollout| rollout.rule == rule && rollout.warns(today))
+    }
+
+    /// Downgrades the finding to a warning while its rule is rolling out, returning the
+    /// rollout responsible
+    pub fn apply(&self, diagnostic: &mut Diagnostic, today: Date) -> Option<&Rollout> {
+        let rollout = self.active(diagnostic, today)?;
+        diagnostic.severity = Some(diagnostic.effective_severity().min(Severity::Warning));
+        Some(rollout)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::Config;
+    use crate::diagnostic::{Diagnostic, Severity};
+    use crate::rollout::Rollouts;
+    use toml::value::Date;
+
+    #[test]
+    fn test_rollouts() {
+        // Schema 1 also took the date as a string
+        let config = Config::parse(
+            r#"
+            [[rollout]]
+            rule = "E501"
+            warn_until = "2025-03-01"
+
+            [[rollout]]
+            rule = "SEC101"
+            warn_until = 2025-04-01
+            "#,
+            false,
+        )
+        .unwrap();
+        let rollouts = Rollouts::new(&config.rollout).unwrap();
+        let day = |month, day| Date {
+            year: 2025,
+            month,
+            day,
+        };
+        let mut diagnostic = Diagnostic::new("a.py", Some(1));
+        diagnostic.rule = Some("E501".to_string());
+        let rollout = rollouts.apply(&mut diagnostic, day(2, 28)).unwrap();
+        assert_eq!(rollout.to_string(), "E501 (warning until 2025-03-01)");
+        assert_eq!(diagnostic.severity, Some(Severity::Warning));
+        assert!(rollouts.active(&diagnostic, day(3, 1)).is_none());
+
+        diagnostic.rule = Some("SEC101".to_string());
+        diagnostic.severity = Some(Severity::Info);
+        assert!(rollouts.apply(&mut diagnostic, day(3, 31)).is_some());
+        assert_eq!(diagnostic.severity, Some(Severity::Info));
+
+        assert!(
+            Config::parse("[[rollout]]\nrule = \"E1\"\nwarn_until = \"soon\"\n", false).is_err()
+        );
+        let config: Config =
+            toml::from_str("[[rollout]]\nrule = \"E1\"\nwarn_until = 2025-03-01T09:00:00\n")
+                .unwrap();
+        assert!(Rollouts::new(&config.rollout).is_err());
+    }
+}
@@ -0,0 +1,115 @@
+//! Links to the documentation of findings' rules, from a table of URL templates picked
+//! by the extension of the finding's file, for reports that link rules, like GitLab
+//! code quality.
+//!
+//! A template names the rule as `{rule}`. The built-in ones only link rule codes of the
+//! tools they document, e.g. rustc's `E0308` but not clippy's lint names; the
+//! `[rule_docs]` config table gives an extension's template instead, linking any rule.
+
+use crate::diagnostic::Diagnostic;
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::LazyLock as Lazy;
+
+/// Extension, rule codes linked and template
+const BUILTIN: &[(&str, &str, &str)] = &[
+    (
+        "py",
+        r"^(?:E[1-9]|W[1-6]|F[4-9]|C9)\d\d$",
+        "https://www.flake8rules.com/rules/{rule}.html",
+    ),
+    (
+        "rs",
+        r"^E\d{4}$",
+        "https://doc.rust-lang.org/error_codes/{rule}.html",
+    ),
+];
+
+static BUILTIN_RULES: Lazy<Vec<Regex>> = Lazy::new(|| {
+    BUILTIN
+        .iter()
+        .map(|(_, rules, _)| Regex::new(rules).unwrap())
+        .collect()
+});
+
+/// The built-in templates, with those configured by extension over them
+#[derive(Debug, Clone, Copy)]
+pub struct RuleDocs<'a> {
+    configured: Option<&'a HashMap<String, String>>,
+}
+
+impl<'a> RuleDocs<'a> {
+    /// Only the built-in templates
+    pub fn builtin() -> Self {
+        RuleDocs { configured: None }
+    }
+
+    pub fn new(configured: &'a HashMap<String, String>) -> Self {
+        RuleDocs {
+            configured: Some(configured),
+        }
+    }
+
+    /// The documentation of the rule of `diagnostic`, if its file's extension has a
+    /// template for it
+    pub fn url(&self, diagnostic: &Diagnostic) -> Option<String> {
+        let rule = diagnostic.rule.as_deref().filter(|rule| !rule.is_empty())?;
+        let extension = Path::new(&diagnostic.path).extension()?.to_str()?;
+        let template = match self.configured.and_then(|table| table.get(extension)) {
+            Some(template) => template.as_str(),
+            None => BUILTIN
+                .iter()
+                .zip(BUILTIN_RULES.iter())
+                .find(|((builtin, _, _), rules)| *builtin == extension && rules.is_match(rule))
+                .map(|((_, _, template), _)| *template)?,
+        };
+        Some(template.replace("{rule}", rule))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::Diagnostic;
+    use crate::rule_docs::RuleDocs;
+    use std::collections::HashMap;
+
+    fn finding(path: &str, rule: &str) -> Diagnostic {
+        let mut diagnostic = Diagnostic::new(path, Some(1));
+        diagnostic.rule = Some(rule.to_string());
+        diagnostic
+    }
+
+    #[test]
+    fn test_url() {
+        let builtin = RuleDocs::builtin();
+        assert_eq!(
+            builtin.url(&finding("src/a.py", "E501")).as_deref(),
+            Some("https://www.flake8rules.com/rules/E501.html")
+        );
+        assert_eq!(
+            builtin.url(&finding("src/lib.rs", "E0308")).as_deref(),
+            Some("https://doc.rust-lang.org/error_codes/E0308.html")
+        );
+        // Codes of other tools for the same language, and other languages, aren't linked
+        assert_eq!(builtin.url(&finding("src/lib.rs", "needless_return")), None);
+        assert_eq!(builtin.url(&finding("src/a.py", "PLR0913")), None);
+        assert_eq!(builtin.url(&finding("src/a.go", "E501")), None);
+        assert_eq!(builtin.url(&Diagnostic::new("src/a.py", Some(1))), None);
+
+        let table: HashMap<_, _> = vec![(
+            "rs".to_string(),
+            "https://rust-lang.github.io/rust-clippy/master/#{rule}".to_string(),
+        )]
+        .into_iter()
+        .collect();
+        let configured = RuleDocs::new(&table);
+        assert_eq!(
+            configured
+                .url(&finding("src/lib.rs", "needless_return"))
+                .as_deref(),
+            Some("https://rust-lang.github.io/rust-clippy/master/#needless_return")
+        );
+        assert!(configured.url(&finding("src/a.py", "E501")).is_some());
+    }
+}
@@ -0,0 +1,117 @@
+//! What identifies a run: a ULID and the time it started as an RFC 3339 UTC timestamp,
+//! carried by the summary and the store so results can be correlated downstream. The
+//! shards of one CI job can share an ID with `--run-id`.
+
+use crate::waiver::civil_date;
+use serde::{Serialize, Serializer};
+use std::hash::{BuildHasher, Hasher, RandomState};
+use std::time::{SystemTime, UNIX_EPOCH};
+use toml::value::{Datetime, Offset, Time};
+
+/// Crockford's base 32, without I, L, O and U
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Milliseconds since the Unix epoch
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+/// Seconds since the Unix epoch
+pub fn now() -> i64 {
+    (now_millis() / 1000) as i64
+}
+
+/// `seconds` since the Unix epoch as `YYYY-MM-DDTHH:MM:SSZ`, whatever the locale and
+/// time zone
+pub fn rfc3339(seconds: i64) -> String {
+    let seconds = seconds.max(0) as u64;
+    let time = seconds % 86_400;
+    Datetime {
+        date: Some(civil_date(seconds / 86_400)),
+        time: Some(Time {
+            hour: (time / 3600) as u8,
+            minute: (time / 60 % 60) as u8,
+            second: Some((time % 60) as u8),
+            nanosecond: None,
+        }),
+        offset: Some(Offset::Z),
+    }
+    .to_string()
+}
+
+/// A ULID: 48 bits of milliseconds then 80 random bits, so IDs sort by time
+pub fn ulid(millis: u64, random: u128) -> String {
+    let value = (u128::from(millis) & ((1 << 48) - 1)) << 80 | random & ((1 << 80) - 1);
+    // 26 digits hold 130 bits, the first one only the top 3
+    (0..26)
+        .map(|digit| CROCKFORD[(value >> (125 - 5 * digit)) as usize & 31] as char)
+        .collect()
+}
+
+/// Random bits from the randomly keyed hasher of the standard library, enough to tell
+/// apart runs started in the same millisecond
+fn random() -> u128 {
+    let state = RandomState::new();
+    let half = |salt: u64| {
+        let mut hasher = state.build_hasher();
+        hasher.write_u64(salt);
+        hasher.write_u32(std::process::id());
+        hasher.finish()
+    };
+    u128::from(half(0)) << 64 | u128::from(half(1))
+}
+
+fn serialize_rfc3339<S: Serializer>(seconds: &i64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&rfc3339(*seconds))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RunInfo {
+    pub id: String,
+    /// Seconds since the Unix epoch
+    #[serde(serialize_with = "serialize_rfc3339")]
+    pub started: i64,
+}
+
+impl RunInfo {
+    /// A run starting now, with a new ULID unless the shards of a job share `id`
+    pub fn new(id: Option<&str>) -> Self {
+        let millis = now_millis();
+        RunInfo {
+            id: id.map_or_else(|| ulid(millis, random()), str::to_string),
+            started: (millis / 1000) as i64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::run::{rfc3339, ulid, RunInfo};
+
+    #[test]
+    fn test_rfc3339() {
+        assert_eq!(rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(rfc3339(1_767_571_200 + 3_723), "2026-01-05T01:02:03Z");
+    }
+
+    #[test]
+    fn test_ulid() {
+        assert_eq!(ulid(0, 0), "00000000000000000000000000");
+        assert_eq!(ulid(1_469_918_176_385, 0), "01ARYZ6S410000000000000000");
+        assert_eq!(ulid(u64::MAX, u128::MAX), "7ZZZZZZZZZZZZZZZZZZZZZZZZZ");
+        let (first, second) = (RunInfo::new(None), RunInfo::new(None));
+        assert_eq!(first.id.len(), 26);
+        assert_ne!(first.id, second.id);
+        assert_eq!(RunInfo::new(Some("job-7")).id, "job-7");
+        assert_eq!(
+            serde_json::to_value(RunInfo {
+                id: "job-7".to_string(),
+                started: 0
+            })
+            .unwrap()["started"],
+            "1970-01-01T00:00:00Z"
+        );
+    }
+}
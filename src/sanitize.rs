@@ -0,0 +1,86 @@
+//! The progress bars and spinners linters draw on the stream they print findings on.
+//!
+//! A terminal shows such a line as its last redraw, but piped it still holds every
+//! frame, each starting with a carriage return or a cursor move back, and a finding
+//! printed between two redraws is stuck to them. Each line is split where it is
+//! redrawn and the parts that are progress are dropped, so the findings in between
+//! are parsed on their own. Lines that aren't redrawn are left as they are.
+
+use crate::remove_ansi_colors;
+use regex::Regex;
+use std::sync::LazyLock as Lazy;
+
+/// Carriage returns and the cursor moves to the start of the line (CHA) or to a line
+/// above (CUU, CPL) progress bars are redrawn with, in their `ESC` and 8-bit forms
+static REDRAW: Lazy<Regex> = Lazy::new(|| Regex::new(r"\r|(?:\x1b\[|\u{9b})[0-9;]*[GAF]").unwrap());
+
+/// A bar: bracketed `[===>   ]`-like runs or runs of block and line characters
+static BAR: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\[[=#>\-.· ]{4,}\]|[█▉▊▋▌▍▎▏▓▒░━╸╺■□]{3,}").unwrap());
+
+static PERCENT: Lazy<Regex> = Lazy::new(|| Regex::new(r"\b\d{1,3}(?:\.\d+)?%").unwrap());
+
+/// A `file:line` location, which only findings have
+static LOCATION: Lazy<Regex> = Lazy::new(|| Regex::new(r"\S:\d+").unwrap());
+
+/// Frames of ASCII spinners, only taken for progress when alone in a part
+const ASCII_SPINNER: [&str; 4] = ["|", "/", "-", "\\"];
+
+/// Whether the redrawn part `text`, stripped of colors, is a spinner frame or progress
+/// bar rather than lint output
+fn is_progress(text: &str) -> bool {
+    let text = text.trim();
+    // Braille and circle spinners, as drawn by indicatif, ora and cli-spinners
+    let spinning = text
+        .chars()
+        .next()
+        .is_some_and(|c| ('\u{2800}'..='\u{28ff}').contains(&c) || "◐◓◑◒◴◷◶◵◜◠◝◞◡◟".contains(c));
+    text.is_empty()
+        || spinning
+        || ASCII_SPINNER.contains(&text)
+        || BAR.is_match(text)
+        || (PERCENT.is_match(text) && !LOCATION.is_match(text))
+}
+
+/// The parts of `line` that aren't progress, each as a line of its own
+pub fn sanitize(line: String) -> Vec<String> {
+    if !REDRAW.is_match(&line) {
+        return vec![line];
+    }
+    REDRAW
+        .split(&line)
+        .filter(|part| !is_progress(&remove_ansi_colors(part)))
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sanitize::sanitize;
+
+    #[test]
+    fn test_sanitize() {
+        let line = |text: &str| sanitize(text.to_string());
+        assert_eq!(
+            line("Linting  12%\rLinting  40%\ra.py:3:1: F401 'os' imported but unused"),
+            ["a.py:3:1: F401 'os' imported but unused"]
+        );
+        // A finding between two redraws of a bar, with the line erased before each
+        assert_eq!(
+            line("[===>      ] 3/10\r\x1b[2Ka.py:1:80: E501 too long\r\x1b[2K[=====>    ] 5/10"),
+            ["\x1b[2Ka.py:1:80: E501 too long"]
+        );
+        assert_eq!(
+            line("\x1b[1G⠋ Checking\x1b[1G⠙ Checking\x1b[1Gsrc/b.py:7: error: 100% wrong"),
+            ["src/b.py:7: error: 100% wrong"]
+        );
+        assert_eq!(
+            line("████░░░░ 50%\x1b[1A\x1b[2K|\rb.c:2:1: warning: unused"),
+            ["b.c:2:1: warning: unused"]
+        );
+        assert!(line("/\r-\r\\\r ").is_empty());
+        // Lines not redrawn are kept whatever they hold
+        assert_eq!(line("Coverage: 75%"), ["Coverage: 75%"]);
+        assert_eq!(line("- a.py:1: E1 x"), ["- a.py:1: E1 x"]);
+    }
+}
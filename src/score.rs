@@ -0,0 +1,88 @@
+//! `--max-score`: a budget for the findings of a run weighted by severity, so a few
+//! warnings pass but a pile of them, or a single error, doesn't.
+//!
+//! ```toml
+//! [score]
+//! error = 5
+//! warning = 1
+//! info = 0
+//! ```
+
+use crate::diagnostic::{Diagnostic, Severity};
+use serde::{Deserialize, Serialize};
+
+/// The `[score]` table: what a finding of each severity adds to the score
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct Weights {
+    pub error: u64,
+    pub warning: u64,
+    pub info: u64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            error: 5,
+            warning: 1,
+            info: 0,
+        }
+    }
+}
+
+impl Weights {
+    pub fn weight(&self, severity: Severity) -> u64 {
+        match severity {
+            Severity::Error => self.error,
+            Severity::Warning => self.warning,
+            Severity::Info => self.info,
+        }
+    }
+
+    /// The score of `diagnostics`, those without a severity counting as errors
+    pub fn score<'a>(&self, diagnostics: impl IntoIterator<Item = &'a Diagnostic>) -> u64 {
+        diagnostics
+            .into_iter()
+            .map(|diagnostic| self.weight(diagnostic.effective_severity()))
+            .sum()
+    }
+}
+
+/// The score of a run against its `--max-score`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Score {
+    pub value: u64,
+    pub max: u64,
+}
+
+impl Score {
+    pub fn exceeded(&self) -> bool {
+        self.value > self.max
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::Config;
+    use crate::diagnostic::{Diagnostic, Severity};
+    use crate::score::Weights;
+
+    #[test]
+    fn test_score() {
+        let finding = |severity| {
+            let mut diagnostic = Diagnostic::new("a.py", Some(1));
+            diagnostic.severity = severity;
+            diagnostic
+        };
+        let findings = [
+            finding(Some(Severity::Warning)),
+            finding(Some(Severity::Warning)),
+            finding(Some(Severity::Info)),
+            finding(None),
+        ];
+        assert_eq!(Weights::default().score(&findings), 7);
+        let config: Config = toml::from_str("[score]\ninfo = 2\n").unwrap();
+        assert_eq!(config.score.score(&findings), 9);
+        assert!(toml::from_str::<Config>("[score]\nfatal = 9\n").is_err());
+    }
+}
@@ -0,0 +1,78 @@
+use anyhow::Result;
+use clap::Args;
+use log::{info, warn};
+use std::path::{Path, PathBuf};
+
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    /// Unix socket to listen on
+    #[arg(long)]
+    socket: PathBuf,
+}
+
+/// Line a client sends to have the hunk map recomputed, e.g. after a commit
+pub const RELOAD: &str = "!reload";
+
+pub enum Request<'a> {
+    Reload,
+    Line(&'a str),
+}
+
+impl ServeArgs {
+    pub fn socket(&self) -> &Path {
+        &self.socket
+    }
+}
+
+/// Answers newline-delimited requests on a Unix socket, one connection at a time, with one
+/// response line per request
+#[cfg(unix)]
+pub fn serve(socket: &Path, mut respond: impl FnMut(Request) -> Result<String>) -> Result<()> {
+    use anyhow::Context;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixListener;
+
+    if socket.exists() {
+        // Left behind by a previous server
+        std::fs::remove_file(socket)
+            .with_context(|| format!("Unable to remove stale socket {}", socket.display()))?;
+    }
+    let listener = UnixListener::bind(socket)
+        .with_context(|| format!("Unable to listen on {}", socket.display()))?;
+    info!("Listening on {}", socket.display());
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                warn!("Failed to accept connection: {}", err);
+                continue;
+            }
+        };
+        let mut writer = stream.try_clone().context("Unable to clone socket")?;
+        for line in BufReader::new(stream).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => {
+                    warn!("Failed to read from client: {}", err);
+                    break;
+                }
+            };
+            let request = if line == RELOAD {
+                Request::Reload
+            } else {
+                Request::Line(&line)
+            };
+            let response = respond(request).unwrap_or_else(|err| format!("error: {:#}", err));
+            if writeln!(writer, "{}", response).is_err() {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn serve(_socket: &Path, _respond: impl FnMut(Request) -> Result<String>) -> Result<()> {
+    anyhow::bail!("serve is only supported on Unix")
+}
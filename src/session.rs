@@ -0,0 +1,120 @@
+//! A filter fed one line or finding at a time, doing no IO of its own, for embedders
+//! that run their own input loop.
+
+use crate::config::{Config, MatchPolicy};
+use crate::diagnostic::{self, Diagnostic, Severity};
+use crate::parsers::Parsers;
+use crate::{remove_ansi_colors, DiffFilter};
+use std::collections::HashMap;
+
+/// What the session decided about one finding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    /// On a change, so new
+    Keep(Diagnostic),
+    /// Elsewhere, so it predates the change
+    Drop(Diagnostic),
+}
+
+impl Decision {
+    pub fn is_kept(&self) -> bool {
+        matches!(self, Decision::Keep(_))
+    }
+
+    pub fn diagnostic(&self) -> &Diagnostic {
+        match self {
+            Decision::Keep(diagnostic) | Decision::Drop(diagnostic) => diagnostic,
+        }
+    }
+}
+
+pub struct FilterSession {
+    filter: DiffFilter,
+    parsers: Parsers,
+    match_policies: HashMap<String, MatchPolicy>,
+    severity: HashMap<String, Severity>,
+}
+
+impl FilterSession {
+    pub fn new(filter: DiffFilter, parsers: Parsers) -> Self {
+        FilterSession {
+            filter,
+            parsers,
+            match_policies: HashMap::new(),
+            severity: HashMap::new(),
+        }
+    }
+
+    /// Uses the per-tool match policies and severity overrides of `config`
+    pub fn with_config(self, config: &Config) -> Self {
+        FilterSession {
+            match_policies: config
+                .tool
+                .iter()
+                .filter_map(|(tool, tool_config)| Some((tool.clone(), tool_config.match_policy?)))
+                .collect(),
+            severity: config.severity.clone(),
+            ..self
+        }
+    }
+
+    /// Parses a line of lint output, calling `on_decision` for each finding on it.
+    ///
+    /// Lines without findings, like summaries, cause no call.
+    pub fn push_line(&mut self, line: &str, mut on_decision: impl FnMut(Decision)) {
+        for diagnostic in self.parsers.parse(&remove_ansi_colors(line)) {
+            on_decision(self.push_diagnostic(diagnostic));
+        }
+    }
+
+    /// Decides about a finding parsed elsewhere; paths must be relative to the
+    /// repository root and lines already resolved
+    pub fn push_diagnostic(&mut self, mut diagnostic: Diagnostic) -> Decision {
+        if let (None, Some(rule)) = (diagnostic.severity, &diagnostic.rule) {
+            diagnostic.severity = diagnostic::infer_severity(rule, &self.severity);
+        }
+        let policy = diagnostic
+            .tool
+            .as_ref()
+            .and_then(|tool| self.match_policies.get(tool))
+            .copied()
+            .unwrap_or_default();
+        if self.filter.matches(&diagnostic, policy) {
+            Decision::Keep(diagnostic)
+        } else {
+            Decision::Drop(diagnostic)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::Config;
+    use crate::diagnostic::Severity;
+    use crate::parsers::{Format, Parsers};
+    use crate::session::{Decision, FilterSession};
+    use crate::DiffFilter;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_push_line() {
+        let filter = DiffFilter::from_hunks(
+            vec![("a.py".to_string(), vec![(10, 12)])]
+                .into_iter()
+                .collect(),
+        );
+        let config: Config = toml::from_str("[tool.python]\nmatch = \"hunk\"").unwrap();
+        let mut session =
+            FilterSession::new(filter, Parsers::new(&[Format::Python], HashMap::new()))
+                .with_config(&config);
+
+        let mut decisions = Vec::new();
+        for line in ["a.py:14:1: W605 x", "a.py:40:1: E501 y", "Found 2 errors"] {
+            session.push_line(line, |decision| decisions.push(decision));
+        }
+        assert_eq!(decisions.len(), 2);
+        assert!(decisions[0].is_kept());
+        assert_eq!(decisions[0].diagnostic().severity, Some(Severity::Warning));
+        assert!(matches!(&decisions[1], Decision::Drop(d) if d.line == Some(40)));
+    }
+}
@@ -0,0 +1,100 @@
+use crate::HunkRange;
+use clap::Args;
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct ShowArgs {
+    /// File to print, relative to the repository root
+    #[arg(long)]
+    pub file: String,
+
+    /// Only print lines around the findings for this file in a lint report
+    #[arg(long)]
+    pub report: Option<PathBuf>,
+
+    /// Lines of context printed around each finding with --report
+    #[arg(long, default_value_t = 3)]
+    pub context: u32,
+}
+
+const CHANGED_STYLE: &str = "\x1b[32m";
+const FINDING_STYLE: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Prints `source` with a gutter of line numbers and markers: `+` for lines considered
+/// changed and `>` for lines with findings, or `*` for both.
+///
+/// When `findings` is given only lines within `context` of one are printed, with `...`
+/// between the gaps.
+pub fn render(
+    source: &str,
+    ranges: &[HunkRange],
+    findings: Option<&[u32]>,
+    context: u32,
+    color: bool,
+) -> String {
+    let changed = |line: u32| {
+        ranges
+            .iter()
+            .any(|&(start, end)| start <= line && line <= end)
+    };
+    let has_finding = |line: u32| findings.is_some_and(|findings| findings.contains(&line));
+    let visible = |line: u32| match findings {
+        Some(findings) => findings
+            .iter()
+            .any(|&finding| line + context >= finding && line <= finding + context),
+        None => true,
+    };
+
+    let mut output = String::new();
+    let mut skipped = false;
+    for (index, text) in source.lines().enumerate() {
+        let line = index as u32 + 1;
+        if !visible(line) {
+            skipped = true;
+            continue;
+        }
+        if skipped && !output.is_empty() {
+            output.push_str("...\n");
+        }
+        skipped = false;
+        let marker = match (changed(line), has_finding(line)) {
+            (true, true) => '*',
+            (true, false) => '+',
+            (false, true) => '>',
+            (false, false) => ' ',
+        };
+        let style = match marker {
+            ' ' => "",
+            '>' => FINDING_STYLE,
+            _ => CHANGED_STYLE,
+        };
+        if color && !style.is_empty() {
+            output.push_str(&format!(
+                "{}{:>5} {} {}{}\n",
+                style, line, marker, text, RESET
+            ));
+        } else {
+            output.push_str(&format!("{:>5} {} {}\n", line, marker, text));
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use crate::show::render;
+
+    #[test]
+    fn test_render() {
+        let source = "a\nb\nc\nd\ne\nf\ng\n";
+        assert_eq!(
+            render(source, &[(2, 3)], None, 0, false),
+            "    1   a\n    2 + b\n    3 + c\n    4   d\n    5   e\n    6   f\n    7   g\n"
+        );
+        assert_eq!(
+            render(source, &[(2, 3)], Some(&[3, 7]), 1, false),
+            "    2 + b\n    3 * c\n    4   d\n...\n    6   f\n    7 > g\n"
+        );
+    }
+}
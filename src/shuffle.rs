@@ -0,0 +1,51 @@
+//! `--shuffle`: hands the findings to the publishers, and runs the publishers, in a
+//! seeded random order, so a publisher relying on findings coming grouped or sorted
+//! fails in CI rather than on the first unusual log. Printed outputs keep their order.
+
+use std::hash::{BuildHasher, Hasher, RandomState};
+
+/// Steps a tiny xorshift generator; `state` must not be 0
+pub fn next_random(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+/// A seed for a run that wasn't given one, from the randomly keyed hasher of the
+/// standard library
+pub fn seed() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+    hasher.finish()
+}
+
+/// Shuffles `items` in an order that only depends on `seed`
+pub fn shuffle<T>(items: &mut [T], seed: u64) {
+    // Any seed, 0 included, gives a non-zero state
+    let mut state = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+    for index in (1..items.len()).rev() {
+        let other = next_random(&mut state) % (index as u64 + 1);
+        items.swap(index, other as usize);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::shuffle::shuffle;
+
+    #[test]
+    fn test_shuffle() {
+        let shuffled = |seed| {
+            let mut items: Vec<u32> = (0..20).collect();
+            shuffle(&mut items, seed);
+            items
+        };
+        assert_eq!(shuffled(7), shuffled(7));
+        assert_ne!(shuffled(7), shuffled(8));
+        assert_ne!(shuffled(0), (0..20).collect::<Vec<_>>());
+        let mut sorted = shuffled(7);
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..20).collect::<Vec<_>>());
+    }
+}
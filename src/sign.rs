@@ -0,0 +1,153 @@
+//! Detached signatures over written reports, so later CI stages can check a gate
+//! result wasn't altered.
+//!
+//! Each report gets a `<report>.attestation` next to it, binding the report's git blob
+//! id to the trees it was computed from, and the attestation is signed. Nothing in
+//! it depends on time or machine, so rerunning on the same input reproduces it. To
+//! verify an SSH signature:
+//!
+//! ```text
+//! ssh-keygen -Y verify -f allowed_signers -I <signer> -n diff-format \
+//!     -s report.sarif.attestation.sig < report.sarif.attestation
+//! git hash-object report.sarif   # must equal the attestation's `blob`
+//! ```
+
+use anyhow::{bail, Context, Result};
+use clap::{Args, ValueEnum};
+use git2::{ObjectType, Oid};
+use log::info;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Namespace of SSH signatures, which `ssh-keygen -Y verify` must be given
+const SSH_NAMESPACE: &str = "diff-format";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Signer {
+    /// `ssh-keygen -Y sign`, writing `.sig` files
+    Ssh,
+    /// `minisign -S`, writing `.minisig` files
+    Minisign,
+}
+
+impl Signer {
+    fn extension(self) -> &'static str {
+        match self {
+            Signer::Ssh => ".sig",
+            Signer::Minisign => ".minisig",
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct SignArgs {
+    /// Sign every report written to a file with this private key
+    #[arg(long, value_name = "KEY")]
+    sign: Option<PathBuf>,
+
+    /// Tool used to sign with --sign
+    #[arg(long, value_enum, default_value = "ssh", requires = "sign")]
+    sign_with: Signer,
+}
+
+/// Tree ids of the two sides of the diff a report was computed from, which unlike
+/// commit ids don't change when a merge base is picked differently
+pub struct Revisions {
+    pub base: Oid,
+    /// `None` when the workdir was diffed
+    pub head: Option<Oid>,
+}
+
+fn attestation(report: &Path, contents: &[u8], revisions: &Revisions) -> Result<String> {
+    let name = report
+        .file_name()
+        .context("Report path has no file name")?
+        .to_string_lossy();
+    Ok(format!(
+        "# diff-format report attestation v1\nreport {}\nblob {}\nbase {}\nhead {}\n",
+        name,
+        Oid::hash_object(ObjectType::Blob, contents)?,
+        revisions.base,
+        revisions
+            .head
+            .map_or_else(|| "workdir".to_string(), |head| head.to_string())
+    ))
+}
+
+impl SignArgs {
+    pub fn enabled(&self) -> bool {
+        self.sign.is_some()
+    }
+
+    /// Writes and signs the attestation of the report at `report`
+    pub fn sign(&self, report: &Path, revisions: &Revisions) -> Result<()> {
+        let key = match &self.sign {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+        let contents =
+            fs::read(report).with_context(|| format!("Unable to read {}", report.display()))?;
+        let mut path = report.as_os_str().to_owned();
+        path.push(".attestation");
+        let path = PathBuf::from(path);
+        fs::write(&path, attestation(report, &contents, revisions)?)
+            .with_context(|| format!("Unable to write {}", path.display()))?;
+
+        // ssh-keygen asks before overwriting the signature of a previous run
+        let mut signature = path.clone().into_os_string();
+        signature.push(self.sign_with.extension());
+        match fs::remove_file(&signature) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                return Err(err).context("Unable to remove previous signature")
+            }
+            _ => {}
+        }
+
+        let mut command = match self.sign_with {
+            Signer::Ssh => {
+                let mut command = Command::new("ssh-keygen");
+                command.args(["-Y", "sign", "-n", SSH_NAMESPACE, "-f"]);
+                command.arg(key).arg(&path);
+                command
+            }
+            Signer::Minisign => {
+                let mut command = Command::new("minisign");
+                command.args(["-S", "-s"]).arg(key).arg("-m").arg(&path);
+                command
+            }
+        };
+        let status = command
+            .status()
+            .with_context(|| format!("Unable to run {:?}", command.get_program()))?;
+        if !status.success() {
+            bail!("Signing {} failed with {}", path.display(), status);
+        }
+        info!("Signed {}", path.display());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sign::{attestation, Revisions};
+    use git2::Oid;
+    use std::path::Path;
+
+    #[test]
+    fn test_attestation() {
+        let revisions = Revisions {
+            base: Oid::from_str("1111111111111111111111111111111111111111").unwrap(),
+            head: None,
+        };
+        assert_eq!(
+            attestation(Path::new("out/report.sarif"), b"{}\n", &revisions).unwrap(),
+            "# diff-format report attestation v1\n\
+             report report.sarif\n\
+             blob 0967ef424bce6791893e9a57bb952f80fd536e93\n\
+             base 1111111111111111111111111111111111111111\n\
+             head workdir\n"
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use anyhow::{Context, Result};
+use git2::{Oid, Repository, Tree};
+use log::info;
+
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    // Howard Hinnant's algorithm, counting days since 1970-01-01
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Parses `YYYY-MM-DD`, optionally followed by `THH:MM[:SS]` or ` HH:MM[:SS]`, as UTC seconds
+pub fn parse_date(text: &str) -> Option<i64> {
+    let (date, time) = match text.find(['T', ' ']) {
+        Some(index) => (&text[..index], Some(&text[index + 1..])),
+        None => (text, None),
+    };
+    let mut date_parts = date.split('-');
+    let year: i64 = date_parts.next()?.parse().ok()?;
+    let month: u32 = date_parts.next()?.parse().ok()?;
+    let day: u32 = date_parts.next()?.parse().ok()?;
+    if date_parts.next().is_some() || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let mut seconds = 0;
+    if let Some(time) = time {
+        let time = time.trim_end_matches('Z');
+        let mut time_parts = time.split(':');
+        let hours: i64 = time_parts.next()?.parse().ok()?;
+        let minutes: i64 = time_parts.next()?.parse().ok()?;
+        let secs: i64 = time_parts.next().map_or(Some(0), |s| s.parse().ok())?;
+        if time_parts.next().is_some() || hours > 23 || minutes > 59 || secs > 60 {
+            return None;
+        }
+        seconds = hours * 3600 + minutes * 60 + secs;
+    }
+    Some(days_from_civil(year, month, day) * 86_400 + seconds)
+}
+
+/// Returns the tree to diff against so the hunk map covers every change since `since`,
+/// which is either a date or a revision.
+///
+/// For dates this is the newest first-parent ancestor of HEAD committed before the date, so
+/// the union of all later commits (and uncommitted work) ends up in the diff. The commit
+/// the tree is of comes along, unless there is none.
+pub fn since_tree<'a>(repo: &'a Repository, since: &str) -> Result<(Tree<'a>, Option<Oid>)> {
+    let timestamp = match parse_date(since) {
+        Some(timestamp) => timestamp,
+        None => {
+            let object = repo
+                .revparse_single(since)
+                .with_context(|| format!("'{}' is neither a date nor a revision", since))?;
+            let commit = object.peel_to_commit().ok().map(|commit| commit.id());
+            let tree = object.peel_to_tree().context("Revision is not a tree")?;
+            return Ok((tree, commit));
+        }
+    };
+
+    let mut commit = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .context("HEAD does not point to a commit")?;
+    loop {
+        if commit.time().seconds() < timestamp {
+            info!("Diffing against {} committed before {}", commit.id(), since);
+            return Ok((commit.tree()?, Some(commit.id())));
+        }
+        match commit.parent(0) {
+            Ok(parent) => commit = parent,
+            Err(_) => break,
+        }
+    }
+
+    info!(
+        "Whole history is newer than {}, diffing against the empty tree",
+        since
+    );
+    let empty = repo
+        .treebuilder(None)
+        .and_then(|builder| builder.write())
+        .context("Unable to create empty tree")?;
+    let tree = repo.find_tree(empty).context("Unable to read empty tree")?;
+    Ok((tree, None))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::since::parse_date;
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("1970-01-01"), Some(0));
+        assert_eq!(parse_date("2024-02-29"), Some(1_709_164_800));
+        assert_eq!(parse_date("2024-02-29T12:30"), Some(1_709_164_800 + 45_000));
+        assert_eq!(
+            parse_date("2024-02-29 12:30:15Z"),
+            Some(1_709_164_800 + 45_015)
+        );
+        assert_eq!(parse_date("origin/main"), None);
+        assert_eq!(parse_date("2024-13-01"), None);
+        assert_eq!(parse_date("HEAD~3"), None);
+    }
+}
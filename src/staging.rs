@@ -0,0 +1,168 @@
+//! Whether the changes in the workdir diff are staged in the index, left unstaged, or
+//! both, for flows that gate what is about to be committed.
+
+use crate::drift::LineMap;
+use crate::ranges::{merge_ranges, HunkMap};
+use crate::HunkRange;
+use anyhow::{Context, Result};
+use git2::{Diff, DiffFindOptions, DiffOptions, Patch, Repository, Tree};
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HunkSource {
+    Staged,
+    Unstaged,
+    Both,
+}
+
+/// Lines changed between the tree and the index, and between the index and the workdir,
+/// both in workdir line numbers
+#[derive(Debug, Default)]
+pub struct Staging {
+    staged: HunkMap,
+    unstaged: HunkMap,
+}
+
+fn overlaps(ranges: Option<&Vec<HunkRange>>, (start, end): HunkRange) -> bool {
+    ranges.is_some_and(|ranges| {
+        ranges
+            .iter()
+            .any(|&(other_start, other_end)| other_start <= end && start <= other_end)
+    })
+}
+
+fn options(pathspecs: Option<&[String]>) -> DiffOptions {
+    let mut options = DiffOptions::new();
+    options.context_lines(0);
+    for pathspec in pathspecs.unwrap_or_default() {
+        options.disable_pathspec_match(true);
+        options.pathspec(pathspec);
+    }
+    options
+}
+
+fn patches<'a>(diff: &'a Diff) -> impl Iterator<Item = Result<(String, Patch<'a>)>> + 'a {
+    (0..diff.deltas().len()).filter_map(move |index| {
+        let path = diff
+            .get_delta(index)?
+            .new_file()
+            .path()?
+            .to_str()?
+            .to_string();
+        let patch = Patch::from_diff(diff, index).transpose()?;
+        Some(patch.map(|patch| (path, patch)).map_err(Into::into))
+    })
+}
+
+impl Staging {
+    pub fn new(repo: &Repository, tree: &Tree, pathspecs: Option<&[String]>) -> Result<Self> {
+        let index = repo.index().context("Unable to read the index")?;
+        let mut staged_diff =
+            repo.diff_tree_to_index(Some(tree), Some(&index), Some(&mut options(pathspecs)))?;
+        staged_diff
+            .find_similar(Some(DiffFindOptions::new().renames(true)))
+            .context("Unable to detect renames")?;
+        let unstaged_diff =
+            repo.diff_index_to_workdir(Some(&index), Some(&mut options(pathspecs)))?;
+
+        let mut staging = Staging::default();
+        let mut line_maps = HashMap::new();
+        for patch in patches(&unstaged_diff) {
+            let (path, patch) = patch?;
+            let ranges = (0..patch.num_hunks())
+                .map(|index| {
+                    let (hunk, _) = patch.hunk(index)?;
+                    Ok((hunk.new_start(), hunk.new_start() + hunk.new_lines()))
+                })
+                .collect::<Result<_>>()?;
+            staging.unstaged.insert(path.clone(), ranges);
+            line_maps.insert(path, LineMap::from_patch(&patch)?);
+        }
+        for patch in patches(&staged_diff) {
+            let (path, patch) = patch?;
+            let line_map = line_maps.get(&path);
+            let mut ranges = Vec::with_capacity(patch.num_hunks());
+            for index in 0..patch.num_hunks() {
+                let (hunk, _) = patch.hunk(index)?;
+                let range = (hunk.new_start(), hunk.new_start() + hunk.new_lines());
+                // Staged lines move with the unstaged edits above them, and may be gone
+                let range = match line_map {
+                    Some(line_map) => line_map.map_range(range),
+                    None => Some(range),
+                };
+                ranges.extend(range);
+            }
+            if !ranges.is_empty() {
+                staging.staged.insert(path, merge_ranges(ranges));
+            }
+        }
+        Ok(staging)
+    }
+
+    /// Where the changes in `range` of `path` come from, `None` if it is in neither
+    /// diff, e.g. lines only changed relative to another ref
+    pub fn source(&self, path: &str, range: HunkRange) -> Option<HunkSource> {
+        match (
+            overlaps(self.staged.get(path), range),
+            overlaps(self.unstaged.get(path), range),
+        ) {
+            (true, true) => Some(HunkSource::Both),
+            (true, false) => Some(HunkSource::Staged),
+            (false, true) => Some(HunkSource::Unstaged),
+            (false, false) => None,
+        }
+    }
+
+    /// Drops the ranges whose changes are all unstaged
+    pub fn require_staged(&self, file_hunks: &mut HunkMap) {
+        file_hunks.retain(|path, ranges| {
+            ranges.retain(|&range| self.source(path, range) != Some(HunkSource::Unstaged));
+            !ranges.is_empty()
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fixtures::TempDir;
+    use crate::staging::{HunkSource, Staging};
+    use git2::{IndexAddOption, Repository, Signature};
+    use std::fs;
+
+    #[test]
+    fn test_staging() {
+        let dir = TempDir::new("staging").unwrap();
+        let repo = Repository::init(&dir).unwrap();
+        let write = |text: &str| fs::write(dir.join("a.py"), text).unwrap();
+        let stage = || {
+            let mut index = repo.index().unwrap();
+            index.add_all(["*"], IndexAddOption::DEFAULT, None).unwrap();
+            index.write().unwrap();
+            index.write_tree().unwrap()
+        };
+        write("a\nb\nc\nd\ne\n");
+        let tree = repo.find_tree(stage()).unwrap();
+        let signature = Signature::now("a", "a@a").unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "a", &tree, &[])
+            .unwrap();
+
+        // `b` and `d` staged, then a line inserted above them and `d` edited again
+        write("a\nB\nc\nD\ne\n");
+        stage();
+        write("x\na\nB\nc\nDD\ne\n");
+
+        let staging = Staging::new(&repo, &tree, None).unwrap();
+        assert_eq!(staging.source("a.py", (1, 1)), Some(HunkSource::Unstaged));
+        assert_eq!(staging.source("a.py", (3, 3)), Some(HunkSource::Staged));
+        assert_eq!(staging.source("a.py", (5, 5)), Some(HunkSource::Both));
+        assert_eq!(staging.source("b.py", (1, 1)), None);
+
+        let mut file_hunks = vec![("a.py".to_string(), vec![(1, 1), (3, 3), (5, 5)])]
+            .into_iter()
+            .collect();
+        staging.require_staged(&mut file_hunks);
+        assert_eq!(file_hunks["a.py"], [(3, 3), (5, 5)]);
+    }
+}
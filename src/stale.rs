@@ -0,0 +1,77 @@
+//! Findings on lines past the end of their file, as a stale report or one of generated
+//! code reports. They would otherwise just never match a hunk.
+
+use crate::diagnostic::Diagnostic;
+use clap::ValueEnum;
+use log::{debug, warn};
+
+/// What `--on-stale-line` does with a finding past the end of its file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OnStaleLine {
+    /// Keep the finding and warn about it
+    #[default]
+    Warn,
+    /// Drop the finding
+    Skip,
+    /// Move the finding to the last line of the file
+    Clamp,
+}
+
+impl OnStaleLine {
+    /// Handles a finding whose line is past the `lines` of its file, returning whether it
+    /// is kept
+    pub fn apply(self, diagnostic: &mut Diagnostic, lines: u32) -> bool {
+        let line = diagnostic.line.unwrap_or_default();
+        match self {
+            OnStaleLine::Warn => {
+                warn!(
+                    "{}:{} is past the end of the file, which has {} line(s); is the lint \
+                     output stale?",
+                    diagnostic.path, line, lines
+                );
+                true
+            }
+            OnStaleLine::Skip => {
+                debug!(
+                    "{}:{}: skipped, past the end of the file",
+                    diagnostic.path, line
+                );
+                false
+            }
+            OnStaleLine::Clamp => {
+                debug!("{}:{}: clamped to line {}", diagnostic.path, line, lines);
+                // An empty file has no line to move to
+                diagnostic.line = (lines > 0).then_some(lines);
+                diagnostic.end_line = diagnostic.end_line.and(diagnostic.line);
+                // Columns were on the missing line
+                diagnostic.column = None;
+                diagnostic.end_column = None;
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::Diagnostic;
+    use crate::stale::OnStaleLine;
+
+    #[test]
+    fn test_on_stale_line() {
+        let mut diagnostic = Diagnostic::new("a.py", Some(40));
+        diagnostic.end_line = Some(42);
+        diagnostic.column = Some(3);
+        assert!(OnStaleLine::Warn.apply(&mut diagnostic.clone(), 10));
+        assert!(!OnStaleLine::Skip.apply(&mut diagnostic.clone(), 10));
+
+        let mut clamped = diagnostic.clone();
+        assert!(OnStaleLine::Clamp.apply(&mut clamped, 10));
+        assert_eq!(
+            (clamped.line, clamped.end_line, clamped.column),
+            (Some(10), Some(10), None)
+        );
+        OnStaleLine::Clamp.apply(&mut diagnostic, 0);
+        assert_eq!((diagnostic.line, diagnostic.end_line), (None, None));
+    }
+}
@@ -0,0 +1,257 @@
+//! Checkouts that aren't on a branch with history: detached HEADs, unborn branches,
+//! rebases in progress and bare repositories. The base is picked from what the state
+//! implies when it can be, and otherwise the error says what to do about it.
+
+use anyhow::Result;
+use git2::{ErrorCode, Object, ObjectType, Oid, Repository, RepositoryState};
+use log::warn;
+use std::fmt;
+use std::fs;
+
+/// Exit code for checkouts the tool can't diff, distinct from failing findings (1)
+pub const EXIT_CODE: i32 = 3;
+
+/// The checkout can't be diffed as asked; main exits with [`EXIT_CODE`] on these
+#[derive(Debug)]
+pub struct CheckoutError(String);
+
+impl fmt::Display for CheckoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for CheckoutError {}
+
+fn checkout_error(message: String) -> anyhow::Error {
+    CheckoutError(message).into()
+}
+
+fn is_rebasing(state: RepositoryState) -> bool {
+    matches!(
+        state,
+        RepositoryState::Rebase
+            | RepositoryState::RebaseInteractive
+            | RepositoryState::RebaseMerge
+            | RepositoryState::ApplyMailboxOrRebase
+    )
+}
+
+/// The commit a rebase in progress replays onto, as git records it
+fn rebase_onto(repo: &Repository) -> Option<Oid> {
+    ["rebase-merge/onto", "rebase-apply/onto"]
+        .iter()
+        .find_map(|file| fs::read_to_string(repo.path().join(file)).ok())
+        .and_then(|onto| Oid::from_str(onto.trim()).ok())
+}
+
+/// How many remote branches an unknown gitref error lists
+const LISTED_BRANCHES: usize = 8;
+
+/// The edit distance between `a` and `b`, in characters
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The refs of `gitref`'s name on any remote, and those a typo away from it, closest
+/// first
+fn near_matches<'a>(gitref: &str, names: &'a [String]) -> Vec<&'a str> {
+    let name = gitref.rsplit('/').next().unwrap_or(gitref);
+    let limit = (gitref.chars().count() / 3).max(2);
+    let mut matches: Vec<_> = names
+        .iter()
+        .filter_map(|candidate| {
+            let same_name = candidate.rsplit('/').next() == Some(name);
+            let distance = if same_name {
+                0
+            } else {
+                distance(gitref, candidate)
+            };
+            (distance <= limit).then_some((distance, candidate.as_str()))
+        })
+        .collect();
+    matches.sort();
+    matches.into_iter().take(3).map(|(_, name)| name).collect()
+}
+
+/// Lines telling what `gitref` may have meant and which remote branches the checkout
+/// has, for errors about it not existing
+fn ref_hints(repo: &Repository, gitref: &str) -> String {
+    let mut names = Vec::new();
+    let mut remote_branches = Vec::new();
+    for reference in repo.references().into_iter().flatten().flatten() {
+        let Some(name) = reference.shorthand() else {
+            continue;
+        };
+        if name.ends_with("/HEAD") || name == gitref {
+            continue;
+        }
+        if reference.is_remote() {
+            remote_branches.push(name.to_string());
+        }
+        names.push(name.to_string());
+    }
+    let mut hints = String::new();
+    let matches = near_matches(gitref, &names);
+    if !matches.is_empty() {
+        let quoted: Vec<_> = matches.iter().map(|name| format!("'{}'", name)).collect();
+        hints.push_str(&format!("\ndid you mean {}?", quoted.join(" or ")));
+    }
+    remote_branches.sort();
+    if remote_branches.is_empty() {
+        hints.push_str("\nthe checkout has no remote-tracking branches");
+    } else {
+        let listed = remote_branches.len().min(LISTED_BRANCHES);
+        hints.push_str(&format!(
+            "\nremote branches: {}",
+            remote_branches[..listed].join(", ")
+        ));
+        if remote_branches.len() > listed {
+            hints.push_str(&format!(" and {} more", remote_branches.len() - listed));
+        }
+    }
+    hints
+}
+
+/// Resolves the `--gitref` to diff against. When it doesn't exist, falls back to the
+/// empty tree on an unborn branch, to the commit a rebase in progress is onto, or to
+/// `<remote>/<gitref>` on a detached HEAD; otherwise fails with a [`CheckoutError`]
+/// explaining the checkout's state
+pub fn resolve<'a>(repo: &'a Repository, gitref: &str, remote: &str) -> Result<Object<'a>> {
+    let err = match repo.revparse_single(gitref) {
+        Ok(object) => return Ok(object),
+        Err(err) => err,
+    };
+    let unborn = matches!(repo.head(), Err(head) if head.code() == ErrorCode::UnbornBranch);
+    if unborn {
+        warn!(
+            "'{}' doesn't exist and HEAD has no commits yet, so every line counts as changed",
+            gitref
+        );
+        let empty = repo.treebuilder(None)?.write()?;
+        return Ok(repo.find_object(empty, Some(ObjectType::Tree))?);
+    }
+    if is_rebasing(repo.state()) {
+        if let Some(onto) = rebase_onto(repo) {
+            warn!(
+                "'{}' doesn't exist, diffing against {} which the rebase in progress is onto",
+                gitref, onto
+            );
+            return Ok(repo.find_object(onto, None)?);
+        }
+        return Err(checkout_error(format!(
+            "'{}' doesn't exist and a rebase is in progress; finish it with \
+             `git rebase --continue` or pass an existing --gitref",
+            gitref
+        )));
+    }
+    if repo.head_detached().unwrap_or(false) {
+        let tracking = format!("{}/{}", remote, gitref);
+        if let Ok(object) = repo.revparse_single(&tracking) {
+            warn!("'{}' doesn't exist, diffing against {}", gitref, tracking);
+            return Ok(object);
+        }
+        return Err(checkout_error(format!(
+            "'{gitref}' doesn't exist in this checkout; HEAD is detached, as in CI checkouts \
+             that fetch only the commit under test. Fetch the base with \
+             `git fetch {remote} {gitref}` and pass --gitref {remote}/{gitref}, \
+             or use --gitref auto{hints}",
+            gitref = gitref,
+            remote = remote,
+            hints = ref_hints(repo, gitref)
+        )));
+    }
+    Err(checkout_error(format!(
+        "Unable to parse gitref '{gitref}': {message}{hints}\n\
+         fetch a base the checkout lacks with `git fetch {remote} <branch>`, or diff \
+         against a remote branch without fetching it with --base-url and --base-ref",
+        gitref = gitref,
+        message = err.message(),
+        hints = ref_hints(repo, gitref),
+        remote = remote
+    )))
+}
+
+/// Fails unless the repository has a working tree to diff against
+pub fn require_workdir(repo: &Repository) -> Result<()> {
+    if repo.is_bare() {
+        return Err(checkout_error(
+            "The repository is bare, so there is no working tree to diff; run in a checkout, \
+             or diff commits with --range or --per-commit"
+                .to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fixtures::TempDir;
+    use crate::state::{distance, resolve, CheckoutError};
+    use git2::{Repository, RepositoryInitOptions, Signature};
+
+    #[test]
+    fn test_resolve() {
+        let dir = TempDir::new("state").unwrap();
+        let repo =
+            Repository::init_opts(&dir, RepositoryInitOptions::new().initial_head("work")).unwrap();
+
+        // Unborn: the empty tree
+        let object = resolve(&repo, "master", "origin").unwrap();
+        assert_eq!(object.peel_to_tree().unwrap().len(), 0);
+
+        let signature = Signature::now("a", "a@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let commit = repo
+            .commit(Some("HEAD"), &signature, &signature, "base", &tree, &[])
+            .unwrap();
+        let err = resolve(&repo, "master", "origin").unwrap_err();
+        assert!(err.downcast_ref::<CheckoutError>().is_some());
+        assert!(err
+            .to_string()
+            .starts_with("Unable to parse gitref 'master'"));
+
+        assert!(err
+            .to_string()
+            .contains("the checkout has no remote-tracking branches"));
+        repo.branch("main", &repo.find_commit(commit).unwrap(), false)
+            .unwrap();
+        repo.reference("refs/remotes/origin/main", commit, false, "")
+            .unwrap();
+        let err = resolve(&repo, "mian", "origin").unwrap_err().to_string();
+        assert!(err.contains("did you mean 'main'?"), "{}", err);
+        assert!(err.contains("remote branches: origin/main\n"), "{}", err);
+        let err = resolve(&repo, "upstream/main", "origin").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("did you mean 'main' or 'origin/main'?"));
+
+        // Detached: the remote-tracking branch, or advice on fetching it
+        repo.set_head_detached(commit).unwrap();
+        let err = resolve(&repo, "master", "origin").unwrap_err();
+        assert!(err.to_string().contains("git fetch origin master"));
+        repo.reference("refs/remotes/origin/master", commit, false, "")
+            .unwrap();
+        assert_eq!(resolve(&repo, "master", "origin").unwrap().id(), commit);
+    }
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(distance("main", "main"), 0);
+        assert_eq!(distance("mian", "main"), 2);
+        assert_eq!(distance("master", "main"), 4);
+        assert_eq!(distance("", "dev"), 3);
+    }
+}
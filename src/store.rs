@@ -0,0 +1,302 @@
+//! A SQLite history of the findings each run reported, written with `--store` and
+//! summarized with `diff-format query`, for trends without a metrics service.
+
+use crate::codeowners::CodeOwners;
+use crate::diagnostic::Diagnostic;
+use crate::fingerprint::finding_fingerprint;
+use crate::run::RunInfo;
+use anyhow::{bail, Context, Result};
+use clap::{Args, ValueEnum};
+use git2::Repository;
+use rusqlite::{params, Connection};
+use std::path::{Path, PathBuf};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    run_id TEXT,
+    branch TEXT,
+    sha TEXT,
+    timestamp INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS findings (
+    run INTEGER NOT NULL REFERENCES runs(id),
+    path TEXT NOT NULL,
+    line INTEGER,
+    severity TEXT NOT NULL,
+    rule TEXT,
+    tool TEXT,
+    message TEXT NOT NULL,
+    team TEXT,
+    fingerprint TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS findings_run ON findings(run);
+";
+
+/// Findings per week they were first stored in, so one reported every week counts once
+const WEEKS: &str = "
+SELECT strftime('%Y-W%W', first, 'unixepoch') AS week, COUNT(*) FROM (
+    SELECT MIN(runs.timestamp) AS first FROM findings
+    JOIN runs ON runs.id = findings.run GROUP BY fingerprint
+) WHERE first >= ?1 GROUP BY week ORDER BY week
+";
+
+fn grouped(column: &str) -> String {
+    format!(
+        "SELECT COALESCE({}, ''), COUNT(DISTINCT fingerprint) AS count FROM findings
+         JOIN runs ON runs.id = findings.run WHERE runs.timestamp >= ?1
+         GROUP BY 1 ORDER BY count DESC, 1",
+        column
+    )
+}
+
+/// What a run was checking, recorded with its findings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Run {
+    /// The ULID or `--run-id` of the run
+    pub id: String,
+    pub branch: Option<String>,
+    pub sha: Option<String>,
+    /// Seconds since the Unix epoch
+    pub timestamp: i64,
+}
+
+impl Run {
+    /// The checked out branch and commit of `repo`, if any, as of `info`
+    pub fn current(info: &RunInfo, repo: Option<&Repository>) -> Self {
+        let head = repo.and_then(|repo| repo.head().ok());
+        Run {
+            id: info.id.clone(),
+            branch: head
+                .as_ref()
+                .filter(|head| head.is_branch())
+                .and_then(|head| head.shorthand().map(str::to_string)),
+            sha: head
+                .as_ref()
+                .and_then(|head| head.target())
+                .map(|id| id.to_string()),
+            timestamp: info.started,
+        }
+    }
+}
+
+/// What `query` counts findings by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GroupBy {
+    /// The week a finding was first stored, as `YYYY-Www`
+    Week,
+    Rule,
+    /// The first CODEOWNERS owner of the file
+    Team,
+    Tool,
+    File,
+}
+
+#[derive(Args, Debug)]
+pub struct QueryArgs {
+    /// The database written by --store
+    #[arg(long, value_name = "PATH")]
+    store: PathBuf,
+
+    /// What to count findings by
+    #[arg(long, value_enum, default_value = "week")]
+    by: GroupBy,
+
+    /// Only count findings stored on or after this date, as YYYY-MM-DD
+    #[arg(long, value_name = "DATE")]
+    since: Option<String>,
+}
+
+impl QueryArgs {
+    /// Prints `key<TAB>count` for each group
+    pub fn run(&self) -> Result<()> {
+        if !self.store.exists() {
+            bail!("No store at {}", self.store.display());
+        }
+        let store = Store::open(&self.store)?;
+        for (key, count) in store.count(self.by, self.since.as_deref())? {
+            println!("{}\t{}", key, count);
+        }
+        Ok(())
+    }
+}
+
+pub struct Store {
+    connection: Connection,
+}
+
+impl Store {
+    /// Opens the database at `path`, creating it and its tables as needed
+    pub fn open(path: &Path) -> Result<Self> {
+        let connection = Connection::open(path)
+            .with_context(|| format!("Unable to open store {}", path.display()))?;
+        connection
+            .execute_batch(SCHEMA)
+            .with_context(|| format!("Unable to create tables in {}", path.display()))?;
+        // Stores from before run IDs were recorded
+        let has_run_id: bool = connection.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('runs') WHERE name = 'run_id'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_run_id {
+            connection.execute("ALTER TABLE runs ADD COLUMN run_id TEXT", [])?;
+        }
+        Ok(Store { connection })
+    }
+
+    /// Appends a run and its `diagnostics`, attributing them to the owners of their files
+    pub fn record(
+        &mut self,
+        run: &Run,
+        diagnostics: &[Diagnostic],
+        owners: &CodeOwners,
+    ) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+        transaction.execute(
+            "INSERT INTO runs (run_id, branch, sha, timestamp) VALUES (?1, ?2, ?3, ?4)",
+            params![run.id, run.branch, run.sha, run.timestamp],
+        )?;
+        let id = transaction.last_insert_rowid();
+        {
+            let mut insert = transaction.prepare(
+                "INSERT INTO findings \
+                 (run, path, line, severity, rule, tool, message, team, fingerprint) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            )?;
+            for diagnostic in diagnostics {
+                insert.execute(params![
+                    id,
+                    diagnostic.path,
+                    diagnostic.line,
+                    diagnostic.effective_severity().as_str(),
+                    diagnostic.rule,
+                    diagnostic.tool,
+                    diagnostic.text(),
+                    owners.owners(&diagnostic.path).first(),
+                    finding_fingerprint(
+                        &diagnostic.path,
+                        diagnostic.rule.as_deref(),
+                        diagnostic.text()
+                    ),
+                ])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Distinct findings per group, so reruns on the same change count once. Weeks are
+    /// in order, other groups from the most findings down
+    pub fn count(&self, by: GroupBy, since: Option<&str>) -> Result<Vec<(String, i64)>> {
+        let since = match since {
+            Some(date) => self
+                .connection
+                .query_row("SELECT unixepoch(?1)", [date], |row| {
+                    row.get::<_, Option<i64>>(0)
+                })?
+                .with_context(|| format!("Invalid date '{}', expected YYYY-MM-DD", date))?,
+            None => i64::MIN,
+        };
+        self.count_since(by, since)
+    }
+
+    /// Like [`Store::count`] for the runs from `since`, in seconds since the Unix epoch
+    pub fn count_since(&self, by: GroupBy, since: i64) -> Result<Vec<(String, i64)>> {
+        let query = match by {
+            GroupBy::Week => WEEKS.to_string(),
+            GroupBy::Rule => grouped("rule"),
+            GroupBy::Team => grouped("team"),
+            GroupBy::Tool => grouped("tool"),
+            GroupBy::File => grouped("path"),
+        };
+        let mut statement = self.connection.prepare(&query)?;
+        let rows = statement.query_map([since], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// How many runs were stored from `since`, and the dates of the first and last
+    pub fn runs_since(&self, since: i64) -> Result<(i64, Option<(String, String)>)> {
+        let (runs, first, last) = self.connection.query_row(
+            "SELECT COUNT(*), date(MIN(timestamp), 'unixepoch'), date(MAX(timestamp), 'unixepoch')
+             FROM runs WHERE timestamp >= ?1",
+            [since],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                ))
+            },
+        )?;
+        Ok((runs, first.zip(last)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::codeowners::CodeOwners;
+    use crate::diagnostic::Diagnostic;
+    use crate::fixtures::TempDir;
+    use crate::store::{GroupBy, Run, Store};
+
+    fn finding(path: &str, rule: &str) -> Diagnostic {
+        let mut diagnostic = Diagnostic::new(path, Some(1));
+        diagnostic.rule = Some(rule.to_string());
+        diagnostic.message = Some(format!("{} issue", rule));
+        diagnostic
+    }
+
+    #[test]
+    fn test_store() {
+        let dir = TempDir::new("store").unwrap();
+        let path = dir.join("findings.db");
+        let owners = CodeOwners::parse("*.py @org/py\n").unwrap();
+        let run = |timestamp| Run {
+            id: format!("run-{}", timestamp),
+            branch: Some("feature".to_string()),
+            sha: None,
+            timestamp,
+        };
+        // 2026-01-05 and 2026-01-12, both Mondays
+        let mut store = Store::open(&path).unwrap();
+        store
+            .record(
+                &run(1_767_571_200),
+                &[finding("a.py", "E1"), finding("a.rs", "E2")],
+                &owners,
+            )
+            .unwrap();
+        drop(store);
+        let mut store = Store::open(&path).unwrap();
+        store
+            .record(
+                &run(1_768_176_000),
+                &[finding("a.py", "E1"), finding("b.py", "E1")],
+                &owners,
+            )
+            .unwrap();
+
+        let count = |by, since| store.count(by, since).unwrap();
+        let pairs = |pairs: &[(&str, i64)]| -> Vec<(String, i64)> {
+            pairs
+                .iter()
+                .map(|&(key, count)| (key.to_string(), count))
+                .collect()
+        };
+        assert_eq!(
+            count(GroupBy::Week, None),
+            pairs(&[("2026-W01", 2), ("2026-W02", 1)])
+        );
+        assert_eq!(count(GroupBy::Rule, None), pairs(&[("E1", 2), ("E2", 1)]));
+        assert_eq!(
+            count(GroupBy::Team, None),
+            pairs(&[("@org/py", 2), ("", 1)])
+        );
+        assert_eq!(
+            count(GroupBy::File, Some("2026-01-10")),
+            pairs(&[("a.py", 1), ("b.py", 1)])
+        );
+        assert!(store.count(GroupBy::Rule, Some("January")).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+//! Changed lines in submodules and in the nested repositories given with `--repo`,
+//! keyed by their path in the superproject, so findings in their files can match.
+
+use crate::hunks::{generate_hunkmap, get_diff};
+use crate::{paths, state, HunkMap};
+use anyhow::{Context, Result};
+use git2::{ObjectType, Oid, Repository, Tree};
+use log::{debug, warn};
+use std::path::Path;
+
+fn prefixed(prefix: &str, hunks: HunkMap) -> HunkMap {
+    hunks
+        .into_iter()
+        .map(|(path, ranges)| (format!("{}/{}", prefix, path), ranges))
+        .collect()
+}
+
+/// The commit a tree records for the submodule at `path`
+fn gitlink(tree: &Tree, path: &Path) -> Option<Oid> {
+    let entry = tree.get_path(path).ok()?;
+    (entry.kind() == Some(ObjectType::Commit)).then(|| entry.id())
+}
+
+fn commit_tree<'r>(repo: &'r Repository, id: Option<Oid>) -> Result<Tree<'r>> {
+    match id {
+        Some(id) => Ok(repo.find_commit(id)?.tree()?),
+        // Added since the base, so everything in it is new
+        None => Ok(repo.find_tree(repo.treebuilder(None)?.write()?)?),
+    }
+}
+
+/// Hunks of every checked out submodule of `repo`, recursively, between the commits
+/// `base` and `head` record for it, or the submodule's workdir when `head` is None
+pub fn submodule_hunks(
+    repo: &Repository,
+    base: &Tree,
+    head: Option<&Tree>,
+    ignore_eol: bool,
+) -> Result<HunkMap> {
+    let mut hunkmap = HunkMap::new();
+    for submodule in repo.submodules()? {
+        let path = submodule.path();
+        let name = paths::normalize_path(&path.to_string_lossy());
+        let sub = match submodule.open() {
+            Ok(sub) => sub,
+            Err(_) => {
+                debug!("Submodule {} is not checked out", name);
+                continue;
+            }
+        };
+        let head_id = match head.map(|head| gitlink(head, path)) {
+            Some(None) => continue,
+            head_id => head_id.flatten(),
+        };
+        let trees = commit_tree(&sub, gitlink(base, path)).and_then(|base| {
+            let head = head_id.map(|id| commit_tree(&sub, Some(id))).transpose()?;
+            Ok((base, head))
+        });
+        let (sub_base, sub_head) = match trees {
+            Ok(trees) => trees,
+            Err(err) => {
+                warn!("Skipping submodule {}: {:#}", name, err);
+                continue;
+            }
+        };
+        let diff = get_diff(&sub, &sub_base, sub_head.as_ref(), None, ignore_eol)?;
+        hunkmap.extend(prefixed(&name, generate_hunkmap(&diff)?));
+        let nested = submodule_hunks(&sub, &sub_base, sub_head.as_ref(), ignore_eol)?;
+        hunkmap.extend(prefixed(&name, nested));
+    }
+    Ok(hunkmap)
+}
+
+/// Hunks of the workdirs of the repositories at `repos`, relative to `workdir`, each
+/// against its own `gitref`, with their submodules
+pub fn nested_repo_hunks(
+    workdir: &Path,
+    repos: &[impl AsRef<Path>],
+    gitref: &str,
+    remote: &str,
+    ignore_eol: bool,
+) -> Result<HunkMap> {
+    let mut hunkmap = HunkMap::new();
+    for dir in repos {
+        let dir = dir.as_ref();
+        let name = paths::normalize_path(&dir.to_string_lossy());
+        let repo = Repository::open(workdir.join(dir))
+            .with_context(|| format!("Can't open repository {}", name))?;
+        let tree = state::resolve(&repo, gitref, remote)?
+            .peel_to_tree()
+            .with_context(|| format!("Gitref is not a tree in {}", name))?;
+        let diff = get_diff(&repo, &tree, None, None, ignore_eol)?;
+        hunkmap.extend(prefixed(&name, generate_hunkmap(&diff)?));
+        let nested = submodule_hunks(&repo, &tree, None, ignore_eol)?;
+        hunkmap.extend(prefixed(&name, nested));
+    }
+    Ok(hunkmap)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fixtures::TempDir;
+    use crate::submodules::{nested_repo_hunks, submodule_hunks};
+    use git2::{IndexEntry, IndexTime, Oid, Repository, Signature};
+    use std::fs;
+    use std::path::Path;
+
+    fn commit(repo: &Repository, files: &[&str], gitlinks: &[(&str, Oid)]) -> Oid {
+        let mut index = repo.index().unwrap();
+        for file in files {
+            index.add_path(Path::new(file)).unwrap();
+        }
+        for (path, id) in gitlinks {
+            index
+                .add(&IndexEntry {
+                    ctime: IndexTime::new(0, 0),
+                    mtime: IndexTime::new(0, 0),
+                    dev: 0,
+                    ino: 0,
+                    mode: 0o160000,
+                    uid: 0,
+                    gid: 0,
+                    file_size: 0,
+                    id: *id,
+                    flags: 0,
+                    flags_extended: 0,
+                    path: path.as_bytes().to_vec(),
+                })
+                .unwrap();
+        }
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("a", "a@example.com").unwrap();
+        let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, "c", &tree, &parents)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_submodule_hunks() {
+        let dir = TempDir::new("sub").unwrap();
+        let superproject = Repository::init(&dir).unwrap();
+        let lib = Repository::init(dir.join("lib")).unwrap();
+        fs::write(dir.join("lib/a.py"), "a\nb\nc\n").unwrap();
+        let recorded = commit(&lib, &["a.py"], &[]);
+        fs::write(
+            dir.join(".gitmodules"),
+            "[submodule \"lib\"]\n\tpath = lib\n\turl = ./lib\n",
+        )
+        .unwrap();
+        commit(&superproject, &[".gitmodules"], &[("lib", recorded)]);
+        let base = superproject.head().unwrap().peel_to_tree().unwrap();
+
+        // Committed inside the submodule and still uncommitted both count
+        fs::write(dir.join("lib/a.py"), "a\nB\nc\n").unwrap();
+        commit(&lib, &["a.py"], &[]);
+        fs::write(dir.join("lib/a.py"), "a\nB\nc\nd\n").unwrap();
+        let hunks = submodule_hunks(&superproject, &base, None, false).unwrap();
+        assert_eq!(hunks["lib/a.py"], [(2, 3), (4, 5)]);
+        assert_eq!(hunks.len(), 1);
+
+        let hunks = nested_repo_hunks(&dir, &["lib"], "HEAD", "origin", false).unwrap();
+        assert_eq!(hunks["lib/a.py"], [(4, 5)]);
+    }
+}
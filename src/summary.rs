@@ -0,0 +1,271 @@
+//! Per-file counts of the findings shown and of those dropped as pre-existing, so the
+//! debt outside the diff stays visible.
+
+use crate::diagnostic::Diagnostic;
+use crate::i18n::{self, Lang, Message};
+use crate::run::{self, RunInfo};
+use crate::score::Score;
+use crate::truncate::Truncation;
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct FileStats {
+    /// Findings on the change, reported
+    pub shown: usize,
+    /// Findings outside the change, dropped
+    pub pre_existing: usize,
+    /// Findings on the change, suppressed by a waiver
+    pub waived: usize,
+    /// Findings on lines past the end of the file, see `--on-stale-line`
+    pub stale: usize,
+}
+
+impl FileStats {
+    fn add(&mut self, other: &FileStats) {
+        self.shown += other.shown;
+        self.pre_existing += other.pre_existing;
+        self.waived += other.waived;
+        self.stale += other.stale;
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Summary {
+    run: Option<RunInfo>,
+    files: BTreeMap<String, FileStats>,
+    truncated: Option<Truncation>,
+    /// Files over `--max-files` or `--max-hunks-per-file`, matched as a whole
+    degraded: usize,
+    score: Option<Score>,
+}
+
+impl Summary {
+    /// A summary headed by the ID and start of `run`
+    pub fn new(run: RunInfo) -> Self {
+        Summary {
+            run: Some(run),
+            files: BTreeMap::new(),
+            truncated: None,
+            degraded: 0,
+            score: None,
+        }
+    }
+
+    fn file(&mut self, diagnostic: &Diagnostic) -> &mut FileStats {
+        // Looked up before inserting, to not copy the path for each of millions of findings
+        if !self.files.contains_key(&diagnostic.path) {
+            self.files
+                .insert(diagnostic.path.clone(), FileStats::default());
+        }
+        self.files.get_mut(&diagnostic.path).unwrap()
+    }
+
+    pub fn pre_existing(&mut self, diagnostic: &Diagnostic) {
+        self.file(diagnostic).pre_existing += 1;
+    }
+
+    pub fn waived(&mut self, diagnostic: &Diagnostic) {
+        self.file(diagnostic).waived += 1;
+    }
+
+    pub fn stale(&mut self, diagnostic: &Diagnostic) {
+        self.file(diagnostic).stale += 1;
+    }
+
+    /// Records that only part of the lint output was filtered, see `--max-input-lines`
+    pub fn truncated(&mut self, truncation: Option<Truncation>) {
+        self.truncated = truncation;
+    }
+
+    /// Records how many files were matched as a whole rather than by their hunks, see
+    /// `--max-files`
+    pub fn degraded(&mut self, files: usize) {
+        self.degraded = files;
+    }
+
+    /// Records the score of the findings against `--max-score`
+    pub fn scored(&mut self, score: Option<Score>) {
+        self.score = score;
+    }
+
+    /// Counts the findings that are reported, once all of them are known
+    pub fn shown(&mut self, matched: &[Diagnostic]) {
+        for diagnostic in matched {
+            self.file(diagnostic).shown += 1;
+        }
+    }
+
+    pub fn total(&self) -> FileStats {
+        let mut total = FileStats::default();
+        for stats in self.files.values() {
+            total.add(stats);
+        }
+        total
+    }
+
+    /// A table with a row per file and a total, in `lang`
+    pub fn render_text(&self, lang: Lang) -> String {
+        let (file, total) = (lang.text(Message::File), lang.text(Message::Total));
+        let width = self
+            .files
+            .keys()
+            .map(|path| i18n::display_width(path))
+            .chain([file, total].map(i18n::display_width))
+            .max()
+            .unwrap_or_default();
+        let columns = [
+            Message::Shown,
+            Message::PreExisting,
+            Message::Waived,
+            Message::Stale,
+        ]
+        .map(|message| lang.text(message));
+        let line = |name: &str, cells: [String; 4]| {
+            let mut line = i18n::pad(name, width, false);
+            for (cell, column) in cells.iter().zip(columns) {
+                line += "  ";
+                line += &i18n::pad(cell, i18n::display_width(column), true);
+            }
+            line + "\n"
+        };
+        let row = |name: &str, stats: &FileStats| {
+            let counts = [stats.shown, stats.pre_existing, stats.waived, stats.stale];
+            line(name, counts.map(|count| count.to_string()))
+        };
+        let mut text = match &self.run {
+            Some(info) => {
+                let started = run::rfc3339(info.started);
+                lang.format(Message::RunStarted, &[&info.id, &started]) + "\n"
+            }
+            None => String::new(),
+        };
+        if let Some(truncation) = &self.truncated {
+            text += &format!("{}\n", truncation.describe(lang));
+        }
+        if self.degraded > 0 {
+            text += &lang.format(Message::Degraded, &[&self.degraded]);
+            text += "\n";
+        }
+        if let Some(score) = &self.score {
+            text += &lang.format(Message::Score, &[&score.value, &score.max]);
+            text += "\n";
+        }
+        text += &line(file, columns.map(str::to_string));
+        for (path, stats) in &self.files {
+            text.push_str(&row(path, stats));
+        }
+        text.push_str(&row(total, &self.total()));
+        text
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "run": self.run,
+            "files": self.files,
+            "total": self.total(),
+            "truncated": self.truncated,
+            "degraded": self.degraded,
+            "score": self.score,
+        })
+    }
+
+    pub fn render_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&self.to_json())? + "\n")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::Diagnostic;
+    use crate::i18n::Lang;
+    use crate::run::RunInfo;
+    use crate::score::Score;
+    use crate::summary::{FileStats, Summary};
+    use crate::truncate::{TruncateStrategy, Truncation};
+
+    #[test]
+    fn test_summary() {
+        let mut summary = Summary::default();
+        let at = |path: &str| Diagnostic::new(path, Some(1));
+        summary.pre_existing(&at("src/a.py"));
+        summary.pre_existing(&at("src/a.py"));
+        summary.waived(&at("b.py"));
+        summary.stale(&at("b.py"));
+        summary.shown(&[at("src/a.py"), at("b.py")]);
+
+        assert_eq!(
+            summary.total(),
+            FileStats {
+                shown: 2,
+                pre_existing: 2,
+                waived: 1,
+                stale: 1
+            }
+        );
+        assert_eq!(
+            summary.render_text(Lang::En),
+            "\
+file      shown  pre-existing  waived  stale
+b.py          1             0       1      1
+src/a.py      1             2       0      0
+total         2             2       1      1
+"
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&summary.render_json().unwrap()).unwrap();
+        assert_eq!(json["files"]["src/a.py"]["pre_existing"], 2);
+        assert_eq!(json["total"]["waived"], 1);
+
+        let mut summary = Summary::new(RunInfo {
+            id: "01ARYZ6S410000000000000000".to_string(),
+            started: 1_767_571_200,
+        });
+        summary.shown(&[at("b.py")]);
+        assert!(summary
+            .render_text(Lang::En)
+            .starts_with("run 01ARYZ6S410000000000000000 started 2026-01-05T00:00:00Z\nfile "));
+        let json: serde_json::Value =
+            serde_json::from_str(&summary.render_json().unwrap()).unwrap();
+        assert_eq!(json["run"]["started"], "2026-01-05T00:00:00Z");
+        assert_eq!(json["truncated"], serde_json::Value::Null);
+
+        summary.truncated(Some(Truncation {
+            strategy: TruncateStrategy::Tail,
+            read: 10,
+            kept: 4,
+        }));
+        assert!(summary
+            .render_text(Lang::En)
+            .contains("Z\ninput truncated to the last 4 of 10 lines\nfile "));
+        assert!(summary.render_text(Lang::Ja).ends_with(
+            "\
+ファイル  表示  既存  免除  範囲外
+b.py         1     0     0       0
+合計         1     0     0       0
+"
+        ));
+        let json: serde_json::Value =
+            serde_json::from_str(&summary.render_json().unwrap()).unwrap();
+        assert_eq!(json["truncated"]["strategy"], "tail");
+        assert_eq!(json["truncated"]["read"], 10);
+        assert_eq!(json["degraded"], 0);
+
+        summary.degraded(3);
+        assert!(summary.render_text(Lang::En).contains(
+            " lines\n3 file(s) over --max-files or --max-hunks-per-file matched as a whole\nfile "
+        ));
+        let json: serde_json::Value =
+            serde_json::from_str(&summary.render_json().unwrap()).unwrap();
+        assert_eq!(json["degraded"], 3);
+
+        summary.scored(Some(Score { value: 7, max: 5 }));
+        assert!(summary
+            .render_text(Lang::En)
+            .contains(" whole\nscore 7, at most 5\nfile "));
+        let json: serde_json::Value =
+            serde_json::from_str(&summary.render_json().unwrap()).unwrap();
+        assert_eq!(json["score"]["value"], 7);
+    }
+}
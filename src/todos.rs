@@ -0,0 +1,179 @@
+//! The `todos` gate: markers such as TODO or FIXME on changed lines, failing unless they
+//! reference a ticket when tickets are required.
+
+use crate::bounds::{Deletions, HunkBounds};
+use crate::{is_number_in_sorted_ranges, HunkMap, HunkRange};
+use anyhow::{Context, Result};
+use clap::Args;
+use log::debug;
+use regex::Regex;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+fn default_markers() -> Vec<String> {
+    ["TODO", "FIXME", "HACK"].map(String::from).to_vec()
+}
+
+/// Options of the gate, as flags or a `[[gate]]` table of `kind = "todos"`
+#[derive(Args, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TodoArgs {
+    /// Markers to look for, matched as whole words
+    #[arg(long, value_delimiter = ',', default_value = "TODO,FIXME,HACK")]
+    #[serde(default = "default_markers")]
+    markers: Vec<String>,
+
+    /// Allow markers that reference a ticket matching this regex in parentheses, e.g.
+    /// `[A-Z]+-\d+` for `TODO(JIRA-123)`; without it every marker fails
+    #[arg(long, value_name = "REGEX")]
+    ticket: Option<String>,
+}
+
+/// A marker introduced on a changed line
+#[derive(Debug, PartialEq, Eq)]
+pub struct Todo {
+    pub line: u32,
+    pub marker: String,
+}
+
+pub struct Scanner {
+    marker: Regex,
+    ticket: Option<Regex>,
+}
+
+impl Scanner {
+    pub fn new(args: &TodoArgs) -> Result<Self> {
+        let markers: Vec<_> = args
+            .markers
+            .iter()
+            .map(|marker| regex::escape(marker))
+            .collect();
+        let marker = Regex::new(&format!(
+            r"\b(?P<marker>{})\b(?:\((?P<ticket>[^)]*)\))?",
+            markers.join("|")
+        ))?;
+        let ticket = args
+            .ticket
+            .as_deref()
+            .map(|ticket| Regex::new(&format!("^(?:{})$", ticket)))
+            .transpose()
+            .context("Invalid --ticket regex")?;
+        Ok(Scanner { marker, ticket })
+    }
+
+    /// Markers without an allowed ticket on the lines of `source` within `hunk_ranges`
+    pub fn scan(&self, source: &str, hunk_ranges: &[HunkRange]) -> Vec<Todo> {
+        let mut todos = Vec::new();
+        for (line, text) in (1..).zip(source.lines()) {
+            if !is_number_in_sorted_ranges(hunk_ranges, line) {
+                continue;
+            }
+            for captures in self.marker.captures_iter(text) {
+                let referenced = match (&self.ticket, captures.name("ticket")) {
+                    (Some(ticket), Some(reference)) => ticket.is_match(reference.as_str().trim()),
+                    _ => false,
+                };
+                if !referenced {
+                    todos.push(Todo {
+                        line,
+                        marker: captures["marker"].to_string(),
+                    });
+                }
+            }
+        }
+        todos
+    }
+}
+
+/// Prints the markers introduced on the added lines of the raw, exclusive-end
+/// `file_hunks`, returning how many there were
+pub fn check(args: &TodoArgs, workdir: &Path, file_hunks: &HunkMap) -> Result<usize> {
+    let scanner = Scanner::new(args)?;
+    let mut found = 0;
+    let mut paths: Vec<_> = file_hunks.keys().collect();
+    paths.sort();
+    for path in paths {
+        let source = match fs::read(workdir.join(path)) {
+            Ok(bytes) => match String::from_utf8(bytes) {
+                Ok(source) => source,
+                Err(_) => {
+                    debug!("Skipping '{}', it is not UTF-8 text", path);
+                    continue;
+                }
+            },
+            Err(err) => return Err(err).with_context(|| format!("Unable to read '{}'", path)),
+        };
+        let added: Vec<_> = file_hunks[path]
+            .iter()
+            .filter_map(|&range| HunkBounds::Exclusive.resolve(Deletions::None, range))
+            .collect();
+        for todo in scanner.scan(&source, &added) {
+            match &args.ticket {
+                Some(_) => println!(
+                    "{}:{}: {} without a ticket, write it as {}(TICKET)",
+                    path, todo.line, todo.marker, todo.marker
+                ),
+                None => println!("{}:{}: {} introduced", path, todo.line, todo.marker),
+            }
+            found += 1;
+        }
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fixtures::TempDir;
+    use crate::todos::{check, Scanner, Todo, TodoArgs};
+    use std::collections::HashMap;
+    use std::fs;
+
+    #[test]
+    fn test_scan() {
+        let source = "# TODO old\nx = 1  # TODO(JIRA-12) later\n# FIXME(soon) and HACK\n# TODOS\n";
+        let todo = |line, marker: &str| Todo {
+            line,
+            marker: marker.to_string(),
+        };
+        let args = TodoArgs {
+            markers: vec!["TODO".to_string(), "FIXME".to_string(), "HACK".to_string()],
+            ticket: None,
+        };
+        let scanner = Scanner::new(&args).unwrap();
+        assert_eq!(
+            scanner.scan(source, &[(2, 4)]),
+            [todo(2, "TODO"), todo(3, "FIXME"), todo(3, "HACK")]
+        );
+
+        let args = TodoArgs {
+            ticket: Some(r"[A-Z]+-\d+".to_string()),
+            ..args
+        };
+        let scanner = Scanner::new(&args).unwrap();
+        assert_eq!(
+            scanner.scan(source, &[(1, 4)]),
+            [todo(1, "TODO"), todo(3, "FIXME"), todo(3, "HACK")]
+        );
+    }
+
+    #[test]
+    fn test_check_added_lines() {
+        let dir = TempDir::new("todos").unwrap();
+        fs::write(dir.join("a.py"), "x = 1\n# TODO old\n# FIXME new\n").unwrap();
+        let args = TodoArgs {
+            markers: vec!["TODO".to_string(), "FIXME".to_string()],
+            ticket: None,
+        };
+        // Line 1 was edited: the old marker right after it wasn't introduced
+        let file_hunks: HashMap<_, _> = vec![("a.py".to_string(), vec![(1, 2)])]
+            .into_iter()
+            .collect();
+        assert_eq!(check(&args, dir.path(), &file_hunks).unwrap(), 0);
+        // A deletion before the last line adds nothing
+        let file_hunks: HashMap<_, _> = vec![("a.py".to_string(), vec![(2, 2), (3, 4)])]
+            .into_iter()
+            .collect();
+        assert_eq!(check(&args, dir.path(), &file_hunks).unwrap(), 1);
+    }
+}
@@ -0,0 +1,135 @@
+//! `--emit-trends`: the new findings of a run totalled by rule, keyed by the commits
+//! and branch diffed, for dashboards to ingest as they are rather than re-deriving the
+//! totals from the findings. The schema only grows: fields are added but never renamed
+//! or removed, and [`SCHEMA`] is bumped should one change meaning.
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::run::RunInfo;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Version of the layout, as `schema` in the file
+pub const SCHEMA: u32 = 1;
+
+/// A side of the diff
+#[derive(Debug, Default, Serialize)]
+pub struct Revision {
+    /// The commit, HEAD's when the working tree or index was diffed
+    pub sha: Option<String>,
+    /// The gitref given for the base, or the branch checked out for the head
+    #[serde(rename = "ref")]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct RuleTotal {
+    pub rule: Option<String>,
+    pub tool: Option<String>,
+    pub count: usize,
+    /// Findings of each severity, counting those without one as errors
+    pub severity: BTreeMap<Severity, usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Trends<'a> {
+    schema: u32,
+    run: &'a RunInfo,
+    base: Revision,
+    head: Revision,
+    total: usize,
+    /// Sorted by rule, then tool
+    rules: Vec<RuleTotal>,
+}
+
+/// The totals of `diagnostics` by rule and tool
+pub fn rule_totals(diagnostics: &[Diagnostic]) -> Vec<RuleTotal> {
+    let mut totals: BTreeMap<(Option<&str>, Option<&str>), RuleTotal> = BTreeMap::new();
+    for diagnostic in diagnostics {
+        let key = (diagnostic.rule.as_deref(), diagnostic.tool.as_deref());
+        let total = totals.entry(key).or_insert_with(|| RuleTotal {
+            rule: diagnostic.rule.clone(),
+            tool: diagnostic.tool.clone(),
+            count: 0,
+            severity: BTreeMap::new(),
+        });
+        total.count += 1;
+        *total
+            .severity
+            .entry(diagnostic.effective_severity())
+            .or_insert(0) += 1;
+    }
+    totals.into_values().collect()
+}
+
+impl<'a> Trends<'a> {
+    pub fn new(run: &'a RunInfo, base: Revision, head: Revision, matched: &[Diagnostic]) -> Self {
+        Trends {
+            schema: SCHEMA,
+            run,
+            base,
+            head,
+            total: matched.len(),
+            rules: rule_totals(matched),
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut text = serde_json::to_string_pretty(self).unwrap();
+        text.push('\n');
+        text
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diagnostic::{Diagnostic, Severity};
+    use crate::run::RunInfo;
+    use crate::totals::{Revision, Trends};
+
+    #[test]
+    fn test_trends() {
+        let finding = |rule: Option<&str>, severity| {
+            let mut diagnostic = Diagnostic::new("a.py", Some(1));
+            diagnostic.rule = rule.map(str::to_string);
+            diagnostic.tool = Some("python".to_string());
+            diagnostic.severity = severity;
+            diagnostic
+        };
+        let matched = [
+            finding(Some("W1"), Some(Severity::Warning)),
+            finding(Some("E1"), None),
+            finding(None, Some(Severity::Info)),
+            finding(Some("W1"), Some(Severity::Error)),
+        ];
+        let run = RunInfo {
+            id: "job-7".to_string(),
+            started: 0,
+        };
+        let base = Revision {
+            sha: Some("abc".to_string()),
+            name: Some("origin/main".to_string()),
+        };
+        let trends = Trends::new(&run, base, Revision::default(), &matched);
+        let value: serde_json::Value = serde_json::from_str(&trends.render()).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "schema": 1,
+                "run": {"id": "job-7", "started": "1970-01-01T00:00:00Z"},
+                "base": {"sha": "abc", "ref": "origin/main"},
+                "head": {"sha": null, "ref": null},
+                "total": 4,
+                "rules": [
+                    {"rule": null, "tool": "python", "count": 1, "severity": {"info": 1}},
+                    {"rule": "E1", "tool": "python", "count": 1, "severity": {"error": 1}},
+                    {
+                        "rule": "W1",
+                        "tool": "python",
+                        "count": 2,
+                        "severity": {"error": 1, "warning": 1}
+                    },
+                ],
+            })
+        );
+    }
+}
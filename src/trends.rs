@@ -0,0 +1,277 @@
+//! A trend report over the `--store` database, as Markdown or HTML to publish on an
+//! internal page. It is built from the local database only and sends nothing anywhere.
+
+use crate::output::Destination;
+use crate::run;
+use crate::store::{GroupBy, Store};
+use anyhow::{bail, Result};
+use clap::{Args, ValueEnum};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Rows in the top rules and top directories tables
+const TOP: usize = 10;
+
+/// Width of the longest bar in the weekly chart
+const BAR: i64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+#[derive(Args, Debug)]
+pub struct ReportArgs {
+    /// The database written by --store
+    #[arg(long, value_name = "PATH")]
+    store: PathBuf,
+
+    /// Cover the runs of the last N weeks
+    #[arg(long, value_name = "N", default_value_t = 12)]
+    last: u32,
+
+    #[arg(long, value_enum, default_value = "markdown")]
+    format: ReportFormat,
+
+    /// Where to write the report, stdout, stderr or a file path
+    #[arg(long, value_name = "DEST", default_value = "stdout")]
+    out: Destination,
+}
+
+impl ReportArgs {
+    /// Whether the report goes to a file rather than a stream
+    pub fn writes_file(&self) -> bool {
+        self.out.path().is_some()
+    }
+
+    pub fn run(&self) -> Result<()> {
+        if !self.store.exists() {
+            bail!("No store at {}", self.store.display());
+        }
+        let store = Store::open(&self.store)?;
+        let since = run::now() - i64::from(self.last) * 7 * 86_400;
+        let trends = Trends::load(&store, since, self.last)?;
+        self.out.write(&match self.format {
+            ReportFormat::Markdown => trends.markdown(),
+            ReportFormat::Html => trends.html(),
+        })
+    }
+}
+
+/// A table of the report, with a count per row
+struct Section {
+    title: &'static str,
+    key: &'static str,
+    count: &'static str,
+    rows: Vec<(String, i64)>,
+    /// Whether rows get a bar scaled to the largest count
+    chart: bool,
+}
+
+pub struct Trends {
+    weeks: u32,
+    runs: i64,
+    /// Dates of the first and last run covered
+    period: Option<(String, String)>,
+    sections: Vec<Section>,
+}
+
+/// The directory of a stored path, `.` for the repository root
+fn directory(path: &str) -> &str {
+    path.rsplit_once('/')
+        .map_or(".", |(directory, _)| directory)
+}
+
+/// Counts per directory, from the most down. Fingerprints hash the path, so per-file
+/// counts of distinct findings add up
+fn directories(files: Vec<(String, i64)>) -> Vec<(String, i64)> {
+    let mut counts: HashMap<String, i64> = HashMap::new();
+    for (path, count) in &files {
+        *counts.entry(directory(path).to_string()).or_default() += count;
+    }
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+    counts
+}
+
+fn bar(count: i64, max: i64) -> String {
+    let width = (count * BAR + max - 1) / max.max(1);
+    "█".repeat(width as usize)
+}
+
+fn escape_markdown(text: &str) -> String {
+    text.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl Trends {
+    /// The runs stored from `since`, in seconds since the Unix epoch, spanning `weeks`
+    pub fn load(store: &Store, since: i64, weeks: u32) -> Result<Self> {
+        let (runs, period) = store.runs_since(since)?;
+        let mut rules = store.count_since(GroupBy::Rule, since)?;
+        rules.truncate(TOP);
+        let mut directories = directories(store.count_since(GroupBy::File, since)?);
+        directories.truncate(TOP);
+        Ok(Trends {
+            weeks,
+            runs,
+            period,
+            sections: vec![
+                Section {
+                    title: "New issues per week",
+                    key: "Week",
+                    count: "New issues",
+                    rows: store.count_since(GroupBy::Week, since)?,
+                    chart: true,
+                },
+                Section {
+                    title: "Top rules",
+                    key: "Rule",
+                    count: "Issues",
+                    rows: rules,
+                    chart: false,
+                },
+                Section {
+                    title: "Top directories",
+                    key: "Directory",
+                    count: "Issues",
+                    rows: directories,
+                    chart: false,
+                },
+            ],
+        })
+    }
+
+    fn summary(&self) -> String {
+        match &self.period {
+            Some((first, last)) => format!(
+                "{} run(s) from {} to {}, over the last {} week(s).",
+                self.runs, first, last, self.weeks
+            ),
+            None => format!("No runs stored in the last {} week(s).", self.weeks),
+        }
+    }
+
+    pub fn markdown(&self) -> String {
+        let mut markdown = format!("# Lint trends\n\n{}\n", self.summary());
+        for section in self
+            .sections
+            .iter()
+            .filter(|section| !section.rows.is_empty())
+        {
+            let max = section.rows.iter().map(|(_, count)| *count).max();
+            markdown += &format!("\n## {}\n\n", section.title);
+            if section.chart {
+                markdown += &format!(
+                    "| {} | {} | |\n| --- | ---: | --- |\n",
+                    section.key, section.count
+                );
+            } else {
+                markdown += &format!("| {} | {} |\n| --- | ---: |\n", section.key, section.count);
+            }
+            for (key, count) in &section.rows {
+                let key = if key.is_empty() { "(none)" } else { key };
+                markdown += &format!("| {} | {} |", escape_markdown(key), count);
+                if section.chart {
+                    markdown += &format!(" {} |", bar(*count, max.unwrap_or(1)));
+                }
+                markdown.push('\n');
+            }
+        }
+        markdown
+    }
+
+    pub fn html(&self) -> String {
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>Lint trends</title>\n</head>\n<body>\n<h1>Lint trends</h1>\n",
+        );
+        html += &format!("<p>{}</p>\n", escape_html(&self.summary()));
+        for section in self
+            .sections
+            .iter()
+            .filter(|section| !section.rows.is_empty())
+        {
+            let max = section.rows.iter().map(|(_, count)| *count).max();
+            html += &format!(
+                "<h2>{}</h2>\n<table>\n<tr><th>{}</th><th>{}</th>{}</tr>\n",
+                section.title,
+                section.key,
+                section.count,
+                if section.chart { "<th></th>" } else { "" }
+            );
+            for (key, count) in &section.rows {
+                let key = if key.is_empty() { "(none)" } else { key };
+                html += &format!("<tr><td>{}</td><td>{}</td>", escape_html(key), count);
+                if section.chart {
+                    html += &format!("<td>{}</td>", bar(*count, max.unwrap_or(1)));
+                }
+                html += "</tr>\n";
+            }
+            html += "</table>\n";
+        }
+        html + "</body>\n</html>\n"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::codeowners::CodeOwners;
+    use crate::diagnostic::Diagnostic;
+    use crate::fixtures::TempDir;
+    use crate::store::{Run, Store};
+    use crate::trends::Trends;
+
+    #[test]
+    fn test_trends() {
+        let dir = TempDir::new("trends").unwrap();
+        let path = dir.join("findings.db");
+        let mut store = Store::open(&path).unwrap();
+        let findings: Vec<_> = [("src/a.py", "E1"), ("src/b.py", "E1"), ("a|b/c.py", "W2")]
+            .iter()
+            .map(|&(path, rule)| {
+                let mut diagnostic = Diagnostic::new(path, Some(1));
+                diagnostic.rule = Some(rule.to_string());
+                diagnostic
+            })
+            .collect();
+        let run = |timestamp| Run {
+            id: format!("run-{}", timestamp),
+            branch: None,
+            sha: None,
+            timestamp,
+        };
+        // 2026-01-05, a Monday, and a week later
+        store
+            .record(&run(1_767_571_200), &findings[..2], &CodeOwners::default())
+            .unwrap();
+        store
+            .record(&run(1_768_176_000), &findings, &CodeOwners::default())
+            .unwrap();
+
+        let trends = Trends::load(&store, 0, 2).unwrap();
+        assert_eq!(
+            trends.markdown(),
+            "# Lint trends\n\n2 run(s) from 2026-01-05 to 2026-01-12, over the last 2 week(s).\n\
+             \n## New issues per week\n\n| Week | New issues | |\n| --- | ---: | --- |\n\
+             | 2026-W01 | 2 | ██████████████████████████████ |\n\
+             | 2026-W02 | 1 | ███████████████ |\n\
+             \n## Top rules\n\n| Rule | Issues |\n| --- | ---: |\n| E1 | 2 |\n| W2 | 1 |\n\
+             \n## Top directories\n\n| Directory | Issues |\n| --- | ---: |\n| src | 2 |\n| a\\|b | 1 |\n"
+        );
+        assert!(trends.html().contains("<tr><td>src</td><td>2</td></tr>\n"));
+
+        let empty = Trends::load(&store, 1_800_000_000, 4).unwrap();
+        assert_eq!(
+            empty.markdown(),
+            "# Lint trends\n\nNo runs stored in the last 4 week(s).\n"
+        );
+    }
+}
@@ -0,0 +1,259 @@
+//! `--max-input-lines`: caps the lint output filtered, so a runaway linter printing
+//! millions of duplicate lines doesn't dominate the run.
+//!
+//! Every line is still read, so the linter isn't blocked writing and the summary can say
+//! how many were dropped; only the kept ones are parsed and matched.
+
+use crate::i18n::{Lang, Message};
+use crate::input::Lines;
+use crate::shuffle::next_random;
+use clap::{Args, ValueEnum};
+use log::warn;
+use serde::Serialize;
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::io;
+use std::rc::Rc;
+
+/// Which lines are kept when there are too many
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TruncateStrategy {
+    /// The first ones, streamed as they are read
+    #[default]
+    Head,
+    /// The last ones, once the input ends
+    Tail,
+    /// An even sample, the same one each run, in input order once the input ends
+    Sample,
+}
+
+/// Whether truncated input fails the run
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OnTruncate {
+    /// Warn and let the findings decide
+    #[default]
+    Warn,
+    /// Fail, as findings may have been dropped with the lines
+    Fail,
+}
+
+/// How much of the input was filtered
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Truncation {
+    pub strategy: TruncateStrategy,
+    pub read: u64,
+    pub kept: u64,
+}
+
+impl Truncation {
+    pub fn describe(&self, lang: Lang) -> String {
+        let message = match self.strategy {
+            TruncateStrategy::Head => Message::TruncatedHead,
+            TruncateStrategy::Tail => Message::TruncatedTail,
+            TruncateStrategy::Sample => Message::TruncatedSample,
+        };
+        lang.format(message, &[&self.kept, &self.read])
+    }
+}
+
+/// The cap on the lint output
+#[derive(Args, Debug, Clone, Copy, Default)]
+pub struct InputLimit {
+    /// Filter at most N lines of lint output, chosen by --truncate-strategy; the rest
+    /// are read and dropped, and counted in the --summary
+    #[arg(long, value_name = "N")]
+    max_input_lines: Option<u64>,
+
+    /// Which lines --max-input-lines keeps
+    #[arg(long, value_enum, default_value_t, requires = "max_input_lines")]
+    truncate_strategy: TruncateStrategy,
+
+    /// Whether input cut by --max-input-lines fails the run
+    #[arg(long, value_enum, default_value_t, requires = "max_input_lines")]
+    on_truncate: OnTruncate,
+}
+
+/// Counts updated as the limited input is read, shared with whoever reports them
+pub type Tally = Rc<Cell<Truncation>>;
+
+impl InputLimit {
+    pub fn new(max: u64, strategy: TruncateStrategy) -> Self {
+        InputLimit {
+            max_input_lines: Some(max),
+            truncate_strategy: strategy,
+            on_truncate: OnTruncate::default(),
+        }
+    }
+
+    /// `input` cut to the limit, and the counts it keeps once read
+    pub fn apply<'a>(self, input: Lines<'a>) -> (Lines<'a>, Tally) {
+        let tally = Tally::default();
+        let max = match self.max_input_lines {
+            Some(max) => max,
+            None => return (input, tally),
+        };
+        tally.set(Truncation {
+            strategy: self.truncate_strategy,
+            ..Truncation::default()
+        });
+        let limited = Limited {
+            input,
+            max,
+            strategy: self.truncate_strategy,
+            held: None,
+            tally: tally.clone(),
+        };
+        (Box::new(limited), tally)
+    }
+
+    /// What was dropped from the input, warning about it in `lang`, once it is all read
+    pub fn truncation(&self, tally: &Tally, lang: Lang) -> Option<Truncation> {
+        let truncation = tally.get();
+        if truncation.read <= truncation.kept {
+            return None;
+        }
+        warn!(
+            "{}; {}",
+            truncation.describe(lang),
+            lang.text(Message::RaiseMaxInputLines)
+        );
+        Some(truncation)
+    }
+
+    /// Whether `truncation` fails the run
+    pub fn fails(&self, truncation: Option<Truncation>) -> bool {
+        truncation.is_some() && self.on_truncate == OnTruncate::Fail
+    }
+}
+
+/// The starting state of the xorshift generator, so samples are the same every run
+const SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+
+struct Limited<'a> {
+    input: Lines<'a>,
+    max: u64,
+    strategy: TruncateStrategy,
+    /// The lines kept by `tail` and `sample`, once the input ends
+    held: Option<VecDeque<io::Result<String>>>,
+    tally: Tally,
+}
+
+impl Limited<'_> {
+    fn tally(&self, read: u64, kept: u64) {
+        let mut truncation = self.tally.get();
+        truncation.read += read;
+        truncation.kept += kept;
+        self.tally.set(truncation);
+    }
+
+    /// Reads the whole input, keeping the lines the strategy asks for; a read error
+    /// ends the input after them
+    fn hold(&mut self) -> VecDeque<io::Result<String>> {
+        let max = self.max as usize;
+        let mut kept: VecDeque<(u64, String)> = VecDeque::new();
+        let mut read = 0;
+        let mut random = SEED;
+        let mut error = None;
+        for line in self.input.by_ref() {
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => {
+                    error = Some(err);
+                    break;
+                }
+            };
+            read += 1;
+            if kept.len() < max {
+                kept.push_back((read, line));
+            } else if max == 0 {
+                continue;
+            } else if self.strategy == TruncateStrategy::Tail {
+                kept.pop_front();
+                kept.push_back((read, line));
+            } else {
+                // Reservoir sampling: each line so far is kept with the same chance
+                let slot = next_random(&mut random) % read;
+                if slot < max as u64 {
+                    kept[slot as usize] = (read, line);
+                }
+            }
+        }
+        self.tally(read, kept.len() as u64);
+        let mut kept: Vec<_> = kept.into();
+        kept.sort_unstable_by_key(|&(index, _)| index);
+        kept.into_iter()
+            .map(|(_, line)| Ok(line))
+            .chain(error.map(Err))
+            .collect()
+    }
+}
+
+impl Iterator for Limited<'_> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.strategy != TruncateStrategy::Head {
+            if self.held.is_none() {
+                self.held = Some(self.hold());
+            }
+            return self.held.as_mut().unwrap().pop_front();
+        }
+        loop {
+            match self.input.next()? {
+                Ok(line) => {
+                    let keep = self.tally.get().read < self.max;
+                    self.tally(1, keep as u64);
+                    if keep {
+                        return Some(Ok(line));
+                    }
+                }
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::i18n::Lang;
+    use crate::input::Lines;
+    use crate::truncate::{InputLimit, TruncateStrategy, Truncation};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    fn limit(max: u64, strategy: TruncateStrategy, lines: u64) -> (Vec<u64>, Truncation) {
+        let input: Lines = Box::new((1..=lines).map(|line| Ok(line.to_string())));
+        let (limited, tally) = InputLimit::new(max, strategy).apply(input);
+        let kept = limited.map(|line| line.unwrap().parse().unwrap()).collect();
+        (kept, tally.get())
+    }
+
+    #[test]
+    fn test_truncate() {
+        let (kept, truncation) = limit(3, TruncateStrategy::Head, 10);
+        assert_eq!(kept, [1, 2, 3]);
+        assert_eq!((truncation.read, truncation.kept), (10, 3));
+        assert_eq!(
+            truncation.describe(Lang::En),
+            "input truncated to the first 3 of 10 lines"
+        );
+        assert_eq!(limit(3, TruncateStrategy::Tail, 10).0, [8, 9, 10]);
+
+        let (kept, truncation) = limit(100, TruncateStrategy::Sample, 10_000);
+        assert_eq!(kept.len(), 100);
+        assert!(kept.windows(2).all(|pair| pair[0] < pair[1]));
+        // Spread over the input rather than bunched at either end
+        assert!(kept.iter().filter(|&&line| line <= 5_000).count() > 25);
+        assert!(kept.iter().filter(|&&line| line > 5_000).count() > 25);
+        assert_eq!(limit(100, TruncateStrategy::Sample, 10_000).0, kept);
+        assert_eq!((truncation.read, truncation.kept), (10_000, 100));
+
+        let (kept, truncation) = limit(20, TruncateStrategy::Tail, 10);
+        assert_eq!(kept.len(), 10);
+        let limits = InputLimit::new(20, TruncateStrategy::Tail);
+        let tally = Rc::new(Cell::new(truncation));
+        assert_eq!(limits.truncation(&tally, Lang::En), None);
+        assert_eq!(limit(0, TruncateStrategy::Sample, 10).0, Vec::<u64>::new());
+    }
+}
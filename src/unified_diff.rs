@@ -0,0 +1,365 @@
+//! Changed lines read from a patch file instead of a repository, for patch-review
+//! pipelines that have no checkout.
+//!
+//! Accepts `git diff` and `diff -u` output as well as `git format-patch` series, where
+//! the ranges of earlier commits are carried through the edits of later ones.
+
+use crate::drift::LineMap;
+use crate::paths;
+use crate::ranges::{merge_ranges, HunkMap};
+use regex::Regex;
+use std::sync::LazyLock as Lazy;
+
+static HUNK_HEADER: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"^@@ -(?P<old>\d+)(?:,(?P<old_len>\d+))? \+(?P<new>\d+)(?:,(?P<new_len>\d+))? @@(?P<rest>.*)",
+    )
+    .unwrap()
+});
+
+const DEV_NULL: &str = "/dev/null";
+
+/// One file of one patch, as `(old_start, old_lines, new_start, new_lines)` hunks
+#[derive(Default)]
+struct FilePatch {
+    old_path: Option<String>,
+    new_path: Option<String>,
+    hunks: Vec<(u32, u32, u32, u32)>,
+}
+
+/// The path in a `---`/`+++` header, without the timestamp diff -u appends
+pub(crate) fn header_path(header: &str) -> &str {
+    header.split('\t').next().unwrap_or(header).trim_end()
+}
+
+/// A `@@` line, with the lengths it leaves out as 1
+pub(crate) struct HunkHeader<'a> {
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    /// Text after the second `@@`, usually the enclosing function
+    pub section: &'a str,
+}
+
+/// A line of a unified diff, by where it is
+pub(crate) enum DiffLine<'a> {
+    /// A line outside hunks, as `diff --git` and `---`/`+++` headers
+    Header(&'a str),
+    Hunk(HunkHeader<'a>),
+    /// A line of the last hunk, one removing `-- x` included
+    Body(&'a str),
+}
+
+/// Tells the lines of a unified diff apart, counting down the old and new lines left
+/// in each hunk so that removed and added lines that look like headers stay in it
+pub(crate) struct DiffLines<I> {
+    lines: I,
+    /// Old and new lines left in the hunk, if in one
+    left: Option<(u32, u32)>,
+}
+
+pub(crate) fn diff_lines<'a, I: IntoIterator<Item = &'a str>>(lines: I) -> DiffLines<I::IntoIter> {
+    DiffLines {
+        lines: lines.into_iter(),
+        left: None,
+    }
+}
+
+impl<'a, I: Iterator<Item = &'a str>> Iterator for DiffLines<I> {
+    type Item = DiffLine<'a>;
+
+    fn next(&mut self) -> Option<DiffLine<'a>> {
+        let line = self.lines.next()?;
+        if let Some(left) = &mut self.left {
+            let first = line.chars().next();
+            // `\ No newline at end of file` is about the line before it, the last included
+            if first == Some('\\') {
+                return Some(DiffLine::Body(line));
+            }
+            if *left != (0, 0) {
+                *left = match first {
+                    Some('-') => (left.0.saturating_sub(1), left.1),
+                    Some('+') => (left.0, left.1.saturating_sub(1)),
+                    // Context, possibly with its trailing space trimmed by a mail client
+                    _ => (left.0.saturating_sub(1), left.1.saturating_sub(1)),
+                };
+                return Some(DiffLine::Body(line));
+            }
+            self.left = None;
+        }
+        let captures = match HUNK_HEADER.captures(line) {
+            Some(captures) => captures,
+            None => return Some(DiffLine::Header(line)),
+        };
+        let number = |name, default| {
+            captures
+                .name(name)
+                .map_or(default, |m| m.as_str().parse().unwrap_or(0))
+        };
+        let header = HunkHeader {
+            old_start: number("old", 0),
+            old_lines: number("old_len", 1),
+            new_start: number("new", 0),
+            new_lines: number("new_len", 1),
+            section: captures.name("rest").map_or("", |m| m.as_str()),
+        };
+        self.left = Some((header.old_lines, header.new_lines));
+        Some(DiffLine::Hunk(header))
+    }
+}
+
+impl FilePatch {
+    /// Strips the `a/` and `b/` git adds, unless it was told not to
+    fn paths(&self) -> (Option<String>, Option<String>) {
+        let (old, new) = (self.old_path.as_deref(), self.new_path.as_deref());
+        let prefixed = old.is_none_or(|old| old == DEV_NULL || old.starts_with("a/"))
+            && new.is_none_or(|new| new == DEV_NULL || new.starts_with("b/"));
+        let strip = |path: Option<&str>, prefix| {
+            let path = path.filter(|&path| path != DEV_NULL)?;
+            let path = if prefixed {
+                path.strip_prefix(prefix).unwrap_or(path)
+            } else {
+                path
+            };
+            Some(paths::normalize_path(path))
+        };
+        (strip(old, "a/"), strip(new, "b/"))
+    }
+
+    fn apply(self, hunkmap: &mut HunkMap) {
+        let (old_path, new_path) = self.paths();
+        let earlier = old_path.and_then(|path| hunkmap.remove(&path));
+        let new_path = match new_path {
+            Some(path) => path,
+            // Deleted, along with whatever earlier patches changed in it
+            None => return,
+        };
+        let mut ranges: Vec<_> = self
+            .hunks
+            .iter()
+            .map(|&(_, _, start, lines)| (start, start + lines))
+            .collect();
+        if let Some(earlier) = earlier {
+            let line_map = LineMap::from_hunks(self.hunks);
+            ranges.extend(
+                earlier
+                    .into_iter()
+                    .filter_map(|range| line_map.map_range(range)),
+            );
+        }
+        if !ranges.is_empty() {
+            hunkmap.insert(new_path, merge_ranges(ranges));
+        }
+    }
+}
+
+/// Walks the lines of a hunk, splitting it into runs of changes as a diff without
+/// context lines would have them, since patches usually carry some
+struct HunkBody {
+    /// Numbers of the next old and new lines
+    old: u32,
+    new: u32,
+    /// `(old_start, old_lines, new_start, new_lines)` of the changes since the last
+    /// context line
+    run: Option<(u32, u32, u32, u32)>,
+}
+
+impl HunkBody {
+    fn new(old_start: u32, old_lines: u32, new_start: u32, new_lines: u32) -> Self {
+        // An empty side's start is the line before it
+        HunkBody {
+            old: old_start + u32::from(old_lines == 0),
+            new: new_start + u32::from(new_lines == 0),
+            run: None,
+        }
+    }
+
+    /// Reads the next line of the hunk
+    fn line(&mut self, line: &str, hunks: &mut Vec<(u32, u32, u32, u32)>) {
+        let (old, new) = (self.old, self.new);
+        match line.chars().next() {
+            Some('-') => {
+                self.run.get_or_insert((old, 0, new, 0)).1 += 1;
+                self.old += 1;
+            }
+            Some('+') => {
+                self.run.get_or_insert((old, 0, new, 0)).3 += 1;
+                self.new += 1;
+            }
+            Some('\\') => {}
+            _ => {
+                self.close(hunks);
+                self.old += 1;
+                self.new += 1;
+            }
+        }
+    }
+
+    fn close(&mut self, hunks: &mut Vec<(u32, u32, u32, u32)>) {
+        if let Some((old_start, old_lines, new_start, new_lines)) = self.run.take() {
+            // As in hunk headers, an empty side starts at the line before it
+            hunks.push((
+                old_start - u32::from(old_lines == 0),
+                old_lines,
+                new_start - u32::from(new_lines == 0),
+                new_lines,
+            ));
+        }
+    }
+}
+
+/// The new-side line ranges of each file's hunks, by its new path, like
+/// [`generate_hunkmap`](crate::hunks::generate_hunkmap) computes from a repository
+pub fn hunkmap(text: &str) -> HunkMap {
+    let mut hunkmap = HunkMap::new();
+    let mut file: Option<FilePatch> = None;
+    let mut body: Option<HunkBody> = None;
+    for line in diff_lines(text.lines()) {
+        let line = match line {
+            DiffLine::Body(line) => {
+                if let (Some(hunk), Some(file)) = (&mut body, &mut file) {
+                    hunk.line(line, &mut file.hunks);
+                }
+                continue;
+            }
+            DiffLine::Hunk(header) => {
+                if let (Some(mut hunk), Some(file)) = (body.take(), &mut file) {
+                    hunk.close(&mut file.hunks);
+                }
+                if file.is_some() {
+                    body = Some(HunkBody::new(
+                        header.old_start,
+                        header.old_lines,
+                        header.new_start,
+                        header.new_lines,
+                    ));
+                }
+                continue;
+            }
+            DiffLine::Header(line) => line,
+        };
+        if let (Some(mut hunk), Some(file)) = (body.take(), &mut file) {
+            hunk.close(&mut file.hunks);
+        }
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            if let Some(file) = file.take() {
+                file.apply(&mut hunkmap);
+            }
+            // Both paths, for a pure rename or a mode change that has no `---` header
+            let (old, new) = match paths.split_once(" b/") {
+                Some((old, new)) => (old.to_string(), format!("b/{}", new)),
+                None => match paths.split_once(' ') {
+                    Some((old, new)) => (old.to_string(), new.to_string()),
+                    None => (paths.to_string(), paths.to_string()),
+                },
+            };
+            file = Some(FilePatch {
+                old_path: Some(old),
+                new_path: Some(new),
+                hunks: Vec::new(),
+            });
+        } else if let Some(path) = line.strip_prefix("--- ") {
+            let file = match &mut file {
+                // A new file without a `diff --git` line, e.g. from diff -u
+                Some(file) if !file.hunks.is_empty() => {
+                    let previous = std::mem::take(file);
+                    previous.apply(&mut hunkmap);
+                    file
+                }
+                Some(file) => file,
+                None => file.insert(FilePatch::default()),
+            };
+            file.old_path = Some(header_path(path).to_string());
+        } else if let (Some(path), Some(file)) = (line.strip_prefix("+++ "), &mut file) {
+            file.new_path = Some(header_path(path).to_string());
+        } else if let Some(path) = line.strip_prefix("rename from ") {
+            if let Some(file) = &mut file {
+                file.old_path = Some(format!("a/{}", path));
+            }
+        } else if let Some(path) = line.strip_prefix("rename to ") {
+            if let Some(file) = &mut file {
+                file.new_path = Some(format!("b/{}", path));
+            }
+        } else if line.starts_with("deleted file mode ") {
+            if let Some(file) = &mut file {
+                file.new_path = Some(DEV_NULL.to_string());
+            }
+        }
+    }
+    if let (Some(mut hunk), Some(file)) = (body, &mut file) {
+        hunk.close(&mut file.hunks);
+    }
+    if let Some(file) = file {
+        file.apply(&mut hunkmap);
+    }
+    hunkmap
+}
+
+#[cfg(test)]
+mod test {
+    use crate::unified_diff::hunkmap;
+
+    #[test]
+    fn test_hunkmap() {
+        let patch = "\
+From 1 Mon Sep 17 00:00:00 2001
+Subject: [PATCH 1/2] first
+
+---
+ a.py | 2 +-
+diff --git a/a.py b/a.py
+--- a/a.py
++++ b/a.py
+@@ -2 +2,2 @@ def f():
+-old
++new
++--- not a header
+diff --git a/gone.py b/gone.py
+deleted file mode 100644
+--- a/gone.py
++++ /dev/null
+@@ -1 +0,0 @@
+-x
+diff --git a/old.py b/moved.py
+similarity index 100%
+rename from old.py
+rename to moved.py
+-- 
+2.40.0
+
+From 2 Mon Sep 17 00:00:00 2001
+Subject: [PATCH 2/2] second
+
+diff --git a/a.py b/a.py
+--- a/a.py
++++ b/a.py
+@@ -0,0 +1,3 @@
++a
++b
++c
+diff --git a/new.py b/new.py
+new file mode 100644
+--- /dev/null
++++ b/new.py
+@@ -0,0 +1,2 @@
++x
++y
+";
+        let hunkmap = hunkmap(patch);
+        // The first patch's 2..4 moved down by the three lines inserted above it
+        assert_eq!(hunkmap["a.py"], [(1, 4), (5, 7)]);
+        assert_eq!(hunkmap["new.py"], [(1, 3)]);
+        assert_eq!(hunkmap.len(), 2);
+
+        let context = "--- a/b.py\n+++ b/b.py\n@@ -1,6 +1,6 @@\n a\n-b\n+B\n c\n d\n-e\n f\n";
+        assert_eq!(hunkmap_of(context), [(2, 3), (4, 4)]);
+
+        let plain = "--- a.py.orig\t2024-01-01\n+++ a.py\t2024-01-02\n@@ -5,0 +6 @@\n+z\n";
+        assert_eq!(hunkmap_of(plain), [(6, 7)]);
+    }
+
+    fn hunkmap_of(patch: &str) -> Vec<(u32, u32)> {
+        hunkmap(patch).into_values().next().unwrap()
+    }
+}
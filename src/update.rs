@@ -0,0 +1,306 @@
+//! `self-update`: replaces the running binary with the latest release built for this
+//! platform once its minisign signature checks out, and installs shell completions and
+//! a pre-commit hook, so machines without cargo can adopt the tool and keep it current.
+//!
+//! A release has an asset per platform named `diff-format-<arch>-<os>`, e.g.
+//! `diff-format-x86_64-linux` or `diff-format-aarch64-macos`, with `.exe` appended on
+//! Windows, each signed by `<asset>.minisig`.
+
+use crate::publish::{self, Agent};
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use clap_complete::Shell;
+use git2::Repository;
+use serde::Deserialize;
+use std::env::{self, consts};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const FEED: &str = "https://api.github.com/repos/shustinm/diff-format/releases/latest";
+
+/// Marks hooks this command wrote, which it may replace
+const HOOK_MARKER: &str = "# Installed by diff-format self-update";
+
+#[derive(Args, Debug)]
+pub struct UpdateArgs {
+    /// The release feed, as the GitHub API URL of the latest release
+    #[arg(long, value_name = "URL", default_value = FEED)]
+    feed: String,
+
+    /// The minisign public key releases are signed with; the binary is only replaced
+    /// when given
+    #[arg(long, value_name = "KEY")]
+    public_key: Option<PathBuf>,
+
+    /// Only tell whether a newer release is available
+    #[arg(long, conflicts_with = "public_key")]
+    check: bool,
+
+    /// Install completions for this shell in its per-user directory, or print them for
+    /// shells without one
+    #[arg(long, value_enum, value_name = "SHELL")]
+    install_completion: Option<Shell>,
+
+    /// Install a pre-commit hook in the repository at --path, filtering the output of
+    /// this linter command to the staged changes
+    #[arg(long, value_name = "LINTER")]
+    install_hook: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+impl Release {
+    fn download_url(&self, name: &str) -> Result<&str> {
+        self.assets
+            .iter()
+            .find(|asset| asset.name == name)
+            .map(|asset| asset.browser_download_url.as_str())
+            .with_context(|| format!("Release {} has no {}", self.tag_name, name))
+    }
+}
+
+/// The release asset built for this platform
+fn asset_name() -> String {
+    format!(
+        "diff-format-{}-{}{}",
+        consts::ARCH,
+        consts::OS,
+        consts::EXE_SUFFIX
+    )
+}
+
+/// The numbers of a `v1.2.3` version, so 1.10 sorts after 1.9. A pre-release suffix is
+/// ignored
+fn version(tag: &str) -> Vec<u64> {
+    tag.trim_start_matches('v')
+        .split(['.', '-', '+'])
+        .map_while(|part| part.parse().ok())
+        .collect()
+}
+
+/// Release binaries are several megabytes, past what ureq reads by default
+#[cfg(feature = "http")]
+const DOWNLOAD_LIMIT: u64 = 256 << 20;
+
+#[cfg(feature = "http")]
+fn fetch(http: &Agent, url: &str) -> Result<Vec<u8>> {
+    let mut response = http
+        .get(url)
+        .header("User-Agent", "diff-format")
+        .call()
+        .with_context(|| format!("Unable to fetch {}", url))?;
+    response
+        .body_mut()
+        .with_config()
+        .limit(DOWNLOAD_LIMIT)
+        .read_to_vec()
+        .with_context(|| format!("Unable to download {}", url))
+}
+
+#[cfg(not(feature = "http"))]
+fn fetch(_http: &Agent, _url: &str) -> Result<Vec<u8>> {
+    bail!("self-update needs diff-format built with the `http` feature")
+}
+
+fn verify(file: &Path, signature: &Path, public_key: &Path) -> Result<()> {
+    let status = Command::new("minisign")
+        .arg("-Vq")
+        .arg("-p")
+        .arg(public_key)
+        .arg("-m")
+        .arg(file)
+        .arg("-x")
+        .arg(signature)
+        .status()
+        .context("Unable to run minisign to verify the release")?;
+    if !status.success() {
+        bail!(
+            "The release signature doesn't verify with {}",
+            public_key.display()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Swaps `exe` for `binary`, once `signature` verifies it. The new binary is written
+/// next to `exe` first, so the rename over it stays on one filesystem
+fn replace(exe: &Path, binary: &[u8], signature: &[u8], public_key: &Path) -> Result<()> {
+    let staged = exe.with_extension("new");
+    let signature_path = exe.with_extension("new.minisig");
+    fs::write(&staged, binary)
+        .and_then(|()| fs::write(&signature_path, signature))
+        .with_context(|| format!("Unable to write {}", staged.display()))?;
+    let verified = verify(&staged, &signature_path, public_key);
+    let _ = fs::remove_file(&signature_path);
+    if verified.is_err() {
+        let _ = fs::remove_file(&staged);
+        return verified;
+    }
+    make_executable(&staged)?;
+    // A running executable can be renamed on Windows, but not replaced
+    if cfg!(windows) {
+        fs::rename(exe, exe.with_extension("old"))?;
+    }
+    fs::rename(&staged, exe).with_context(|| format!("Unable to replace {}", exe.display()))
+}
+
+fn install_completion(shell: Shell, mut command: clap::Command) -> Result<()> {
+    let home = env::var_os("HOME").map(PathBuf::from);
+    let path = match (shell, home) {
+        (Shell::Bash, Some(home)) => {
+            home.join(".local/share/bash-completion/completions/diff-format")
+        }
+        (Shell::Zsh, Some(home)) => home.join(".zfunc/_diff-format"),
+        (Shell::Fish, Some(home)) => home.join(".config/fish/completions/diff-format.fish"),
+        _ => {
+            clap_complete::generate(shell, &mut command, "diff-format", &mut io::stdout());
+            return Ok(());
+        }
+    };
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut command, "diff-format", &mut script);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Unable to create {}", parent.display()))?;
+    }
+    fs::write(&path, script).with_context(|| format!("Unable to write {}", path.display()))?;
+    eprintln!("Installed {} completions in {}", shell, path.display());
+    Ok(())
+}
+
+/// A pre-commit hook running `exe` on the output of `linter`
+fn hook_script(exe: &Path, linter: &str) -> String {
+    let exe = exe.to_string_lossy().replace('\'', r"'\''");
+    format!(
+        "#!/bin/sh\n{}\nexec '{}' --staged -- {}\n",
+        HOOK_MARKER, exe, linter
+    )
+}
+
+fn install_hook(path: &Path, linter: &str) -> Result<()> {
+    let repo = Repository::discover(path).context("Can't open repository")?;
+    let hooks = match repo.config()?.get_path("core.hooksPath") {
+        Ok(hooks) => repo.workdir().unwrap_or_else(|| repo.path()).join(hooks),
+        Err(_) => repo.path().join("hooks"),
+    };
+    let hook = hooks.join("pre-commit");
+    if let Ok(existing) = fs::read_to_string(&hook) {
+        if !existing.contains(HOOK_MARKER) {
+            bail!(
+                "{} exists and wasn't installed by diff-format, not replacing it",
+                hook.display()
+            );
+        }
+    }
+    fs::create_dir_all(&hooks).with_context(|| format!("Unable to create {}", hooks.display()))?;
+    fs::write(&hook, hook_script(&env::current_exe()?, linter))
+        .with_context(|| format!("Unable to write {}", hook.display()))?;
+    make_executable(&hook)?;
+    eprintln!("Installed {}", hook.display());
+    Ok(())
+}
+
+impl UpdateArgs {
+    /// Updates as asked, then installs the completions of `command` and the hook in
+    /// the repository at `path`
+    pub fn run(&self, path: &Path, ca_cert: Option<&Path>, command: clap::Command) -> Result<()> {
+        if self.check || self.public_key.is_some() {
+            self.update(ca_cert)?;
+        } else if self.install_completion.is_none() && self.install_hook.is_none() {
+            bail!("Give --public-key to verify and install the latest release, or --check");
+        }
+        if let Some(shell) = self.install_completion {
+            install_completion(shell, command)?;
+        }
+        if let Some(linter) = &self.install_hook {
+            install_hook(path, linter)?;
+        }
+        Ok(())
+    }
+
+    fn update(&self, ca_cert: Option<&Path>) -> Result<()> {
+        let http = publish::agent(ca_cert)?;
+        let release: Release = serde_json::from_slice(&fetch(&http, &self.feed)?)
+            .with_context(|| format!("Malformed release feed {}", self.feed))?;
+        let current = env!("CARGO_PKG_VERSION");
+        if version(&release.tag_name) <= version(current) {
+            println!("diff-format {} is up to date", current);
+            return Ok(());
+        }
+        let public_key = match &self.public_key {
+            Some(public_key) => public_key,
+            None => {
+                println!(
+                    "diff-format {} is available, {} is installed",
+                    release.tag_name, current
+                );
+                return Ok(());
+            }
+        };
+        let name = asset_name();
+        let binary = fetch(&http, release.download_url(&name)?)?;
+        let signature = fetch(&http, release.download_url(&format!("{}.minisig", name))?)?;
+        let exe = env::current_exe().context("Unable to locate the running binary")?;
+        replace(&exe, &binary, &signature, public_key)?;
+        println!("Updated diff-format {} to {}", current, release.tag_name);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::update::{hook_script, version, Release};
+    use std::path::Path;
+
+    #[test]
+    fn test_version() {
+        assert!(version("v0.10.0") > version("0.9.3"));
+        assert!(version("v1.2.0-rc1") == version("1.2.0"));
+        assert!(version("0.1.0") <= version(env!("CARGO_PKG_VERSION")));
+    }
+
+    #[test]
+    fn test_release() {
+        let release: Release = serde_json::from_str(
+            r#"{"tag_name": "v0.2.0", "assets": [
+                {"name": "diff-format-x86_64-linux", "browser_download_url": "https://x/a"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            release.download_url("diff-format-x86_64-linux").unwrap(),
+            "https://x/a"
+        );
+        assert!(release
+            .download_url("diff-format-x86_64-linux.minisig")
+            .is_err());
+        assert_eq!(
+            hook_script(Path::new("/opt/it's/diff-format"), "ruff check ."),
+            "#!/bin/sh\n# Installed by diff-format self-update\n\
+             exec '/opt/it'\\''s/diff-format' --staged -- ruff check .\n"
+        );
+    }
+}
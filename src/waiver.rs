@@ -0,0 +1,189 @@
+//! Approved, expiring exceptions for rules on paths, configured as `[[waivers]]`.
+
+use crate::config::{compile_glob, WaiverConfig};
+use crate::diagnostic::Diagnostic;
+use anyhow::{Context, Result};
+use globset::GlobMatcher;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use toml::value::Date;
+
+pub struct Waiver {
+    pub rule: String,
+    pub path: String,
+    matcher: GlobMatcher,
+    pub approved_by: String,
+    /// Last day the waiver applies
+    pub expires: Date,
+}
+
+impl Waiver {
+    pub fn expired(&self, today: Date) -> bool {
+        (self.expires.year, self.expires.month, self.expires.day)
+            < (today.year, today.month, today.day)
+    }
+}
+
+impl fmt::Display for Waiver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} on '{}' (approved by {}, expires {})",
+            self.rule, self.path, self.approved_by, self.expires
+        )
+    }
+}
+
+pub struct Waivers {
+    waivers: Vec<Waiver>,
+}
+
+impl Waivers {
+    pub fn new(configs: &[WaiverConfig]) -> Result<Self> {
+        let waivers = configs
+            .iter()
+            .map(|config| {
+                let matcher = compile_glob(&config.path)
+                    .with_context(|| format!("Invalid [[waivers]] path '{}'", config.path))?;
+                let expires = config
+                    .expires
+                    .date
+                    .filter(|_| config.expires.time.is_none())
+                    .with_context(|| {
+                        format!("[[waivers]] expires '{}' is not a date", config.expires)
+                    })?;
+                Ok(Waiver {
+                    rule: config.rule.clone(),
+                    path: config.path.clone(),
+                    matcher,
+                    approved_by: config.approved_by.clone(),
+                    expires,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Waivers { waivers })
+    }
+
+    /// The first waiver covering the finding's rule and path that hasn't expired by
+    /// `today`, or the first expired one if none is valid, so a renewal listed after the
+    /// waiver it renews applies
+    pub fn find(&self, diagnostic: &Diagnostic, today: Date) -> Option<&Waiver> {
+        let rule = diagnostic.rule.as_deref()?;
+        let mut covering = self
+            .waivers
+            .iter()
+            .filter(|waiver| waiver.rule == rule && waiver.matcher.is_match(&diagnostic.path));
+        let first = covering.next()?;
+        if !first.expired(today) {
+            return Some(first);
+        }
+        covering
+            .find(|waiver| !waiver.expired(today))
+            .or(Some(first))
+    }
+}
+
+/// The current UTC date
+pub fn today() -> Date {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    civil_date(seconds / 86_400)
+}
+
+/// Converts days since 1970-01-01 to a proleptic Gregorian date
+pub fn civil_date(days: u64) -> Date {
+    // Shifted so eras start on March 1st, putting leap days at the end of the year
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    Date {
+        year: year as u16,
+        month: month as u8,
+        day: day as u8,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::Config;
+    use crate::diagnostic::Diagnostic;
+    use crate::waiver::{civil_date, Waivers};
+    use toml::value::Date;
+
+    #[test]
+    fn test_civil_date() {
+        let date = |days| {
+            let Date { year, month, day } = civil_date(days);
+            (year, month, day)
+        };
+        assert_eq!(date(0), (1970, 1, 1));
+        assert_eq!(date(11_016), (2000, 2, 29));
+        assert_eq!(date(20_740), (2026, 10, 14));
+    }
+
+    #[test]
+    fn test_waivers() {
+        let config: Config = toml::from_str(
+            r#"
+            [[waivers]]
+            rule = "E501"
+            path = "src/legacy/**"
+            approved_by = "alice"
+            expires = 2026-06-30
+            "#,
+        )
+        .unwrap();
+        let waivers = Waivers::new(&config.waivers).unwrap();
+        let mut diagnostic = Diagnostic::new("src/legacy/a.py", Some(1));
+        diagnostic.rule = Some("E501".to_string());
+        let waiver = waivers.find(&diagnostic, civil_date(20_634)).unwrap();
+        assert!(!waiver.expired(civil_date(20_634)));
+        assert!(waiver.expired(civil_date(20_635)));
+        assert_eq!(
+            waiver.to_string(),
+            "E501 on 'src/legacy/**' (approved by alice, expires 2026-06-30)"
+        );
+
+        diagnostic.path = "src/a.py".to_string();
+        assert!(waivers.find(&diagnostic, civil_date(20_634)).is_none());
+
+        // A renewal listed after the waiver it renews
+        let config: Config = toml::from_str(
+            r#"
+            [[waivers]]
+            rule = "E2"
+            path = "a.py"
+            approved_by = "alice"
+            expires = 2026-01-01
+
+            [[waivers]]
+            rule = "E2"
+            path = "a.py"
+            approved_by = "bob"
+            expires = 2027-01-01
+            "#,
+        )
+        .unwrap();
+        let waivers = Waivers::new(&config.waivers).unwrap();
+        let mut diagnostic = Diagnostic::new("a.py", Some(1));
+        diagnostic.rule = Some("E2".to_string());
+        let approver = |days| waivers.find(&diagnostic, civil_date(days)).unwrap();
+        // 2025-12-01, 2026-10-14 and 2027-02-01
+        assert_eq!(approver(20_423).approved_by, "alice");
+        assert_eq!(approver(20_740).approved_by, "bob");
+        assert!(!approver(20_740).expired(civil_date(20_740)));
+        assert!(approver(20_850).expired(civil_date(20_850)));
+    }
+}